@@ -0,0 +1,57 @@
+// A minimal example of the C API: evaluates a Nickel file given on the command line, and
+// prints the top-level fields of the result as well as its JSON serialization.
+//
+// Build with (from the root of the repository, after `cargo build -p nickel-lang-capi`):
+//
+//   cc capi/examples/eval.c -Icapi/include -Ltarget/debug -lnickel_lang -o eval
+#include <stdio.h>
+
+#include "nickel_lang.h"
+
+static int report(nickel_error *error) {
+  fprintf(stderr, "%s", nickel_error_message(error));
+  nickel_error_free(error);
+  return 1;
+}
+
+int main(int argc, char **argv) {
+  if (argc != 2) {
+    fprintf(stderr, "usage: %s <file.ncl>\n", argv[0]);
+    return 2;
+  }
+
+  nickel_program *program = NULL;
+  nickel_error *error = NULL;
+
+  if (nickel_program_new_from_file(argv[1], &program, &error) != NICKEL_RESULT_OK) {
+    return report(error);
+  }
+
+  nickel_value *value = NULL;
+
+  if (nickel_program_eval(program, &value, &error) != NICKEL_RESULT_OK) {
+    nickel_program_free(program);
+    return report(error);
+  }
+
+  if (nickel_value_get_kind(value) == NICKEL_VALUE_RECORD) {
+    for (size_t i = 0; i < nickel_value_len(value); i++) {
+      printf("field: %s\n", nickel_value_record_key(value, i, NULL));
+    }
+  }
+
+  nickel_value_free(value);
+
+  char *json = NULL;
+
+  if (nickel_program_export(program, NICKEL_EXPORT_FORMAT_JSON, &json, &error) != NICKEL_RESULT_OK) {
+    nickel_program_free(program);
+    return report(error);
+  }
+
+  printf("%s\n", json);
+  nickel_string_free(json);
+  nickel_program_free(program);
+
+  return 0;
+}
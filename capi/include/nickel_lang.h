@@ -0,0 +1,270 @@
+#ifndef NICKEL_LANG_H
+#define NICKEL_LANG_H
+
+/* This file is generated by cbindgen from capi/src/lib.rs. Do not edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The outcome of a fallible function.
+typedef enum nickel_result {
+  NICKEL_RESULT_OK = 0,
+  NICKEL_RESULT_ERR = 1,
+} nickel_result;
+
+// The kind of a [nickel_value].
+typedef enum nickel_value_kind {
+  NICKEL_VALUE_NULL = 0,
+  NICKEL_VALUE_BOOL = 1,
+  NICKEL_VALUE_NUMBER = 2,
+  NICKEL_VALUE_STRING = 3,
+  // An enum tag, such as `'Foo`. Its name is accessed like a string.
+  NICKEL_VALUE_ENUM = 4,
+  NICKEL_VALUE_ARRAY = 5,
+  NICKEL_VALUE_RECORD = 6,
+} nickel_value_kind;
+
+// The serialization formats supported by [nickel_program_export]. The function takes the format
+// as an integer, since C doesn't prevent passing a value which isn't one of the variants of an
+// enum, and fails on unknown formats.
+typedef enum nickel_export_format {
+  NICKEL_EXPORT_FORMAT_JSON = 0,
+  NICKEL_EXPORT_FORMAT_YAML = 1,
+  NICKEL_EXPORT_FORMAT_TOML = 2,
+  NICKEL_EXPORT_FORMAT_TEXT = 3,
+  NICKEL_EXPORT_FORMAT_YAML_DOCUMENTS = 4,
+  NICKEL_EXPORT_FORMAT_XML = 5,
+  NICKEL_EXPORT_FORMAT_INI = 6,
+  NICKEL_EXPORT_FORMAT_PROPERTIES = 7,
+  NICKEL_EXPORT_FORMAT_DOTENV = 8,
+  NICKEL_EXPORT_FORMAT_HCL = 9,
+} nickel_export_format;
+
+// An error, holding the diagnostics rendered as text.
+typedef struct nickel_error nickel_error;
+
+// A Nickel program.
+typedef struct nickel_program nickel_program;
+
+// A fully evaluated Nickel value.
+typedef struct nickel_value nickel_value;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns the version of the Nickel interpreter, as a static string.
+const char *nickel_version(void);
+
+// Creates a program from a string of Nickel source code. `name` is the name of the source in
+// error messages. Imports are resolved relatively to the current directory.
+//
+// # Safety
+//
+// `source` and `name` must be valid null-terminated strings. `out_program` must be a valid
+// pointer, and `out_error` must be either null or a valid pointer.
+enum nickel_result nickel_program_new_from_source(const char *source,
+                                                  const char *name,
+                                                  struct nickel_program **out_program,
+                                                  struct nickel_error **out_error);
+
+// Creates a program from a Nickel file.
+//
+// # Safety
+//
+// `path` must be a valid null-terminated string. `out_program` must be a valid pointer, and
+// `out_error` must be either null or a valid pointer.
+enum nickel_result nickel_program_new_from_file(const char *path,
+                                                struct nickel_program **out_program,
+                                                struct nickel_error **out_error);
+
+// Frees a program. Does nothing if `program` is null.
+//
+// # Safety
+//
+// `program` must be null or a program returned by this API which hasn't been freed yet.
+void nickel_program_free(struct nickel_program *program);
+
+// Adds a directory to the list of paths to search for imports in, as `--import-path` does for the
+// CLI.
+//
+// # Safety
+//
+// `program` must be a valid program, and `path` a valid null-terminated string. `out_error` must
+// be either null or a valid pointer.
+enum nickel_result nickel_program_add_import_path(struct nickel_program *program,
+                                                  const char *path,
+                                                  struct nickel_error **out_error);
+
+// Overrides a field of the program, given an assignment `path.to.field = value` where `value` is
+// a Nickel expression. If `force` is false, the field is merged with the given value with the
+// default priority, as with `nickel eval -- path.to.field=value`. Otherwise, the value is merged
+// with the highest priority, as with `--override`.
+//
+// # Safety
+//
+// `program` must be a valid program, and `assignment` a valid null-terminated string.
+// `out_error` must be either null or a valid pointer.
+enum nickel_result nickel_program_add_override(struct nickel_program *program,
+                                               const char *assignment,
+                                               bool force,
+                                               struct nickel_error **out_error);
+
+// Selects the field to evaluate, given as a dot-separated path such as `path.to.field`. By
+// default, or if `field_path` is empty, the whole program is evaluated.
+//
+// # Safety
+//
+// `program` must be a valid program, and `field_path` a valid null-terminated string.
+// `out_error` must be either null or a valid pointer.
+enum nickel_result nickel_program_set_field(struct nickel_program *program,
+                                            const char *field_path,
+                                            struct nickel_error **out_error);
+
+// Evaluates the program (or the selected field) fully, and returns the result as a value tree,
+// which must be freed with [nickel_value_free]. The result must be serializable: it can't
+// contain functions, for example.
+//
+// # Safety
+//
+// `program` must be a valid program, and `out_value` a valid pointer. `out_error` must be either
+// null or a valid pointer.
+enum nickel_result nickel_program_eval(struct nickel_program *program,
+                                       struct nickel_value **out_value,
+                                       struct nickel_error **out_error);
+
+// Evaluates the program (or the selected field) fully, and serializes the result to the given
+// format, one of the [nickel_export_format] values. The returned string must be freed with
+// [nickel_string_free].
+//
+// # Safety
+//
+// `program` must be a valid program, and `out_string` a valid pointer. `out_error` must be
+// either null or a valid pointer.
+enum nickel_result nickel_program_export(struct nickel_program *program,
+                                         uint32_t format,
+                                         char **out_string,
+                                         struct nickel_error **out_error);
+
+// Frees a string returned by [nickel_program_export]. Does nothing if `s` is null.
+//
+// # Safety
+//
+// `s` must be null or a string returned by this API which hasn't been freed yet.
+void nickel_string_free(char *s);
+
+// Returns the rendered diagnostics of an error. The string is valid until the error is freed.
+//
+// # Safety
+//
+// `error` must be a valid error.
+const char *nickel_error_message(const struct nickel_error *error);
+
+// Frees an error. Does nothing if `error` is null.
+//
+// # Safety
+//
+// `error` must be null or an error returned by this API which hasn't been freed yet.
+void nickel_error_free(struct nickel_error *error);
+
+// Returns the kind of a value.
+//
+// # Safety
+//
+// `value` must be a valid value.
+enum nickel_value_kind nickel_value_get_kind(const struct nickel_value *value);
+
+// Returns the value of a boolean, or false if `value` isn't a boolean.
+//
+// # Safety
+//
+// `value` must be a valid value.
+bool nickel_value_as_bool(const struct nickel_value *value);
+
+// Returns the closest double to a number, or 0 if `value` isn't a number.
+//
+// # Safety
+//
+// `value` must be a valid value.
+double nickel_value_as_f64(const struct nickel_value *value);
+
+// Stores a number in `out` if it's an integer that fits in 64 bits. Returns false, leaving `out`
+// untouched, if it doesn't, if `value` isn't a number or if `out` is null.
+//
+// # Safety
+//
+// `value` must be a valid value, and `out` either null or a valid pointer.
+bool nickel_value_as_i64(const struct nickel_value *value, int64_t *out);
+
+// Returns the content of a string, or the name of an enum tag, as a null-terminated UTF-8
+// string. The length in bytes, which doesn't include the null terminator, is stored in `out_len`
+// if it isn't null: Nickel strings can contain null characters. Returns null if `value` is
+// neither a string nor an enum tag.
+//
+// # Safety
+//
+// `value` must be a valid value, and `out_len` either null or a valid pointer.
+const char *nickel_value_as_string(const struct nickel_value *value, size_t *out_len);
+
+// Returns the number of elements of an array, or the number of fields of a record. Returns 0
+// for other values.
+//
+// # Safety
+//
+// `value` must be a valid value.
+size_t nickel_value_len(const struct nickel_value *value);
+
+// Returns the element at `index` of an array, or null if `value` isn't an array or if `index`
+// is out of bounds.
+//
+// # Safety
+//
+// `value` must be a valid value.
+const struct nickel_value *nickel_value_array_get(const struct nickel_value *value, size_t index);
+
+// Returns the name of the field at `index` of a record, where fields are sorted by name. The
+// length in bytes of the name is stored in `out_len` if it isn't null. Returns null if `value`
+// isn't a record or if `index` is out of bounds.
+//
+// # Safety
+//
+// `value` must be a valid value, and `out_len` either null or a valid pointer.
+const char *nickel_value_record_key(const struct nickel_value *value,
+                                    size_t index,
+                                    size_t *out_len);
+
+// Returns the value of the field at `index` of a record, where fields are sorted by name.
+// Returns null if `value` isn't a record or if `index` is out of bounds.
+//
+// # Safety
+//
+// `value` must be a valid value.
+const struct nickel_value *nickel_value_record_value(const struct nickel_value *value,
+                                                     size_t index);
+
+// Returns the value of the field `name` of a record, or null if `value` isn't a record or
+// doesn't have such a field.
+//
+// # Safety
+//
+// `value` must be a valid value, and `name` a valid null-terminated string.
+const struct nickel_value *nickel_value_record_get(const struct nickel_value *value,
+                                                   const char *name);
+
+// Frees a value returned by [nickel_program_eval], together with all its children. Does nothing
+// if `value` is null.
+//
+// # Safety
+//
+// `value` must be null or a value returned by [nickel_program_eval] which hasn't been freed yet.
+// In particular, it must not be a child of another value.
+void nickel_value_free(struct nickel_value *value);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NICKEL_LANG_H */
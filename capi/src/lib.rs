@@ -0,0 +1,870 @@
+//! A C API for embedding the Nickel evaluator.
+//!
+//! This crate exposes a stable C ABI on top of [nickel_lang_core::program::Program]. The
+//! corresponding header is `include/nickel_lang.h`. The API is built around three opaque types:
+//!
+//! - [nickel_program]: a Nickel program, created from a source string or a file, which can be
+//!   customized with import paths, field overrides and a field path, and then evaluated.
+//! - [nickel_value]: the fully evaluated result of a program, as a tree that can be walked from C.
+//! - [nickel_error]: an error, holding the rendered diagnostics, as printed by the CLI.
+//!
+//! Fallible functions return a [nickel_result], and store the error in their `out_error` argument
+//! on failure, unless it's null. The other output arguments of fallible functions must not be null:
+//! if they are, the function fails without doing anything. Every object returned by the API is
+//! owned by the caller, and must be released with the corresponding `_free` function. Strings and
+//! values borrowed from another object are valid until that object is freed.
+//!
+//! Strings passed to the API must be null-terminated and valid UTF-8.
+#![allow(non_camel_case_types)]
+
+use std::{
+    ffi::{c_char, CStr, CString},
+    io::Cursor,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use malachite::{num::conversion::traits::RoundingFrom, rounding_modes::RoundingMode};
+use nickel_lang_core::{
+    error::{Error, ExportError, ExportErrorData, IOError},
+    eval::cache::CacheImpl,
+    position::TermPos,
+    program::{FieldPath, Program},
+    serialize::{self, ExportFormat},
+    term::{record::RecordData, MergePriority, RichTerm, Term},
+};
+
+/// The outcome of a fallible function.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum nickel_result {
+    NICKEL_RESULT_OK = 0,
+    NICKEL_RESULT_ERR = 1,
+}
+
+use nickel_result::*;
+
+/// The serialization formats supported by [nickel_program_export]. The function takes the format
+/// as an integer, since C doesn't prevent passing a value which isn't one of the variants of an
+/// enum, and fails on unknown formats.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum nickel_export_format {
+    NICKEL_EXPORT_FORMAT_JSON = 0,
+    NICKEL_EXPORT_FORMAT_YAML = 1,
+    NICKEL_EXPORT_FORMAT_TOML = 2,
+    NICKEL_EXPORT_FORMAT_TEXT = 3,
+    NICKEL_EXPORT_FORMAT_YAML_DOCUMENTS = 4,
+    NICKEL_EXPORT_FORMAT_XML = 5,
+    NICKEL_EXPORT_FORMAT_INI = 6,
+    NICKEL_EXPORT_FORMAT_PROPERTIES = 7,
+    NICKEL_EXPORT_FORMAT_DOTENV = 8,
+    NICKEL_EXPORT_FORMAT_HCL = 9,
+}
+
+impl nickel_export_format {
+    /// Converts a format passed through the C API, which may not be a valid variant.
+    fn from_raw(format: u32) -> Option<ExportFormat> {
+        use nickel_export_format::*;
+
+        let formats = [
+            (NICKEL_EXPORT_FORMAT_JSON, ExportFormat::Json),
+            (NICKEL_EXPORT_FORMAT_YAML, ExportFormat::Yaml),
+            (NICKEL_EXPORT_FORMAT_TOML, ExportFormat::Toml),
+            (NICKEL_EXPORT_FORMAT_TEXT, ExportFormat::Text),
+            (
+                NICKEL_EXPORT_FORMAT_YAML_DOCUMENTS,
+                ExportFormat::YamlDocuments,
+            ),
+            (NICKEL_EXPORT_FORMAT_XML, ExportFormat::Xml),
+            (NICKEL_EXPORT_FORMAT_INI, ExportFormat::Ini),
+            (NICKEL_EXPORT_FORMAT_PROPERTIES, ExportFormat::Properties),
+            (NICKEL_EXPORT_FORMAT_DOTENV, ExportFormat::Dotenv),
+            (NICKEL_EXPORT_FORMAT_HCL, ExportFormat::Hcl),
+        ];
+
+        formats.into_iter().find_map(|(variant, export_format)| {
+            (variant as u32 == format).then_some(export_format)
+        })
+    }
+}
+
+/// The kind of a [nickel_value].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum nickel_value_kind {
+    NICKEL_VALUE_NULL = 0,
+    NICKEL_VALUE_BOOL = 1,
+    NICKEL_VALUE_NUMBER = 2,
+    NICKEL_VALUE_STRING = 3,
+    /// An enum tag, such as `'Foo`. Its name is accessed like a string.
+    NICKEL_VALUE_ENUM = 4,
+    NICKEL_VALUE_ARRAY = 5,
+    NICKEL_VALUE_RECORD = 6,
+}
+
+/// A Nickel program.
+pub struct nickel_program {
+    program: Program<CacheImpl>,
+}
+
+/// An error, holding the diagnostics rendered as text.
+pub struct nickel_error {
+    message: CString,
+}
+
+/// A fully evaluated Nickel value.
+pub struct nickel_value(Value);
+
+enum Value {
+    Null,
+    Bool(bool),
+    Number(nickel_lang_core::term::Number),
+    /// A string, or the name of an enum tag. The bytes are followed by a null terminator which
+    /// isn't part of the string, since Nickel strings can contain null characters.
+    String(Vec<u8>),
+    Enum(Vec<u8>),
+    Array(Vec<nickel_value>),
+    /// The fields of a record, sorted by name.
+    Record(Vec<(Vec<u8>, nickel_value)>),
+}
+
+fn null_terminated(s: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(s.len() + 1);
+    bytes.extend_from_slice(s.as_bytes());
+    bytes.push(0);
+    bytes
+}
+
+impl nickel_value {
+    /// Converts a fully evaluated term. Fails on terms that can't be serialized, such as
+    /// functions.
+    fn from_term(rt: &RichTerm) -> Result<Self, Error> {
+        let value = match rt.as_ref() {
+            Term::Null => Value::Null,
+            Term::Bool(b) => Value::Bool(*b),
+            Term::Num(n) => Value::Number(n.clone()),
+            Term::Str(s) => Value::String(null_terminated(s)),
+            Term::Enum(tag) => Value::Enum(null_terminated(tag.label())),
+            Term::Array(elts, _) => Value::Array(
+                elts.iter()
+                    .map(nickel_value::from_term)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Term::Record(record) => Value::Record(Self::fields(record, rt.pos)?),
+            _ => {
+                return Err(ExportError::from(ExportErrorData::NonSerializable(rt.clone())).into())
+            }
+        };
+
+        Ok(nickel_value(value))
+    }
+
+    fn fields(record: &RecordData, pos: TermPos) -> Result<Vec<(Vec<u8>, nickel_value)>, Error> {
+        let mut fields = record
+            .iter_serializable()
+            .map(|field| {
+                let (id, value) = field.map_err(|missing| missing.into_eval_err(pos, pos))?;
+                Ok((null_terminated(id.label()), nickel_value::from_term(value)?))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        fields.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+        Ok(fields)
+    }
+}
+
+/// Stores an error in `out_error`, if it isn't null.
+unsafe fn set_error(out_error: *mut *mut nickel_error, message: String) -> nickel_result {
+    if !out_error.is_null() {
+        // Interior null characters would truncate the message, so we replace them.
+        let message = CString::new(message.replace('\0', "\u{FFFD}")).unwrap_or_default();
+        *out_error = Box::into_raw(Box::new(nickel_error { message }));
+    }
+
+    NICKEL_RESULT_ERR
+}
+
+/// Reads a null-terminated UTF-8 string passed to the API.
+unsafe fn read_str<'a>(s: *const c_char, what: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(format!("the {what} is a null pointer"));
+    }
+
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| format!("the {what} isn't valid UTF-8"))
+}
+
+/// Runs an operation of the API, turning errors and panics into a [nickel_error].
+unsafe fn run(
+    out_error: *mut *mut nickel_error,
+    f: impl FnOnce() -> Result<(), String>,
+) -> nickel_result {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => NICKEL_RESULT_OK,
+        Ok(Err(message)) => set_error(out_error, message),
+        Err(_) => set_error(
+            out_error,
+            "internal error: the Nickel interpreter panicked".to_owned(),
+        ),
+    }
+}
+
+/// Same as [run], for an operation whose result is stored in `out`. Fails without running the
+/// operation if `out` is null.
+unsafe fn run_with_output<T>(
+    out: *mut T,
+    out_error: *mut *mut nickel_error,
+    f: impl FnOnce() -> Result<T, String>,
+) -> nickel_result {
+    if out.is_null() {
+        return set_error(
+            out_error,
+            "the output argument is a null pointer".to_owned(),
+        );
+    }
+
+    run(out_error, || {
+        *out = f()?;
+        Ok(())
+    })
+}
+
+impl nickel_program {
+    fn new(mut program: Program<CacheImpl>) -> Self {
+        // Diagnostics are returned as plain text.
+        program.color_opt = clap::ColorChoice::Never.into();
+        nickel_program { program }
+    }
+
+    fn report(&mut self, error: impl Into<Error>) -> String {
+        self.program.report_as_str(error.into())
+    }
+
+    fn eval_for_export(&mut self) -> Result<RichTerm, String> {
+        self.program
+            .eval_full_for_export()
+            .map_err(|err| self.report(err))
+    }
+}
+
+/// Returns the version of the Nickel interpreter, as a static string.
+#[no_mangle]
+pub extern "C" fn nickel_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// Creates a program from a string of Nickel source code. `name` is the name of the source in
+/// error messages. Imports are resolved relatively to the current directory.
+///
+/// # Safety
+///
+/// `source` and `name` must be valid null-terminated strings. `out_program` must be a valid
+/// pointer, and `out_error` must be either null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_new_from_source(
+    source: *const c_char,
+    name: *const c_char,
+    out_program: *mut *mut nickel_program,
+    out_error: *mut *mut nickel_error,
+) -> nickel_result {
+    run_with_output(out_program, out_error, || {
+        let source = read_str(source, "source")?;
+        let name = read_str(name, "source name")?;
+        let program = Program::new_from_source(Cursor::new(source), name, std::io::sink())
+            .map_err(|err| err.to_string())?;
+
+        Ok(Box::into_raw(Box::new(nickel_program::new(program))))
+    })
+}
+
+/// Creates a program from a Nickel file.
+///
+/// # Safety
+///
+/// `path` must be a valid null-terminated string. `out_program` must be a valid pointer, and
+/// `out_error` must be either null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_new_from_file(
+    path: *const c_char,
+    out_program: *mut *mut nickel_program,
+    out_error: *mut *mut nickel_error,
+) -> nickel_result {
+    run_with_output(out_program, out_error, || {
+        let path = read_str(path, "path")?;
+        let program = Program::new_from_file(path, std::io::sink())
+            .map_err(|err| format!("could not read `{path}`: {err}"))?;
+
+        Ok(Box::into_raw(Box::new(nickel_program::new(program))))
+    })
+}
+
+/// Frees a program. Does nothing if `program` is null.
+///
+/// # Safety
+///
+/// `program` must be null or a program returned by this API which hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_free(program: *mut nickel_program) {
+    if !program.is_null() {
+        drop(Box::from_raw(program));
+    }
+}
+
+/// Adds a directory to the list of paths to search for imports in, as `--import-path` does for the
+/// CLI.
+///
+/// # Safety
+///
+/// `program` must be a valid program, and `path` a valid null-terminated string. `out_error` must
+/// be either null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_add_import_path(
+    program: *mut nickel_program,
+    path: *const c_char,
+    out_error: *mut *mut nickel_error,
+) -> nickel_result {
+    let program = &mut *program;
+
+    run(out_error, || {
+        let path = read_str(path, "import path")?;
+        program.program.add_import_paths(std::iter::once(path));
+        Ok(())
+    })
+}
+
+/// Overrides a field of the program, given an assignment `path.to.field = value` where `value` is
+/// a Nickel expression. If `force` is false, the field is merged with the given value with the
+/// default priority, as with `nickel eval -- path.to.field=value`. Otherwise, the value is merged
+/// with the highest priority, as with `--override`.
+///
+/// # Safety
+///
+/// `program` must be a valid program, and `assignment` a valid null-terminated string.
+/// `out_error` must be either null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_add_override(
+    program: *mut nickel_program,
+    assignment: *const c_char,
+    force: bool,
+    out_error: *mut *mut nickel_error,
+) -> nickel_result {
+    let program = &mut *program;
+
+    run(out_error, || {
+        let assignment = read_str(assignment, "assignment")?.to_owned();
+        let priority = if force {
+            MergePriority::Top
+        } else {
+            MergePriority::default()
+        };
+
+        let field_override = program
+            .program
+            .parse_override(assignment, priority)
+            .map_err(|err| program.report(err))?;
+        program.program.add_overrides([field_override]);
+        Ok(())
+    })
+}
+
+/// Selects the field to evaluate, given as a dot-separated path such as `path.to.field`. By
+/// default, or if `field_path` is empty, the whole program is evaluated.
+///
+/// # Safety
+///
+/// `program` must be a valid program, and `field_path` a valid null-terminated string.
+/// `out_error` must be either null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_set_field(
+    program: *mut nickel_program,
+    field_path: *const c_char,
+    out_error: *mut *mut nickel_error,
+) -> nickel_result {
+    let program = &mut *program;
+
+    run(out_error, || {
+        let field_path = read_str(field_path, "field path")?;
+
+        program.program.field = if field_path.is_empty() {
+            FieldPath::new()
+        } else {
+            program
+                .program
+                .parse_field_path(field_path.to_owned())
+                .map_err(|err| program.report(err))?
+        };
+
+        Ok(())
+    })
+}
+
+/// Evaluates the program (or the selected field) fully, and returns the result as a value tree,
+/// which must be freed with [nickel_value_free]. The result must be serializable: it can't
+/// contain functions, for example.
+///
+/// # Safety
+///
+/// `program` must be a valid program, and `out_value` a valid pointer. `out_error` must be either
+/// null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_eval(
+    program: *mut nickel_program,
+    out_value: *mut *mut nickel_value,
+    out_error: *mut *mut nickel_error,
+) -> nickel_result {
+    let program = &mut *program;
+
+    run_with_output(out_value, out_error, || {
+        let rt = program.eval_for_export()?;
+        let value = nickel_value::from_term(&rt).map_err(|err| program.report(err))?;
+
+        Ok(Box::into_raw(Box::new(value)))
+    })
+}
+
+/// Evaluates the program (or the selected field) fully, and serializes the result to the given
+/// format, one of the [nickel_export_format] values. The returned string must be freed with
+/// [nickel_string_free].
+///
+/// # Safety
+///
+/// `program` must be a valid program, and `out_string` a valid pointer. `out_error` must be
+/// either null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_program_export(
+    program: *mut nickel_program,
+    format: u32,
+    out_string: *mut *mut c_char,
+    out_error: *mut *mut nickel_error,
+) -> nickel_result {
+    let program = &mut *program;
+
+    run_with_output(out_string, out_error, || {
+        let format = nickel_export_format::from_raw(format)
+            .ok_or_else(|| format!("unknown export format {format}"))?;
+        let rt = program.eval_for_export()?;
+        let serialized = serialize::validate(format, &rt)
+            .and_then(|()| serialize::to_string(format, &rt))
+            .map_err(|err| program.report(err))?;
+        let serialized = CString::new(serialized).map_err(|_| {
+            program.report(IOError(
+                "the serialized output contains a null character".to_owned(),
+            ))
+        })?;
+
+        Ok(serialized.into_raw())
+    })
+}
+
+/// Frees a string returned by [nickel_program_export]. Does nothing if `s` is null.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this API which hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Returns the rendered diagnostics of an error. The string is valid until the error is freed.
+///
+/// # Safety
+///
+/// `error` must be a valid error.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_error_message(error: *const nickel_error) -> *const c_char {
+    (*error).message.as_ptr()
+}
+
+/// Frees an error. Does nothing if `error` is null.
+///
+/// # Safety
+///
+/// `error` must be null or an error returned by this API which hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_error_free(error: *mut nickel_error) {
+    if !error.is_null() {
+        drop(Box::from_raw(error));
+    }
+}
+
+/// Returns the kind of a value.
+///
+/// # Safety
+///
+/// `value` must be a valid value.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_value_get_kind(value: *const nickel_value) -> nickel_value_kind {
+    use nickel_value_kind::*;
+
+    match &(*value).0 {
+        Value::Null => NICKEL_VALUE_NULL,
+        Value::Bool(_) => NICKEL_VALUE_BOOL,
+        Value::Number(_) => NICKEL_VALUE_NUMBER,
+        Value::String(_) => NICKEL_VALUE_STRING,
+        Value::Enum(_) => NICKEL_VALUE_ENUM,
+        Value::Array(_) => NICKEL_VALUE_ARRAY,
+        Value::Record(_) => NICKEL_VALUE_RECORD,
+    }
+}
+
+/// Returns the value of a boolean, or false if `value` isn't a boolean.
+///
+/// # Safety
+///
+/// `value` must be a valid value.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_value_as_bool(value: *const nickel_value) -> bool {
+    matches!((*value).0, Value::Bool(true))
+}
+
+/// Returns the closest double to a number, or 0 if `value` isn't a number.
+///
+/// # Safety
+///
+/// `value` must be a valid value.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_value_as_f64(value: *const nickel_value) -> f64 {
+    match &(*value).0 {
+        Value::Number(n) => f64::rounding_from(n, RoundingMode::Nearest).0,
+        _ => 0.0,
+    }
+}
+
+/// Stores a number in `out` if it's an integer that fits in 64 bits. Returns false, leaving `out`
+/// untouched, if it doesn't, if `value` isn't a number or if `out` is null.
+///
+/// # Safety
+///
+/// `value` must be a valid value, and `out` either null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_value_as_i64(value: *const nickel_value, out: *mut i64) -> bool {
+    if out.is_null() {
+        return false;
+    }
+
+    match &(*value).0 {
+        Value::Number(n) => match i64::try_from(n) {
+            Ok(i) => {
+                *out = i;
+                true
+            }
+            Err(_) => false,
+        },
+        _ => false,
+    }
+}
+
+/// Returns the content of a string, or the name of an enum tag, as a null-terminated UTF-8
+/// string. The length in bytes, which doesn't include the null terminator, is stored in `out_len`
+/// if it isn't null: Nickel strings can contain null characters. Returns null if `value` is
+/// neither a string nor an enum tag.
+///
+/// # Safety
+///
+/// `value` must be a valid value, and `out_len` either null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_value_as_string(
+    value: *const nickel_value,
+    out_len: *mut usize,
+) -> *const c_char {
+    match &(*value).0 {
+        Value::String(s) | Value::Enum(s) => borrow_str(s, out_len),
+        _ => ptr::null(),
+    }
+}
+
+unsafe fn borrow_str(s: &[u8], out_len: *mut usize) -> *const c_char {
+    if !out_len.is_null() {
+        *out_len = s.len() - 1;
+    }
+
+    s.as_ptr() as *const c_char
+}
+
+/// Returns the number of elements of an array, or the number of fields of a record. Returns 0
+/// for other values.
+///
+/// # Safety
+///
+/// `value` must be a valid value.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_value_len(value: *const nickel_value) -> usize {
+    match &(*value).0 {
+        Value::Array(elts) => elts.len(),
+        Value::Record(fields) => fields.len(),
+        _ => 0,
+    }
+}
+
+/// Returns the element at `index` of an array, or null if `value` isn't an array or if `index`
+/// is out of bounds.
+///
+/// # Safety
+///
+/// `value` must be a valid value.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_value_array_get(
+    value: *const nickel_value,
+    index: usize,
+) -> *const nickel_value {
+    match &(*value).0 {
+        Value::Array(elts) => elts.get(index).map_or(ptr::null(), |elt| elt as *const _),
+        _ => ptr::null(),
+    }
+}
+
+/// Returns the name of the field at `index` of a record, where fields are sorted by name. The
+/// length in bytes of the name is stored in `out_len` if it isn't null. Returns null if `value`
+/// isn't a record or if `index` is out of bounds.
+///
+/// # Safety
+///
+/// `value` must be a valid value, and `out_len` either null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_value_record_key(
+    value: *const nickel_value,
+    index: usize,
+    out_len: *mut usize,
+) -> *const c_char {
+    match &(*value).0 {
+        Value::Record(fields) => fields
+            .get(index)
+            .map_or(ptr::null(), |(key, _)| borrow_str(key, out_len)),
+        _ => ptr::null(),
+    }
+}
+
+/// Returns the value of the field at `index` of a record, where fields are sorted by name.
+/// Returns null if `value` isn't a record or if `index` is out of bounds.
+///
+/// # Safety
+///
+/// `value` must be a valid value.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_value_record_value(
+    value: *const nickel_value,
+    index: usize,
+) -> *const nickel_value {
+    match &(*value).0 {
+        Value::Record(fields) => fields
+            .get(index)
+            .map_or(ptr::null(), |(_, value)| value as *const _),
+        _ => ptr::null(),
+    }
+}
+
+/// Returns the value of the field `name` of a record, or null if `value` isn't a record or
+/// doesn't have such a field.
+///
+/// # Safety
+///
+/// `value` must be a valid value, and `name` a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_value_record_get(
+    value: *const nickel_value,
+    name: *const c_char,
+) -> *const nickel_value {
+    let name = CStr::from_ptr(name).to_bytes_with_nul();
+
+    match &(*value).0 {
+        Value::Record(fields) => fields
+            .binary_search_by(|(key, _)| key.as_slice().cmp(name))
+            .map_or(ptr::null(), |index| &fields[index].1 as *const _),
+        _ => ptr::null(),
+    }
+}
+
+/// Frees a value returned by [nickel_program_eval], together with all its children. Does nothing
+/// if `value` is null.
+///
+/// # Safety
+///
+/// `value` must be null or a value returned by [nickel_program_eval] which hasn't been freed yet.
+/// In particular, it must not be a child of another value.
+#[no_mangle]
+pub unsafe extern "C" fn nickel_value_free(value: *mut nickel_value) {
+    if !value.is_null() {
+        drop(Box::from_raw(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(source: &str) -> *mut nickel_program {
+        let source = CString::new(source).unwrap();
+        let mut program = ptr::null_mut();
+
+        let result = unsafe {
+            nickel_program_new_from_source(
+                source.as_ptr(),
+                c"<test>".as_ptr(),
+                &mut program,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(result, NICKEL_RESULT_OK);
+
+        program
+    }
+
+    unsafe fn string(value: *const nickel_value) -> &'static str {
+        let mut len = 0;
+        let s = nickel_value_as_string(value, &mut len);
+        std::str::from_utf8(std::slice::from_raw_parts(s as *const u8, len)).unwrap()
+    }
+
+    #[test]
+    fn eval_value_tree() {
+        let program = program(r#"{ b = [1, 2.5, "héllo"], a = { c = 'Foo, d = null } }"#);
+
+        unsafe {
+            let mut value = ptr::null_mut();
+            let result = nickel_program_eval(program, &mut value, ptr::null_mut());
+            assert_eq!(result, NICKEL_RESULT_OK);
+
+            assert_eq!(
+                nickel_value_get_kind(value),
+                nickel_value_kind::NICKEL_VALUE_RECORD
+            );
+            assert_eq!(nickel_value_len(value), 2);
+            assert_eq!(
+                CStr::from_ptr(nickel_value_record_key(value, 0, ptr::null_mut())),
+                c"a"
+            );
+
+            let b = nickel_value_record_get(value, c"b".as_ptr());
+            let mut i = 0;
+            assert!(nickel_value_as_i64(nickel_value_array_get(b, 0), &mut i));
+            assert_eq!(i, 1);
+            assert!(!nickel_value_as_i64(nickel_value_array_get(b, 1), &mut i));
+            assert_eq!(nickel_value_as_f64(nickel_value_array_get(b, 1)), 2.5);
+            assert_eq!(string(nickel_value_array_get(b, 2)), "héllo");
+            assert!(nickel_value_array_get(b, 3).is_null());
+
+            let a = nickel_value_record_value(value, 0);
+            let c = nickel_value_record_get(a, c"c".as_ptr());
+            assert_eq!(
+                nickel_value_get_kind(c),
+                nickel_value_kind::NICKEL_VALUE_ENUM
+            );
+            assert_eq!(string(c), "Foo");
+            assert!(nickel_value_record_get(a, c"e".as_ptr()).is_null());
+
+            nickel_value_free(value);
+            nickel_program_free(program);
+        }
+    }
+
+    #[test]
+    fn export_field_with_overrides() {
+        let program = program("{ server = { host | String, port | default = 80 } }");
+
+        unsafe {
+            let assignments = [c"server.host = \"localhost\"", c"server.port = 8080"];
+
+            for (assignment, force) in assignments.iter().zip([false, true]) {
+                let result = nickel_program_add_override(
+                    program,
+                    assignment.as_ptr(),
+                    force,
+                    ptr::null_mut(),
+                );
+                assert_eq!(result, NICKEL_RESULT_OK);
+            }
+
+            let result = nickel_program_set_field(program, c"server".as_ptr(), ptr::null_mut());
+            assert_eq!(result, NICKEL_RESULT_OK);
+
+            let mut output = ptr::null_mut();
+            let result = nickel_program_export(
+                program,
+                nickel_export_format::NICKEL_EXPORT_FORMAT_TOML as u32,
+                &mut output,
+                ptr::null_mut(),
+            );
+            assert_eq!(result, NICKEL_RESULT_OK);
+            assert_eq!(
+                CStr::from_ptr(output).to_str().unwrap(),
+                "host = \"localhost\"\nport = 8080\n"
+            );
+
+            nickel_string_free(output);
+            nickel_program_free(program);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let program = program("{ a = 1 + \"a\" }");
+
+        unsafe {
+            let mut value = ptr::null_mut();
+            let mut error = ptr::null_mut();
+            let result = nickel_program_eval(program, &mut value, &mut error);
+            assert_eq!(result, NICKEL_RESULT_ERR);
+            assert!(value.is_null());
+
+            let message = CStr::from_ptr(nickel_error_message(error))
+                .to_str()
+                .unwrap();
+            assert!(
+                message.starts_with("error: dynamic type error"),
+                "{message}"
+            );
+
+            nickel_error_free(error);
+            nickel_program_free(program);
+        }
+    }
+
+    unsafe fn error_message(error: *mut nickel_error) -> String {
+        let message = CStr::from_ptr(nickel_error_message(error))
+            .to_str()
+            .unwrap()
+            .to_owned();
+        nickel_error_free(error);
+        message
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let program = program("{ a = 1 }");
+
+        unsafe {
+            let mut error = ptr::null_mut();
+            let result = nickel_program_eval(program, ptr::null_mut(), &mut error);
+            assert_eq!(result, NICKEL_RESULT_ERR);
+            assert_eq!(
+                error_message(error),
+                "the output argument is a null pointer"
+            );
+
+            let mut output = ptr::null_mut();
+            let result = nickel_program_export(program, 42, &mut output, &mut error);
+            assert_eq!(result, NICKEL_RESULT_ERR);
+            assert!(output.is_null());
+            assert_eq!(error_message(error), "unknown export format 42");
+
+            let result = nickel_program_new_from_source(
+                c"1".as_ptr(),
+                c"<test>".as_ptr(),
+                ptr::null_mut(),
+                &mut error,
+            );
+            assert_eq!(result, NICKEL_RESULT_ERR);
+            assert_eq!(
+                error_message(error),
+                "the output argument is a null pointer"
+            );
+
+            nickel_program_free(program);
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use std::{fs, path::Path};
+
+/// Checks that `include/nickel_lang.h` is the header generated from the current sources.
+#[test]
+fn header_is_up_to_date() {
+    let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap();
+
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_crate(crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("failed to generate the header")
+        .write(&mut generated);
+
+    let header = fs::read(crate_dir.join("include/nickel_lang.h")).unwrap();
+
+    assert!(
+        header == generated,
+        "include/nickel_lang.h is out of date, regenerate it with \
+        `cbindgen --config cbindgen.toml --output include/nickel_lang.h` from the capi directory"
+    );
+}
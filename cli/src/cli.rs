@@ -3,8 +3,9 @@
 use git_version::git_version;
 
 use crate::{
-    completions::GenCompletionsCommand, eval::EvalCommand, export::ExportCommand,
-    pprint_ast::PprintAstCommand, query::QueryCommand, typecheck::TypecheckCommand,
+    completions::GenCompletionsCommand, diff::DiffCommand, eval::EvalCommand,
+    export::ExportCommand, package::PackageCommand, pprint_ast::PprintAstCommand,
+    query::QueryCommand, schema::SchemaCommand, typecheck::TypecheckCommand,
 };
 
 use nickel_lang_core::error::report::ErrorFormat;
@@ -70,10 +71,16 @@ pub enum Command {
     PprintAst(PprintAstCommand),
     /// Evaluates a Nickel program and serializes the result to a given format
     Export(ExportCommand),
+    /// Evaluates two Nickel programs and reports the differences between the results
+    Diff(DiffCommand),
     /// Prints the metadata attached to an attribute, given as a path
     Query(QueryCommand),
+    /// Generates a JSON schema from a record contract
+    Schema(SchemaCommand),
     /// Typechecks the program but does not run it
     Typecheck(TypecheckCommand),
+    /// Manages the dependencies of a package
+    Package(PackageCommand),
     /// Starts a REPL session
     #[cfg(feature = "repl")]
     Repl(ReplCommand),
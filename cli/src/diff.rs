@@ -0,0 +1,192 @@
+use std::{fmt, io::Write, path::PathBuf};
+
+use nickel_lang_core::{
+    diff::{self, Change, Difference},
+    pretty::PrettyPrintCap,
+    serialize::{self, ExportFormat},
+    term::RichTerm,
+};
+use serde::Serialize;
+
+use crate::{
+    cli::GlobalOptions,
+    error::{CliResult, ResultErrorExt},
+    input::{EvalLimitOptions, SourceOptions},
+};
+
+const VALUE_MAX_WIDTH: usize = 80;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, clap::ValueEnum)]
+pub enum DiffFormat {
+    /// One line per difference, prefixed with `+` (added), `-` (removed) or `~` (changed)
+    #[default]
+    Text,
+    /// An array of objects, one per difference
+    Json,
+}
+
+impl fmt::Display for DiffFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct DiffCommand {
+    /// The old version of the configuration
+    pub old: PathBuf,
+
+    /// The new version of the configuration
+    pub new: PathBuf,
+
+    #[arg(long, short, value_enum, default_value_t)]
+    pub format: DiffFormat,
+
+    /// Also compare the metadata of fields (documentation, type and contract annotations,
+    /// priority and optionality)
+    #[arg(long)]
+    pub metadata: bool,
+
+    #[command(flatten)]
+    pub limits: EvalLimitOptions,
+
+    #[command(flatten)]
+    pub sources: SourceOptions,
+}
+
+/// A difference as serialized in the JSON output. Values are serialized as JSON as well, while
+/// metadata are rendered as strings.
+#[derive(Serialize)]
+#[serde(tag = "change", rename_all = "lowercase")]
+enum DiffEntry<'a> {
+    Added {
+        path: String,
+        value: &'a RichTerm,
+    },
+    Removed {
+        path: String,
+        value: &'a RichTerm,
+    },
+    Changed {
+        path: String,
+        old: &'a RichTerm,
+        new: &'a RichTerm,
+    },
+    Metadata {
+        path: String,
+        attribute: &'static str,
+        old: Option<&'a str>,
+        new: Option<&'a str>,
+    },
+}
+
+impl DiffCommand {
+    pub fn run(self, global: GlobalOptions) -> CliResult<()> {
+        let old = self.eval(&self.old, &global)?;
+        let new = self.eval(&self.new, &global)?;
+        let differences = diff::diff(&old, &new, self.metadata);
+
+        match self.format {
+            DiffFormat::Text => print_text(&differences),
+            DiffFormat::Json => print_json(&differences),
+        }
+    }
+
+    fn eval(&self, file: &PathBuf, global: &GlobalOptions) -> CliResult<RichTerm> {
+        let mut program = self.sources.load(std::slice::from_ref(file), global)?;
+        program.set_eval_limits(self.limits.limits());
+
+        let result = program.eval_full_for_export().and_then(|rt| {
+            // Values must be serializable to JSON to be part of the JSON output.
+            if self.format == DiffFormat::Json {
+                serialize::validate(ExportFormat::Json, &rt)?;
+            }
+
+            Ok(rt)
+        });
+
+        result.report_with_program(program)
+    }
+}
+
+fn print_text(differences: &[Difference]) -> CliResult<()> {
+    let mut out = std::io::stdout().lock();
+
+    for Difference { path, change } in differences {
+        let path = path.to_string();
+        let path = display_path(&path);
+
+        match change {
+            Change::Added(value) => writeln!(out, "+ {path} = {}", cap(value))?,
+            Change::Removed(value) => writeln!(out, "- {path} = {}", cap(value))?,
+            Change::Changed { old, new } => {
+                writeln!(out, "~ {path} = {} -> {}", cap(old), cap(new))?
+            }
+            Change::Metadata(changes) => {
+                // Documentation is often multi-line, so we escape newlines to keep one line per
+                // difference.
+                let render = |value: &Option<String>| {
+                    value.as_deref().unwrap_or("<none>").replace('\n', "\\n")
+                };
+
+                for change in changes {
+                    writeln!(
+                        out,
+                        "~ {path} | {}: {} -> {}",
+                        change.attribute,
+                        render(&change.old),
+                        render(&change.new),
+                    )?
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_json(differences: &[Difference]) -> CliResult<()> {
+    let entries: Vec<_> = differences
+        .iter()
+        .flat_map(|Difference { path, change }| {
+            let path = path.to_string();
+
+            match change {
+                Change::Added(value) => vec![DiffEntry::Added { path, value }],
+                Change::Removed(value) => vec![DiffEntry::Removed { path, value }],
+                Change::Changed { old, new } => vec![DiffEntry::Changed { path, old, new }],
+                Change::Metadata(changes) => changes
+                    .iter()
+                    .map(|change| DiffEntry::Metadata {
+                        path: path.clone(),
+                        attribute: change.attribute,
+                        old: change.old.as_deref(),
+                        new: change.new.as_deref(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let mut out = std::io::stdout().lock();
+    serde_json::to_writer_pretty(&mut out, &entries).map_err(std::io::Error::from)?;
+    writeln!(out)?;
+
+    Ok(())
+}
+
+/// The path of the root of the configuration is empty, which isn't very readable.
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "<root>"
+    } else {
+        path
+    }
+}
+
+fn cap(value: &RichTerm) -> String {
+    value.pretty_print_cap(VALUE_MAX_WIDTH)
+}
@@ -14,7 +14,7 @@ use crate::{
     cli::GlobalOptions,
     customize::ExtractFieldOnly,
     error::{CliResult, ResultErrorExt},
-    input::{InputOptions, Prepare},
+    input::{EvalLimitOptions, InputOptions, Prepare},
 };
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, clap::ValueEnum)]
@@ -56,6 +56,9 @@ pub struct DocCommand {
     #[arg(long, value_enum, default_value_t)]
     pub format: crate::doc::DocFormat,
 
+    #[command(flatten)]
+    pub limits: EvalLimitOptions,
+
     #[command(flatten)]
     pub input: InputOptions<ExtractFieldOnly>,
 }
@@ -65,6 +68,7 @@ const DEFAULT_OUT_DIR: &str = ".nickel/doc/";
 impl DocCommand {
     pub fn run(self, global: GlobalOptions) -> CliResult<()> {
         let mut program = self.input.prepare(&global)?;
+        program.set_eval_limits(self.limits.limits());
         self.export_doc(&mut program).report_with_program(program)
     }
 
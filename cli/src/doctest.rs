@@ -27,11 +27,14 @@ use crate::{
     cli::GlobalOptions,
     customize::ExtractFieldOnly,
     error::CliResult,
-    input::{InputOptions, Prepare},
+    input::{EvalLimitOptions, InputOptions, Prepare},
 };
 
 #[derive(clap::Parser, Debug)]
 pub struct TestCommand {
+    #[command(flatten)]
+    pub limits: EvalLimitOptions,
+
     #[command(flatten)]
     pub input: InputOptions<ExtractFieldOnly>,
 }
@@ -233,6 +236,7 @@ fn run_tests(
 impl TestCommand {
     pub fn run(self, global: GlobalOptions) -> CliResult<()> {
         let mut program = self.input.prepare(&global)?;
+        program.set_eval_limits(self.limits.limits());
 
         let (spine, registry) = match self.prepare_tests(&mut program) {
             Ok(x) => x,
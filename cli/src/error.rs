@@ -3,10 +3,11 @@
 use nickel_lang_core::{
     error::{
         report::{ColorOpt, ErrorFormat},
-        Diagnostic, IntoDiagnostics, ParseError,
+        Diagnostic, Error as CoreError, EvalError, IntoDiagnostics, Label, ParseError,
     },
     eval::cache::lazy::CBNCache,
     files::{FileId, Files},
+    json_schema::UnsupportedContract,
     program::{FieldOverride, FieldPath, Program},
 };
 
@@ -61,6 +62,9 @@ pub enum Error {
     /// Upon receiving this error, the caller should simply exit without proceeding with evaluation.
     CustomizeInfoPrinted,
     FailedTests,
+    Package {
+        error: nickel_lang_core::package::Error,
+    },
 }
 
 impl IntoDiagnostics for CliUsageError {
@@ -159,23 +163,55 @@ pub enum Warning {
     /// querying won't show most information, and it's most probably not
     /// what the user wanted.
     EmptyQueryPath,
+    /// A contract couldn't be translated when generating a JSON schema, and has been replaced
+    /// with a schema accepting any value.
+    UnsupportedSchemaContract(UnsupportedContract),
 }
 
 impl IntoDiagnostics for Warning {
     fn into_diagnostics(self, _files: &mut Files) -> Vec<Diagnostic<FileId>> {
-        vec![Diagnostic::warning()
-            .with_message("empty query path")
-            .with_notes(vec![
-                "You queried a value without requesting a specific field path. \
-            This operation can't find any metadata, beside listing the fields of a record."
-                    .into(),
-                "Try to query the root configuration and provide a query path instead.".into(),
-                "For example, instead of querying the expression \
-            `(import \"config.ncl\").module.input` with an empty path, query \
-            `config.ncl` with the `module.input` path: \
-            \n`nickel query config.ncl --field module.input"
-                    .into(),
-            ])]
+        match self {
+            Warning::EmptyQueryPath => vec![Diagnostic::warning()
+                .with_message("empty query path")
+                .with_notes(vec![
+                    "You queried a value without requesting a specific field path. \
+                This operation can't find any metadata, beside listing the fields of a record."
+                        .into(),
+                    "Try to query the root configuration and provide a query path instead.".into(),
+                    "For example, instead of querying the expression \
+                `(import \"config.ncl\").module.input` with an empty path, query \
+                `config.ncl` with the `module.input` path: \
+                \n`nickel query config.ncl --field module.input"
+                        .into(),
+                ])],
+            Warning::UnsupportedSchemaContract(UnsupportedContract {
+                path,
+                contract,
+                pos,
+            }) => {
+                let labels = pos
+                    .into_opt()
+                    .map(|span| {
+                        Label::primary(span.src_id, span.start.to_usize()..span.end.to_usize())
+                    })
+                    .into_iter()
+                    .collect();
+                let path = if path.0.is_empty() {
+                    String::from("the configuration")
+                } else {
+                    format!("`{path}`")
+                };
+
+                vec![Diagnostic::warning()
+                    .with_message(format!(
+                        "contract `{contract}` can't be represented in JSON Schema"
+                    ))
+                    .with_labels(labels)
+                    .with_notes(vec![format!(
+                        "The generated schema doesn't check this contract for {path}."
+                    )])]
+            }
+        }
     }
 }
 
@@ -187,6 +223,21 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<nickel_lang_core::package::Error> for Error {
+    fn from(error: nickel_lang_core::package::Error) -> Self {
+        match error {
+            // Evaluation errors are better reported by the program that produced them.
+            nickel_lang_core::package::Error::ManifestEval { program, error, .. } => {
+                Error::Program {
+                    program: *program,
+                    error: *error,
+                }
+            }
+            error => Error::Package { error },
+        }
+    }
+}
+
 #[cfg(feature = "format")]
 impl From<crate::format::FormatError> for Error {
     fn from(error: crate::format::FormatError) -> Self {
@@ -233,7 +284,24 @@ impl Error {
         // the same format set (potentitally by default) by the `--error-format` flag. This also
         // makes error styling more consistent.
         match self {
-            Error::Program { mut program, error } => program.report(error, format),
+            Error::Program { mut program, error } => {
+                let limit_exceeded = matches!(
+                    error,
+                    CoreError::EvalError(
+                        EvalError::StepLimitExceeded { .. }
+                            | EvalError::StackLimitExceeded { .. }
+                            | EvalError::MemoryLimitExceeded { .. }
+                            | EvalError::TimeLimitExceeded { .. }
+                    )
+                );
+                program.report(error, format);
+
+                // The state of an evaluation aborted by a resource limit can be arbitrarily deep,
+                // and the process is about to exit anyway: we don't risk dropping it recursively.
+                if limit_exceeded {
+                    std::mem::forget(program);
+                }
+            }
             Error::Io { error } => {
                 report_standalone("IO error", Some(error.to_string()));
             }
@@ -253,6 +321,19 @@ impl Error {
             Error::Format { error } => report_standalone("format error", Some(error.to_string())),
             Error::CliUsage { error, mut program } => program.report(error, format),
             Error::FailedTests => report_standalone("tests failed", None),
+            Error::Package { error } => {
+                use nickel_lang_core::{
+                    cache::{Cache, ErrorTolerance},
+                    error::report::report as core_report,
+                };
+
+                core_report(
+                    &mut Cache::new(ErrorTolerance::Tolerant),
+                    error,
+                    format,
+                    color,
+                );
+            }
             Error::CustomizeInfoPrinted => {
                 // Nothing to do, the caller should simply exit.
             }
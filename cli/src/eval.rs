@@ -2,11 +2,20 @@ use crate::{
     cli::GlobalOptions,
     customize::CustomizeMode,
     error::{CliResult, ResultErrorExt},
-    input::{InputOptions, Prepare},
+    input::{EvalLimitOptions, InputOptions, Prepare},
+    watch::watch,
 };
 
 #[derive(clap::Parser, Debug)]
 pub struct EvalCommand {
+    /// Evaluate the program again each time one of its source files (the input files or the
+    /// files they import) is modified, until interrupted
+    #[arg(long, requires = "files")]
+    pub watch: bool,
+
+    #[command(flatten)]
+    pub limits: EvalLimitOptions,
+
     #[command(flatten)]
     pub input: InputOptions<CustomizeMode>,
 }
@@ -14,6 +23,13 @@ pub struct EvalCommand {
 impl EvalCommand {
     pub fn run(self, global: GlobalOptions) -> CliResult<()> {
         let mut program = self.input.prepare(&global)?;
+        program.set_eval_limits(self.limits.limits());
+
+        if self.watch {
+            watch(&mut program, &global, |program| {
+                program.eval_full().map(|t| println!("{t}"))
+            })
+        }
 
         program
             .eval_full()
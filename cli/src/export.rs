@@ -1,17 +1,24 @@
-use std::{fs, io::Write, path::PathBuf};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    thread,
+};
 
 use nickel_lang_core::{
     error::{Error, IOError},
     eval::cache::lazy::CBNCache,
     program::Program,
     serialize::{self, ExportFormat},
+    term::RichTerm,
 };
 
 use crate::{
     cli::GlobalOptions,
     customize::CustomizeMode,
     error::{CliResult, ResultErrorExt},
-    input::{InputOptions, Prepare},
+    input::{ContractReportOptions, EvalLimitOptions, InputOptions, Prepare, ProfileOptions},
+    watch::watch,
 };
 
 #[derive(clap::Parser, Debug)]
@@ -23,6 +30,46 @@ pub struct ExportCommand {
     #[arg(short, long)]
     pub output: Option<PathBuf>,
 
+    /// Export multiple files at once into the given directory. The program must evaluate to a
+    /// record mapping relative file paths to the content of each file. The format of each file is
+    /// inferred from its extension, and defaults to `--format`. It can also be given explicitly
+    /// as `{ format = 'Yaml, content = <value> }`.
+    #[arg(long, conflicts_with = "output")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Export the program again each time one of its source files (the input files or the files
+    /// they import) is modified, until interrupted
+    #[arg(
+        long,
+        requires = "files",
+        conflicts_with_all = ["profile", "contract_report"]
+    )]
+    pub watch: bool,
+
+    /// Evaluates the fields of the program on the given number of threads, or on as many threads
+    /// as there are CPUs if 0. Top-level fields are split into the fields of nested record
+    /// literals without contracts, but a computed record or a field with a contract is evaluated
+    /// by a single thread. Each thread loads its own copy of the program, so this only pays off
+    /// for programs whose fields are expensive to evaluate and mostly independent. Evaluation
+    /// limits apply to each thread separately
+    #[arg(
+        long,
+        short = 'j',
+        value_name = "N",
+        requires = "files",
+        conflicts_with_all = ["profile", "contract_report"]
+    )]
+    pub jobs: Option<usize>,
+
+    #[command(flatten)]
+    pub limits: EvalLimitOptions,
+
+    #[command(flatten)]
+    pub profile: ProfileOptions,
+
+    #[command(flatten)]
+    pub contract_report: ContractReportOptions,
+
     #[command(flatten)]
     pub input: InputOptions<CustomizeMode>,
 }
@@ -30,12 +77,46 @@ pub struct ExportCommand {
 impl ExportCommand {
     pub fn run(self, global: GlobalOptions) -> CliResult<()> {
         let mut program = self.input.prepare(&global)?;
+        program.set_eval_limits(self.limits.limits());
+        self.profile.enable(&mut program);
+        self.contract_report.enable(&mut program);
 
-        self.export(&mut program).report_with_program(program)
+        if self.watch {
+            watch(&mut program, &global, |program| {
+                self.export(program, &global)
+            })
+        }
+
+        // The profile and the contract report are written even if the export fails, as they can
+        // help to understand a timeout or a contract failure, for example.
+        let result = self.export(&mut program, &global);
+        result
+            .and(self.profile.write(&program))
+            .and(self.contract_report.write(&program))
+            .report_with_program(program)
     }
 
-    fn export(self, program: &mut Program<CBNCache>) -> Result<(), Error> {
-        let rt = program.eval_full_for_export()?;
+    fn export(&self, program: &mut Program<CBNCache>, global: &GlobalOptions) -> Result<(), Error> {
+        let rt = match self.jobs {
+            Some(jobs) => {
+                let jobs = if jobs == 0 {
+                    thread::available_parallelism().map_or(1, |n| n.get())
+                } else {
+                    jobs
+                };
+
+                program.eval_full_for_export_parallel(jobs, || {
+                    let mut program = self.input.prepare(global).ok()?;
+                    program.set_eval_limits(self.limits.limits());
+                    Some(program)
+                })?
+            }
+            None => program.eval_full_for_export()?,
+        };
+
+        if let Some(output_dir) = &self.output_dir {
+            return export_tree(output_dir, self.format, &rt);
+        }
 
         // We only add a trailing newline for JSON exports. Both YAML and TOML
         // exporters already append a trailing newline by default.
@@ -43,7 +124,7 @@ impl ExportCommand {
 
         serialize::validate(self.format, &rt)?;
 
-        if let Some(file) = self.output {
+        if let Some(file) = &self.output {
             let mut file = fs::File::create(file).map_err(IOError::from)?;
             serialize::to_writer(&mut file, self.format, &rt)?;
 
@@ -61,3 +142,29 @@ impl ExportCommand {
         Ok(())
     }
 }
+
+/// Writes all the files of a multi-file export under `output_dir`. All the files are validated
+/// before anything is written.
+fn export_tree(output_dir: &Path, format: ExportFormat, rt: &RichTerm) -> Result<(), Error> {
+    let files = serialize::tree::files(rt, format)?;
+
+    fs::create_dir_all(output_dir).map_err(IOError::from)?;
+    serialize::tree::check_output_dir(output_dir, &files)?;
+
+    for file in files {
+        let path = output_dir.join(&file.path);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(IOError::from)?;
+        }
+
+        let mut out = fs::File::create(&path).map_err(IOError::from)?;
+        serialize::to_writer(&mut out, file.format, &file.value)?;
+
+        if file.format == ExportFormat::Json {
+            writeln!(out).map_err(IOError::from)?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,8 +1,26 @@
-use std::path::PathBuf;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    time::Duration,
+};
 
-use nickel_lang_core::{eval::cache::lazy::CBNCache, program::Program};
+use nickel_lang_core::{
+    cache::ImportPolicy,
+    disk_cache::DiskCache,
+    error::{Error, IOError},
+    eval::{
+        cache::lazy::CBNCache,
+        contract_trace::ContractReportFormat,
+        limits::EvalLimits,
+        profiler::{ProfileFormat, ProfileWeight},
+    },
+    program::Program,
+};
 
-use crate::{cli::GlobalOptions, customize::Customize, error::CliResult};
+use crate::{
+    cli::GlobalOptions, customize::Customize, error::CliResult, package::load_package_map,
+};
 
 #[derive(clap::Parser, Debug)]
 pub struct InputOptions<Customize: clap::Args> {
@@ -10,6 +28,17 @@ pub struct InputOptions<Customize: clap::Args> {
     /// Nickel expressions are merged (combined with `&`) to produce the result.
     pub files: Vec<PathBuf>,
 
+    #[command(flatten)]
+    pub sources: SourceOptions,
+
+    #[command(flatten)]
+    pub customize_mode: Customize,
+}
+
+/// Options controlling how a program and its imports are loaded, independently from the way the
+/// input files are specified.
+#[derive(clap::Parser, Debug)]
+pub struct SourceOptions {
     #[cfg(debug_assertions)]
     /// Skips the standard library import. For debugging only
     #[arg(long, global = true)]
@@ -24,17 +53,46 @@ pub struct InputOptions<Customize: clap::Args> {
     #[arg(long, short = 'I', global = true)]
     pub import_path: Vec<PathBuf>,
 
-    #[command(flatten)]
-    pub customize_mode: Customize,
-}
+    /// The path of the package manifest used to resolve package imports (`import <name>`).
+    ///
+    /// If omitted, the manifest is searched for in the directory of the first input file (or the
+    /// current directory when reading from stdin) and in its parent directories, except in sandbox
+    /// mode. Package imports are only available once the package has been locked with `nickel
+    /// package lock`.
+    #[arg(long, global = true)]
+    pub manifest_path: Option<PathBuf>,
 
-pub trait Prepare {
-    fn prepare(&self, global: &GlobalOptions) -> CliResult<Program<CBNCache>>;
+    /// Restricts imports, for evaluating untrusted programs.
+    ///
+    /// In sandbox mode, only the files located under the directories given by `--allow-import-dir`
+    /// can be imported, or under the directories of the input files (the current directory when
+    /// reading from stdin) if there are none. Absolute import paths are forbidden, and
+    /// `NICKEL_IMPORT_PATH` is ignored. The package manifest isn't searched for: package imports
+    /// are only available with `--manifest-path`, and the packages must be located under the
+    /// allowed directories as well.
+    #[arg(long, global = true)]
+    pub sandbox: bool,
+
+    /// Only allows importing files located under the given directory. Can be repeated to allow
+    /// several directories.
+    #[arg(long, global = true, value_name = "DIR")]
+    pub allow_import_dir: Vec<PathBuf>,
+
+    /// Enables the on-disk cache of prepared sources.
+    ///
+    /// The standard library and the input files, once parsed, typechecked and transformed, are
+    /// stored in the user's cache directory, and are loaded from there instead of being prepared
+    /// again when neither they nor their imports have changed. Setting the `NICKEL_CACHE_DIR`
+    /// environment variable enables the cache as well, and stores it in the given directory.
+    #[arg(long, global = true)]
+    pub cache: bool,
 }
 
-impl<C: clap::Args + Customize> Prepare for InputOptions<C> {
-    fn prepare(&self, global: &GlobalOptions) -> CliResult<Program<CBNCache>> {
-        let mut program = match self.files.as_slice() {
+impl SourceOptions {
+    /// Creates a program from the given input files (or from stdin if `files` is empty), set up
+    /// according to these options.
+    pub fn load(&self, files: &[PathBuf], global: &GlobalOptions) -> CliResult<Program<CBNCache>> {
+        let mut program = match files {
             [] => Program::new_from_stdin(std::io::stderr()),
             [p] => Program::new_from_file(p, std::io::stderr()),
             files => Program::new_from_files(files, std::io::stderr()),
@@ -44,8 +102,29 @@ impl<C: clap::Args + Customize> Prepare for InputOptions<C> {
 
         program.add_import_paths(self.import_path.iter());
 
-        if let Ok(nickel_path) = std::env::var("NICKEL_IMPORT_PATH") {
-            program.add_import_paths(nickel_path.split(':'));
+        if !self.sandbox {
+            if let Ok(nickel_path) = std::env::var("NICKEL_IMPORT_PATH") {
+                program.add_import_paths(nickel_path.split(':'));
+            }
+        }
+
+        if let Some(policy) = self.import_policy(files) {
+            program.set_import_policy(policy);
+        }
+
+        // The manifest and the lock file found next to an untrusted program are just as untrusted,
+        // so we only use them in sandbox mode if they've been chosen explicitly.
+        if !self.sandbox || self.manifest_path.is_some() {
+            if let Some(package_map) = load_package_map(
+                self.manifest_path.as_deref(),
+                files.first().map(PathBuf::as_path),
+            )? {
+                program.set_package_map(package_map);
+            }
+        }
+
+        if let Some(disk_cache) = self.disk_cache() {
+            program.set_disk_cache(Some(disk_cache));
         }
 
         #[cfg(debug_assertions)]
@@ -53,6 +132,194 @@ impl<C: clap::Args + Customize> Prepare for InputOptions<C> {
             program.set_skip_stdlib();
         }
 
+        Ok(program)
+    }
+
+    /// Returns the on-disk cache of prepared terms, if enabled by `--cache` or by the
+    /// `NICKEL_CACHE_DIR` environment variable. It is located in the directory given by
+    /// `NICKEL_CACHE_DIR` if set, or in the user's cache directory otherwise.
+    fn disk_cache(&self) -> Option<DiskCache> {
+        if let Some(dir) = std::env::var_os("NICKEL_CACHE_DIR") {
+            return Some(DiskCache::new(dir));
+        }
+
+        if !self.cache {
+            return None;
+        }
+
+        let dirs = directories::ProjectDirs::from("org", "nickel-lang", "nickel")?;
+        Some(DiskCache::new(dirs.cache_dir().join("terms")))
+    }
+
+    /// Returns the import policy corresponding to `--sandbox` and `--allow-import-dir`, if any.
+    fn import_policy(&self, files: &[PathBuf]) -> Option<ImportPolicy> {
+        if self.sandbox {
+            let allowed_dirs = if !self.allow_import_dir.is_empty() {
+                self.allow_import_dir.clone()
+            } else if files.is_empty() {
+                vec![PathBuf::from(".")]
+            } else {
+                files
+                    .iter()
+                    .map(|file| match file.parent() {
+                        Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+                        _ => PathBuf::from("."),
+                    })
+                    .collect()
+            };
+
+            Some(ImportPolicy::sandbox(allowed_dirs))
+        } else if !self.allow_import_dir.is_empty() {
+            Some(ImportPolicy {
+                allowed_dirs: Some(self.allow_import_dir.clone()),
+                ..Default::default()
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Resource limits of the evaluation, for evaluating untrusted programs.
+#[derive(clap::Parser, Debug)]
+pub struct EvalLimitOptions {
+    /// Aborts the evaluation after the given number of reduction steps
+    #[arg(long)]
+    pub max_steps: Option<u64>,
+
+    /// Aborts the evaluation when its stack grows deeper than the given number of elements
+    #[arg(long)]
+    pub max_stack_depth: Option<usize>,
+
+    /// Aborts the evaluation when the memory usage of the process exceeds the given number of
+    /// megabytes. The memory usage is checked periodically, and only on Linux
+    #[arg(long, value_name = "MEGABYTES")]
+    pub max_memory: Option<u64>,
+
+    /// Aborts the evaluation after the given number of seconds
+    #[arg(long, value_name = "SECONDS")]
+    pub timeout: Option<u64>,
+}
+
+impl EvalLimitOptions {
+    pub fn limits(&self) -> EvalLimits {
+        EvalLimits {
+            max_steps: self.max_steps,
+            max_stack_depth: self.max_stack_depth,
+            max_memory: self.max_memory.map(|megabytes| megabytes * 1024 * 1024),
+            timeout: self.timeout.map(Duration::from_secs),
+        }
+    }
+}
+
+/// Options to profile the evaluation.
+#[derive(clap::Parser, Debug)]
+pub struct ProfileOptions {
+    /// Profiles the evaluation, and writes the profile to the given file. Reduction steps and
+    /// time are attributed to function calls, record fields and contract applications
+    #[arg(long, value_name = "FILE")]
+    pub profile: Option<PathBuf>,
+
+    /// The format of the profile. Defaults to `speedscope` if the profile file has the `json`
+    /// extension, and to `folded` otherwise
+    #[arg(long, value_enum, requires = "profile")]
+    pub profile_format: Option<ProfileFormat>,
+
+    /// The weight of each stack in the `folded` format
+    #[arg(long, value_enum, default_value_t, requires = "profile")]
+    pub profile_weight: ProfileWeight,
+}
+
+impl ProfileOptions {
+    /// Enables profiling if a profile file was given.
+    pub fn enable(&self, program: &mut Program<CBNCache>) {
+        if self.profile.is_some() {
+            program.enable_profiling();
+        }
+    }
+
+    /// Writes the profile recorded by the program, if a profile file was given.
+    pub fn write(&self, program: &Program<CBNCache>) -> Result<(), Error> {
+        let (Some(path), Some(profiler)) = (&self.profile, program.profiler()) else {
+            return Ok(());
+        };
+
+        let format = self.profile_format.unwrap_or_else(|| {
+            if path.extension().is_some_and(|ext| ext == "json") {
+                ProfileFormat::Speedscope
+            } else {
+                ProfileFormat::Folded
+            }
+        });
+
+        let mut out = BufWriter::new(File::create(path).map_err(IOError::from)?);
+
+        match format {
+            ProfileFormat::Folded => {
+                profiler.write_folded(program.files(), self.profile_weight, &mut out)
+            }
+            ProfileFormat::Speedscope => profiler.write_speedscope(program.files(), &mut out),
+        }
+        .and_then(|()| out.flush())
+        .map_err(|err| IOError::from(err).into())
+    }
+}
+
+/// Options to report which contract annotations were checked during the evaluation.
+#[derive(clap::Parser, Debug)]
+pub struct ContractReportOptions {
+    /// Traces the contracts applied during the evaluation, and writes to the given file a report
+    /// listing, for each contract annotation of the program, whether it was checked, passed or
+    /// failed. Annotations of values which are never evaluated are never checked
+    #[arg(long, value_name = "FILE")]
+    pub contract_report: Option<PathBuf>,
+
+    /// The format of the contract report. Defaults to `json` if the report file has the `json`
+    /// extension, and to `text` otherwise
+    #[arg(long, value_enum, requires = "contract_report")]
+    pub contract_report_format: Option<ContractReportFormat>,
+}
+
+impl ContractReportOptions {
+    /// Enables contract tracing if a report file was given.
+    pub fn enable(&self, program: &mut Program<CBNCache>) {
+        if self.contract_report.is_some() {
+            program.enable_contract_tracing();
+        }
+    }
+
+    /// Writes the contract report of the program, if a report file was given.
+    pub fn write(&self, program: &Program<CBNCache>) -> Result<(), Error> {
+        let (Some(path), Some(report)) = (&self.contract_report, program.contract_report()) else {
+            return Ok(());
+        };
+
+        let format = self.contract_report_format.unwrap_or_else(|| {
+            if path.extension().is_some_and(|ext| ext == "json") {
+                ContractReportFormat::Json
+            } else {
+                ContractReportFormat::Text
+            }
+        });
+
+        let mut out = BufWriter::new(File::create(path).map_err(IOError::from)?);
+
+        match format {
+            ContractReportFormat::Text => report.write_text(program.files(), &mut out),
+            ContractReportFormat::Json => report.write_json(program.files(), &mut out),
+        }
+        .and_then(|()| out.flush())
+        .map_err(|err| IOError::from(err).into())
+    }
+}
+
+pub trait Prepare {
+    fn prepare(&self, global: &GlobalOptions) -> CliResult<Program<CBNCache>>;
+}
+
+impl<C: clap::Args + Customize> Prepare for InputOptions<C> {
+    fn prepare(&self, global: &GlobalOptions) -> CliResult<Program<CBNCache>> {
+        let program = self.sources.load(&self.files, global)?;
         self.customize_mode.customize(program)
     }
 }
@@ -14,13 +14,17 @@ mod repl;
 mod cli;
 mod completions;
 mod customize;
+mod diff;
 mod error;
 mod eval;
 mod export;
 mod input;
+mod package;
 mod pprint_ast;
 mod query;
+mod schema;
 mod typecheck;
+mod watch;
 
 use std::process::ExitCode;
 
@@ -41,8 +45,11 @@ fn main() -> ExitCode {
         Command::Eval(eval) => eval.run(opts.global),
         Command::PprintAst(pprint_ast) => pprint_ast.run(opts.global),
         Command::Export(export) => export.run(opts.global),
+        Command::Diff(diff) => diff.run(opts.global),
         Command::Query(query) => query.run(opts.global),
+        Command::Schema(schema) => schema.run(opts.global),
         Command::Typecheck(typecheck) => typecheck.run(opts.global),
+        Command::Package(package) => package.run(opts.global),
         Command::GenCompletions(completions) => completions.run(opts.global),
 
         #[cfg(feature = "repl")]
@@ -0,0 +1,128 @@
+use std::path::{Path, PathBuf};
+
+use nickel_lang_core::package::{
+    fetch::PackageCache, lock::LockFile, manifest::ManifestFile, Error as PackageError, PackageMap,
+    LOCK_FILE,
+};
+
+use crate::{cli::GlobalOptions, error::CliResult};
+
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    /// Resolves the dependencies of the package and writes the lock file, fetching git
+    /// dependencies in the process
+    Lock {
+        /// Updates git dependencies to the latest revision matching their declaration, instead
+        /// of keeping the revisions pinned by an existing lock file
+        #[arg(long)]
+        update: bool,
+    },
+    /// Fetches all the dependencies pinned by the lock file into the package cache
+    Fetch,
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct PackageCommand {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// The path of the package manifest. If omitted, the manifest is searched for in the current
+    /// directory and in its parent directories.
+    #[arg(long, global = true)]
+    pub manifest_path: Option<PathBuf>,
+}
+
+impl PackageCommand {
+    pub fn run(self, _: GlobalOptions) -> CliResult<()> {
+        let manifest_path = find_manifest(self.manifest_path.as_deref(), None)?;
+        let lock_path = lock_path(&manifest_path);
+        let cache = package_cache()?;
+
+        match self.command {
+            Command::Lock { update } => {
+                let manifest = ManifestFile::from_path(&manifest_path)?;
+                let previous = lock_path
+                    .is_file()
+                    .then(|| LockFile::from_path(&lock_path))
+                    .transpose()?;
+                let lock = LockFile::resolve(&manifest, previous.as_ref(), &cache, update)?;
+                lock.write(&lock_path)?;
+            }
+            Command::Fetch => {
+                LockFile::from_path(&lock_path)?.fetch(&cache)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Loads the package map used to resolve package imports for a program whose first input file
+/// is `input`. Returns `None` if no manifest was found, or if the package hasn't been locked yet.
+pub fn load_package_map(
+    manifest_path: Option<&Path>,
+    input: Option<&Path>,
+) -> CliResult<Option<PackageMap>> {
+    let manifest_path = match manifest_path {
+        Some(path) => path.to_owned(),
+        None => {
+            let dir = input
+                .and_then(Path::parent)
+                .map(Path::to_owned)
+                .unwrap_or_default();
+
+            match find_manifest(None, Some(&dir)) {
+                Ok(path) => path,
+                Err(_) => return Ok(None),
+            }
+        }
+    };
+
+    let lock_path = lock_path(&manifest_path);
+
+    if !lock_path.is_file() {
+        return Ok(None);
+    }
+
+    let root = manifest_path.parent().unwrap_or(Path::new(""));
+    let map = LockFile::from_path(&lock_path)?.package_map(root, &package_cache()?)?;
+
+    Ok(Some(map))
+}
+
+/// Returns `manifest_path` if specified, or searches for a manifest in `dir` (defaulting to the
+/// current directory) and its parent directories.
+fn find_manifest(manifest_path: Option<&Path>, dir: Option<&Path>) -> CliResult<PathBuf> {
+    if let Some(path) = manifest_path {
+        return Ok(path.to_owned());
+    }
+
+    let dir = match dir {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+        _ => std::env::current_dir()?,
+    };
+
+    ManifestFile::find(&dir).ok_or_else(|| PackageError::ManifestNotFound { path: dir }.into())
+}
+
+fn lock_path(manifest_path: &Path) -> PathBuf {
+    manifest_path.with_file_name(LOCK_FILE)
+}
+
+/// The package cache, which is located in the directory given by the `NICKEL_PACKAGE_CACHE`
+/// environment variable if set, or in the user's cache directory otherwise.
+fn package_cache() -> CliResult<PackageCache> {
+    if let Some(dir) = std::env::var_os("NICKEL_PACKAGE_CACHE") {
+        return Ok(PackageCache::new(dir));
+    }
+
+    let dirs = directories::ProjectDirs::from("org", "nickel-lang", "nickel").ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "couldn't determine the package cache directory; \
+            set NICKEL_PACKAGE_CACHE to choose one",
+        )
+    })?;
+
+    Ok(PackageCache::new(dirs.cache_dir().join("packages")))
+}
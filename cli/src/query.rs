@@ -14,7 +14,7 @@ use crate::{
     cli::GlobalOptions,
     customize::{Customize, ExtractFieldOnly},
     error::{CliResult, ResultErrorExt, Warning},
-    input::{InputOptions, Prepare},
+    input::{EvalLimitOptions, InputOptions, Prepare},
 };
 
 const VALUE_EXPORT_MAX_WIDTH: usize = 80;
@@ -47,6 +47,9 @@ pub struct QueryCommand {
     #[arg(short, long, conflicts_with_all(["doc", "contract", "typ", "default", "value"]))]
     pub output: Option<PathBuf>,
 
+    #[command(flatten)]
+    pub limits: EvalLimitOptions,
+
     #[command(flatten)]
     pub inputs: InputOptions<ExtractFieldOnly>,
 }
@@ -157,6 +160,7 @@ impl QueryCommand {
 
     pub fn run(self, global: GlobalOptions) -> CliResult<()> {
         let mut program = self.inputs.prepare(&global)?;
+        program.set_eval_limits(self.limits.limits());
 
         if self.inputs.customize_mode.field().is_none() {
             program.report(Warning::EmptyQueryPath, global.error_format);
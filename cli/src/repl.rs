@@ -3,12 +3,15 @@ use std::path::PathBuf;
 use directories::BaseDirs;
 use nickel_lang_core::repl::rustyline_frontend;
 
-use crate::{cli::GlobalOptions, error::CliResult};
+use crate::{cli::GlobalOptions, error::CliResult, input::EvalLimitOptions};
 
 #[derive(clap::Parser, Debug)]
 pub struct ReplCommand {
     #[arg(long)]
     pub history_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub limits: EvalLimitOptions,
 }
 
 impl ReplCommand {
@@ -21,6 +24,10 @@ impl ReplCommand {
                 .home_dir()
                 .join(".nickel_history")
         };
-        Ok(rustyline_frontend::repl(histfile, global.color.into())?)
+        Ok(rustyline_frontend::repl(
+            histfile,
+            global.color.into(),
+            self.limits.limits(),
+        )?)
     }
 }
@@ -0,0 +1,62 @@
+use std::{fs, io::Write, path::PathBuf};
+
+use nickel_lang_core::{
+    error::{Error, IOError},
+    eval::cache::CacheImpl,
+    program::Program,
+};
+
+use crate::{
+    cli::GlobalOptions,
+    customize::ExtractFieldOnly,
+    error::{CliResult, ResultErrorExt, Warning},
+    input::{EvalLimitOptions, InputOptions, Prepare},
+};
+
+#[derive(clap::Parser, Debug)]
+pub struct SchemaCommand {
+    /// Output file. Standard output by default
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub limits: EvalLimitOptions,
+
+    #[command(flatten)]
+    pub input: InputOptions<ExtractFieldOnly>,
+}
+
+impl SchemaCommand {
+    pub fn run(self, global: GlobalOptions) -> CliResult<()> {
+        let mut program = self.input.prepare(&global)?;
+        program.set_eval_limits(self.limits.limits());
+        self.export_schema(&mut program, &global)
+            .report_with_program(program)
+    }
+
+    fn export_schema(
+        &self,
+        program: &mut Program<CacheImpl>,
+        global: &GlobalOptions,
+    ) -> Result<(), Error> {
+        let schema = program.json_schema()?;
+
+        for contract in schema.unsupported {
+            program.report(
+                Warning::UnsupportedSchemaContract(contract),
+                global.error_format,
+            );
+        }
+
+        let mut out: Box<dyn Write> = match &self.output {
+            Some(path) => Box::new(fs::File::create(path).map_err(IOError::from)?),
+            None => Box::new(std::io::stdout()),
+        };
+
+        serde_json::to_writer_pretty(&mut out, &schema.schema)
+            .map_err(|err| IOError(err.to_string()))?;
+        writeln!(out).map_err(IOError::from)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+//! Watch mode for commands evaluating a program (`--watch`).
+//!
+//! We poll the modification time of the source files instead of relying on filesystem
+//! notifications. This is portable, and the set of files to watch is small: the input files and
+//! the files they transitively import, as recorded in the import graph of the cache.
+
+use std::{thread, time::Duration};
+
+use nickel_lang_core::{error::Error, eval::cache::lazy::CBNCache, program::Program};
+
+use crate::cli::GlobalOptions;
+
+/// The delay between two checks of the source files.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Runs `run` on the program, and runs it again each time one of the source files of the program
+/// is modified. Errors are reported but don't stop the watch, which only ends when the process
+/// is interrupted.
+///
+/// The program is kept between runs, so that only the modified files and the files that depend
+/// on them are parsed and typechecked again.
+pub fn watch<F>(program: &mut Program<CBNCache>, global: &GlobalOptions, mut run: F) -> !
+where
+    F: FnMut(&mut Program<CBNCache>) -> Result<(), Error>,
+{
+    loop {
+        if let Err(error) = run(program) {
+            program.report(error, global.error_format);
+        }
+
+        while program.reload_modified_sources().is_empty() {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
@@ -73,3 +73,380 @@ fn automatic_color_on_non_tty() {
         );
     }
 }
+
+#[test]
+fn export_output_dir() {
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    let output = dir.path().join("output");
+
+    let export = |src: &str| {
+        std::fs::write(dir.path().join("main.ncl"), src).unwrap();
+        Command::new(nickel_bin)
+            .args(["export", "main.ncl", "--output-dir"])
+            .arg(&output)
+            .current_dir(dir.path())
+            .output()
+            .expect("Nickel should be runnable")
+    };
+
+    let written = export(r#"{ "./a//b.yaml" = { x = 1 }, c = { format = 'Text, content = "c" } }"#);
+    assert!(written.status.success());
+    assert_eq!(
+        std::fs::read_to_string(output.join("a/b.yaml")).unwrap(),
+        "x: 1\n"
+    );
+    assert_eq!(std::fs::read_to_string(output.join("c")).unwrap(), "c");
+
+    // Conflicting paths are rejected before anything is written.
+    let conflict = export(r#"{ "d.json" = 1, "./d.json" = 2 }"#);
+    assert!(!conflict.status.success());
+    assert!(String::from_utf8_lossy(&conflict.stderr).contains("conflicting output paths"));
+    assert!(!output.join("d.json").exists());
+
+    // Symbolic links can't be used to write outside of the output directory.
+    #[cfg(unix)]
+    {
+        let outside = dir.path().join("outside");
+        std::fs::create_dir(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, output.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("dangling.json"), output.join("dangling.json"))
+            .unwrap();
+        std::os::unix::fs::symlink(output.join("a"), output.join("inside")).unwrap();
+
+        for escaping in [r#"{ "link/e.json" = 1 }"#, r#"{ "dangling.json" = 1 }"#] {
+            let escaped = export(escaping);
+            assert!(!escaped.status.success());
+            assert!(String::from_utf8_lossy(&escaped.stderr).contains("symbolic link"));
+        }
+        assert_eq!(std::fs::read_dir(&outside).unwrap().count(), 0);
+
+        // Links within the output directory are fine.
+        assert!(export(r#"{ "inside/f.json" = 1 }"#).status.success());
+        assert!(output.join("a/f.json").exists());
+    }
+}
+
+#[test]
+fn package_path_dependency() {
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    let app = dir.path().join("app");
+    let dep = dir.path().join("dep");
+    std::fs::create_dir_all(&app).unwrap();
+    std::fs::create_dir_all(&dep).unwrap();
+
+    std::fs::write(
+        app.join("electroplate.ncl"),
+        r#"{ name = "app", version = "0.1.0", dependencies = { dep = 'Path "../dep" } }
+          | std.package.Manifest"#,
+    )
+    .unwrap();
+    std::fs::write(app.join("main.ncl"), "(import dep).value + 1").unwrap();
+    std::fs::write(dep.join("main.ncl"), "{ value = 1 }").unwrap();
+
+    let lock = Command::new(nickel_bin)
+        .args(["package", "lock"])
+        .current_dir(&app)
+        .env("NICKEL_PACKAGE_CACHE", dir.path().join("cache"))
+        .output()
+        .expect("Nickel should be runnable");
+    assert!(lock.status.success());
+    assert!(app.join("electroplate.lock").exists());
+
+    let export = Command::new(nickel_bin)
+        .args(["export", "main.ncl"])
+        .current_dir(&app)
+        .env("NICKEL_PACKAGE_CACHE", dir.path().join("cache"))
+        .output()
+        .expect("Nickel should be runnable");
+    assert!(export.status.success());
+    assert_eq!(String::from_utf8_lossy(&export.stdout).trim(), "2");
+}
+
+#[test]
+fn package_git_dependency() {
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    let app = dir.path().join("app");
+    let dep = dir.path().join("dep");
+    std::fs::create_dir_all(&app).unwrap();
+    std::fs::create_dir_all(&dep).unwrap();
+
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(&dep)
+            .output()
+            .expect("git should be runnable")
+            .status;
+        assert!(status.success(), "git {args:?} failed");
+    };
+    let commit = |value: &str| {
+        std::fs::write(dep.join("main.ncl"), format!("{{ value = {value} }}")).unwrap();
+        git(&["add", "main.ncl"]);
+        git(&["commit", "--quiet", "-m", value]);
+    };
+
+    git(&["init", "--quiet", "--initial-branch=main"]);
+    commit("1");
+
+    std::fs::write(
+        app.join("electroplate.ncl"),
+        format!(
+            r#"{{ name = "app", version = "0.1.0", dependencies = {{ dep = 'Git {{ url = "{}", branch = "main" }} }} }}
+              | std.package.Manifest"#,
+            dep.display()
+        ),
+    )
+    .unwrap();
+    std::fs::write(app.join("main.ncl"), "(import dep).value + 1").unwrap();
+
+    let nickel = |args: &[&str]| {
+        let output = Command::new(nickel_bin)
+            .args(args)
+            .current_dir(&app)
+            .env("NICKEL_PACKAGE_CACHE", dir.path().join("cache"))
+            .output()
+            .expect("Nickel should be runnable");
+        assert!(
+            output.status.success(),
+            "nickel {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).trim().to_owned()
+    };
+
+    nickel(&["package", "lock"]);
+    assert_eq!(nickel(&["export", "main.ncl"]), "2");
+
+    // The revision is pinned by the lock file until the dependencies are updated.
+    commit("2");
+    nickel(&["package", "lock"]);
+    assert_eq!(nickel(&["export", "main.ncl"]), "2");
+    nickel(&["package", "lock", "--update"]);
+    assert_eq!(nickel(&["export", "main.ncl"]), "3");
+}
+
+#[test]
+fn sandbox_package_imports() {
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    let sub = dir.path().join("sub");
+    let dep = dir.path().join("dep");
+    std::fs::create_dir_all(&sub).unwrap();
+    std::fs::create_dir_all(&dep).unwrap();
+
+    std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+    std::fs::write(dep.join("main.ncl"), "{ value = 1 }").unwrap();
+    std::fs::write(
+        sub.join("electroplate.ncl"),
+        r#"{ name = "sub", version = "0.1.0", dependencies = {} } | std.package.Manifest"#,
+    )
+    .unwrap();
+    // A lock file shipped with the program, making the whole file system a package.
+    std::fs::write(
+        sub.join("electroplate.lock"),
+        r#"{"dependencies":{"root":{"type":"path","path":"/"},"dep":{"type":"path","path":"../dep"}}}"#,
+    )
+    .unwrap();
+
+    let nickel = |args: &[&str], main: &str| {
+        std::fs::write(sub.join("main.ncl"), main).unwrap();
+        Command::new(nickel_bin)
+            .args(args)
+            .current_dir(dir.path())
+            .env("NICKEL_PACKAGE_CACHE", dir.path().join("cache"))
+            .output()
+            .expect("Nickel should be runnable")
+    };
+
+    // The lock file next to the program isn't used in sandbox mode.
+    let output = nickel(
+        &["export", "--sandbox", "sub/main.ncl"],
+        r#"import "../secret.txt" as 'Text"#,
+    );
+    assert!(!output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("secret"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is forbidden"));
+
+    let output = nickel(&["export", "--sandbox", "sub/main.ncl"], "import dep");
+    assert!(!output.status.success());
+
+    // With an explicit manifest, the packages must still be under the allowed directories.
+    let manifest_args = [
+        "export",
+        "--sandbox",
+        "--manifest-path",
+        "sub/electroplate.ncl",
+        "sub/main.ncl",
+    ];
+    let output = nickel(&manifest_args, r#"import "../secret.txt" as 'Text"#);
+    assert!(!output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("secret"));
+
+    let output = nickel(&manifest_args, "(import dep).value");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("import of dep is forbidden"));
+
+    let output = nickel(
+        &[
+            &manifest_args[..],
+            &["--allow-import-dir", "sub", "--allow-import-dir", "dep"],
+        ]
+        .concat(),
+        "(import dep).value",
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "1");
+}
+
+#[test]
+fn eval_limits() {
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    let input = dir.path().join("loop.ncl");
+    std::fs::write(&input, "let rec f = fun n => f (n + 1) in f 0").unwrap();
+
+    for (limit, message) in [
+        (["--max-steps", "100000"], "maximum number of steps"),
+        (["--timeout", "1"], "time limit"),
+    ] {
+        let output = Command::new(nickel_bin)
+            .arg("eval")
+            .args(limit)
+            .arg(&input)
+            .output()
+            .expect("Nickel should be runnable");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        // The evaluation must terminate with an error, and not be killed while cleaning up.
+        assert_eq!(output.status.code(), Some(1), "{limit:?}: {stderr}");
+        assert!(stderr.contains(message), "{limit:?}: {stderr}");
+        assert!(stderr.contains("outer elements of the call stack were left out"));
+        assert!(stderr.len() < 20_000, "{limit:?}: {} bytes", stderr.len());
+    }
+}
+
+#[test]
+fn export_profile() {
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    let input = dir.path().join("main.ncl");
+    let profile = dir.path().join("profile.folded");
+    std::fs::write(
+        &input,
+        r#"
+        let rec fib = fun n => if n < 2 then n else fib (n - 1) + fib (n - 2) in
+        { small = fib 5, large = fib 15, nested = { value = fib 10 } }
+        "#,
+    )
+    .unwrap();
+
+    let export = |profile_args: &[&std::ffi::OsStr]| {
+        let output = Command::new(nickel_bin)
+            .arg("export")
+            .arg(&input)
+            .args(profile_args)
+            .output()
+            .expect("Nickel should be runnable");
+        assert!(output.status.success());
+        output.stdout
+    };
+
+    let plain = export(&[]);
+    let profiled = export(&["--profile".as_ref(), profile.as_os_str()]);
+    assert_eq!(plain, profiled);
+
+    let folded = std::fs::read_to_string(&profile).expect("the profile should be written");
+    // The frames of each stack, without their position.
+    let stacks: Vec<Vec<_>> = folded
+        .lines()
+        .map(|line| {
+            let (stack, _weight) = line
+                .rsplit_once(' ')
+                .expect("a weight should follow the stack");
+            stack
+                .split(';')
+                .map(|frame| frame.split_once(" (").map_or(frame, |(name, _pos)| name))
+                .collect()
+        })
+        .collect();
+    for fields in [
+        &["field small"][..],
+        &["field large"],
+        &["field nested", "field value"],
+    ] {
+        assert!(
+            stacks.iter().any(|stack| stack.starts_with(fields)),
+            "no stack starts with {fields:?} in:\n{folded}"
+        );
+    }
+}
+
+#[test]
+fn export_watch() {
+    use std::{
+        io::{BufRead, BufReader},
+        sync::mpsc,
+        thread,
+        time::{Duration, SystemTime},
+    };
+
+    let nickel_bin = env!("CARGO_BIN_EXE_nickel");
+    let dir = tempdir().expect("should be able to make a temporary directory");
+    // Sets the modification time explicitly, so that successive writes are seen as modifications
+    // regardless of the precision of the filesystem timestamps.
+    let write_at = |name: &str, contents: &str, secs: u64| {
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    };
+    write_at("main.ncl", "(import \"dep.ncl\") + 1", 1);
+    write_at("dep.ncl", "1", 1);
+
+    let mut nickel = Command::new(nickel_bin)
+        .args(["export", "--watch", "main.ncl"])
+        .current_dir(dir.path())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Nickel should be runnable");
+
+    // The watch never ends, so the output is read from another thread to be able to time out.
+    let (sender, lines) = mpsc::channel();
+    let stdout = nickel.stdout.take().unwrap();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if sender.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    let next_line = || lines.recv_timeout(Duration::from_secs(30));
+
+    assert_eq!(next_line().as_deref(), Ok("2"));
+    // Modifying an imported file triggers a new export.
+    write_at("dep.ncl", "2", 2);
+    assert_eq!(next_line().as_deref(), Ok("3"));
+    write_at("main.ncl", "(import \"dep.ncl\") + 2", 3);
+    assert_eq!(next_line().as_deref(), Ok("4"));
+    // Errors are reported without ending the watch.
+    write_at("dep.ncl", "\"not a number\"", 4);
+    thread::sleep(Duration::from_secs(1));
+    write_at("dep.ncl", "3", 5);
+    assert_eq!(next_line().as_deref(), Ok("5"));
+
+    nickel.kill().unwrap();
+    nickel.wait().unwrap();
+}
@@ -14,7 +14,12 @@ use std::{ffi::OsString, rc};
 use pattern::Pattern;
 use record::Record;
 
-use crate::{cache::InputFormat, error::ParseError, identifier::LocIdent, position::TermPos};
+use crate::{
+    cache::InputFormat,
+    error::ParseError,
+    identifier::{Ident, LocIdent},
+    position::TermPos,
+};
 
 // For now, we reuse those types from the term module.
 pub use crate::term::{Number, StrChunk};
@@ -140,10 +145,7 @@ pub enum Node<'ast> {
     },
 
     /// An import.
-    Import {
-        path: &'ast OsString,
-        format: InputFormat,
-    },
+    Import(Import<'ast>),
 
     /// A type in term position, such as in `let my_contract = Number -> Number in ...`.
     ///
@@ -155,6 +157,18 @@ pub enum Node<'ast> {
     ParseError(&'ast ParseError),
 }
 
+/// The target of an import.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Import<'ast> {
+    /// A path to a file, with the format used to interpret it.
+    Path {
+        path: &'ast OsString,
+        format: InputFormat,
+    },
+    /// A package declared in the package manifest.
+    Package { id: Ident },
+}
+
 /// A branch of a match expression.
 #[derive(Debug, PartialEq, Clone)]
 pub struct MatchBranch<'ast> {
@@ -379,11 +393,15 @@ impl AstAlloc {
         }
     }
 
-    pub fn import(&self, path: OsString, format: InputFormat) -> Node<'_> {
-        Node::Import {
+    pub fn import_path(&self, path: OsString, format: InputFormat) -> Node<'_> {
+        Node::Import(Import::Path {
             path: self.generic_arena.alloc(path),
             format,
-        }
+        })
+    }
+
+    pub fn import_package(&self, id: Ident) -> Node<'_> {
+        Node::Import(Import::Package { id })
     }
 
     /// As opposed to [Self::typ], this method takes an already constructed type and move it into
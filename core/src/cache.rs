@@ -1,26 +1,33 @@
 //! Source cache.
 
 use crate::closurize::Closurize as _;
-use crate::error::{Error, ImportError, ParseError, ParseErrors, TypecheckError};
+use crate::disk_cache::{self, DiskCache, EntryBody, EntryHead, FileRef, ImportRecord};
+use crate::error::{
+    Error, ImportError, ImportPolicyViolation, ParseError, ParseErrors, TypecheckError,
+};
 use crate::eval::cache::Cache as EvalCache;
 use crate::eval::Closure;
 use crate::files::{FileId, Files};
 use crate::metrics::measure_runtime;
 #[cfg(feature = "nix-experimental")]
 use crate::nix_ffi;
+use crate::package::{self, PackageMap};
 use crate::parser::{lexer::Lexer, ErrorTolerantParser};
 use crate::position::TermPos;
 use crate::program::FieldPath;
 use crate::stdlib::{self as nickel_stdlib, StdlibModule};
+use crate::term::foreign::ForeignBinding;
 use crate::term::record::{Field, RecordData};
-use crate::term::{RichTerm, SharedTerm, Term};
+use crate::term::{Import, RichTerm, SharedTerm, Term};
 use crate::transform::import_resolution;
 use crate::typ::UnboundTypeVariableError;
-use crate::typecheck::{self, type_check, TypecheckMode, Wildcards};
+use crate::typecheck::{
+    self, eq::SimpleTermEnvironment, type_check, TypecheckMode, UnifType, Wildcards,
+};
 use crate::{eval, parser, transform};
 
 use io::Read;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::hash_map;
 use std::collections::{HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
@@ -32,13 +39,22 @@ use std::time::SystemTime;
 use void::Void;
 
 /// Supported input formats.
-#[derive(Default, Clone, Copy, Eq, Debug, PartialEq, Hash)]
+#[derive(Default, Clone, Copy, Eq, Debug, PartialEq, Hash, Serialize, Deserialize)]
 pub enum InputFormat {
     #[default]
     Nickel,
     Json,
     Yaml,
+    /// A stream of YAML documents, which is always imported as an array, even if it contains a
+    /// single document.
+    YamlDocuments,
     Toml,
+    /// A CSV file with a header row, which is imported as an array of records.
+    Csv,
+    /// A JSON Lines file, with one JSON value per line, which is imported as an array.
+    JsonLines,
+    /// A JSON Schema, which is imported as the equivalent Nickel contract.
+    JsonSchema,
     #[cfg(feature = "nix-experimental")]
     Nix,
     Text,
@@ -52,6 +68,8 @@ impl InputFormat {
             Some("json") => Some(InputFormat::Json),
             Some("yaml") | Some("yml") => Some(InputFormat::Yaml),
             Some("toml") => Some(InputFormat::Toml),
+            Some("csv") => Some(InputFormat::Csv),
+            Some("jsonl") | Some("ndjson") => Some(InputFormat::JsonLines),
             #[cfg(feature = "nix-experimental")]
             Some("nix") => Some(InputFormat::Nix),
             Some("txt") => Some(InputFormat::Text),
@@ -65,7 +83,11 @@ impl InputFormat {
             "Nickel" => InputFormat::Nickel,
             "Text" => InputFormat::Text,
             "Yaml" => InputFormat::Yaml,
+            "YamlDocuments" => InputFormat::YamlDocuments,
             "Toml" => InputFormat::Toml,
+            "Csv" => InputFormat::Csv,
+            "JsonLines" => InputFormat::JsonLines,
+            "JsonSchema" => InputFormat::JsonSchema,
             #[cfg(feature = "nix-experimental")]
             "Nix" => InputFormat::Nix,
             _ => return None,
@@ -77,7 +99,11 @@ impl InputFormat {
             InputFormat::Nickel => "Nickel",
             InputFormat::Json => "Json",
             InputFormat::Yaml => "Yaml",
+            InputFormat::YamlDocuments => "YamlDocuments",
             InputFormat::Toml => "Toml",
+            InputFormat::Csv => "Csv",
+            InputFormat::JsonLines => "JsonLines",
+            InputFormat::JsonSchema => "JsonSchema",
             InputFormat::Text => "Text",
             #[cfg(feature = "nix-experimental")]
             InputFormat::Nix => "Nix",
@@ -125,6 +151,21 @@ pub struct Cache {
     /// Whether processing should try to continue even in case of errors. Needed by the NLS.
     error_tolerance: ErrorTolerance,
     import_paths: Vec<PathBuf>,
+    /// The locations of the packages that can be imported with `import <package>`, if a package
+    /// lock file has been loaded.
+    package_map: Option<PackageMap>,
+    /// The restrictions on the files that can be imported.
+    import_policy: ImportPolicy,
+    /// The bindings added to the initial environment by the program embedding Nickel.
+    foreign_bindings: Vec<ForeignBinding>,
+    /// The on-disk cache of prepared terms, if enabled.
+    disk_cache: Option<DiskCache>,
+    /// The imports of each file, together with the files they resolved to. Only recorded when the
+    /// disk cache is enabled, to be stored in the entries of the importing files.
+    import_specs: HashMap<FileId, Vec<(Import, FileId)>>,
+    /// The files whose prepared term is known to be stored in the disk cache, either because it
+    /// was loaded from there or because it has been stored already.
+    on_disk: HashSet<FileId>,
 
     #[cfg(debug_assertions)]
     /// Skip loading the stdlib, used for debugging purpose
@@ -139,6 +180,78 @@ pub enum ErrorTolerance {
     Strict,
 }
 
+/// Restrictions on the files that a program can import, used to evaluate untrusted programs.
+///
+/// The default policy doesn't restrict anything. Package imports (`import <name>`) and the imports
+/// done by the files of a package are subject to the same restrictions as the other imports: the
+/// package directories aren't trusted just because they are listed in a lock file, which might come
+/// with the program being evaluated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportPolicy {
+    /// If set, only the files located under one of these directories can be imported. Symbolic
+    /// links are resolved before checking the location of a file, so that they can't be used to
+    /// escape the allowed directories.
+    pub allowed_dirs: Option<Vec<PathBuf>>,
+    /// Whether absolute import paths, such as `import "/etc/passwd"`, are forbidden.
+    pub forbid_absolute_paths: bool,
+    /// The formats which can't be imported.
+    pub forbidden_formats: Vec<InputFormat>,
+}
+
+impl ImportPolicy {
+    /// A policy only allowing to import files located under `allowed_dirs`, through relative
+    /// import paths.
+    pub fn sandbox(allowed_dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        ImportPolicy {
+            allowed_dirs: Some(allowed_dirs.into_iter().collect()),
+            forbid_absolute_paths: true,
+            forbidden_formats: Vec::new(),
+        }
+    }
+
+    /// Checks an import path as written in the program, before looking for the imported file.
+    fn check_import(&self, path: &Path, format: InputFormat) -> Result<(), ImportPolicyViolation> {
+        if self.forbid_absolute_paths && path.is_absolute() {
+            return Err(ImportPolicyViolation::AbsolutePath);
+        }
+
+        if self.forbidden_formats.contains(&format) {
+            return Err(ImportPolicyViolation::ForbiddenFormat(format));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that an existing file, or the root directory of a package, is located in an allowed
+    /// directory.
+    fn check_location(&self, file: &Path) -> Result<(), ImportPolicyViolation> {
+        let Some(allowed_dirs) = &self.allowed_dirs else {
+            return Ok(());
+        };
+
+        let file = real_path(file);
+
+        if allowed_dirs
+            .iter()
+            .any(|dir| file.starts_with(real_path(dir)))
+        {
+            Ok(())
+        } else {
+            Err(ImportPolicyViolation::OutsideAllowedDirs(
+                allowed_dirs.clone(),
+            ))
+        }
+    }
+}
+
+/// Returns the absolute path of `path` with symbolic links resolved, or just normalized if it
+/// can't be resolved.
+fn real_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path)
+        .or_else(|_| normalize_path(path))
+        .unwrap_or_else(|_| path.to_owned())
+}
+
 /// The different environments maintained during the REPL session for evaluation and typechecking.
 #[derive(Debug, Clone)]
 pub struct Envs {
@@ -374,6 +487,12 @@ impl Cache {
             rev_imports: HashMap::new(),
             error_tolerance,
             import_paths: Vec::new(),
+            package_map: None,
+            import_policy: ImportPolicy::default(),
+            foreign_bindings: Vec::new(),
+            disk_cache: None,
+            import_specs: HashMap::new(),
+            on_disk: HashSet::new(),
 
             #[cfg(debug_assertions)]
             skip_stdlib: false,
@@ -387,6 +506,39 @@ impl Cache {
         self.import_paths.extend(paths.map(PathBuf::from));
     }
 
+    /// Sets the package map used to resolve package imports.
+    pub fn set_package_map(&mut self, map: PackageMap) {
+        self.package_map = Some(map);
+    }
+
+    /// Sets the policy restricting the files that can be imported.
+    pub fn set_import_policy(&mut self, policy: ImportPolicy) {
+        self.import_policy = policy;
+    }
+
+    /// Sets the on-disk cache used to store prepared terms across runs, or disables it if `None`.
+    /// See [crate::disk_cache].
+    pub fn set_disk_cache(&mut self, disk_cache: Option<DiskCache>) {
+        self.disk_cache = disk_cache;
+    }
+
+    pub fn disk_cache(&self) -> Option<&DiskCache> {
+        self.disk_cache.as_ref()
+    }
+
+    /// Disables the disk cache, and returns it.
+    pub fn take_disk_cache(&mut self) -> Option<DiskCache> {
+        self.disk_cache.take()
+    }
+
+    /// Adds a binding to the initial environment, on top of the standard library, see
+    /// [crate::term::foreign]. A binding with the same name as a previous one replaces it.
+    pub fn add_foreign_binding(&mut self, binding: ForeignBinding) {
+        self.foreign_bindings
+            .retain(|other| other.id.ident() != binding.id.ident());
+        self.foreign_bindings.push(binding);
+    }
+
     /// Same as [Self::add_file], but assume that the path is already normalized, and take the
     /// timestamp as a parameter.
     fn add_file_(
@@ -426,7 +578,9 @@ impl Cache {
 
     /// Try to retrieve the id of a file from the cache.
     ///
-    /// If it was not in cache, try to read it from the filesystem and add it as a new entry.
+    /// If it was not in cache, try to read it from the filesystem and add it as a new entry. As
+    /// for [Cache::add_file], entries are indexed by the normalized path, so `a/../b.ncl` and
+    /// `b.ncl` refer to the same entry.
     pub fn get_or_add_file(
         &mut self,
         path: impl Into<OsString>,
@@ -434,7 +588,7 @@ impl Cache {
     ) -> io::Result<CacheOp<FileId>> {
         let path = path.into();
         let normalized = normalize_path(&path)?;
-        match self.id_or_new_timestamp_of(path.as_ref(), format)? {
+        match self.id_or_new_timestamp_of(&normalized, format)? {
             SourceState::UpToDate(id) => Ok(CacheOp::Cached(id)),
             SourceState::Stale(timestamp) => self
                 .add_file_(normalized, format, timestamp)
@@ -576,30 +730,23 @@ impl Cache {
 
                 Ok((t, parse_errs))
             }
-            InputFormat::Json => serde_json::from_str(self.files.source(file_id))
-                .map(|t| (attach_pos(t), ParseErrors::default()))
-                .map_err(|err| ParseError::from_serde_json(err, file_id, &self.files)),
-            InputFormat::Yaml => {
+            InputFormat::Json => crate::serialize::json_deser::from_str(buf, file_id)
+                .map(|t| (attach_pos(t), ParseErrors::default())),
+            InputFormat::Yaml | InputFormat::YamlDocuments => {
                 // YAML files can contain multiple documents. If there is only
-                // one we transparently deserialize it. If there are multiple,
-                // we deserialize the file as an array.
-                let de = serde_yaml::Deserializer::from_str(self.files.source(file_id));
-                let mut terms = de
-                    .map(|de| {
-                        RichTerm::deserialize(de)
-                            .map(attach_pos)
-                            .map_err(|err| (ParseError::from_serde_yaml(err, file_id)))
-                    })
-                    .collect::<Result<Vec<_>, _>>()?;
-
-                if terms.is_empty() {
+                // one we transparently deserialize it, unless the format
+                // explicitly asks for a stream of documents. If there are
+                // multiple, we deserialize the file as an array.
+                let mut terms = crate::serialize::yaml_deser::from_str(buf, file_id)?;
+
+                if terms.is_empty() && format == InputFormat::Yaml {
                     unreachable!(
-                        "serde always produces at least one document, \
+                        "the YAML loader always produces at least one document, \
                         the empty string turns into `null`"
                     )
-                } else if terms.len() == 1 {
+                } else if terms.len() == 1 && format == InputFormat::Yaml {
                     Ok((
-                        terms.pop().expect("we just checked the length"),
+                        attach_pos(terms.pop().expect("we just checked the length")),
                         ParseErrors::default(),
                     ))
                 } else {
@@ -616,6 +763,14 @@ impl Cache {
                     .map(|t| (attach_pos(t), ParseErrors::default()))
                     .map_err(|err| (ParseError::from_toml(err, file_id)))
             }
+            InputFormat::Csv => crate::serialize::line_based::csv_from_str(buf)
+                .map(|t| (attach_pos(t), ParseErrors::default()))
+                .map_err(|err| ParseError::from_line_error("csv", err, file_id)),
+            InputFormat::JsonLines => crate::serialize::line_based::jsonl_from_str(buf)
+                .map(|t| (attach_pos(t), ParseErrors::default()))
+                .map_err(|err| ParseError::from_line_error("jsonl", err, file_id)),
+            InputFormat::JsonSchema => crate::json_schema::import::contract_from_str(buf, file_id)
+                .map(|t| (attach_pos(t), ParseErrors::default())),
             #[cfg(feature = "nix-experimental")]
             InputFormat::Nix => {
                 let json = nix_ffi::eval_to_json(self.files.source(file_id))
@@ -996,7 +1151,9 @@ impl Cache {
             .get(&file_id)
             .and_then(InputFormat::from_source_path)
             .unwrap_or_default();
-        if let CacheOp::Done(_) = self.parse(file_id, format)? {
+        if self.load_prepared(file_id) {
+            result = CacheOp::Done(());
+        } else if let CacheOp::Done(_) = self.parse(file_id, format)? {
             result = CacheOp::Done(());
         }
 
@@ -1034,6 +1191,8 @@ impl Cache {
             result = CacheOp::Done(());
         };
 
+        self.store_prepared(file_id);
+
         Ok(result)
     }
 
@@ -1069,6 +1228,232 @@ impl Cache {
         Ok((term, pending))
     }
 
+    /// Returns the disk cache if it can be used. Programs with foreign bindings aren't cached, as
+    /// their terms depend on the initial environment, and neither are the error tolerant ones,
+    /// whose terms may be partial.
+    fn active_disk_cache(&self) -> Option<&DiskCache> {
+        match self.error_tolerance {
+            ErrorTolerance::Strict if self.foreign_bindings.is_empty() => self.disk_cache.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the key of a source in the disk cache, if it can be cached. Only files and modules
+    /// of the standard library are cached.
+    fn disk_cache_key(&self, file_id: FileId) -> Option<String> {
+        let (name, format) = match self.file_ref(file_id)? {
+            FileRef::Stdlib(module) => (module.name().to_owned(), InputFormat::Nickel),
+            FileRef::Path(path, format) => (path.to_str()?.to_owned(), format),
+        };
+
+        #[cfg(feature = "nix-experimental")]
+        if format == InputFormat::Nix {
+            return None;
+        }
+
+        Some(DiskCache::key(
+            &name,
+            format.to_tag(),
+            self.files.source(file_id),
+        ))
+    }
+
+    /// Returns the reference to a file which is stored in the disk cache, and which identifies the
+    /// file across runs.
+    fn file_ref(&self, file_id: FileId) -> Option<FileRef> {
+        if let Some((module, _)) = self.files.stdlib_modules().find(|(_, id)| *id == file_id) {
+            return Some(FileRef::Stdlib(module));
+        }
+
+        match self.file_paths.get(&file_id)? {
+            SourcePath::Path(path, format) => Some(FileRef::Path(path.clone(), *format)),
+            _ => None,
+        }
+    }
+
+    /// Returns the current id of a file referred to by an entry of the disk cache.
+    fn file_of_ref(&self, file_ref: &FileRef) -> Option<FileId> {
+        match file_ref {
+            FileRef::Stdlib(module) => self
+                .files
+                .stdlib_modules()
+                .find_map(|(m, id)| (m == *module).then_some(id)),
+            FileRef::Path(path, format) => self.id_of(&SourcePath::Path(path.clone(), *format)),
+        }
+    }
+
+    /// Loads the prepared term of a source and of all its transitive imports from the disk
+    /// cache, and puts them in the [EntryState::Transformed] state. Imports which are already in
+    /// the term cache are kept as they are.
+    ///
+    /// An entry is only used if its imports still resolve to the same files, with the same
+    /// content, as when it was stored. Nothing is loaded unless all the entries are valid. Returns
+    /// `true` if the terms were loaded.
+    fn load_prepared(&mut self, file_id: FileId) -> bool {
+        if self.terms.contains_key(&file_id) {
+            return false;
+        }
+
+        let Some(disk_cache) = self.active_disk_cache().cloned() else {
+            return false;
+        };
+
+        let mut pending = vec![file_id];
+        let mut visited = HashSet::new();
+        let mut entries = Vec::new();
+
+        while let Some(id) = pending.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+
+            let Some(entry) = self
+                .disk_cache_key(id)
+                .and_then(|key| disk_cache.load(&key))
+            else {
+                return false;
+            };
+
+            let mut imports = Vec::new();
+
+            for ImportRecord { import, key } in &entry.head.imports {
+                let Ok((_, dep_id, _)) = self.locate_import(import, Some(id), &TermPos::None)
+                else {
+                    return false;
+                };
+
+                if self.disk_cache_key(dep_id).as_ref() != Some(key) {
+                    return false;
+                }
+
+                if !self.terms.contains_key(&dep_id) {
+                    pending.push(dep_id);
+                }
+
+                imports.push((import.clone(), dep_id));
+            }
+
+            entries.push((id, entry, imports));
+        }
+
+        let mut bodies = Vec::new();
+
+        for (id, entry, imports) in entries {
+            let files: Option<Vec<_>> = entry
+                .head
+                .files
+                .iter()
+                .map(|file_ref| self.file_of_ref(file_ref))
+                .collect();
+
+            let Some(EntryBody { term, wildcards }) = files.and_then(|files| entry.decode(files))
+            else {
+                return false;
+            };
+
+            bodies.push((id, term, wildcards, imports));
+        }
+
+        for (id, term, wildcards, imports) in bodies {
+            self.terms.insert(
+                id,
+                TermEntry {
+                    term,
+                    state: EntryState::Transformed,
+                    parse_errs: ParseErrors::none(),
+                },
+            );
+            self.wildcards.insert(id, wildcards);
+
+            for (_, dep_id) in &imports {
+                self.imports.entry(id).or_default().insert(*dep_id);
+                self.rev_imports.entry(*dep_id).or_default().insert(id);
+            }
+
+            self.import_specs.insert(id, imports);
+            self.on_disk.insert(id);
+        }
+
+        true
+    }
+
+    /// Stores the prepared terms of a source and of its transitive imports in the disk cache,
+    /// when they can be cached and aren't stored already.
+    fn store_prepared(&mut self, file_id: FileId) {
+        let Some(disk_cache) = self.active_disk_cache().cloned() else {
+            return;
+        };
+
+        let mut pending = vec![file_id];
+        let mut visited = HashSet::new();
+
+        while let Some(id) = pending.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+
+            pending.extend(self.get_imports(id));
+
+            if !self.on_disk.contains(&id) && self.store_entry(&disk_cache, id).is_some() {
+                self.on_disk.insert(id);
+            }
+        }
+    }
+
+    /// Stores the prepared term of a source in the disk cache, if it can be cached. Returns `None`
+    /// otherwise.
+    fn store_entry(&self, disk_cache: &DiskCache, file_id: FileId) -> Option<()> {
+        let key = self.disk_cache_key(file_id)?;
+
+        let TermEntry {
+            term,
+            state: EntryState::Transformed,
+            parse_errs,
+        } = self.terms.get(&file_id)?
+        else {
+            return None;
+        };
+
+        if !parse_errs.no_errors() {
+            return None;
+        }
+
+        // The entry can only be checked later if all its imports have been recorded.
+        let specs = self
+            .import_specs
+            .get(&file_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let imported: HashSet<_> = specs.iter().map(|(_, dep_id)| *dep_id).collect();
+
+        if imported != self.get_imports(file_id).collect() {
+            return None;
+        }
+
+        let imports = specs
+            .iter()
+            .map(|(import, dep_id)| {
+                Some(ImportRecord {
+                    import: import.clone(),
+                    key: self.disk_cache_key(*dep_id)?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let (body, files) = disk_cache::encode(&EntryBody {
+            term: term.clone(),
+            wildcards: self.wildcards.get(&file_id).cloned().unwrap_or_default(),
+        })?;
+
+        let files = files
+            .into_iter()
+            .map(|id| self.file_ref(id))
+            .collect::<Option<Vec<_>>>()?;
+
+        disk_cache.store(&key, &EntryHead { files, imports }, &body);
+        Some(())
+    }
+
     /// Retrieve the name of a source given an id.
     pub fn name(&self, file_id: FileId) -> &OsStr {
         self.files.name(file_id)
@@ -1157,6 +1542,8 @@ impl Cache {
         fn invalidate_rec(slf: &mut Cache, acc: &mut Vec<FileId>, file_id: FileId) {
             slf.terms.remove(&file_id);
             slf.imports.remove(&file_id);
+            slf.import_specs.remove(&file_id);
+            slf.on_disk.remove(&file_id);
             let rev_deps = slf.rev_imports.remove(&file_id).unwrap_or_default();
 
             acc.extend(rev_deps.iter().copied());
@@ -1170,6 +1557,58 @@ impl Cache {
         ret
     }
 
+    /// Reload a file from the filesystem if it has been modified since it was loaded, and
+    /// invalidate its cached term together with the ones of the files that import it (see
+    /// [Self::invalidate_cache]).
+    ///
+    /// As opposed to [Self::get_or_add_file], the new content replaces the old one and the file id
+    /// is reused, so that the file doesn't need to be added again by its importers (or by the
+    /// caller, if it's the main file of a program). Sources that weren't loaded from the
+    /// filesystem, or older versions of a file which have since been replaced by a new entry, are
+    /// never reloaded.
+    ///
+    /// Returns `true` if the file was reloaded.
+    pub fn reload_if_modified(&mut self, file_id: FileId) -> io::Result<bool> {
+        let Some(src_path) = self.file_paths.get(&file_id).cloned() else {
+            return Ok(false);
+        };
+
+        let SourcePath::Path(path, _) = &src_path else {
+            return Ok(false);
+        };
+
+        let Some(NameIdEntry {
+            id,
+            source: SourceKind::Filesystem(old_timestamp),
+        }) = self.file_ids.get(&src_path)
+        else {
+            return Ok(false);
+        };
+
+        if *id != file_id {
+            return Ok(false);
+        }
+
+        let new_timestamp = timestamp(path)?;
+
+        if *old_timestamp == new_timestamp {
+            return Ok(false);
+        }
+
+        let contents = std::fs::read_to_string(path)?;
+        self.files.update(file_id, contents);
+        self.file_ids.insert(
+            src_path,
+            NameIdEntry {
+                id: file_id,
+                source: SourceKind::Filesystem(new_timestamp),
+            },
+        );
+        self.invalidate_cache(file_id);
+
+        Ok(true)
+    }
+
     /// Retrieve the state of an entry. Return `None` if the entry is not in the term cache,
     /// meaning that the content of the source has been loaded but has not been parsed yet.
     pub fn entry_state(&self, file_id: FileId) -> Option<EntryState> {
@@ -1223,6 +1662,22 @@ impl Cache {
             .copied()
     }
 
+    /// Returns the set of files that this file transitively imports.
+    pub fn get_imports_transitive(&self, file: FileId) -> HashSet<FileId> {
+        let mut ret = HashSet::new();
+        let mut stack = vec![file];
+
+        while let Some(file) = stack.pop() {
+            for f in self.get_imports(file) {
+                if ret.insert(f) {
+                    stack.push(f);
+                }
+            }
+        }
+
+        ret
+    }
+
     /// Returns the set of files that transitively depend on this file.
     pub fn get_rev_imports_transitive(&self, file: FileId) -> HashSet<FileId> {
         let mut ret = HashSet::new();
@@ -1294,6 +1749,9 @@ impl Cache {
         if self.skip_stdlib {
             return Ok(Envs::new());
         }
+        for (_, file_id) in self.files.stdlib_modules() {
+            self.load_prepared(file_id);
+        }
         self.load_stdlib()?;
         let type_ctxt = self.mk_type_ctxt().unwrap();
 
@@ -1319,6 +1777,11 @@ impl Cache {
                         .into(),
                 )
             })?;
+
+        for (_, file_id) in self.files.stdlib_modules() {
+            self.store_prepared(file_id);
+        }
+
         let eval_env = self.mk_eval_env(eval_cache).unwrap();
         Ok(Envs {
             eval_env,
@@ -1340,7 +1803,17 @@ impl Cache {
                 )
             })
             .collect();
-        Ok(typecheck::mk_initial_ctxt(&stdlib_terms_vec).unwrap())
+        let mut ctxt = typecheck::mk_initial_ctxt(&stdlib_terms_vec).unwrap();
+
+        for ForeignBinding { id, value, typ } in &self.foreign_bindings {
+            ctxt.term_env
+                .0
+                .insert(id.ident(), (value.clone(), SimpleTermEnvironment::new()));
+            ctxt.type_env
+                .insert(id.ident(), UnifType::from_type(typ.clone(), &ctxt.term_env));
+        }
+
+        Ok(ctxt)
     }
 
     /// Generate the initial evaluation environment from the list of `file_ids` corresponding to the
@@ -1381,6 +1854,16 @@ impl Cache {
             }
         });
 
+        for ForeignBinding { id, value, .. } in &self.foreign_bindings {
+            eval::env_add(
+                eval_cache,
+                &mut eval_env,
+                *id,
+                value.clone(),
+                eval::Environment::new(),
+            );
+        }
+
         Ok(eval_env)
     }
 }
@@ -1410,8 +1893,7 @@ pub trait ImportResolver {
     /// already transformed in the cache and do not need further processing.
     fn resolve(
         &mut self,
-        path: &OsStr,
-        format: InputFormat,
+        import: &Import,
         parent: Option<FileId>,
         pos: &TermPos,
     ) -> Result<(ResolvedTerm, FileId), ImportError>;
@@ -1422,36 +1904,96 @@ pub trait ImportResolver {
     fn get_path(&self, file_id: FileId) -> Option<&OsStr>;
 }
 
-impl ImportResolver for Cache {
-    fn resolve(
+impl Cache {
+    /// Finds the file targeted by an import and adds it to the file database if needed, without
+    /// parsing it. Returns the result of [Self::get_or_add_file], the file id and the path of the
+    /// file.
+    fn locate_import(
         &mut self,
-        path: &OsStr,
-        format: InputFormat,
+        import: &Import,
         parent: Option<FileId>,
         pos: &TermPos,
-    ) -> Result<(ResolvedTerm, FileId), ImportError> {
-        // `parent` is the file that did the import. We first look in its containing directory.
-        let mut parent_path = parent
-            .and_then(|p| self.get_path(p))
-            .map(PathBuf::from)
-            .unwrap_or_default();
-        parent_path.pop();
+    ) -> Result<(CacheOp<FileId>, FileId, PathBuf), ImportError> {
+        let (possible_parents, path, format) = match import {
+            Import::Path { path, format } => {
+                self.import_policy
+                    .check_import(Path::new(path), *format)
+                    .map_err(|violation| ImportError::Forbidden {
+                        path: path.to_string_lossy().into_owned(),
+                        violation,
+                        pos: *pos,
+                    })?;
+
+                // `parent` is the file that did the import. We first look in its containing
+                // directory.
+                let mut parent_path = parent
+                    .and_then(|p| self.get_path(p))
+                    .map(PathBuf::from)
+                    .unwrap_or_default();
+                parent_path.pop();
+
+                let possible_parents: Vec<PathBuf> = std::iter::once(parent_path)
+                    .chain(self.import_paths.iter().cloned())
+                    .collect();
+
+                (possible_parents, path.as_os_str(), *format)
+            }
+            Import::Package { id } => {
+                let package_map = self
+                    .package_map
+                    .as_ref()
+                    .ok_or(ImportError::NoPackageMap { pos: *pos })?;
+                let parent_path = parent.and_then(|p| self.get_path(p)).map(Path::new);
+                let package_root = package_map.get(parent_path, *id, *pos)?;
+
+                self.import_policy
+                    .check_location(package_root)
+                    .map_err(|violation| ImportError::Forbidden {
+                        path: id.to_string(),
+                        violation,
+                        pos: *pos,
+                    })?;
+
+                // The entry point of a package is the `main.ncl` file at its root.
+                (
+                    vec![package_root.to_owned()],
+                    OsStr::new(package::MAIN_FILE),
+                    InputFormat::Nickel,
+                )
+            }
+        };
 
-        let possible_parents: Vec<PathBuf> = std::iter::once(parent_path)
-            .chain(self.import_paths.iter().cloned())
-            .collect();
+        // Try to import from all possibilities, taking the first one that succeeds. Existing files
+        // which are forbidden by the import policy are skipped, but the violation is reported if
+        // no other possibility succeeds.
+        let mut violation = None;
 
-        // Try to import from all possibilities, taking the first one that succeeds.
         let (id_op, path_buf) = possible_parents
             .iter()
             .find_map(|parent| {
                 let mut path_buf = parent.clone();
                 path_buf.push(path);
+
+                if path_buf.exists() {
+                    if let Err(err) = self.import_policy.check_location(&path_buf) {
+                        violation.get_or_insert(err);
+                        return None;
+                    }
+                }
+
                 self.get_or_add_file(&path_buf, format)
                     .ok()
                     .map(|x| (x, path_buf))
             })
             .ok_or_else(|| {
+                if let Some(violation) = violation.take() {
+                    return ImportError::Forbidden {
+                        path: path.to_string_lossy().into_owned(),
+                        violation,
+                        pos: *pos,
+                    };
+                }
+
                 let parents = possible_parents
                     .iter()
                     .map(|p| p.to_string_lossy())
@@ -1463,18 +2005,50 @@ impl ImportResolver for Cache {
                 )
             })?;
 
-        let (result, file_id) = match id_op {
-            CacheOp::Cached(id) => (ResolvedTerm::FromCache, id),
-            CacheOp::Done(id) => (ResolvedTerm::FromFile { path: path_buf }, id),
+        let file_id = match id_op {
+            CacheOp::Cached(id) | CacheOp::Done(id) => id,
+        };
+
+        Ok((id_op, file_id, path_buf))
+    }
+}
+
+impl ImportResolver for Cache {
+    fn resolve(
+        &mut self,
+        import: &Import,
+        parent: Option<FileId>,
+        pos: &TermPos,
+    ) -> Result<(ResolvedTerm, FileId), ImportError> {
+        let (id_op, file_id, path_buf) = self.locate_import(import, parent, pos)?;
+
+        let result = match id_op {
+            CacheOp::Cached(_) => ResolvedTerm::FromCache,
+            CacheOp::Done(_) => ResolvedTerm::FromFile { path: path_buf },
         };
 
         if let Some(parent) = parent {
             self.imports.entry(parent).or_default().insert(file_id);
             self.rev_imports.entry(file_id).or_default().insert(parent);
+
+            if self.disk_cache.is_some() {
+                let specs = self.import_specs.entry(parent).or_default();
+
+                if !specs.iter().any(|(other, _)| other == import) {
+                    specs.push((import.clone(), file_id));
+                }
+            }
         }
 
-        self.parse(file_id, format)
-            .map_err(|err| ImportError::ParseErrors(err, *pos))?;
+        if !self.load_prepared(file_id) {
+            let format = match import {
+                Import::Path { format, .. } => *format,
+                Import::Package { .. } => InputFormat::Nickel,
+            };
+
+            self.parse(file_id, format)
+                .map_err(|err| ImportError::ParseErrors(err, *pos))?;
+        }
 
         Ok((result, file_id))
     }
@@ -1561,8 +2135,7 @@ pub mod resolvers {
     impl ImportResolver for DummyResolver {
         fn resolve(
             &mut self,
-            _path: &OsStr,
-            _format: InputFormat,
+            _import: &Import,
             _parent: Option<FileId>,
             _pos: &TermPos,
         ) -> Result<(ResolvedTerm, FileId), ImportError> {
@@ -1603,11 +2176,14 @@ pub mod resolvers {
     impl ImportResolver for SimpleResolver {
         fn resolve(
             &mut self,
-            path: &OsStr,
-            _format: InputFormat,
+            import: &Import,
             _parent: Option<FileId>,
             pos: &TermPos,
         ) -> Result<(ResolvedTerm, FileId), ImportError> {
+            let Import::Path { path, .. } = import else {
+                return Err(ImportError::NoPackageMap { pos: *pos });
+            };
+
             let file_id = self
                 .file_cache
                 .get(path.to_string_lossy().as_ref())
@@ -0,0 +1,286 @@
+//! Structural diff between two fully evaluated configurations.
+//!
+//! The two sides are expected to be the result of a full evaluation, as done by
+//! [crate::program::Program::eval_full_for_export]: records and arrays are compared recursively,
+//! and any other value is compared as a whole. Differences are reported with the path of the
+//! value within the configuration, so that they can be mapped back to Nickel fields.
+//!
+//! Optionally, the metadata of fields present on both sides (documentation, type and contract
+//! annotations, priority and optionality) can be compared as well.
+use crate::{
+    identifier::{Ident, LocIdent},
+    serialize::{NickelPointer, NickelPointerElem},
+    term::{
+        record::{Field, FieldMetadata, RecordData},
+        RichTerm, Term,
+    },
+};
+
+/// A difference at a specific location between the old and the new configuration.
+#[derive(Clone, Debug)]
+pub struct Difference {
+    /// The path of the value, from the root of the configuration.
+    pub path: NickelPointer,
+    pub change: Change,
+}
+
+/// The different kinds of differences.
+#[derive(Clone, Debug)]
+pub enum Change {
+    /// A field or an array element only present in the new configuration.
+    Added(RichTerm),
+    /// A field or an array element only present in the old configuration.
+    Removed(RichTerm),
+    /// A value which is present on both sides but differs, and which isn't a record or an array
+    /// on both sides (in which case it's compared recursively).
+    Changed { old: RichTerm, new: RichTerm },
+    /// A field which is present on both sides but whose metadata differ. Only reported when
+    /// comparing metadata. The list is never empty.
+    Metadata(Vec<MetadataChange>),
+}
+
+/// A difference in one attribute of the metadata of a field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MetadataChange {
+    /// The name of the attribute: `doc`, `type`, `contracts`, `priority` or `optional`.
+    pub attribute: &'static str,
+    /// The rendered value of the attribute in the old configuration, if set.
+    pub old: Option<String>,
+    /// The rendered value of the attribute in the new configuration, if set.
+    pub new: Option<String>,
+}
+
+/// Computes the differences between two fully evaluated configurations. If `with_metadata` is
+/// `true`, the metadata of the fields present on both sides are compared as well.
+///
+/// Fields are visited in alphabetical order, and array elements by index, so that the output is
+/// deterministic.
+pub fn diff(old: &RichTerm, new: &RichTerm, with_metadata: bool) -> Vec<Difference> {
+    let mut differ = Differ {
+        with_metadata,
+        path: NickelPointer::new(),
+        result: Vec::new(),
+    };
+
+    differ.diff_values(old, new);
+    differ.result
+}
+
+struct Differ {
+    with_metadata: bool,
+    /// The path of the value being currently compared.
+    path: NickelPointer,
+    result: Vec<Difference>,
+}
+
+impl Differ {
+    fn push(&mut self, change: Change) {
+        self.result.push(Difference {
+            path: self.path.clone(),
+            change,
+        });
+    }
+
+    fn with_elem(&mut self, elem: NickelPointerElem, f: impl FnOnce(&mut Self)) {
+        self.path.0.push(elem);
+        f(self);
+        self.path.0.pop();
+    }
+
+    fn diff_values(&mut self, old: &RichTerm, new: &RichTerm) {
+        match (old.as_ref(), new.as_ref()) {
+            (Term::Record(old_record), Term::Record(new_record)) => {
+                self.diff_records(old_record, new_record)
+            }
+            (Term::Array(old_array, _), Term::Array(new_array, _)) => {
+                let old_len = old_array.len();
+                let new_len = new_array.len();
+
+                for index in 0..old_len.max(new_len) {
+                    self.with_elem(NickelPointerElem::Index(index), |slf| {
+                        match (old_array.get(index), new_array.get(index)) {
+                            (Some(old), Some(new)) => slf.diff_values(old, new),
+                            (Some(old), None) => slf.push(Change::Removed(old.clone())),
+                            (None, Some(new)) => slf.push(Change::Added(new.clone())),
+                            (None, None) => unreachable!("index is within one of the arrays"),
+                        }
+                    })
+                }
+            }
+            _ if same_leaf(old, new) => (),
+            _ => self.push(Change::Changed {
+                old: old.clone(),
+                new: new.clone(),
+            }),
+        }
+    }
+
+    fn diff_records(&mut self, old: &RecordData, new: &RecordData) {
+        let mut ids: Vec<Ident> = old
+            .fields
+            .keys()
+            .chain(new.fields.keys())
+            .map(LocIdent::ident)
+            .collect();
+        ids.sort_by(|id1, id2| id1.label().cmp(id2.label()));
+        ids.dedup();
+
+        for id in ids {
+            let old_field = old.fields.get(&LocIdent::from(id));
+            let new_field = new.fields.get(&LocIdent::from(id));
+
+            self.with_elem(NickelPointerElem::Field(id), |slf| {
+                slf.diff_fields(old_field, new_field)
+            });
+        }
+    }
+
+    fn diff_fields(&mut self, old: Option<&Field>, new: Option<&Field>) {
+        if let (true, Some(old), Some(new)) = (self.with_metadata, old, new) {
+            let changes = diff_metadata(&old.metadata, &new.metadata);
+
+            if !changes.is_empty() {
+                self.push(Change::Metadata(changes));
+            }
+        }
+
+        // Fields without a value (such as optional fields) are considered absent.
+        match (
+            old.and_then(|field| field.value.as_ref()),
+            new.and_then(|field| field.value.as_ref()),
+        ) {
+            (Some(old), Some(new)) => self.diff_values(old, new),
+            (Some(old), None) => self.push(Change::Removed(old.clone())),
+            (None, Some(new)) => self.push(Change::Added(new.clone())),
+            (None, None) => (),
+        }
+    }
+}
+
+/// Compares two values which aren't both records or both arrays. We can't use the equality of
+/// [RichTerm], which also takes positions into account.
+fn same_leaf(old: &RichTerm, new: &RichTerm) -> bool {
+    match (old.as_ref(), new.as_ref()) {
+        (Term::Null, Term::Null) => true,
+        (Term::Bool(b1), Term::Bool(b2)) => b1 == b2,
+        (Term::Num(n1), Term::Num(n2)) => n1 == n2,
+        (Term::Str(s1), Term::Str(s2)) => s1 == s2,
+        (Term::Enum(id1), Term::Enum(id2)) => id1 == id2,
+        // Other values (such as enum variants) are compared through their pretty-printed form.
+        _ => old.to_string() == new.to_string(),
+    }
+}
+
+fn diff_metadata(old: &FieldMetadata, new: &FieldMetadata) -> Vec<MetadataChange> {
+    fn render_contracts(metadata: &FieldMetadata) -> Option<String> {
+        let contracts = &metadata.annotation.contracts;
+
+        (!contracts.is_empty()).then(|| {
+            contracts
+                .iter()
+                .map(|ctr| ctr.label.typ.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    }
+
+    let attributes = [
+        ("doc", old.doc.clone(), new.doc.clone()),
+        (
+            "type",
+            old.annotation.typ.as_ref().map(|t| t.label.typ.to_string()),
+            new.annotation.typ.as_ref().map(|t| t.label.typ.to_string()),
+        ),
+        ("contracts", render_contracts(old), render_contracts(new)),
+        (
+            "priority",
+            Some(old.priority.to_string()),
+            Some(new.priority.to_string()),
+        ),
+        (
+            "optional",
+            Some(old.opt.to_string()),
+            Some(new.opt.to_string()),
+        ),
+    ];
+
+    attributes
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(attribute, old, new)| MetadataChange {
+            attribute,
+            old,
+            new,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::cache::CacheImpl;
+    use crate::program::Program;
+    use std::io::Cursor;
+
+    fn eval(s: &str) -> RichTerm {
+        let src = Cursor::new(s);
+        let mut prog =
+            Program::<CacheImpl>::new_from_source(src, "<test>", std::io::stderr()).unwrap();
+        prog.eval_full_for_export()
+            .expect("program eval should succeed")
+    }
+
+    /// Renders the differences as `<kind> <path>` strings, for easier comparison.
+    fn diff_str(old: &str, new: &str, with_metadata: bool) -> Vec<String> {
+        diff(&eval(old), &eval(new), with_metadata)
+            .into_iter()
+            .map(|Difference { path, change }| {
+                let kind = match change {
+                    Change::Added(_) => "+",
+                    Change::Removed(_) => "-",
+                    Change::Changed { .. } => "~",
+                    Change::Metadata(_) => "m",
+                };
+
+                format!("{kind} {path}")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn values() {
+        assert!(diff_str("{a = 1, b = [1, 2]}", "{b = [1, 2], a = 1}", true).is_empty());
+        assert_eq!(
+            diff_str(
+                "{a = 1, b = {c = \"x\", d = 'Foo}, e = [1, 2]}",
+                "{a = 2, b = {d = 'Foo, f = null}, e = [1, 2, 3]}",
+                false
+            ),
+            vec!["~ a", "- b.c", "+ b.f", "+ e[2]"]
+        );
+        assert_eq!(diff_str("{a = {b = 1}}", "{a = [1]}", false), vec!["~ a"]);
+    }
+
+    #[test]
+    fn metadata() {
+        let old = "{a | doc \"old\" | Number = 1, b | default = 1}";
+        let new = "{a | doc \"new\" | Number = 1, b | force = 2}";
+
+        assert_eq!(diff_str(old, new, false), vec!["~ b"]);
+        assert_eq!(diff_str(old, new, true), vec!["m a", "m b", "~ b"]);
+
+        let changes = diff(&eval(old), &eval(new), true);
+        let Change::Metadata(changes) = &changes[1].change else {
+            panic!("expected a metadata change");
+        };
+
+        assert_eq!(
+            changes,
+            &vec![MetadataChange {
+                attribute: "priority",
+                old: Some("default".to_owned()),
+                new: Some("force".to_owned()),
+            }]
+        );
+    }
+}
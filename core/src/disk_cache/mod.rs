@@ -0,0 +1,354 @@
+//! The on-disk cache of prepared terms.
+//!
+//! Each run of Nickel parses, typechecks and transforms all the sources of a program again,
+//! including the standard library, which accounts for most of the startup time of small programs.
+//! The disk cache stores the terms in the state they have once ready for evaluation, so that later
+//! runs can load them instead of preparing the sources again.
+//!
+//! A source is identified by a key, derived from its name (the path of a file or the name of a
+//! standard library module), its format and its content. The entry of a source also records the
+//! imports it contains, together with the key of each imported file, since typechecking depends on
+//! the imported terms. An entry is only used if all the imports still resolve to files with the
+//! same keys, see [crate::cache::Cache].
+//!
+//! The layout of the cache directory is the following:
+//!
+//! ```text
+//! <cache dir>/<build>/<key>.bin    entry of a source
+//! <cache dir>/last-prune           marker of the last removal of unused entries
+//! ```
+//!
+//! where `<build>` is the version of Nickel. Development builds can change the representation of
+//! terms without changing the version, so they also include the modification time of the
+//! executable, and the directories of the other development builds are removed on the next prune.
+//! Entries which haven't been used for [MAX_AGE] are removed as well.
+//!
+//! Errors when reading or writing the cache are ignored: the sources are prepared as if they
+//! weren't cached.
+pub(crate) mod repr;
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cache::InputFormat,
+    files::FileId,
+    stdlib::StdlibModule,
+    term::{Import, RichTerm},
+    typecheck::Wildcards,
+};
+
+/// The version of the encoding of cache entries. It must be bumped whenever the encoding changes
+/// without the version of Nickel changing.
+const FORMAT_VERSION: u32 = 2;
+
+/// Entries which haven't been used for this long are removed.
+pub const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The minimum delay between two removals of unused entries.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const PRUNE_MARKER: &str = "last-prune";
+
+/// The separator between the version and the executable timestamp in the name of the directories
+/// of development builds.
+const DEV_BUILD_SEPARATOR: &str = "-dev-";
+
+/// A local directory where prepared terms are stored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiskCache {
+    pub dir: PathBuf,
+    /// The name of the subdirectory holding the entries of the running build.
+    build: String,
+}
+
+/// The header of an entry file. The key is stored again to detect the (unlikely) collisions of the
+/// truncated file names.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    key: String,
+}
+
+/// A file referred to by the positions or the resolved imports of a cached term.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum FileRef {
+    Stdlib(StdlibModule),
+    Path(PathBuf, InputFormat),
+}
+
+/// An import of a cached source, and the key of the file it resolved to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ImportRecord {
+    pub import: Import,
+    pub key: String,
+}
+
+/// The part of an entry which is needed to check that it's still valid, and to decode the rest.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct EntryHead {
+    /// The files referred to by the term, in the order of the file table of [repr::encoding].
+    pub files: Vec<FileRef>,
+    pub imports: Vec<ImportRecord>,
+}
+
+/// A prepared term, as stored in the cache.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct EntryBody {
+    pub term: RichTerm,
+    pub wildcards: Wildcards,
+}
+
+/// An entry read from the cache, whose body is decoded separately by [Entry::decode].
+pub(crate) struct Entry {
+    pub head: EntryHead,
+    body: Vec<u8>,
+}
+
+impl Entry {
+    /// Decodes the body of the entry, `files` being the files of the running program
+    /// corresponding to [EntryHead::files].
+    pub(crate) fn decode(&self, files: Vec<FileId>) -> Option<EntryBody> {
+        repr::decoding(files, || bincode::deserialize(&self.body).ok())
+    }
+}
+
+/// Encodes an entry body. Returns the encoded body and the files it refers to, or `None` if the
+/// term contains data which can't be cached.
+pub(crate) fn encode(body: &EntryBody) -> Option<(Vec<u8>, Vec<FileId>)> {
+    let (bytes, files) = repr::encoding(|| bincode::serialize(body));
+    Some((bytes.ok()?, files))
+}
+
+impl DiskCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        DiskCache {
+            dir: dir.into(),
+            build: build_name(),
+        }
+    }
+
+    /// The directory holding the entries of the running build.
+    fn build_dir(&self) -> PathBuf {
+        self.dir.join(&self.build)
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.build_dir().join(format!("{}.bin", &key[..32]))
+    }
+
+    /// Computes the key of a source, given its name, the tag of its input format and its content.
+    pub(crate) fn key(name: &str, format: &str, source: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(FORMAT_VERSION.to_le_bytes());
+
+        for part in [name, format, source] {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// Loads the entry `key`. Returns `None` if there is no such entry or if it can't be read.
+    /// Loading an entry marks it as used, see [Self::prune].
+    pub(crate) fn load(&self, key: &str) -> Option<Entry> {
+        let path = self.entry_path(key);
+        let bytes = fs::read(&path).ok()?;
+        let mut payload = bytes.as_slice();
+        let header: Header = bincode::deserialize_from(&mut payload).ok()?;
+
+        if header.version != FORMAT_VERSION || header.key != key {
+            return None;
+        }
+
+        let head: EntryHead = bincode::deserialize_from(&mut payload).ok()?;
+
+        let _ = fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+
+        Some(Entry {
+            head,
+            body: payload.to_vec(),
+        })
+    }
+
+    /// Stores an entry, given its head and its body encoded by [encode].
+    pub(crate) fn store(&self, key: &str, head: &EntryHead, body: &[u8]) {
+        let header = Header {
+            version: FORMAT_VERSION,
+            key: key.to_owned(),
+        };
+
+        // Errors are ignored, see the module documentation.
+        let _ = self.write_entry(&self.entry_path(key), &header, head, body);
+        let _ = self.prune_if_due();
+    }
+
+    /// Writes an entry to a temporary file first, and then moves it in place, so that concurrent
+    /// runs never read a partially written entry.
+    fn write_entry(
+        &self,
+        path: &Path,
+        header: &Header,
+        head: &EntryHead,
+        body: &[u8],
+    ) -> std::io::Result<()> {
+        let dir = self.build_dir();
+        fs::create_dir_all(&dir)?;
+
+        let tmp_path = dir.join(format!(
+            "{}.{}.tmp",
+            path.file_stem().and_then(|s| s.to_str()).unwrap_or("entry"),
+            std::process::id()
+        ));
+
+        let result = (|| {
+            let mut file = fs::File::create(&tmp_path)?;
+            bincode::serialize_into(&mut file, header).map_err(std::io::Error::other)?;
+            bincode::serialize_into(&mut file, head).map_err(std::io::Error::other)?;
+            file.write_all(body)?;
+            file.sync_all()?;
+            fs::rename(&tmp_path, path)
+        })();
+
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+
+        result
+    }
+
+    /// Runs [Self::prune] if it hasn't been run for [PRUNE_INTERVAL].
+    fn prune_if_due(&self) -> std::io::Result<()> {
+        let marker = self.dir.join(PRUNE_MARKER);
+        let now = SystemTime::now();
+
+        if let Ok(last) = fs::metadata(&marker).and_then(|metadata| metadata.modified()) {
+            if now.duration_since(last).unwrap_or_default() < PRUNE_INTERVAL {
+                return Ok(());
+            }
+        }
+
+        fs::write(&marker, [])?;
+        self.prune(now)
+    }
+
+    /// Removes the entries which haven't been used for [MAX_AGE] before `now`, and the directories
+    /// of the other builds which don't hold any entry anymore. If the running build is a
+    /// development build, the directories of the other development builds are removed as well.
+    pub fn prune(&self, now: SystemTime) -> std::io::Result<()> {
+        let is_dev_build = self.build.contains(DEV_BUILD_SEPARATOR);
+
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let dir_entry = dir_entry?;
+
+            if !dir_entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            let name = dir_entry.file_name();
+            let path = dir_entry.path();
+            let is_current = name == self.build.as_str();
+
+            if !is_current && is_dev_build && name.to_string_lossy().contains(DEV_BUILD_SEPARATOR) {
+                fs::remove_dir_all(&path)?;
+                continue;
+            }
+
+            let mut empty = true;
+
+            for entry in fs::read_dir(&path)? {
+                let entry = entry?;
+                let modified = entry.metadata()?.modified()?;
+
+                if now.duration_since(modified).unwrap_or_default() > MAX_AGE {
+                    fs::remove_file(entry.path())?;
+                } else {
+                    empty = false;
+                }
+            }
+
+            if empty && !is_current {
+                fs::remove_dir(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The name of the directory holding the entries of the running build.
+fn build_name() -> String {
+    let version = env!("CARGO_PKG_VERSION");
+
+    #[cfg(debug_assertions)]
+    if let Some(modified) = std::env::current_exe()
+        .and_then(fs::metadata)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+    {
+        return format!("{version}{DEV_BUILD_SEPARATOR}{}", modified.as_nanos());
+    }
+
+    version.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::{Cache, ErrorTolerance},
+        eval::cache::{Cache as _, CacheImpl},
+        files::Files,
+    };
+
+    fn entry(body: Vec<u8>) -> Entry {
+        Entry {
+            head: EntryHead {
+                files: Vec::new(),
+                imports: Vec::new(),
+            },
+            body,
+        }
+    }
+
+    #[test]
+    fn stdlib_round_trip() {
+        let mut cache = Cache::new(ErrorTolerance::Strict);
+        cache.prepare_stdlib(&mut CacheImpl::new()).unwrap();
+
+        for (_, file_id) in cache.files().stdlib_modules() {
+            let body = EntryBody {
+                term: cache.get_owned(file_id).unwrap(),
+                wildcards: Vec::new(),
+            };
+            let (bytes, files) = encode(&body).unwrap();
+            assert_eq!(files, vec![file_id]);
+            assert_eq!(entry(bytes).decode(files), Some(body));
+        }
+    }
+
+    #[test]
+    fn malformed_input() {
+        let file_id = Files::new().stdlib_modules().next().unwrap().1;
+
+        for bytes in [&[][..], &[200], &[5, 0xff, 0xff, 0xff], &[0, 2, 0]] {
+            assert!(entry(bytes.to_vec()).decode(vec![file_id]).is_none());
+        }
+    }
+}
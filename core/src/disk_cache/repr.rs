@@ -0,0 +1,300 @@
+//! The binary representation of terms in the disk cache.
+//!
+//! The AST types derive their serde instances, which are shared with the human-readable formats
+//! used for export. The few types whose export representation loses information (terms, merge
+//! priorities, labeled types and identifiers) check [serde::Serializer::is_human_readable] and
+//! fall back to the representations of this module for binary formats.
+//!
+//! Terms also refer to data which only makes sense within a given run of Nickel: file ids and
+//! generated identifiers. File ids are relocated while an entry is encoded or decoded, and
+//! generated identifiers are reserved when decoded, see [encoding] and [decoding].
+use std::{cell::RefCell, collections::HashMap};
+
+use serde::{ser::SerializeTuple, Deserialize, Deserializer, Serialize, Serializer};
+use smallvec::SmallVec;
+
+use crate::{
+    error::{EvalError, ParseError},
+    eval::cache::CacheIndex,
+    files::FileId,
+    identifier::{Ident, LocIdent},
+    label::Label,
+    position::TermPos,
+    term::{
+        array::{Array, ArrayAttrs},
+        pattern::Pattern,
+        record::{Field, RecordData, RecordDeps},
+        string::NickelString,
+        BinaryOp, EnumVariantAttrs, ForeignIdPayload, Import, LabeledType, LetAttrs, MatchData,
+        MergePriority, NAryOp, Number, RichTerm, SealingKey, StrChunk, Term, TypeAnnotation,
+        UnaryOp,
+    },
+    typ::Type,
+};
+
+/// The relocation in effect while encoding or decoding an entry.
+enum Relocation {
+    /// File ids are replaced by their index in `files`, which is filled along the way.
+    Encode {
+        files: Vec<FileId>,
+        indices: HashMap<FileId, u32>,
+    },
+    /// Indices are replaced by the file ids of the running program, and generated identifiers are
+    /// reserved.
+    Decode { files: Vec<FileId> },
+}
+
+thread_local! {
+    static RELOCATION: RefCell<Option<Relocation>> = const { RefCell::new(None) };
+}
+
+/// Resets the relocation when dropped, including on unwinding.
+struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        RELOCATION.with(|r| r.borrow_mut().take());
+    }
+}
+
+/// Runs `f`, which encodes some terms, and returns its result together with the files referred to
+/// by the encoded terms. The n-th file of this table is encoded as `n`.
+pub(crate) fn encoding<T>(f: impl FnOnce() -> T) -> (T, Vec<FileId>) {
+    RELOCATION.with(|r| {
+        *r.borrow_mut() = Some(Relocation::Encode {
+            files: Vec::new(),
+            indices: HashMap::new(),
+        })
+    });
+    let guard = Guard;
+    let result = f();
+
+    let files = match RELOCATION.with(|r| r.borrow_mut().take()) {
+        Some(Relocation::Encode { files, .. }) => files,
+        _ => Vec::new(),
+    };
+    drop(guard);
+
+    (result, files)
+}
+
+/// Runs `f`, which decodes some terms encoded by [encoding], `files` being the files of the running
+/// program which correspond to the file table of the encoded terms.
+pub(crate) fn decoding<T>(files: Vec<FileId>, f: impl FnOnce() -> T) -> T {
+    RELOCATION.with(|r| *r.borrow_mut() = Some(Relocation::Decode { files }));
+    let _guard = Guard;
+    f()
+}
+
+/// Reserves an identifier read from the disk cache, so that the identifiers generated later by
+/// this run don't clash with it. See [Ident::reserve]. Outside of [decoding], this does nothing.
+pub(crate) fn reserve_ident(ident: Ident) {
+    let decoding = RELOCATION.with(|r| matches!(&*r.borrow(), Some(Relocation::Decode { .. })));
+
+    if decoding {
+        ident.reserve();
+    }
+}
+
+/// Serde instances of file ids relocated by [encoding] and [decoding]. Outside of those, file
+/// ids are represented as is.
+pub(crate) mod file_id {
+    use super::*;
+
+    pub(crate) fn serialize<S>(file_id: &FileId, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let index = RELOCATION.with(|r| match &mut *r.borrow_mut() {
+            Some(Relocation::Encode { files, indices }) => {
+                Some(*indices.entry(*file_id).or_insert_with(|| {
+                    files.push(*file_id);
+                    (files.len() - 1) as u32
+                }))
+            }
+            _ => None,
+        });
+
+        match index {
+            Some(index) => serializer.serialize_u32(index),
+            None => file_id.serialize(serializer),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<FileId, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let decoding = RELOCATION.with(|r| matches!(&*r.borrow(), Some(Relocation::Decode { .. })));
+
+        if !decoding {
+            return FileId::deserialize(deserializer);
+        }
+
+        let index = u32::deserialize(deserializer)?;
+
+        RELOCATION
+            .with(|r| match &*r.borrow() {
+                Some(Relocation::Decode { files }) => files.get(index as usize).copied(),
+                _ => None,
+            })
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown file index {index}")))
+    }
+}
+
+/// Serde instances of the errors which can appear in a term before evaluation, that is, the ones
+/// generated by the compilation of patterns.
+mod runtime_error {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    enum Repr {
+        NonExhaustiveMatch { value: RichTerm, pos: TermPos },
+        FailedDestructuring { value: RichTerm, pattern: Pattern },
+    }
+
+    pub(super) fn serialize<S>(error: &EvalError, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let repr = match error {
+            EvalError::NonExhaustiveMatch { value, pos } => Repr::NonExhaustiveMatch {
+                value: value.clone(),
+                pos: *pos,
+            },
+            EvalError::FailedDestructuring { value, pattern } => Repr::FailedDestructuring {
+                value: value.clone(),
+                pattern: pattern.clone(),
+            },
+            _ => return Err(serde::ser::Error::custom("unsupported runtime error")),
+        };
+
+        repr.serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D>(deserializer: D) -> Result<EvalError, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::NonExhaustiveMatch { value, pos } => EvalError::NonExhaustiveMatch { value, pos },
+            Repr::FailedDestructuring { value, pattern } => {
+                EvalError::FailedDestructuring { value, pattern }
+            }
+        })
+    }
+}
+
+/// Serializes a term together with its position.
+pub(crate) fn serialize_rich_term<S>(rt: &RichTerm, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    struct Compact<'a>(&'a Term);
+
+    impl Serialize for Compact<'_> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            TermRepr::serialize(self.0, serializer)
+        }
+    }
+
+    let mut tuple = serializer.serialize_tuple(2)?;
+    tuple.serialize_element(&Compact(&rt.term))?;
+    tuple.serialize_element(&rt.pos)?;
+    tuple.end()
+}
+
+/// Deserializes a term serialized by [serialize_rich_term].
+pub(crate) fn deserialize_rich_term<'de, D>(deserializer: D) -> Result<RichTerm, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Compact(#[serde(with = "TermRepr")] Term, TermPos);
+
+    let Compact(term, pos) = Compact::deserialize(deserializer)?;
+    Ok(RichTerm::new(term, pos))
+}
+
+/// The binary representation of [Term]. Unlike the export representation, it covers all the
+/// terms produced by the parser and the program transformations. The terms only produced during
+/// evaluation can't be serialized. They come last, because binary formats identify variants by
+/// their index among the variants which aren't skipped.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Term")]
+enum TermRepr {
+    Null,
+    Bool(bool),
+    Num(Number),
+    Str(NickelString),
+    StrChunks(Vec<StrChunk<RichTerm>>),
+    Fun(LocIdent, RichTerm),
+    FunPattern(Pattern, RichTerm),
+    Lbl(Label),
+    Let(SmallVec<[(LocIdent, RichTerm); 4]>, RichTerm, LetAttrs),
+    LetPattern(SmallVec<[(Pattern, RichTerm); 1]>, RichTerm, LetAttrs),
+    App(RichTerm, RichTerm),
+    Var(LocIdent),
+    Enum(LocIdent),
+    EnumVariant {
+        tag: LocIdent,
+        arg: RichTerm,
+        attrs: EnumVariantAttrs,
+    },
+    Record(RecordData),
+    RecRecord(RecordData, Vec<(RichTerm, Field)>, Option<RecordDeps>),
+    Match(MatchData),
+    Array(Array, ArrayAttrs),
+    Op1(UnaryOp, RichTerm),
+    Op2(BinaryOp, RichTerm, RichTerm),
+    OpN(NAryOp, Vec<RichTerm>),
+    Annotated(TypeAnnotation, RichTerm),
+    Import(Import),
+    ResolvedImport(#[serde(with = "file_id")] FileId),
+    Type {
+        typ: Type,
+        contract: RichTerm,
+    },
+    CustomContract(RichTerm),
+    #[serde(with = "runtime_error")]
+    RuntimeError(EvalError),
+    SealingKey(SealingKey),
+    #[serde(skip)]
+    Sealed(SealingKey, RichTerm, Label),
+    #[serde(skip)]
+    ParseError(ParseError),
+    #[serde(skip)]
+    Closure(CacheIndex),
+    #[serde(skip)]
+    ForeignId(ForeignIdPayload),
+}
+
+/// Fails to deserialize a variant which is never serialized, but which can't be skipped without
+/// shifting the index of the following variants.
+pub(crate) fn unsupported<'de, D, T>(_deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Err(serde::de::Error::custom("unsupported variant"))
+}
+
+/// The binary representation of [MergePriority].
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "MergePriority")]
+pub(crate) enum MergePriorityRepr {
+    Bottom,
+    Neutral,
+    Numeral(Number),
+    Top,
+}
+
+/// The binary representation of [LabeledType].
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "LabeledType")]
+pub(crate) struct LabeledTypeRepr {
+    typ: Type,
+    label: Label,
+}
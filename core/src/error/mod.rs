@@ -10,16 +10,19 @@ use codespan_reporting::term::termcolor::{ColorChoice, StandardStream, WriteColo
 use lalrpop_util::ErrorRecovery;
 use malachite::num::conversion::traits::ToSci;
 
+use std::time::Duration;
+
 use crate::{
-    cache::Cache,
+    cache::{Cache, InputFormat},
     eval::callstack::CallStack,
     files::{FileId, Files},
-    identifier::LocIdent,
+    identifier::{Ident, LocIdent},
     label::{
         self,
         ty_path::{self, PathSpan},
         MergeKind, MergeLabel,
     },
+    package::{LOCK_FILE, MANIFEST_FILE},
     parser::{
         self,
         error::{InvalidRecordTypeError, LexicalError, ParseError as InternalParseError},
@@ -185,6 +188,52 @@ pub enum EvalError {
         /// Evaluated expression
         value: RichTerm,
     },
+    /// The evaluation exceeded its maximum number of reduction steps.
+    StepLimitExceeded {
+        limit: u64,
+        call_stack: CallStack,
+        /// The number of outer elements of the call stack which were left out of `call_stack`.
+        elided_frames: usize,
+        /// The position of the term being evaluated when the limit was reached.
+        pos: TermPos,
+    },
+    /// The evaluation stack exceeded its maximum depth.
+    StackLimitExceeded {
+        limit: usize,
+        call_stack: CallStack,
+        /// The number of outer elements of the call stack which were left out of `call_stack`.
+        elided_frames: usize,
+        /// The position of the term being evaluated when the limit was reached.
+        pos: TermPos,
+    },
+    /// The memory usage exceeded its maximum, in bytes.
+    MemoryLimitExceeded {
+        limit: u64,
+        call_stack: CallStack,
+        /// The number of outer elements of the call stack which were left out of `call_stack`.
+        elided_frames: usize,
+        /// The position of the term being evaluated when the limit was reached.
+        pos: TermPos,
+    },
+    /// The evaluation didn't finish within its time limit.
+    TimeLimitExceeded {
+        limit: Duration,
+        call_stack: CallStack,
+        /// The number of outer elements of the call stack which were left out of `call_stack`.
+        elided_frames: usize,
+        /// The position of the term being evaluated when the limit was reached.
+        pos: TermPos,
+    },
+    /// A native function provided by the program embedding Nickel returned an error.
+    NativeFunctionError {
+        /// The name of the native function.
+        name: String,
+        /// The error message returned by the native function.
+        message: String,
+        call_stack: CallStack,
+        /// The position of the call.
+        pos: TermPos,
+    },
     /// An unexpected internal error.
     InternalError(String, TermPos),
     /// Errors occurring rarely enough to not deserve a dedicated variant.
@@ -590,6 +639,37 @@ pub enum ImportError {
         /* error */ ParseErrors,
         /* import position */ TermPos,
     ),
+    /// A package was imported, but no package manifest or lock file was loaded.
+    NoPackageMap { pos: TermPos },
+    /// A package was imported, but it isn't declared as a dependency of the importing package.
+    MissingDependency {
+        /// The root directory of the package that tried to import the missing dependency. `None`
+        /// for an import from the top-level package, that is, the one being evaluated.
+        parent: Option<std::path::PathBuf>,
+        /// The name of the missing dependency.
+        missing: Ident,
+        /// The position of the import.
+        pos: TermPos,
+    },
+    /// An import was rejected by the import policy, see [crate::cache::ImportPolicy].
+    Forbidden {
+        /// The imported path, as written in the program.
+        path: String,
+        violation: ImportPolicyViolation,
+        /// The position of the import.
+        pos: TermPos,
+    },
+}
+
+/// The reason why an import was rejected by the import policy.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ImportPolicyViolation {
+    /// The import path is absolute, but absolute import paths are forbidden.
+    AbsolutePath,
+    /// The imported file isn't located under any of the allowed directories.
+    OutsideAllowedDirs(Vec<std::path::PathBuf>),
+    /// The format of the import is forbidden.
+    ForbiddenFormat(InputFormat),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -608,10 +688,36 @@ pub enum ExportErrorData {
     UnsupportedNull(ExportFormat, RichTerm),
     /// Tried exporting something else than a `String` to raw format.
     NotAString(RichTerm),
+    /// Tried exporting something else than an `Array` to a format representing a sequence of
+    /// documents.
+    NotAnArray(ExportFormat, RichTerm),
+    /// Tried exporting something else than a `Record` to a format representing a set of
+    /// key-value pairs.
+    NotARecord(ExportFormat, RichTerm),
+    /// Encountered a record or an array where the format only supports scalar values.
+    UnsupportedNesting(ExportFormat, RichTerm),
+    /// A field name can't be used as a key in the format.
+    InvalidKey(ExportFormat, String),
+    /// Two different fields are written with the same key, for a format which flattens nested
+    /// records.
+    DuplicateKey(ExportFormat, String),
+    /// The top-level value of an XML export isn't a record with exactly one field.
+    InvalidXmlRoot(RichTerm),
+    /// The value of a multi-file export isn't a record mapping file paths to values.
+    NotAFileTree(RichTerm),
+    /// A path of a multi-file export is absolute or escapes the output directory.
+    InvalidOutputPath(String),
+    /// Two paths of a multi-file export refer to the same file, or one of them is a directory
+    /// containing the other one.
+    ConflictingOutputPaths(String, String),
+    /// A path of a multi-file export escapes the output directory through a symbolic link.
+    OutputPathEscapes(String),
     /// A term contains constructs that cannot be serialized.
     NonSerializable(RichTerm),
     /// No exportable documentation was found when requested.
     NoDocumentation(RichTerm),
+    /// Tried generating a JSON schema from something else than a record contract.
+    NotARecordContract(RichTerm),
     /// A number was too large (in absolute value) to be serialized as `f64`
     NumberOutOfRange {
         term: RichTerm,
@@ -872,6 +978,22 @@ impl ParseError {
         )
     }
 
+    pub fn from_line_error(
+        format: &str,
+        error: crate::serialize::line_based::LineError,
+        file_id: FileId,
+    ) -> Self {
+        ParseError::ExternalFormatError(
+            String::from(format),
+            error.to_string(),
+            Some(RawSpan {
+                src_id: file_id,
+                start: ByteIndex::from(error.span.start as u32),
+                end: ByteIndex::from(error.span.end as u32),
+            }),
+        )
+    }
+
     #[cfg(feature = "nix-experimental")]
     pub fn from_nix(error: &str, _file_id: FileId) -> Self {
         // Span is shown in the nix error message
@@ -1019,6 +1141,42 @@ fn secondary_term(term: &RichTerm, files: &mut Files) -> Label<FileId> {
     secondary_alt(term.pos, term.to_string(), files)
 }
 
+/// Generates the diagnostics of an evaluation which exceeded one of its resource limits.
+fn limit_exceeded_diagnostics(
+    files: &Files,
+    msg: String,
+    call_stack: &CallStack,
+    elided_frames: usize,
+    pos: TermPos,
+) -> Vec<Diagnostic<FileId>> {
+    use blame_error::ExtendWithCallStack;
+
+    let labels = pos
+        .as_opt_ref()
+        .map(|span| vec![primary(span).with_message("evaluation stopped here")])
+        .unwrap_or_default();
+
+    let mut notes = vec![String::from(
+        "The evaluation was aborted because it reached a resource limit. The program might loop \
+        forever, or the limit might be too low for this program.",
+    )];
+
+    if elided_frames > 0 {
+        notes.push(format!(
+            "Only the innermost calls are shown: {elided_frames} outer elements of the call stack \
+            were left out."
+        ));
+    }
+
+    let mut diags = vec![Diagnostic::error()
+        .with_message(msg)
+        .with_labels(labels)
+        .with_notes(notes)];
+
+    diags.extend_with_call_stack(files, call_stack);
+    diags
+}
+
 fn cardinal(number: usize) -> String {
     let suffix = if number % 10 == 1 {
         "st"
@@ -1321,6 +1479,75 @@ impl IntoDiagnostics for EvalError {
                     .with_message("infinite recursion")
                     .with_labels(labels)]
             }
+            EvalError::StepLimitExceeded {
+                limit,
+                call_stack,
+                elided_frames,
+                pos,
+            } => limit_exceeded_diagnostics(
+                files,
+                format!("evaluation exceeded the maximum number of steps ({limit})"),
+                &call_stack,
+                elided_frames,
+                pos,
+            ),
+            EvalError::StackLimitExceeded {
+                limit,
+                call_stack,
+                elided_frames,
+                pos,
+            } => limit_exceeded_diagnostics(
+                files,
+                format!("evaluation exceeded the maximum stack depth ({limit})"),
+                &call_stack,
+                elided_frames,
+                pos,
+            ),
+            EvalError::MemoryLimitExceeded {
+                limit,
+                call_stack,
+                elided_frames,
+                pos,
+            } => limit_exceeded_diagnostics(
+                files,
+                format!("evaluation exceeded the maximum memory usage ({limit} bytes)"),
+                &call_stack,
+                elided_frames,
+                pos,
+            ),
+            EvalError::TimeLimitExceeded {
+                limit,
+                call_stack,
+                elided_frames,
+                pos,
+            } => limit_exceeded_diagnostics(
+                files,
+                format!("evaluation exceeded its time limit ({limit:?})"),
+                &call_stack,
+                elided_frames,
+                pos,
+            ),
+            EvalError::NativeFunctionError {
+                name,
+                message,
+                call_stack,
+                pos,
+            } => {
+                use blame_error::ExtendWithCallStack;
+
+                let labels = pos
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("in this call")])
+                    .unwrap_or_default();
+
+                let mut diags = vec![Diagnostic::error()
+                    .with_message(format!("native function `{name}` failed"))
+                    .with_labels(labels)
+                    .with_notes(vec![message])];
+
+                diags.extend_with_call_stack(files, &call_stack);
+                diags
+            }
             EvalError::Other(msg, span_opt) => {
                 let labels = span_opt
                     .as_opt_ref()
@@ -2562,6 +2789,97 @@ impl IntoDiagnostics for ImportError {
 
                 diagnostic
             }
+            ImportError::NoPackageMap { pos } => {
+                let labels = pos
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("imported here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message("tried to import a package without a package manifest")
+                    .with_labels(labels)
+                    .with_notes(vec![
+                        format!(
+                            "Packages are declared in a package manifest file, \
+                            `{MANIFEST_FILE}`, and pinned by a lock file, `{LOCK_FILE}`."
+                        ),
+                        "Run `nickel package lock` next to the manifest to generate the lock \
+                        file."
+                            .into(),
+                    ])]
+            }
+            ImportError::MissingDependency {
+                parent,
+                missing,
+                pos,
+            } => {
+                let labels = pos
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("imported here")])
+                    .unwrap_or_default();
+
+                let msg = match parent {
+                    Some(parent) => format!(
+                        "package `{missing}` isn't a dependency of the package at `{}`",
+                        parent.display()
+                    ),
+                    None => format!("package `{missing}` isn't a dependency of this package"),
+                };
+
+                vec![Diagnostic::error()
+                    .with_message(msg)
+                    .with_labels(labels)
+                    .with_notes(vec![
+                        format!(
+                            "Dependencies must be declared in the package manifest file, \
+                            `{MANIFEST_FILE}`."
+                        ),
+                        "If you've recently added this dependency, run `nickel package lock` to \
+                        update the lock file."
+                            .into(),
+                    ])]
+            }
+            ImportError::Forbidden {
+                path,
+                violation,
+                pos,
+            } => {
+                let labels = pos
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("imported here")])
+                    .unwrap_or_default();
+
+                let note = match violation {
+                    ImportPolicyViolation::AbsolutePath => {
+                        "Absolute import paths are forbidden. Use a path relative to the \
+                        importing file instead."
+                            .to_owned()
+                    }
+                    ImportPolicyViolation::OutsideAllowedDirs(dirs) => {
+                        let dirs: Vec<_> = dirs
+                            .iter()
+                            .map(|dir| format!("`{}`", dir.display()))
+                            .collect();
+
+                        format!(
+                            "Only the files located under the following directories can be \
+                            imported: {}.",
+                            dirs.join(", ")
+                        )
+                    }
+                    ImportPolicyViolation::ForbiddenFormat(format) => {
+                        format!(
+                            "Importing files in the `{}` format is forbidden.",
+                            format.to_tag()
+                        )
+                    }
+                };
+
+                vec![Diagnostic::error()
+                    .with_message(format!("import of {path} is forbidden"))
+                    .with_labels(labels)
+                    .with_notes(vec![note])]
+            }
         }
     }
 }
@@ -2584,6 +2902,133 @@ impl IntoDiagnostics for ExportError {
                 ))
                 .with_labels(vec![primary_term(&rt, files)])
                 .with_notes(notes)],
+            ExportErrorData::NotAnArray(format, rt) => vec![Diagnostic::error()
+                .with_message(format!(
+                    "{format} export expects an Array value, but got {}",
+                    rt.as_ref()
+                        .type_of()
+                        .unwrap_or_else(|| String::from("<unevaluated>"))
+                ))
+                .with_labels(vec![primary_term(&rt, files)])
+                .with_notes(notes)],
+            ExportErrorData::NotARecord(format, rt) => vec![Diagnostic::error()
+                .with_message(format!(
+                    "{format} export expects a Record value, but got {}",
+                    rt.as_ref()
+                        .type_of()
+                        .unwrap_or_else(|| String::from("<unevaluated>"))
+                ))
+                .with_labels(vec![primary_term(&rt, files)])
+                .with_notes(notes)],
+            ExportErrorData::UnsupportedNesting(format, rt) => {
+                let shape = match format {
+                    ExportFormat::Ini => {
+                        "The ini format only supports records whose fields are either scalar \
+                        values, or sections: records whose fields are scalar values."
+                    }
+                    ExportFormat::Properties => {
+                        "The properties format only supports (possibly nested) records of scalar \
+                        values. Nested records are flattened using dotted keys."
+                    }
+                    ExportFormat::Dotenv => {
+                        "The dotenv format only supports flat records of scalar values."
+                    }
+                    ExportFormat::Xml => {
+                        "In the xml format, arrays are serialized as repeated elements, and \
+                        attributes (fields starting with `@`) and text content (`$text`) must \
+                        be scalar values."
+                    }
+                    _ => "This format only supports scalar values here.",
+                };
+                notes.push(shape.into());
+
+                vec![Diagnostic::error()
+                    .with_message(format!(
+                        "{format} format doesn't support {} values here",
+                        rt.as_ref()
+                            .type_of()
+                            .unwrap_or_else(|| String::from("<unevaluated>"))
+                    ))
+                    .with_labels(vec![primary_term(&rt, files)])
+                    .with_notes(notes)]
+            }
+            ExportErrorData::InvalidKey(format, key) => {
+                let rule = match format {
+                    ExportFormat::Dotenv => {
+                        "Dotenv keys must be non-empty and only contain ASCII letters, digits and \
+                        underscores, and must not start with a digit."
+                    }
+                    ExportFormat::Ini => {
+                        "Ini keys and section names must be non-empty, must not start or end \
+                        with whitespace, and must not contain newlines or any of `=`, `;`, `#`, \
+                        `[` or `]`."
+                    }
+                    ExportFormat::Xml => {
+                        "Xml element and attribute names must start with a letter or `_`, and \
+                        only contain letters, digits, `_`, `-`, `.` or `:`."
+                    }
+                    _ => "This key can't be represented in this format.",
+                };
+                notes.push(rule.into());
+
+                vec![Diagnostic::error()
+                    .with_message(format!("invalid key `{key}` for the {format} format"))
+                    .with_notes(notes)]
+            }
+            ExportErrorData::InvalidXmlRoot(rt) => vec![Diagnostic::error()
+                .with_message("xml export expects a record with exactly one field")
+                .with_labels(vec![primary_term(&rt, files)])
+                .with_notes(vec![
+                    "The only field of the exported record is the root element of the \
+                    XML document."
+                        .into(),
+                    "For example, `{ config = { port = 80 } }` is exported as \
+                    `<config><port>80</port></config>`."
+                        .into(),
+                ])],
+            ExportErrorData::NotAFileTree(rt) => vec![Diagnostic::error()
+                .with_message(format!(
+                    "multi-file export expects a Record value, but got {}",
+                    rt.as_ref()
+                        .type_of()
+                        .unwrap_or_else(|| String::from("<unevaluated>"))
+                ))
+                .with_labels(vec![primary_term(&rt, files)])
+                .with_notes(vec![
+                    "The exported value must be a record mapping relative file paths to the \
+                    content of each file, for example `{ \"config/app.yaml\" = { port = 80 } }`."
+                        .into(),
+                ])],
+            ExportErrorData::DuplicateKey(format, key) => vec![Diagnostic::error()
+                .with_message(format!("duplicate key `{key}` for the {format} format"))
+                .with_notes(vec![
+                    "Nested records are flattened using dotted keys, which can collide with a \
+                    field whose name contains a dot, as in `{ a.b = 1, \"a.b\" = 2 }`."
+                        .into(),
+                ])],
+            ExportErrorData::InvalidOutputPath(path) => vec![Diagnostic::error()
+                .with_message(format!("invalid output path `{path}`"))
+                .with_notes(vec![
+                    "Paths of a multi-file export must be non-empty relative paths, and can't \
+                    contain `..`, so that all files are written within the output directory."
+                        .into(),
+                ])],
+            ExportErrorData::ConflictingOutputPaths(path1, path2) => vec![Diagnostic::error()
+                .with_message(format!("conflicting output paths `{path1}` and `{path2}`"))
+                .with_notes(vec![
+                    "Two files of a multi-file export can't be written at the same path, and a \
+                    file can't be written at the path of a directory containing other files."
+                        .into(),
+                ])],
+            ExportErrorData::OutputPathEscapes(path) => vec![Diagnostic::error()
+                .with_message(format!(
+                    "output path `{path}` escapes the output directory through a symbolic link"
+                ))
+                .with_notes(vec![
+                    "Files of a multi-file export are only written within the output directory, \
+                    and symbolic links of the output directory can't point outside of it."
+                        .into(),
+                ])],
             ExportErrorData::UnsupportedNull(format, rt) => vec![Diagnostic::error()
                 .with_message(format!("{format} format doesn't support null values"))
                 .with_labels(vec![primary_term(&rt, files)])
@@ -2615,6 +3060,20 @@ impl IntoDiagnostics for ExportError {
                     .with_labels(vec![primary_term(&rt, files)])
                     .with_notes(notes)]
             }
+            ExportErrorData::NotARecordContract(rt) => {
+                notes
+                    .push("A JSON schema can only be generated from a record contract.".to_owned());
+
+                vec![Diagnostic::error()
+                    .with_message(format!(
+                        "JSON schema generation expects a record contract, but got {}",
+                        rt.as_ref()
+                            .type_of()
+                            .unwrap_or_else(|| String::from("<unevaluated>"))
+                    ))
+                    .with_labels(vec![primary_term(&rt, files)])
+                    .with_notes(notes)]
+            }
             ExportErrorData::NumberOutOfRange { term, value } => {
                 notes.push(format!(
                     "Only numbers in the range {:e} to {:e} can be portably serialized",
@@ -22,8 +22,9 @@ pub struct CallDescr {
 /// A call stack element.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum StackElem {
-    /// A function body was entered. The position is the position of the original application.
-    Fun(TermPos),
+    /// A function body was entered. `pos_app` is the position of the original application, and
+    /// `pos_fun` the position of the function.
+    Fun { pos_app: TermPos, pos_fun: TermPos },
     /// An application was evaluated.
     App(TermPos),
     /// A variable was entered.
@@ -35,6 +36,9 @@ pub enum StackElem {
         pos_field: TermPos,
         pos_access: TermPos,
     },
+    /// A contract was applied. The span is the one of the contract annotation. This element is
+    /// only pushed when profiling, see [super::profiler].
+    Contract(RawSpan),
 }
 
 impl CallStack {
@@ -58,10 +62,10 @@ impl CallStack {
     /// Push a marker to indicate that during the evaluation an application, the function part was
     /// finally evaluated to an expression of the form `fun x => body`, and that the body of this
     /// function was entered.
-    pub fn enter_fun(&mut self, pos: TermPos) {
+    pub fn enter_fun(&mut self, pos_app: TermPos, pos_fun: TermPos) {
         // We ignore application without positions, which have been generated by the interpreter.
-        if pos.is_def() {
-            self.0.push(StackElem::Fun(pos));
+        if pos_app.is_def() {
+            self.0.push(StackElem::Fun { pos_app, pos_fun });
         }
     }
 
@@ -81,6 +85,11 @@ impl CallStack {
         });
     }
 
+    /// Push a marker to indicate that a contract was applied.
+    pub fn enter_contract(&mut self, span: RawSpan) {
+        self.0.push(StackElem::Contract(span));
+    }
+
     /// Process a raw callstack by aggregating elements belonging to the same call. Return a list of
     /// call descriptions from the most nested/recent to the least nested/recent, together with the
     /// last pending call, if any.
@@ -135,7 +144,7 @@ impl CallStack {
             StackElem::Var {id, ..} if id.is_generated() => false,
             StackElem::Var{ pos: TermPos::Original(RawSpan { src_id, .. }), ..}
             | StackElem::Var{pos: TermPos::Inherited(RawSpan { src_id, .. }), ..}
-            | StackElem::Fun(TermPos::Original(RawSpan { src_id, .. }))
+            | StackElem::Fun { pos_app: TermPos::Original(RawSpan { src_id, .. }), .. }
             | StackElem::Field {pos_access: TermPos::Original(RawSpan { src_id, .. }), ..}
             | StackElem::Field {pos_access: TermPos::Inherited(RawSpan { src_id, .. }), ..}
             | StackElem::App(TermPos::Original(RawSpan { src_id, .. }))
@@ -187,8 +196,8 @@ impl CallStack {
                         _ => pending.push(CallDescr { head: None, span }),
                     }
                 }
-                StackElem::Fun(pos) => {
-                    let span = pos.unwrap();
+                StackElem::Fun { pos_app, .. } => {
+                    let span = pos_app.unwrap();
                     if pending
                         .last()
                         .map(|cdescr| cdescr.span == span)
@@ -200,6 +209,8 @@ impl CallStack {
                     // active call (e.g. in an multi-ary application `f g h`, a subcall would be `f
                     // g`). In any case, we do nothing.
                 }
+                // Contract applications are filtered out above.
+                StackElem::Contract(_) => (),
             }
         }
 
@@ -207,6 +218,13 @@ impl CallStack {
         (entered, pending.pop())
     }
 
+    /// Returns the `len` innermost elements of the call stack, together with the number of outer
+    /// elements that were left out.
+    pub fn innermost(&self, len: usize) -> (CallStack, usize) {
+        let elided = self.0.len().saturating_sub(len);
+        (CallStack(self.0[elided..].to_vec()), elided)
+    }
+
     /// Return the length of the callstack. Wrapper for `callstack.0.len()`.
     pub fn len(&self) -> usize {
         self.0.len()
@@ -0,0 +1,565 @@
+//! Tracing of contract applications.
+//!
+//! When contract tracing is enabled on the virtual machine, each application of a contract, be it
+//! through `%contract/apply%` or `%contract/check%`, is recorded together with its label: the span
+//! of the contract annotation, the name of the field it was attached to and the path within the
+//! type being checked. Contract failures, which blame and abort the evaluation, are recorded where
+//! they happen. The errors returned by `%contract/check%`, as when an alternative of
+//! `std.contract.any_of` doesn't match, are recorded separately as rejections: they don't make the
+//! contract fail unless the error is eventually turned into blame.
+//!
+//! Because of laziness, a contract annotation is only checked if the annotated value is actually
+//! forced, and lazy contracts (records, arrays, functions) are only partially checked. Comparing
+//! the trace with the contract annotations of the source tells which annotations have been checked
+//! at all, see [ContractTrace::report].
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+};
+
+use super::profiler::contract_source;
+use crate::{
+    files::Files,
+    identifier::{Ident, LocIdent},
+    label::{ty_path, Label},
+    position::RawSpan,
+    term::{RichTerm, Term, Traverse, TraverseControl, TypeAnnotation},
+};
+
+/// The format of a contract coverage report.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, clap::ValueEnum)]
+pub enum ContractReportFormat {
+    /// A human-readable table, one line per contract annotation
+    #[default]
+    Text,
+    /// A JSON document, including the paths checked by each contract annotation
+    Json,
+}
+
+/// The number of applications, failures and rejections of a contract with a given label.
+#[derive(Clone, Debug, Default)]
+struct Counts {
+    applications: u64,
+    failures: u64,
+    rejected: u64,
+}
+
+/// What identifies the application of a contract: the span of the contract annotation, the name
+/// of the field it was attached to, if any, and the path within the type being checked.
+type Key = (RawSpan, Option<Ident>, ty_path::Path);
+
+/// A trace of contract applications. See the module documentation.
+#[derive(Clone, Debug, Default)]
+pub struct ContractTrace {
+    counts: HashMap<Key, Counts>,
+    /// The keys of `counts`, in the order they were first seen.
+    keys: Vec<Key>,
+}
+
+impl ContractTrace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn counts_mut(&mut self, label: &Label) -> &mut Counts {
+        let key = (
+            label.span,
+            label.field_name.map(|id| id.ident()),
+            label.path.clone(),
+        );
+
+        if !self.counts.contains_key(&key) {
+            self.keys.push(key.clone());
+        }
+
+        self.counts.entry(key).or_default()
+    }
+
+    /// Records the application of a contract with the given label.
+    pub fn record_application(&mut self, label: &Label) {
+        self.counts_mut(label).applications += 1;
+    }
+
+    /// Records the failure of a contract with the given label, that is a blame error.
+    pub fn record_failure(&mut self, label: &Label) {
+        self.counts_mut(label).failures += 1;
+    }
+
+    /// Records an error returned by `%contract/check%` for the given label. The error may be
+    /// recovered from, so it doesn't count as a failure by itself.
+    pub fn record_rejection(&mut self, label: &Label) {
+        self.counts_mut(label).rejected += 1;
+    }
+
+    /// Returns the total number of contract applications recorded.
+    pub fn total_applications(&self) -> u64 {
+        self.counts.values().map(|counts| counts.applications).sum()
+    }
+
+    /// Builds the coverage report of the given contract annotations, as returned by
+    /// [annotations]. Annotations with the same span are only reported once.
+    pub fn report(&self, annotations: impl IntoIterator<Item = Annotation>) -> ContractReport {
+        let mut seen = HashSet::new();
+
+        let entries = annotations
+            .into_iter()
+            .filter(|annotation| seen.insert(annotation.span))
+            .map(|annotation| {
+                let paths: Vec<_> = self
+                    .keys
+                    .iter()
+                    .filter(|(span, ..)| *span == annotation.span)
+                    .map(|key @ (_, field, path)| {
+                        let counts = &self.counts[key];
+
+                        CheckedPath {
+                            path: path_to_string(*field, path),
+                            applications: counts.applications,
+                            failures: counts.failures,
+                            rejected: counts.rejected,
+                        }
+                    })
+                    .collect();
+
+                let status = if paths.iter().any(|path| path.failures > 0) {
+                    CoverageStatus::Failed
+                } else if paths.iter().any(|path| path.applications > 0) {
+                    CoverageStatus::Passed
+                } else {
+                    CoverageStatus::Unchecked
+                };
+
+                AnnotationCoverage {
+                    annotation,
+                    status,
+                    paths,
+                }
+            })
+            .collect();
+
+        ContractReport { entries }
+    }
+}
+
+/// Renders the field name and the type path of a contract label, as in `foo.<array>`.
+fn path_to_string(field: Option<Ident>, path: &ty_path::Path) -> String {
+    let elems = field
+        .map(|id| id.to_string())
+        .into_iter()
+        .chain(path.iter().map(|elem| match elem {
+            ty_path::Elem::Domain => "<domain>".to_owned(),
+            ty_path::Elem::Codomain => "<codomain>".to_owned(),
+            ty_path::Elem::Field(id) => id.to_string(),
+            ty_path::Elem::Array => "<array>".to_owned(),
+            ty_path::Elem::Dict => "<dict>".to_owned(),
+        }));
+
+    let path = elems.collect::<Vec<_>>().join(".");
+
+    if path.is_empty() {
+        "<value>".to_owned()
+    } else {
+        path
+    }
+}
+
+/// The kind of a contract annotation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AnnotationKind {
+    /// A type annotation `value : Type`, which is also checked at run-time.
+    Type,
+    /// A contract annotation `value | Contract`.
+    Contract,
+}
+
+/// A type or contract annotation of the source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    pub span: RawSpan,
+    pub kind: AnnotationKind,
+    /// The field the annotation is attached to, if any.
+    pub field: Option<LocIdent>,
+}
+
+/// Returns the type and contract annotations of a term, including the annotations of record
+/// fields, in the order they appear in the term.
+pub fn annotations(term: &RichTerm) -> Vec<Annotation> {
+    let mut annotations = Vec::new();
+
+    let mut collect = |annot: &TypeAnnotation, field: Option<LocIdent>| {
+        let types = annot.typ.iter().map(|typ| (typ, AnnotationKind::Type));
+        let contracts = annot
+            .contracts
+            .iter()
+            .map(|ctr| (ctr, AnnotationKind::Contract));
+
+        for (labeled_typ, kind) in types.chain(contracts) {
+            annotations.push(Annotation {
+                span: labeled_typ.label.span,
+                kind,
+                field,
+            });
+        }
+    };
+
+    term.traverse_ref(
+        &mut |rt: &RichTerm, _: &()| {
+            match rt.as_ref() {
+                Term::Annotated(annot, _) => collect(annot, None),
+                Term::Record(data) => {
+                    for (id, field) in &data.fields {
+                        collect(&field.metadata.annotation, Some(*id));
+                    }
+                }
+                Term::RecRecord(data, dyn_fields, _) => {
+                    for (id, field) in &data.fields {
+                        collect(&field.metadata.annotation, Some(*id));
+                    }
+
+                    for (_, field) in dyn_fields {
+                        collect(&field.metadata.annotation, None);
+                    }
+                }
+                _ => (),
+            }
+
+            TraverseControl::<(), ()>::Continue
+        },
+        &(),
+    );
+
+    annotations.sort_by_key(|annotation| (annotation.span.src_id, annotation.span.start));
+    annotations
+}
+
+/// Whether a contract annotation has been checked.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CoverageStatus {
+    /// The contract has never been applied, because the annotated value wasn't forced.
+    Unchecked,
+    /// The contract has been applied, and hasn't failed.
+    Passed,
+    /// The contract has failed.
+    Failed,
+}
+
+impl CoverageStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CoverageStatus::Unchecked => "unchecked",
+            CoverageStatus::Passed => "passed",
+            CoverageStatus::Failed => "failed",
+        }
+    }
+}
+
+/// The applications of a contract annotation for a given field and type path.
+#[derive(Clone, Debug)]
+pub struct CheckedPath {
+    /// The field name and the type path, as in `foo.<array>`.
+    pub path: String,
+    pub applications: u64,
+    pub failures: u64,
+    /// The number of errors returned by `%contract/check%`, which may have been recovered from.
+    pub rejected: u64,
+}
+
+/// The coverage of a contract annotation.
+#[derive(Clone, Debug)]
+pub struct AnnotationCoverage {
+    pub annotation: Annotation,
+    pub status: CoverageStatus,
+    pub paths: Vec<CheckedPath>,
+}
+
+impl AnnotationCoverage {
+    pub fn applications(&self) -> u64 {
+        self.paths.iter().map(|path| path.applications).sum()
+    }
+
+    pub fn rejected(&self) -> u64 {
+        self.paths.iter().map(|path| path.rejected).sum()
+    }
+}
+
+/// A contract coverage report, listing whether each contract annotation was checked.
+#[derive(Clone, Debug)]
+pub struct ContractReport {
+    pub entries: Vec<AnnotationCoverage>,
+}
+
+impl ContractReport {
+    /// Returns the number of annotations with the given status.
+    pub fn count(&self, status: CoverageStatus) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.status == status)
+            .count()
+    }
+
+    /// Writes the report as a human-readable table.
+    pub fn write_text(&self, files: &Files, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "{} contract annotations: {} passed, {} failed, {} unchecked",
+            self.entries.len(),
+            self.count(CoverageStatus::Passed),
+            self.count(CoverageStatus::Failed),
+            self.count(CoverageStatus::Unchecked),
+        )?;
+
+        let rows: Vec<_> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let span = entry.annotation.span;
+                let location = match location(files, span) {
+                    Some((line, column)) => {
+                        format!(
+                            "{}:{line}:{column}",
+                            files.name(span.src_id).to_string_lossy()
+                        )
+                    }
+                    None => files.name(span.src_id).to_string_lossy().into_owned(),
+                };
+
+                let mut details = Vec::new();
+
+                if let Some(field) = entry.annotation.field {
+                    details.push(format!("field {field}"));
+                }
+
+                match entry.applications() {
+                    0 => (),
+                    1 => details.push("1 application".to_owned()),
+                    n => details.push(format!("{n} applications")),
+                }
+
+                match entry.rejected() {
+                    0 => (),
+                    n => details.push(format!("{n} rejected")),
+                }
+
+                (
+                    entry.status.as_str(),
+                    location,
+                    contract_source(files, span),
+                    details.join(", "),
+                )
+            })
+            .collect();
+
+        let location_width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0);
+        let source_width = rows
+            .iter()
+            .map(|row| row.2.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        for (status, location, source, details) in rows {
+            let line = format!(
+                "{status:<9}  {location:<location_width$}  {source:<source_width$}  {details}"
+            );
+            writeln!(out, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the report as JSON.
+    pub fn write_json(&self, files: &Files, out: &mut dyn Write) -> io::Result<()> {
+        let annotations: Vec<_> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let span = entry.annotation.span;
+                let (line, column) = location(files, span).unzip();
+                let paths: Vec<_> = entry
+                    .paths
+                    .iter()
+                    .map(|path| {
+                        serde_json::json!({
+                            "path": path.path,
+                            "applications": path.applications,
+                            "failures": path.failures,
+                            "rejected": path.rejected,
+                        })
+                    })
+                    .collect();
+
+                serde_json::json!({
+                    "file": files.name(span.src_id).to_string_lossy(),
+                    "line": line,
+                    "column": column,
+                    "start": span.start.to_usize(),
+                    "end": span.end.to_usize(),
+                    "annotation": contract_source(files, span),
+                    "kind": match entry.annotation.kind {
+                        AnnotationKind::Type => "type",
+                        AnnotationKind::Contract => "contract",
+                    },
+                    "field": entry.annotation.field.map(|id| id.to_string()),
+                    "status": entry.status.as_str(),
+                    "applications": entry.applications(),
+                    "rejected": entry.rejected(),
+                    "paths": paths,
+                })
+            })
+            .collect();
+
+        let report = serde_json::json!({
+            "passed": self.count(CoverageStatus::Passed),
+            "failed": self.count(CoverageStatus::Failed),
+            "unchecked": self.count(CoverageStatus::Unchecked),
+            "annotations": annotations,
+        });
+
+        serde_json::to_writer_pretty(&mut *out, &report)?;
+        writeln!(out)
+    }
+}
+
+/// Returns the line and the column of the start of a span, starting from 1.
+fn location(files: &Files, span: RawSpan) -> Option<(usize, usize)> {
+    let location = files.location(span.src_id, span.start).ok()?;
+    Some((location.line.to_usize() + 1, location.column.to_usize() + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{eval::cache::CacheImpl, program::Program};
+
+    fn statuses(src: &str) -> Vec<(String, CoverageStatus, u64)> {
+        let mut program: Program<CacheImpl> =
+            Program::new_from_source(Cursor::new(src.to_owned()), "<test>", std::io::sink())
+                .unwrap();
+        program.enable_contract_tracing();
+        program.eval_full_for_export().unwrap();
+
+        program
+            .contract_report()
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|entry| {
+                let rejected = entry.rejected();
+                (
+                    entry.annotation.field.unwrap().to_string(),
+                    entry.status,
+                    rejected,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn report() {
+        let mut files = Files::new();
+        let file_id = files.add("<test>", "{ a | Number, b | Array String, c | Bool }");
+        let span = |start: u32, end: u32| RawSpan {
+            src_id: file_id,
+            start: start.into(),
+            end: end.into(),
+        };
+        let label = |span: RawSpan, field: &str, path: ty_path::Path| Label {
+            span,
+            field_name: Some(LocIdent::from(field)),
+            path,
+            ..Default::default()
+        };
+        let annotation = |span: RawSpan, field: &str| Annotation {
+            span,
+            kind: AnnotationKind::Contract,
+            field: Some(LocIdent::from(field)),
+        };
+
+        let mut trace = ContractTrace::new();
+        trace.record_application(&label(span(6, 12), "a", Vec::new()));
+        trace.record_application(&label(span(6, 12), "a", Vec::new()));
+        trace.record_rejection(&label(span(6, 12), "a", Vec::new()));
+        trace.record_application(&label(span(18, 30), "b", Vec::new()));
+        trace.record_application(&label(span(18, 30), "b", vec![ty_path::Elem::Array]));
+        trace.record_failure(&label(span(18, 30), "b", vec![ty_path::Elem::Array]));
+        assert_eq!(trace.total_applications(), 4);
+
+        let report = trace.report([
+            annotation(span(6, 12), "a"),
+            annotation(span(18, 30), "b"),
+            annotation(span(36, 40), "c"),
+            annotation(span(6, 12), "a"),
+        ]);
+
+        let entries: Vec<_> = report
+            .entries
+            .iter()
+            .map(|entry| {
+                let paths: Vec<_> = entry
+                    .paths
+                    .iter()
+                    .map(|path| {
+                        (
+                            path.path.as_str(),
+                            path.applications,
+                            path.failures,
+                            path.rejected,
+                        )
+                    })
+                    .collect();
+                (entry.status, paths)
+            })
+            .collect();
+
+        assert_eq!(
+            entries,
+            vec![
+                (CoverageStatus::Passed, vec![("a", 2, 0, 1)]),
+                (
+                    CoverageStatus::Failed,
+                    vec![("b", 1, 0, 0), ("b.<array>", 1, 1, 0)]
+                ),
+                (CoverageStatus::Unchecked, vec![]),
+            ]
+        );
+
+        let mut text = Vec::new();
+        report.write_text(&files, &mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "3 contract annotations: 1 passed, 1 failed, 1 unchecked\n\
+            passed     <test>:1:7   Number        field a, 2 applications, 1 rejected\n\
+            failed     <test>:1:19  Array String  field b, 2 applications\n\
+            unchecked  <test>:1:37  Bool          field c\n"
+        );
+    }
+
+    #[test]
+    fn recovered_failures() {
+        // A contract failing within `%contract/check%` doesn't abort the evaluation: the error is
+        // counted as a rejection, and the annotation passes unless the error is turned into blame.
+        assert_eq!(
+            statuses(
+                "let Lenient = std.contract.custom (fun label value =>
+                   std.contract.check Number label value
+                   |> match { 'Ok value => 'Ok value, 'Error _ => 'Ok 0 })
+                 in
+                 {
+                   a | Lenient = \"a\",
+                   b | Lenient = 1,
+                   c | std.contract.any_of [Number, String] = \"c\",
+                   d | std.contract.any_of [Number, String] = 1,
+                   x | std.contract.any_of [Number, String] = \"a\",
+                 }"
+            ),
+            vec![
+                ("a".to_owned(), CoverageStatus::Passed, 1),
+                ("b".to_owned(), CoverageStatus::Passed, 0),
+                ("c".to_owned(), CoverageStatus::Passed, 1),
+                ("d".to_owned(), CoverageStatus::Passed, 0),
+                ("x".to_owned(), CoverageStatus::Passed, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,16 @@
+//! Support for interactive debuggers.
+//!
+//! A debugger is plugged into the virtual machine as a [DebugHook], which is called before each
+//! reduction step with the closure about to be evaluated. The hook can inspect the state of the
+//! machine (the environment of the closure, the call stack, the evaluation cache), evaluate
+//! expressions with [VirtualMachine::eval_in_env], and block until the user decides to resume the
+//! evaluation.
+use super::{cache::Cache, Closure, VirtualMachine};
+use crate::cache::ImportResolver;
+
+/// A hook called by the virtual machine before each reduction step. See the module documentation.
+pub trait DebugHook<R: ImportResolver, C: Cache> {
+    /// Called before evaluating `closure`. The hook isn't called again until this method returns,
+    /// including for the evaluations it performs itself.
+    fn before_step(&mut self, vm: &mut VirtualMachine<R, C>, closure: &Closure);
+}
@@ -0,0 +1,172 @@
+//! Resource limits for evaluation.
+//!
+//! By default, the evaluation of a Nickel program is unbounded: a program can loop or allocate
+//! forever. When evaluating untrusted programs, [EvalLimits] can be set on the virtual machine to
+//! abort the evaluation once it exceeds a budget of reduction steps, stack depth, memory or time.
+//! Each budget raises a dedicated [crate::error::EvalError] when exceeded.
+//!
+//! The budgets are counted from the last [super::VirtualMachine::reset_budget], which is performed
+//! by [crate::program::Program] at the beginning of each of its evaluation entry points, so that
+//! an evaluation made of several steps is still bounded as a whole.
+use std::time::{Duration, Instant};
+
+/// The number of steps between two checks of the time and memory budgets, which are more costly
+/// to measure than the step and stack budgets.
+const SLOW_CHECK_INTERVAL: u64 = 1024;
+
+/// The resource limits of an evaluation. Each limit is optional, and the default is to have no
+/// limit at all.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EvalLimits {
+    /// The maximum number of reduction steps of the abstract machine.
+    pub max_steps: Option<u64>,
+    /// The maximum depth of the evaluation stack, which stores arguments, pending operations and
+    /// thunks to update.
+    pub max_stack_depth: Option<usize>,
+    /// The maximum memory usage, in bytes. This is only an approximation: the memory usage is
+    /// measured as the resident set size of the whole process, and only periodically. This limit
+    /// is only enforced on Linux, and is ignored on other platforms.
+    pub max_memory: Option<u64>,
+    /// The maximum duration of an evaluation.
+    pub timeout: Option<Duration>,
+}
+
+impl EvalLimits {
+    /// Returns `true` if no limit is set.
+    pub fn is_unlimited(&self) -> bool {
+        *self == EvalLimits::default()
+    }
+}
+
+/// A limit which has been exceeded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExceededLimit {
+    Steps(u64),
+    StackDepth(usize),
+    Memory(u64),
+    Timeout(Duration),
+}
+
+/// The resources consumed by an evaluation so far, checked against [EvalLimits].
+#[derive(Clone, Debug, Default)]
+pub struct Budget {
+    limits: EvalLimits,
+    steps: u64,
+    /// The start of the evaluation, which is only recorded when there is a timeout.
+    start: Option<Instant>,
+}
+
+impl Budget {
+    pub fn new(limits: EvalLimits) -> Self {
+        Budget {
+            limits,
+            ..Default::default()
+        }
+    }
+
+    pub fn limits(&self) -> EvalLimits {
+        self.limits
+    }
+
+    /// Resets the resources consumed so far.
+    pub fn reset(&mut self) {
+        self.steps = 0;
+        self.start = None;
+    }
+
+    /// Records a reduction step, and checks that the evaluation is still within its limits.
+    /// `stack_depth` is the current depth of the evaluation stack.
+    #[inline]
+    pub fn step(&mut self, stack_depth: usize) -> Result<(), ExceededLimit> {
+        if self.limits.is_unlimited() {
+            return Ok(());
+        }
+
+        self.steps += 1;
+
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                return Err(ExceededLimit::Steps(max_steps));
+            }
+        }
+
+        if let Some(max_stack_depth) = self.limits.max_stack_depth {
+            if stack_depth > max_stack_depth {
+                return Err(ExceededLimit::StackDepth(max_stack_depth));
+            }
+        }
+
+        if let Some(timeout) = self.limits.timeout {
+            let start = *self.start.get_or_insert_with(Instant::now);
+
+            if self.steps.is_multiple_of(SLOW_CHECK_INTERVAL) && start.elapsed() > timeout {
+                return Err(ExceededLimit::Timeout(timeout));
+            }
+        }
+
+        if let Some(max_memory) = self.limits.max_memory {
+            if self.steps.is_multiple_of(SLOW_CHECK_INTERVAL)
+                && memory_usage().is_some_and(|usage| usage > max_memory)
+            {
+                return Err(ExceededLimit::Memory(max_memory));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the resident set size of the current process in bytes, if it can be measured on this
+/// platform.
+#[cfg(target_os = "linux")]
+fn memory_usage() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+
+    Some(kilobytes * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn memory_usage() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_and_stack() {
+        let mut budget = Budget::new(EvalLimits {
+            max_steps: Some(3),
+            max_stack_depth: Some(10),
+            ..Default::default()
+        });
+
+        assert_eq!(budget.step(0), Ok(()));
+        assert_eq!(budget.step(11), Err(ExceededLimit::StackDepth(10)));
+        assert_eq!(budget.step(0), Ok(()));
+        assert_eq!(budget.step(0), Err(ExceededLimit::Steps(3)));
+
+        budget.reset();
+        assert_eq!(budget.step(0), Ok(()));
+    }
+
+    #[test]
+    fn timeout() {
+        let mut budget = Budget::new(EvalLimits {
+            timeout: Some(Duration::ZERO),
+            ..Default::default()
+        });
+
+        let result = (0..SLOW_CHECK_INTERVAL).try_for_each(|_| budget.step(0));
+        assert_eq!(result, Err(ExceededLimit::Timeout(Duration::ZERO)));
+    }
+}
@@ -81,6 +81,7 @@ use crate::{
     files::FileId,
     identifier::Ident,
     identifier::LocIdent,
+    label::Label,
     match_sharedterm,
     metrics::{increment, measure_runtime},
     position::TermPos,
@@ -91,7 +92,7 @@ use crate::{
         pattern::compile::Compile,
         record::{Field, RecordData},
         string::NickelString,
-        BinaryOp, BindingType, LetAttrs, MatchBranch, MatchData, RecordOpKind, RichTerm,
+        BinaryOp, BindingType, Import, LetAttrs, MatchBranch, MatchData, RecordOpKind, RichTerm,
         RuntimeContract, StrChunk, Term, UnaryOp,
     },
 };
@@ -100,17 +101,30 @@ use std::io::Write;
 
 pub mod cache;
 pub mod callstack;
+pub mod contract_trace;
+pub mod debugger;
 pub mod fixpoint;
+pub mod limits;
 pub mod merge;
 pub mod operation;
+pub mod profiler;
 pub mod stack;
 
 use callstack::*;
+use contract_trace::ContractTrace;
+use debugger::DebugHook;
+use limits::{Budget, EvalLimits, ExceededLimit};
 use operation::OperationCont;
+use profiler::Profiler;
 use stack::{Stack, StrAccData};
 
 use self::cache::{Cache, CacheIndex};
 
+/// The maximum number of elements of the call stack attached to the errors of an evaluation
+/// aborted by a resource limit. A runaway recursion can build an arbitrarily large call stack, of
+/// which only the innermost elements are useful.
+const LIMIT_CALL_STACK_LEN: usize = 64;
+
 impl AsRef<Vec<StackElem>> for CallStack {
     fn as_ref(&self) -> &Vec<StackElem> {
         &self.0
@@ -131,6 +145,14 @@ pub struct VirtualMachine<R: ImportResolver, C: Cache> {
     initial_env: Environment,
     // The stream for writing trace output.
     trace: Box<dyn Write>,
+    // The resources consumed by the current evaluation, and their limits.
+    budget: Budget,
+    // The profiler, if profiling is enabled.
+    profiler: Option<Profiler>,
+    // The trace of contract applications, if contract tracing is enabled.
+    contract_trace: Option<ContractTrace>,
+    // The hook of an attached debugger, if any.
+    debug_hook: Option<Box<dyn DebugHook<R, C>>>,
 }
 
 impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
@@ -142,6 +164,10 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             cache: Cache::new(),
             initial_env: Environment::new(),
             trace: Box::new(trace),
+            budget: Budget::default(),
+            profiler: None,
+            contract_trace: None,
+            debug_hook: None,
         }
     }
 
@@ -153,6 +179,10 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             cache,
             trace: Box::new(trace),
             initial_env: Environment::new(),
+            budget: Budget::default(),
+            profiler: None,
+            contract_trace: None,
+            debug_hook: None,
         }
     }
 
@@ -163,6 +193,101 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
         self.stack.reset(&mut self.cache);
     }
 
+    /// Starts counting the resource budgets again, see [limits]. This must be done once at the
+    /// beginning of each top-level evaluation. [Self::reset] doesn't, as it's also performed
+    /// between the steps of a single evaluation, which would otherwise escape the limits.
+    pub fn reset_budget(&mut self) {
+        self.budget.reset();
+    }
+
+    /// Sets the resource limits of evaluation. See [limits].
+    pub fn set_limits(&mut self, limits: EvalLimits) {
+        self.budget = Budget::new(limits);
+    }
+
+    pub fn limits(&self) -> EvalLimits {
+        self.budget.limits()
+    }
+
+    /// Enables profiling, see [profiler]. The profile accumulates over all the subsequent
+    /// evaluations, until profiling is enabled again.
+    pub fn enable_profiling(&mut self) {
+        self.profiler = Some(Profiler::new());
+    }
+
+    /// Returns the profile recorded so far, if profiling is enabled.
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Enables contract tracing, see [contract_trace]. The trace accumulates over all the
+    /// subsequent evaluations, until contract tracing is enabled again.
+    pub fn enable_contract_tracing(&mut self) {
+        self.contract_trace = Some(ContractTrace::new());
+    }
+
+    /// Returns the trace of contract applications recorded so far, if contract tracing is
+    /// enabled.
+    pub fn contract_trace(&self) -> Option<&ContractTrace> {
+        self.contract_trace.as_ref()
+    }
+
+    /// Records the failure of a contract in the contract trace, if contract tracing is enabled.
+    /// This must be called where the blame error is raised rather than when the evaluation stops,
+    /// as the error might not reach the top-level.
+    fn trace_contract_failure(&mut self, label: &Label) {
+        if let Some(trace) = &mut self.contract_trace {
+            trace.record_failure(label);
+        }
+    }
+
+    /// Records an error returned by `%contract/check%` in the contract trace, if contract tracing
+    /// is enabled. Such errors can be recovered from, for example by `std.contract.any_of`.
+    fn trace_contract_rejection(&mut self, label: &Label) {
+        if let Some(trace) = &mut self.contract_trace {
+            trace.record_rejection(label);
+        }
+    }
+
+    /// Attaches a debugger to the machine, see [debugger].
+    pub fn set_debug_hook(&mut self, hook: impl DebugHook<R, C> + 'static) {
+        self.debug_hook = Some(Box::new(hook));
+    }
+
+    /// Detaches the debugger, if any.
+    pub fn remove_debug_hook(&mut self) -> Option<Box<dyn DebugHook<R, C>>> {
+        self.debug_hook.take()
+    }
+
+    /// Returns the call stack of the current evaluation.
+    pub fn call_stack(&self) -> &CallStack {
+        &self.call_stack
+    }
+
+    /// Returns the initial environment, which contains the standard library.
+    pub fn initial_env(&self) -> &Environment {
+        &self.initial_env
+    }
+
+    /// Evaluates a term to a weak head normal form in the given environment, in the middle of
+    /// another evaluation. The state of the current evaluation is saved beforehand and restored
+    /// afterwards, but the values forced by the nested evaluation stay evaluated. This is used by
+    /// debuggers to evaluate expressions while the evaluation is paused.
+    pub fn eval_in_env(&mut self, t: RichTerm, env: Environment) -> Result<RichTerm, EvalError> {
+        let stack = std::mem::replace(&mut self.stack, Stack::new());
+        let call_stack = std::mem::take(&mut self.call_stack);
+
+        let result = self.eval_closure(Closure { body: t, env });
+
+        // If the evaluation failed, the nested stack might still hold pending updates of the
+        // cache, which must be reverted.
+        self.stack.reset(&mut self.cache);
+        self.stack = stack;
+        self.call_stack = call_stack;
+
+        result.map(|closure| closure.body)
+    }
+
     pub fn import_resolver(&self) -> &R {
         &self.import_resolver
     }
@@ -442,6 +567,15 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
             self.call_stack.enter_var(var, pos);
         }
 
+        if let Some((id, pos_record)) = self
+            .profiler
+            .as_mut()
+            .and_then(|profiler| profiler.take_field(&idx))
+        {
+            self.call_stack
+                .enter_field(id, pos_record, pos, TermPos::None);
+        }
+
         // If we are fetching a recursive field from the environment that doesn't have
         // a definition, we complete the error with the additional information of where
         // it was accessed:
@@ -485,11 +619,36 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
     /// Either:
     ///  - an evaluation error
     ///  - the evaluated term with its final environment
-    pub fn eval_closure(&mut self, mut clos: Closure) -> Result<Closure, EvalError> {
+    pub fn eval_closure(&mut self, clos: Closure) -> Result<Closure, EvalError> {
         #[cfg(feature = "metrics")]
         let start_time = std::time::Instant::now();
 
-        let result = loop {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.start();
+        }
+
+        let result = self.eval_loop(clos);
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.stop();
+        }
+
+        #[cfg(feature = "metrics")]
+        increment!("runtime:eval", start_time.elapsed().as_millis() as u64);
+
+        result
+    }
+
+    /// The evaluation loop proper, see [Self::eval_closure].
+    fn eval_loop(&mut self, mut clos: Closure) -> Result<Closure, EvalError> {
+        loop {
+            // The hook is taken out of the machine while it runs, so that it's not called for the
+            // evaluations it performs itself.
+            if let Some(mut hook) = self.debug_hook.take() {
+                hook.before_step(self, &clos);
+                self.debug_hook = Some(hook);
+            }
+
             let Closure {
                 body:
                     RichTerm {
@@ -499,6 +658,47 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 mut env,
             } = clos;
 
+            if let Some(profiler) = &mut self.profiler {
+                profiler.step(&self.call_stack);
+            }
+
+            if let Err(exceeded) = self.budget.step(self.stack.len()) {
+                let (call_stack, elided_frames) = self.call_stack.innermost(LIMIT_CALL_STACK_LEN);
+
+                // The state of a runaway evaluation can be too deep to be dropped recursively, so
+                // we leak it (see [Stack::reset_leaking]).
+                self.stack.reset_leaking(&mut self.cache);
+                std::mem::forget(env);
+                std::mem::forget(shared_term);
+
+                break Err(match exceeded {
+                    ExceededLimit::Steps(limit) => EvalError::StepLimitExceeded {
+                        limit,
+                        call_stack,
+                        elided_frames,
+                        pos,
+                    },
+                    ExceededLimit::StackDepth(limit) => EvalError::StackLimitExceeded {
+                        limit,
+                        call_stack,
+                        elided_frames,
+                        pos,
+                    },
+                    ExceededLimit::Memory(limit) => EvalError::MemoryLimitExceeded {
+                        limit,
+                        call_stack,
+                        elided_frames,
+                        pos,
+                    },
+                    ExceededLimit::Timeout(limit) => EvalError::TimeLimitExceeded {
+                        limit,
+                        call_stack,
+                        elided_frames,
+                        pos,
+                    },
+                });
+            }
+
             let has_cont_on_stack = self.stack.is_top_idx() || self.stack.is_top_cont();
 
             clos = match_sharedterm!(match (shared_term) {
@@ -542,6 +742,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         }
                         None | Some(..) => {
                             // This operation should not be allowed to evaluate a sealed term
+                            self.trace_contract_failure(&label);
                             break Err(EvalError::BlameError {
                                 evaluated_arg: label.get_evaluated_arg(&self.cache),
                                 label,
@@ -799,9 +1000,14 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         ));
                     }
                 }
-                Term::Import { path, .. } => {
+                Term::Import(import) => {
+                    let target = match import {
+                        Import::Path { path, .. } => path.to_string_lossy().into_owned(),
+                        Import::Package { id } => id.to_string(),
+                    };
+
                     break Err(EvalError::InternalError(
-                        format!("Unresolved import ({})", path.to_string_lossy()),
+                        format!("Unresolved import ({target})"),
                         pos,
                     ));
                 }
@@ -879,7 +1085,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 // is just an argument to a primop or to put in the eval cache)
                 Term::Fun(x, t) if !has_cont_on_stack => {
                     if let Some((idx, pos_app)) = self.stack.pop_arg_as_idx(&mut self.cache) {
-                        self.call_stack.enter_fun(pos_app);
+                        self.call_stack.enter_fun(pos_app, pos);
                         env.insert(x.ident(), idx);
                         Closure { body: t, env }
                     } else {
@@ -944,12 +1150,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     }
                 }
             })
-        };
-
-        #[cfg(feature = "metrics")]
-        increment!("runtime:eval", start_time.elapsed().as_millis() as u64);
-
-        result
+        }
     }
 
     /// Evaluate a term, but attempt to continue on errors.
@@ -1015,6 +1216,7 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 },
             }
         }
+        self.reset_budget();
         let mut ret = Vec::new();
         inner(self, &mut ret, rt, recursion_limit);
         ret
@@ -1190,7 +1392,7 @@ pub fn subst<C: Cache>(
         | v @ Term::ForeignId(_)
         | v @ Term::SealingKey(_)
         | v @ Term::Enum(_)
-        | v @ Term::Import{..}
+        | v @ Term::Import(_)
         | v @ Term::ResolvedImport(_)
         // We could recurse here, because types can contain terms which would then be subject to
         // substitution. Not recursing should be fine, though, because a type in term position
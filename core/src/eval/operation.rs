@@ -8,6 +8,7 @@
 //! receive evaluated operands and implement the actual semantics of operators.
 use super::{
     cache::lazy::Thunk,
+    callstack::StackElem,
     merge::{self, split, MergeMode},
     stack::StrAccData,
     subst, Cache, Closure, Environment, ImportResolver, VirtualMachine,
@@ -56,6 +57,8 @@ use md5::digest::Digest;
 use simple_counter::*;
 use unicode_segmentation::UnicodeSegmentation;
 
+use serde::Deserialize;
+
 use std::{convert::TryFrom, iter::Extend};
 
 generate_counter!(FreshVariableCounter, usize);
@@ -307,11 +310,15 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 }
             }
             UnaryOp::Blame => match_sharedterm!(match (t) {
-                Term::Lbl(label) => Err(EvalError::BlameError {
-                    evaluated_arg: label.get_evaluated_arg(&self.cache),
-                    label,
-                    call_stack: std::mem::take(&mut self.call_stack),
-                }),
+                Term::Lbl(label) => {
+                    self.trace_contract_failure(&label);
+
+                    Err(EvalError::BlameError {
+                        evaluated_arg: label.get_evaluated_arg(&self.cache),
+                        label,
+                        call_stack: std::mem::take(&mut self.call_stack),
+                    })
+                }
                 _ => mk_type_error!("Label"),
             }),
             UnaryOp::EnumEmbed(_id) => {
@@ -1077,6 +1084,18 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                             })
                             .map_err(|e| e.into_eval_err(pos, pos_op))?;
 
+                        // When profiling, the work done to evaluate the fields is attributed to
+                        // them, see [super::profiler].
+                        if let Some(profiler) = &mut self.profiler {
+                            for (id, field) in &fields {
+                                if let Some(Term::Closure(idx)) =
+                                    field.value.as_ref().map(AsRef::as_ref)
+                                {
+                                    profiler.register_field(idx.clone(), *id, pos);
+                                }
+                            }
+                        }
+
                         let terms = fields.clone().into_values().map(|field| {
                             field.value.expect(
                                 "map_values_closurize ensures that values without a \
@@ -1197,6 +1216,45 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     _ => mk_type_error!("Label"),
                 })
             }
+            UnaryOp::NativeCall(ref native) => {
+                let Term::Array(args, _) = &*t else {
+                    return mk_type_error!("Array");
+                };
+
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|arg| subst(&self.cache, arg.clone(), &self.initial_env, &env))
+                    .collect();
+
+                // The position of the call is the one of the application which entered the body of
+                // the Nickel function wrapping the native function.
+                let pos_call = self
+                    .call_stack
+                    .0
+                    .iter()
+                    .rev()
+                    .find_map(|elem| match elem {
+                        StackElem::Fun { pos_app, .. } => Some(*pos_app),
+                        _ => None,
+                    })
+                    .unwrap_or(pos_op);
+
+                let mut result =
+                    native
+                        .call(&args)
+                        .map_err(|message| EvalError::NativeFunctionError {
+                            name: native.name().to_owned(),
+                            message,
+                            call_stack: self.call_stack.clone(),
+                            pos: pos_call,
+                        })?;
+
+                if !result.pos.is_def() {
+                    result.pos = pos_call.into_inherited();
+                }
+
+                Ok(Closure::atomic_closure(result))
+            }
             #[cfg(feature = "nix-experimental")]
             UnaryOp::EvalNix => {
                 if let Term::Str(s) = &*t {
@@ -1372,6 +1430,23 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     _ => mk_type_error!("[| 'Ok, 'Error {..} |]'"),
                 }
             }
+            UnaryOp::ContractTraceResult => {
+                // The label is the second argument, taken from the stack.
+                let (label_closure, _) = self.stack.pop_arg(&self.cache).unwrap();
+
+                if let (Term::EnumVariant { tag, .. }, Term::Lbl(label)) =
+                    (&*t, label_closure.body.as_ref())
+                {
+                    if tag.label() == "Error" {
+                        self.trace_contract_rejection(label);
+                    }
+                }
+
+                Ok(Closure {
+                    body: RichTerm { term: t, pos },
+                    env,
+                })
+            }
             UnaryOp::NumberArcCos => Self::process_unary_number_operation(
                 RichTerm { term: t, pos },
                 arg_pos,
@@ -1808,8 +1883,29 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                     // We update the label and convert it back to a term form that can be cheaply cloned
                     label.arg_pos = self.cache.get_then(idx.clone(), |c| c.body.pos);
                     label.arg_idx = Some(idx.clone());
+                    let contract_span = label.span;
+
+                    if let Some(trace) = &mut self.contract_trace {
+                        trace.record_application(&label);
+                    }
+
                     let new_label = RichTerm::new(Term::Lbl(label), pos2);
 
+                    // When tracing contracts, the result of `%contract/check%` is recorded before
+                    // being returned, since its errors don't blame. That is, we prepare the
+                    // stack to represent the evaluation context `%contract/trace_result% [.]
+                    // label`.
+                    if self.contract_trace.is_some() && matches!(b_op, BinaryOp::ContractCheck) {
+                        self.stack
+                            .push_arg(Closure::atomic_closure(new_label.clone()), pos_op_inh);
+
+                        self.stack.push_op_cont(
+                            OperationCont::Op1(UnaryOp::ContractTraceResult, pos1.into_inherited()),
+                            self.call_stack.len(),
+                            pos_op_inh,
+                        );
+                    }
+
                     // If we're evaluating a plain contract application but we are applying
                     // something with the signature of a custom contract, we need to setup some
                     // post-processing.
@@ -1907,6 +2003,10 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         _ => return mk_type_error!("Contract", 1, t1, pos1),
                     };
 
+                    if self.profiler.is_some() {
+                        self.call_stack.enter_contract(contract_span);
+                    }
+
                     Ok(functoid)
                 } else {
                     mk_type_error!("Label", 2, t2.into(), pos2)
@@ -2551,7 +2651,14 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 }
             }
             BinaryOp::Serialize => {
-                let mk_err_fst = |t1| mk_type_error!("[| 'Json, 'Yaml, 'Toml |]", 1, t1, pos1);
+                let mk_err_fst = |t1| {
+                    mk_type_error!(
+                        "[| 'Json, 'Yaml, 'YamlDocuments, 'Toml, 'Xml, 'Ini, 'Properties, 'Dotenv, 'Hcl |]",
+                        1,
+                        t1,
+                        pos1
+                    )
+                };
 
                 if let Term::Enum(ref id) = t1.as_ref() {
                     // Serialization needs all variables term to be fully substituted
@@ -2566,11 +2673,11 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         &env2,
                     );
 
-                    let format = match id.to_string().as_str() {
-                        "Json" => ExportFormat::Json,
-                        "Yaml" => ExportFormat::Yaml,
-                        "Toml" => ExportFormat::Toml,
-                        _ => return mk_err_fst(t1),
+                    let format = match ExportFormat::from_tag(id.label()) {
+                        // Text isn't supported by `std.serialize`: it would be the identity on
+                        // strings.
+                        Some(ExportFormat::Text) | None => return mk_err_fst(t1),
+                        Some(format) => format,
                     };
 
                     serialize::validate(format, &rt2)?;
@@ -2583,7 +2690,14 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                 }
             }
             BinaryOp::Deserialize => {
-                let mk_err_fst = |t1| mk_type_error!("[| 'Json, 'Yaml, 'Toml |]", 1, t1, pos1);
+                let mk_err_fst = |t1| {
+                    mk_type_error!(
+                        "[| 'Json, 'Yaml, 'YamlDocuments, 'Toml, 'Csv, 'JsonLines |]",
+                        1,
+                        t1,
+                        pos1
+                    )
+                };
 
                 if let Term::Enum(id) = &*t1 {
                     if let Term::Str(s) = &*t2 {
@@ -2602,6 +2716,17 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                                     pos_op,
                                 )
                             })?,
+                            "YamlDocuments" => serde_yaml::Deserializer::from_str(s)
+                                .map(RichTerm::deserialize)
+                                .collect::<Result<Array, _>>()
+                                .map(|docs| RichTerm::from(Term::Array(docs, Default::default())))
+                                .map_err(|err| {
+                                    EvalError::DeserializationError(
+                                        String::from("yaml"),
+                                        format!("{err}"),
+                                        pos_op,
+                                    )
+                                })?,
                             "Toml" => toml::from_str(s).map_err(|err| {
                                 EvalError::DeserializationError(
                                     String::from("toml"),
@@ -2609,6 +2734,22 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                                     pos_op,
                                 )
                             })?,
+                            "Csv" => serialize::line_based::csv_from_str(s).map_err(|err| {
+                                EvalError::DeserializationError(
+                                    String::from("csv"),
+                                    format!("{err}"),
+                                    pos_op,
+                                )
+                            })?,
+                            "JsonLines" => {
+                                serialize::line_based::jsonl_from_str(s).map_err(|err| {
+                                    EvalError::DeserializationError(
+                                        String::from("jsonl"),
+                                        format!("{err}"),
+                                        pos_op,
+                                    )
+                                })?
+                            }
                             _ => return mk_err_fst(t1),
                         };
 
@@ -3262,10 +3403,14 @@ impl<R: ImportResolver, C: Cache> VirtualMachine<R, C> {
                         .clone()
                         .sealed_tail
                         .and_then(|t| t.unseal(s).cloned())
-                        .ok_or_else(|| EvalError::BlameError {
-                            evaluated_arg: l.get_evaluated_arg(&self.cache),
-                            label: l.clone(),
-                            call_stack: std::mem::take(&mut self.call_stack),
+                        .ok_or_else(|| {
+                            self.trace_contract_failure(l);
+
+                            EvalError::BlameError {
+                                evaluated_arg: l.get_evaluated_arg(&self.cache),
+                                label: l.clone(),
+                                call_stack: std::mem::take(&mut self.call_stack),
+                            }
                         })
                         .map(|t| Closure { body: t, env: env3 }),
                     (Term::SealingKey(_), Term::Lbl(_), _) => {
@@ -0,0 +1,522 @@
+//! Profiling of evaluation.
+//!
+//! When profiling is enabled on the virtual machine, each reduction step and the time it takes
+//! are attributed to the current stack of frames, which is derived from the [CallStack]. A frame
+//! is either a function call, the evaluation of a record field or the application of a contract.
+//!
+//! As the call stack, the profile is only an approximation of the actual evaluation: because of
+//! laziness, the work done to evaluate an expression may be attributed to the frame which first
+//! forced it.
+//!
+//! Profiling doesn't change the evaluation. The fields of a record which are forced all at once, as
+//! when exporting, aren't accessed one by one: the profiler rather remembers the thunks of their
+//! values (see [Profiler::register_field]), and the virtual machine pushes a field frame on the
+//! call stack when such a thunk is entered. The profile can be written in the collapsed stack
+//! format, understood by most flamegraph tools, or in the [speedscope](https://www.speedscope.app)
+//! format.
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use super::{
+    cache::CacheIndex,
+    callstack::{CallStack, StackElem},
+};
+use crate::{
+    files::Files,
+    identifier::{Ident, LocIdent},
+    position::{RawSpan, TermPos},
+};
+
+/// The maximum width of the source of a contract in the name of its frame. Beyond this limit, the
+/// source is cut and an ellipsis is appended.
+const CONTRACT_MAX_WIDTH: usize = 40;
+
+/// The format of a profile.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, clap::ValueEnum)]
+pub enum ProfileFormat {
+    /// One line per stack of frames, with the frames separated by semicolons and followed by
+    /// their weight, as expected by flamegraph tools
+    #[default]
+    Folded,
+    /// A JSON file for the speedscope profile viewer, including both the time and the reduction
+    /// steps
+    Speedscope,
+}
+
+/// The weight of the stacks of a profile in the collapsed stack format.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, clap::ValueEnum)]
+pub enum ProfileWeight {
+    /// The time spent, in nanoseconds
+    #[default]
+    Time,
+    /// The number of reduction steps
+    Steps,
+}
+
+/// A frame of the profile.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Frame {
+    /// The body of a function was entered. `name` is the name of the variable or of the field the
+    /// function was accessed through, if any, and `pos` is the position of the function, or of
+    /// the application if the function doesn't have one.
+    Call { name: Option<Ident>, pos: TermPos },
+    /// A record field was entered. `pos` is the position of the value of the field, or of its name
+    /// if the value doesn't have one.
+    Field { id: Ident, pos: TermPos },
+    /// A contract was applied. The span is the one of the contract annotation.
+    Contract(RawSpan),
+}
+
+/// A node of the tree of the stacks of frames seen so far.
+#[derive(Clone, Debug)]
+struct Node {
+    /// The frame of this node, or `None` for the root.
+    frame: Option<Frame>,
+    parent: usize,
+    steps: u64,
+    time: Duration,
+}
+
+/// An element of the call stack, together with the state of the profiler after processing it.
+#[derive(Clone, Debug)]
+struct Processed {
+    elem: StackElem,
+    node: usize,
+    /// The name of the function being called, if known.
+    name: Option<Ident>,
+}
+
+/// The root of the tree of stacks, which collects the steps performed outside of any frame.
+const ROOT: usize = 0;
+
+/// An evaluation profiler. See the module documentation.
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    nodes: Vec<Node>,
+    children: HashMap<(usize, Frame), usize>,
+    /// The call stack as of the last step, with the corresponding nodes.
+    processed: Vec<Processed>,
+    /// The node of the last step.
+    current: usize,
+    /// The time of the last step, if it is part of the current evaluation.
+    last_step: Option<Instant>,
+    /// The thunks of the fields being forced which haven't been entered yet, by uid, with the
+    /// name of the field and the position of the record. The thunks are kept alive, so that their
+    /// uid isn't reused in the meantime.
+    fields: HashMap<usize, (CacheIndex, LocIdent, TermPos)>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            nodes: vec![Node {
+                frame: None,
+                parent: ROOT,
+                steps: 0,
+                time: Duration::ZERO,
+            }],
+            children: HashMap::new(),
+            processed: Vec::new(),
+            current: ROOT,
+            last_step: None,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Registers the thunk of the value of a field which is being forced, so that the work done
+    /// to evaluate it is attributed to the field, see [Self::take_field].
+    pub fn register_field(&mut self, idx: CacheIndex, id: LocIdent, pos_record: TermPos) {
+        self.fields.insert(idx.uid(), (idx, id, pos_record));
+    }
+
+    /// If `idx` is the thunk of a field registered by [Self::register_field], returns the name of
+    /// the field and the position of the record. A field is only returned the first time its
+    /// thunk is entered, when it's actually evaluated.
+    pub fn take_field(&mut self, idx: &CacheIndex) -> Option<(LocIdent, TermPos)> {
+        self.fields
+            .remove(&idx.uid())
+            .map(|(_, id, pos_record)| (id, pos_record))
+    }
+
+    /// Records the start of an evaluation. The time elapsed since the last step of the previous
+    /// evaluation, if any, isn't attributed to any frame.
+    pub fn start(&mut self) {
+        self.last_step = None;
+    }
+
+    /// Records the end of an evaluation, attributing the time elapsed since the last step to its
+    /// frame.
+    pub fn stop(&mut self) {
+        if let Some(last_step) = self.last_step.take() {
+            self.nodes[self.current].time += last_step.elapsed();
+        }
+    }
+
+    /// Records a reduction step, performed with the given call stack.
+    pub fn step(&mut self, call_stack: &CallStack) {
+        let now = Instant::now();
+
+        if let Some(last_step) = self.last_step {
+            self.nodes[self.current].time += now - last_step;
+        }
+
+        self.last_step = Some(now);
+        self.sync(&call_stack.0);
+        self.current = self
+            .processed
+            .last()
+            .map_or(ROOT, |processed| processed.node);
+        self.nodes[self.current].steps += 1;
+    }
+
+    /// Updates the processed call stack to match `elems`. The call stack only changes by being
+    /// truncated or by growing, so we just look for the first element which differs, starting from
+    /// the top of the processed stack.
+    fn sync(&mut self, elems: &[StackElem]) {
+        let mut common = self.processed.len().min(elems.len());
+
+        while common > 0 && self.processed[common - 1].elem != elems[common - 1] {
+            common -= 1;
+        }
+
+        self.processed.truncate(common);
+
+        for elem in &elems[common..] {
+            let (node, name) = self
+                .processed
+                .last()
+                .map_or((ROOT, None), |processed| (processed.node, processed.name));
+
+            let (node, name) = match elem {
+                // A new application starts: the name of the function isn't known yet.
+                StackElem::App(_) => (node, None),
+                StackElem::Var { id, .. } if id.is_generated() => (node, name),
+                StackElem::Var { id, .. } => (node, Some(id.ident())),
+                StackElem::Field { id, pos_field, .. } => {
+                    let pos = if pos_field.is_def() {
+                        *pos_field
+                    } else {
+                        id.pos
+                    };
+                    let frame = Frame::Field {
+                        id: id.ident(),
+                        pos,
+                    };
+                    (self.child(node, frame), Some(id.ident()))
+                }
+                // The arguments of a multi-ary application are entered one after the other: they
+                // belong to the same call.
+                StackElem::Fun { .. }
+                    if matches!(
+                        self.processed.last(),
+                        Some(Processed {
+                            elem: StackElem::Fun { .. },
+                            ..
+                        })
+                    ) =>
+                {
+                    (node, None)
+                }
+                StackElem::Fun { pos_app, pos_fun } => {
+                    let pos = if pos_fun.is_def() { *pos_fun } else { *pos_app };
+                    (self.child(node, Frame::Call { name, pos }), None)
+                }
+                StackElem::Contract(span) => (self.child(node, Frame::Contract(*span)), None),
+            };
+
+            self.processed.push(Processed {
+                elem: elem.clone(),
+                node,
+                name,
+            });
+        }
+    }
+
+    fn child(&mut self, parent: usize, frame: Frame) -> usize {
+        *self.children.entry((parent, frame)).or_insert_with(|| {
+            self.nodes.push(Node {
+                frame: Some(frame),
+                parent,
+                steps: 0,
+                time: Duration::ZERO,
+            });
+            self.nodes.len() - 1
+        })
+    }
+
+    /// Returns the total number of reduction steps recorded.
+    pub fn total_steps(&self) -> u64 {
+        self.nodes.iter().map(|node| node.steps).sum()
+    }
+
+    /// Returns the total time recorded.
+    pub fn total_time(&self) -> Duration {
+        self.nodes.iter().map(|node| node.time).sum()
+    }
+
+    /// Returns the stacks of frames, from the outermost to the innermost frame, with their steps
+    /// and time, in the order they were first seen. Field frames of the standard library are
+    /// omitted, as they mostly correspond to accesses such as `std.array.map`, and stacks which
+    /// become identical are merged.
+    fn stacks(&self, files: &Files) -> Vec<(Vec<Frame>, u64, Duration)> {
+        let mut stacks: Vec<(Vec<Frame>, u64, Duration)> = Vec::new();
+        let mut indices = HashMap::new();
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.steps == 0 && node.time.is_zero() {
+                continue;
+            }
+
+            let mut frames = Vec::new();
+            let mut current = index;
+
+            while current != ROOT {
+                let node = &self.nodes[current];
+
+                match node.frame {
+                    Some(Frame::Field { pos, .. }) if is_stdlib(files, pos) => (),
+                    Some(frame) => frames.push(frame),
+                    None => (),
+                }
+
+                current = node.parent;
+            }
+
+            frames.reverse();
+
+            match indices.get(&frames) {
+                Some(&index) => {
+                    let (_, steps, time) = &mut stacks[index];
+                    *steps += node.steps;
+                    *time += node.time;
+                }
+                None => {
+                    indices.insert(frames.clone(), stacks.len());
+                    stacks.push((frames, node.steps, node.time));
+                }
+            }
+        }
+
+        stacks
+    }
+
+    /// Writes the profile in the collapsed stack format.
+    pub fn write_folded(
+        &self,
+        files: &Files,
+        weight: ProfileWeight,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let mut names = HashMap::new();
+        let mut lines = Vec::new();
+
+        for (frames, steps, time) in self.stacks(files) {
+            let weight = match weight {
+                ProfileWeight::Time => time.as_nanos() as u64,
+                ProfileWeight::Steps => steps,
+            };
+
+            if weight == 0 {
+                continue;
+            }
+
+            let stack: Vec<_> = frames
+                .iter()
+                .map(|frame| {
+                    names
+                        .entry(*frame)
+                        .or_insert_with(|| frame_name(files, frame).replace(';', ","))
+                        .clone()
+                })
+                .collect();
+
+            if stack.is_empty() {
+                lines.push(format!("<top-level> {weight}"));
+            } else {
+                lines.push(format!("{} {weight}", stack.join(";")));
+            }
+        }
+
+        lines.sort();
+        lines.iter().try_for_each(|line| writeln!(out, "{line}"))
+    }
+
+    /// Writes the profile in the speedscope format, with two profiles: one for the time, and one
+    /// for the reduction steps.
+    pub fn write_speedscope(&self, files: &Files, out: &mut dyn Write) -> io::Result<()> {
+        let mut frame_indices = HashMap::new();
+        let mut frames = Vec::new();
+        let mut samples = Vec::new();
+        let mut steps = Vec::new();
+        let mut times = Vec::new();
+
+        for (stack, stack_steps, stack_time) in self.stacks(files) {
+            let stack: Vec<_> = stack
+                .iter()
+                .map(|frame| {
+                    *frame_indices.entry(*frame).or_insert_with(|| {
+                        frames.push(speedscope_frame(files, frame));
+                        frames.len() - 1
+                    })
+                })
+                .collect();
+
+            samples.push(stack);
+            steps.push(stack_steps);
+            times.push(stack_time.as_nanos() as u64);
+        }
+
+        let sampled = |name: &str, unit: &str, weights: Vec<u64>| {
+            serde_json::json!({
+                "type": "sampled",
+                "name": name,
+                "unit": unit,
+                "startValue": 0,
+                "endValue": weights.iter().sum::<u64>(),
+                "samples": samples,
+                "weights": weights,
+            })
+        };
+
+        let profile = serde_json::json!({
+            "$schema": "https://www.speedscope.app/file-format-schema.json",
+            "exporter": concat!("nickel ", env!("CARGO_PKG_VERSION")),
+            "shared": { "frames": frames },
+            "profiles": [
+                sampled("time", "nanoseconds", times),
+                sampled("reduction steps", "none", steps),
+            ],
+        });
+
+        serde_json::to_writer(&mut *out, &profile)?;
+        writeln!(out)
+    }
+}
+
+fn is_stdlib(files: &Files, pos: TermPos) -> bool {
+    pos.into_opt()
+        .is_some_and(|span| files.is_stdlib(span.src_id))
+}
+
+/// Returns the position of a frame, as a file name, a line and a column, starting from 1.
+fn frame_location(files: &Files, frame: &Frame) -> Option<(String, usize, usize)> {
+    let span = match frame {
+        Frame::Call { pos, .. } | Frame::Field { pos, .. } => pos.into_opt()?,
+        Frame::Contract(span) => *span,
+    };
+
+    let location = files.location(span.src_id, span.start).ok()?;
+
+    Some((
+        files.name(span.src_id).to_string_lossy().into_owned(),
+        location.line.to_usize() + 1,
+        location.column.to_usize() + 1,
+    ))
+}
+
+/// Returns the name of a frame, without its position.
+fn frame_label(files: &Files, frame: &Frame) -> String {
+    match frame {
+        Frame::Call {
+            name: Some(name), ..
+        } => name.to_string(),
+        Frame::Call { name: None, .. } => "<function>".to_owned(),
+        Frame::Field { id, .. } => format!("field {id}"),
+        Frame::Contract(span) => format!("contract {}", contract_source(files, *span)),
+    }
+}
+
+/// Returns the source of a contract annotation on a single line, cut if it's too long.
+pub(super) fn contract_source(files: &Files, span: RawSpan) -> String {
+    let source = files
+        .source_slice(span)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if source.chars().count() > CONTRACT_MAX_WIDTH {
+        let cut: String = source.chars().take(CONTRACT_MAX_WIDTH).collect();
+        format!("{cut}…")
+    } else {
+        source
+    }
+}
+
+/// Returns the name of a frame, including its position, if any.
+fn frame_name(files: &Files, frame: &Frame) -> String {
+    let label = frame_label(files, frame);
+
+    match frame_location(files, frame) {
+        Some((file, line, column)) => format!("{label} ({file}:{line}:{column})"),
+        None => label,
+    }
+}
+
+fn speedscope_frame(files: &Files, frame: &Frame) -> serde_json::Value {
+    match frame_location(files, frame) {
+        Some((file, line, column)) => serde_json::json!({
+            "name": frame_label(files, frame),
+            "file": file,
+            "line": line,
+            "col": column,
+        }),
+        None => serde_json::json!({ "name": frame_label(files, frame) }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identifier::LocIdent;
+
+    #[test]
+    fn folded_stacks() {
+        let mut files = Files::new();
+        let file_id = files.add("<test>", "let f = fun x => x in f 1");
+        let span = |start: u32, end: u32| RawSpan {
+            src_id: file_id,
+            start: start.into(),
+            end: end.into(),
+        };
+
+        let call = vec![
+            StackElem::App(TermPos::Original(span(22, 25))),
+            StackElem::Var {
+                id: LocIdent::from("f"),
+                pos: TermPos::Original(span(22, 23)),
+            },
+            StackElem::Fun {
+                pos_app: TermPos::Original(span(22, 25)),
+                pos_fun: TermPos::Original(span(8, 18)),
+            },
+        ];
+
+        let mut profiler = Profiler::new();
+        profiler.start();
+        profiler.step(&CallStack(Vec::new()));
+        profiler.step(&CallStack(call[..2].to_vec()));
+        profiler.step(&CallStack(call.clone()));
+        profiler.step(&CallStack(call.clone()));
+        profiler.stop();
+
+        let mut out = Vec::new();
+        profiler
+            .write_folded(&files, ProfileWeight::Steps, &mut out)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<top-level> 2\nf (<test>:1:9) 2\n"
+        );
+        assert_eq!(profiler.total_steps(), 4);
+    }
+}
@@ -147,6 +147,28 @@ impl<C: Cache> Stack<C> {
         }
     }
 
+    /// Same as [Self::reset], but leaks the elements of the stack instead of dropping them. When
+    /// an evaluation is aborted by a resource limit, the stack may hold the only references to
+    /// arbitrarily long chains of thunks, whose recursive destruction would overflow the native
+    /// stack.
+    pub fn reset_leaking(&mut self, cache: &mut C) {
+        while let Some(mut marker) = self.0.pop() {
+            if let Marker::UpdateIndex(uidx) = &mut marker {
+                cache.reset_index_state(uidx);
+            }
+            std::mem::forget(marker);
+        }
+    }
+
+    /// The number of elements on the stack.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     /// Count the number of arguments at the top of the stack.
     pub fn count_args(&self) -> usize {
         Stack::count(self, Marker::is_arg)
@@ -7,7 +7,7 @@ use crate::label::Label;
 use crate::parser::{grammar, lexer, ErrorTolerantParser};
 use crate::term::make as mk_term;
 use crate::term::Number;
-use crate::term::{BinaryOp, StrChunk, UnaryOp};
+use crate::term::{BinaryOp, ForeignIdPayload, StrChunk, UnaryOp};
 use crate::transform::import_resolution::strict::resolve_imports;
 use crate::{mk_app, mk_fun, mk_record};
 use assert_matches::assert_matches;
@@ -174,6 +174,15 @@ fn imports() {
         _ => panic!(),
     };
 
+    // The simple resolver doesn't support packages.
+    assert_matches!(
+        resolve_imports(
+            Term::Import(crate::term::Import::Package { id: "pkg".into() }).into(),
+            vm.import_resolver_mut(),
+        ),
+        Err(ImportError::NoPackageMap { .. })
+    );
+
     // let x = import "two" in x
     let mk_import_two = mk_import("x", "two", mk_term::var("x"), &mut vm).unwrap();
     vm.reset();
@@ -359,10 +368,11 @@ fn substitution() {
 
 #[test]
 fn foreign_id() {
+    let foreign = ForeignIdPayload::new(42u64);
     let t = mk_term::op2(
         BinaryOp::Merge(Label::default().into()),
         mk_record!(("a", RichTerm::from(Term::Num(Number::from(1))))),
-        mk_record!(("b", RichTerm::from(Term::ForeignId(42)))),
+        mk_record!(("b", RichTerm::from(Term::ForeignId(foreign.clone())))),
     );
 
     // Terms that include foreign ids can be manipulated like normal, and the ids
@@ -372,13 +382,16 @@ fn foreign_id() {
     };
     let b = LocIdent::from(Ident::new("b"));
     let field = data.fields.get(&b).unwrap();
-    assert_matches!(field.value.as_ref().unwrap().as_ref(), Term::ForeignId(42));
+    assert_matches!(
+        field.value.as_ref().unwrap().as_ref(),
+        Term::ForeignId(id) if *id == foreign && id.downcast_ref::<u64>() == Some(&42)
+    );
 
     // Foreign ids cannot be compared for equality.
     let t_eq = mk_term::op2(
         BinaryOp::Eq,
-        RichTerm::from(Term::ForeignId(43)),
-        RichTerm::from(Term::ForeignId(42)),
+        RichTerm::from(Term::ForeignId(ForeignIdPayload::new(43u64))),
+        RichTerm::from(Term::ForeignId(foreign.clone())),
     );
     assert_matches!(
         eval_no_import(t_eq),
@@ -396,7 +409,7 @@ fn foreign_id() {
         Err(EvalError::MergeIncompatibleArgs { .. })
     );
 
-    let t_typeof = mk_term::op1(UnaryOp::Typeof, Term::ForeignId(42));
+    let t_typeof = mk_term::op1(UnaryOp::Typeof, Term::ForeignId(foreign));
     let ty = eval_no_import(t_typeof).unwrap();
     let fid = LocIdent::from(Ident::new("ForeignId"));
     assert_matches!(ty, Term::Enum(f) if f == fid);
@@ -114,11 +114,10 @@ impl Files {
     ///
     /// Panics if `file_id` is invalid.
     pub fn update(&mut self, file_id: FileId, source: impl Into<Rc<str>>) {
-        // This implementation would be a little nicer if `Vector` supported mutable access.
         // unwrap: we're allowed to panic if file_id is invalid
-        let mut old = self.get(file_id).unwrap().clone();
-        old.source = source.into();
-        self.files.set(file_id.0 as usize, old);
+        let name = self.get(file_id).unwrap().name.clone();
+        // The line starts need to be recomputed for the new source, so we build a new file.
+        self.files.set(file_id.0 as usize, File::new(name, source));
     }
 
     /// Returns a span containing all of a source.
@@ -1,6 +1,6 @@
 //! Define the type of an identifier.
 use once_cell::sync::Lazy;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     borrow::Borrow,
     fmt::{self, Debug},
@@ -16,8 +16,8 @@ static INTERNER: Lazy<interner::Interner> = Lazy::new(interner::Interner::new);
 //
 // Implementation-wise, this is just a wrapper around interner::Symbol that uses a hard-coded,
 // static `Interner`.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
-#[serde(into = "String", from = "String")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(into = "String")]
 pub struct Ident(interner::Symbol);
 
 impl Ident {
@@ -41,6 +41,31 @@ impl Ident {
         increment!("Ident::fresh");
         Self::new(format!("{}{}", GEN_PREFIX, GeneratedCounter::next()))
     }
+
+    /// If this is a generated identifier, make sure that the identifiers returned by
+    /// [Self::fresh] from now on are different from it. Used for the identifiers generated by a
+    /// previous run and loaded from the disk cache.
+    pub(crate) fn reserve(&self) {
+        if let Some(n) = self
+            .label()
+            .strip_prefix(GEN_PREFIX)
+            .and_then(|n| n.parse::<usize>().ok())
+        {
+            let next = GeneratedCounter::next();
+            GeneratedCounter::set(next.max(n + 1));
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Ident {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let ident = Ident::new(String::deserialize(deserializer)?);
+        crate::disk_cache::repr::reserve_ident(ident);
+        Ok(ident)
+    }
 }
 
 impl fmt::Display for Ident {
@@ -103,8 +128,10 @@ impl Into<String> for Ident {
 ///
 /// The location is ignored for equality comparison and hashing; it's mainly
 /// intended for error messages.
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
-#[serde(into = "String", from = "String")]
+///
+/// Human-readable formats represent an identifier as a string, while binary formats (see
+/// [crate::disk_cache]) also store its location.
+#[derive(Clone, Copy, Debug)]
 pub struct LocIdent {
     ident: Ident,
     pub pos: TermPos,
@@ -186,6 +213,33 @@ impl Borrow<Ident> for LocIdent {
     }
 }
 
+impl Serialize for LocIdent {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(self.label())
+        } else {
+            (self.ident, self.pos).serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LocIdent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            String::deserialize(deserializer).map(LocIdent::from)
+        } else {
+            let (ident, pos) = <(Ident, TermPos)>::deserialize(deserializer)?;
+            Ok(LocIdent::from(ident).with_pos(pos))
+        }
+    }
+}
+
 impl fmt::Display for LocIdent {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.label())
@@ -0,0 +1,445 @@
+//! Generation of a [JSON Schema](https://json-schema.org/) from a Nickel record contract.
+//!
+//! The other direction, from a JSON Schema to a Nickel contract, is implemented in [import].
+//!
+//! The record contract is expected to be evaluated by
+//! [crate::program::Program::eval_record_spine_with_contracts], so that the record contracts
+//! referred to by name in annotations can be inspected. The generated schema follows the 2020-12
+//! draft:
+//!
+//! - the fields of a record contract become `properties`. Fields which are neither optional nor
+//!   defined are `required`, and closed record contracts forbid `additionalProperties`.
+//! - the documentation of a field becomes its `description`. The value of a field becomes its
+//!   `default` if it has a lower or higher priority than the data, or a `const` otherwise, since
+//!   merging it with a different value would fail.
+//! - types (`Number`, `String`, `Bool`, arrays, dictionaries, record types and enums of tags) and
+//!   some contracts of the standard library, such as `std.number.Integer`, have a direct
+//!   equivalent. The contracts of the standard library are recognized even through aliases such
+//!   as `let Nat = std.number.Nat`: the evaluation replaces the contracts which evaluate to one of
+//!   [STD_CONTRACTS] with its path (see [std_contract_term]).
+//!
+//! Other contracts, such as custom predicates, can't be expressed in JSON Schema. They are
+//! replaced by a permissive schema which accepts any value, and reported as
+//! [UnsupportedContract]s so that the caller can warn the user.
+use serde_json::{json, Map, Value};
+
+pub mod import;
+
+use crate::{
+    error::{ExportError, ExportErrorData},
+    identifier::Ident,
+    position::TermPos,
+    serialize::{NickelPointer, NickelPointerElem},
+    term::{
+        make as mk_term,
+        record::{Field, RecordData},
+        MergePriority, RichTerm, Term, UnaryOp,
+    },
+    typ::{EnumRows, EnumRowsIteratorItem, RecordRows, RecordRowsIteratorItem, Type, TypeF},
+};
+
+/// The JSON Schema dialect of the generated schemas.
+pub const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The paths, within the standard library, of the contracts which have an equivalent in JSON
+/// Schema.
+pub const STD_CONTRACTS: &[&[&str]] = &[
+    &["number", "Integer"],
+    &["number", "Nat"],
+    &["number", "PosNat"],
+    &["number", "NonZero"],
+    &["string", "NonEmpty"],
+    &["string", "BoolLiteral"],
+    &["array", "NonEmpty"],
+];
+
+/// Builds the term `std.<path>`, for a path of [STD_CONTRACTS].
+pub fn std_contract_term(path: &[&str]) -> RichTerm {
+    mk_term::static_access(mk_term::var("std"), path.iter().copied())
+}
+
+/// Returns the path of a term of the form `std.<path>`.
+fn std_path(rt: &RichTerm) -> Option<Vec<Ident>> {
+    match rt.as_ref() {
+        Term::Op1(UnaryOp::RecordAccess(id), record) => {
+            let mut path = std_path(record)?;
+            path.push(id.ident());
+            Some(path)
+        }
+        Term::Var(id) if id.label() == "std" => Some(Vec::new()),
+        _ => None,
+    }
+}
+
+/// A generated JSON schema, together with the contracts which couldn't be translated.
+#[derive(Clone, Debug)]
+pub struct JsonSchema {
+    pub schema: Value,
+    pub unsupported: Vec<UnsupportedContract>,
+}
+
+/// A contract which can't be expressed in JSON Schema, and has been replaced with a schema
+/// accepting any value.
+#[derive(Clone, Debug)]
+pub struct UnsupportedContract {
+    /// The path of the field annotated with the contract.
+    pub path: NickelPointer,
+    /// The contract, as written in the annotation.
+    pub contract: String,
+    /// The position of the contract in the annotation.
+    pub pos: TermPos,
+}
+
+/// Generates a JSON schema from an evaluated record contract.
+pub fn from_record_contract(rt: &RichTerm) -> Result<JsonSchema, ExportError> {
+    let (Term::Record(record) | Term::RecRecord(record, ..)) = rt.as_ref() else {
+        return Err(ExportErrorData::NotARecordContract(rt.clone()).into());
+    };
+
+    let mut generator = Generator {
+        path: NickelPointer::new(),
+        unsupported: Vec::new(),
+    };
+
+    let mut schema = Map::new();
+    schema.insert("$schema".to_owned(), json!(SCHEMA_DIALECT));
+    schema.extend(generator.record(record, !record.attrs.open));
+
+    Ok(JsonSchema {
+        schema: Value::Object(schema),
+        unsupported: generator.unsupported,
+    })
+}
+
+struct Generator {
+    /// The path of the field being currently translated.
+    path: NickelPointer,
+    unsupported: Vec<UnsupportedContract>,
+}
+
+impl Generator {
+    fn with_field<T>(&mut self, elem: NickelPointerElem, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.path.0.push(elem);
+        let result = f(self);
+        self.path.0.pop();
+        result
+    }
+
+    /// Translates a record. `closed` is `true` for closed record contracts, which don't accept
+    /// additional fields. Record values, on the other hand, are merged with the data and thus
+    /// accept additional fields.
+    fn record(&mut self, record: &RecordData, closed: bool) -> Map<String, Value> {
+        let mut fields: Vec<_> = record
+            .fields
+            .iter()
+            .filter(|(_, field)| !field.metadata.not_exported)
+            .collect();
+        fields.sort_by(|(id1, _), (id2, _)| id1.label().cmp(id2.label()));
+
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+
+        for (id, field) in fields {
+            let schema =
+                self.with_field(NickelPointerElem::Field(id.ident()), |slf| slf.field(field));
+            properties.insert(id.label().to_owned(), schema);
+
+            if field.value.is_none() && !field.metadata.opt {
+                required.push(json!(id.label()));
+            }
+        }
+
+        object(properties, required, closed)
+    }
+
+    fn field(&mut self, field: &Field) -> Value {
+        let annotation = &field.metadata.annotation;
+        let mut parts: Vec<Value> = annotation
+            .typ
+            .iter()
+            .chain(annotation.contracts.iter())
+            .map(|labeled_ty| self.typ(&labeled_ty.typ))
+            .collect();
+
+        let value = field.value.as_ref();
+
+        if let Some(Term::Record(record) | Term::RecRecord(record, ..)) = value.map(AsRef::as_ref) {
+            parts.push(Value::Object(self.record(record, false)));
+        }
+
+        let mut schema = all_of(parts);
+
+        if let Some(doc) = &field.metadata.doc {
+            schema.insert("description".to_owned(), json!(doc));
+        }
+
+        // Values which aren't fully evaluated (because they depend on a field without definition)
+        // fail to serialize, and are just ignored.
+        let value = value
+            .filter(|value| !matches!(value.as_ref(), Term::Record(_) | Term::RecRecord(..)))
+            .and_then(|value| serde_json::to_value(value).ok());
+
+        if let Some(value) = value {
+            let keyword = match field.metadata.priority {
+                MergePriority::Neutral => "const",
+                _ => "default",
+            };
+
+            schema.insert(keyword.to_owned(), value);
+        }
+
+        Value::Object(schema)
+    }
+
+    fn typ(&mut self, typ: &Type) -> Value {
+        match &typ.typ {
+            TypeF::Dyn => json!({}),
+            TypeF::Number => json!({ "type": "number" }),
+            TypeF::Bool => json!({ "type": "boolean" }),
+            TypeF::String => json!({ "type": "string" }),
+            TypeF::Array(elts) => json!({ "type": "array", "items": self.typ(elts) }),
+            TypeF::Dict { type_fields, .. } => json!({
+                "type": "object",
+                "additionalProperties": self.typ(type_fields),
+            }),
+            TypeF::Record(rows) => self.record_rows(rows),
+            TypeF::Enum(rows) => self.enum_rows(rows, typ),
+            TypeF::Contract(ctr) => self.contract(ctr, typ),
+            TypeF::Symbol
+            | TypeF::ForeignId
+            | TypeF::Arrow(..)
+            | TypeF::Var(_)
+            | TypeF::Forall { .. }
+            | TypeF::Wildcard(_) => self.unsupported(typ),
+        }
+    }
+
+    fn record_rows(&mut self, rows: &RecordRows) -> Value {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        let mut closed = true;
+
+        for item in rows.iter() {
+            match item {
+                RecordRowsIteratorItem::Row(row) => {
+                    let schema = self.with_field(NickelPointerElem::Field(row.id.ident()), |slf| {
+                        slf.typ(row.typ)
+                    });
+                    properties.insert(row.id.label().to_owned(), schema);
+                    required.push(json!(row.id.label()));
+                }
+                RecordRowsIteratorItem::TailDyn | RecordRowsIteratorItem::TailVar(_) => {
+                    closed = false
+                }
+            }
+        }
+
+        Value::Object(object(properties, required, closed))
+    }
+
+    fn enum_rows(&mut self, rows: &EnumRows, typ: &Type) -> Value {
+        let mut tags = Vec::new();
+
+        for item in rows.iter() {
+            match item {
+                EnumRowsIteratorItem::Row(row) if row.typ.is_none() => {
+                    tags.push(json!(row.id.label()))
+                }
+                // Enum variants can't be serialized.
+                EnumRowsIteratorItem::Row(_) => return self.unsupported(typ),
+                EnumRowsIteratorItem::TailVar(_) => (),
+            }
+        }
+
+        json!({ "type": "string", "enum": tags })
+    }
+
+    fn contract(&mut self, ctr: &RichTerm, typ: &Type) -> Value {
+        if let Term::Record(record) | Term::RecRecord(record, ..) = ctr.as_ref() {
+            return Value::Object(self.record(record, !record.attrs.open));
+        }
+
+        let path = std_path(ctr).unwrap_or_default();
+        let path: Vec<_> = path.iter().map(Ident::label).collect();
+
+        match path.as_slice() {
+            ["number", "Integer"] => json!({ "type": "integer" }),
+            ["number", "Nat"] => json!({ "type": "integer", "minimum": 0 }),
+            ["number", "PosNat"] => json!({ "type": "integer", "minimum": 1 }),
+            ["number", "NonZero"] => json!({ "type": "number", "not": { "const": 0 } }),
+            ["string", "NonEmpty"] => json!({ "type": "string", "minLength": 1 }),
+            ["string", "BoolLiteral"] => json!({ "type": "string", "enum": ["true", "false"] }),
+            ["array", "NonEmpty"] => json!({ "type": "array", "minItems": 1 }),
+            _ => self.unsupported(typ),
+        }
+    }
+
+    fn unsupported(&mut self, typ: &Type) -> Value {
+        self.unsupported.push(UnsupportedContract {
+            path: self.path.clone(),
+            contract: typ.to_string(),
+            pos: typ.pos,
+        });
+
+        json!({})
+    }
+}
+
+fn object(
+    properties: Map<String, Value>,
+    required: Vec<Value>,
+    closed: bool,
+) -> Map<String, Value> {
+    let mut schema = Map::new();
+    schema.insert("type".to_owned(), json!("object"));
+    schema.insert("properties".to_owned(), Value::Object(properties));
+
+    if !required.is_empty() {
+        schema.insert("required".to_owned(), Value::Array(required));
+    }
+
+    if closed {
+        schema.insert("additionalProperties".to_owned(), json!(false));
+    }
+
+    schema
+}
+
+/// Combines the schemas of the different annotations of a field. Permissive schemas are dropped.
+fn all_of(parts: Vec<Value>) -> Map<String, Value> {
+    let mut parts: Vec<_> = parts
+        .into_iter()
+        .filter_map(|part| match part {
+            Value::Object(map) if !map.is_empty() => Some(map),
+            _ => None,
+        })
+        .collect();
+
+    match parts.len() {
+        0 => Map::new(),
+        1 => parts.pop().unwrap(),
+        _ => {
+            let mut schema = Map::new();
+            schema.insert(
+                "allOf".to_owned(),
+                Value::Array(parts.into_iter().map(Value::Object).collect()),
+            );
+            schema
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::cache::CacheImpl;
+    use crate::program::Program;
+    use std::io::Cursor;
+
+    fn schema(s: &str) -> JsonSchema {
+        let src = Cursor::new(s);
+        let mut prog =
+            Program::<CacheImpl>::new_from_source(src, "<test>", std::io::stderr()).unwrap();
+        let rt = prog.eval_record_spine_with_contracts().unwrap();
+        from_record_contract(&rt).unwrap()
+    }
+
+    #[test]
+    fn record_contract() {
+        let JsonSchema {
+            schema,
+            unsupported,
+        } = schema(
+            r#"
+            let Server = { host | String, port | std.number.Nat | default = 80, .. } in
+            {
+              name | String | doc "The name",
+              servers | Array Server,
+              mode | [| 'debug, 'release |] | optional,
+              version = 1,
+            }
+            "#,
+        );
+
+        assert!(unsupported.is_empty());
+        assert_eq!(
+            schema,
+            json!({
+                "$schema": SCHEMA_DIALECT,
+                "type": "object",
+                "properties": {
+                    "mode": { "type": "string", "enum": ["debug", "release"] },
+                    "name": { "type": "string", "description": "The name" },
+                    "servers": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "host": { "type": "string" },
+                                "port": { "type": "integer", "minimum": 0, "default": 80 },
+                            },
+                            "required": ["host"],
+                        },
+                    },
+                    "version": { "const": 1 },
+                },
+                "required": ["name", "servers"],
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[test]
+    fn std_contract_aliases() {
+        let JsonSchema {
+            schema,
+            unsupported,
+        } = schema(
+            r#"
+            let Nat = std.number.Nat in
+            let string = std.string in
+            let Positive = std.contract.from_predicate (fun x => x > 0) in
+            {
+              a | Nat,
+              b | string.NonEmpty,
+              c | Array Nat,
+              d | Positive,
+            }
+            "#,
+        );
+
+        assert_eq!(
+            unsupported
+                .iter()
+                .map(|ctr| (ctr.path.to_string(), ctr.contract.clone()))
+                .collect::<Vec<_>>(),
+            vec![("d".to_owned(), "Positive".to_owned())]
+        );
+        assert_eq!(
+            schema["properties"],
+            json!({
+                "a": { "type": "integer", "minimum": 0 },
+                "b": { "type": "string", "minLength": 1 },
+                "c": { "type": "array", "items": { "type": "integer", "minimum": 0 } },
+                "d": {},
+            })
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        let JsonSchema {
+            schema,
+            unsupported,
+        } = schema("{ a | std.contract.from_predicate (fun x => x != 0), b | Number -> Number }");
+
+        assert_eq!(
+            unsupported
+                .iter()
+                .map(|ctr| ctr.path.to_string())
+                .collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+        assert_eq!(schema["properties"], json!({ "a": {}, "b": {} }));
+    }
+}
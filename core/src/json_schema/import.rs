@@ -0,0 +1,665 @@
+//! Conversion of a JSON Schema to a Nickel contract, which implements the `'JsonSchema` import
+//! format.
+//!
+//! The schema is parsed with [crate::serialize::json_deser], and each contract is annotated with
+//! the position of the keyword it comes from, so that blame errors point back to the schema file.
+//! The conversion follows the shape of the schema:
+//!
+//! - `type` becomes the corresponding Nickel type, or `std.number.Integer` for `integer`. Several
+//!   types are combined with `std.contract.any_of`.
+//! - `properties` becomes a record contract. Properties which aren't `required` are `optional`,
+//!   the `description` of a property becomes its documentation and its `default` value a default
+//!   definition. The record contract is closed if `additionalProperties` is `false`. Without
+//!   `properties`, an `additionalProperties` schema becomes a dictionary contract.
+//! - `items` becomes an array type.
+//! - `enum`s of strings become enum types (preceded by `std.enum.TagOrString`, so that strings
+//!   are accepted as well). Other `enum`s and `const`s become validators checking for equality.
+//! - `anyOf`, `oneOf`, `allOf` and `not` become the corresponding `std.contract` combinators.
+//!   `oneOf` is approximated by `std.contract.any_of`.
+//! - constraints such as `minimum`, `maxLength` or `pattern` become validators, which only apply
+//!   to values of the right type, as in JSON Schema.
+//!
+//! Validators are used rather than e.g. `std.contract.Equal`, because they work well with
+//! `std.contract.any_of` and give better error messages.
+//! - `$ref`s to `#` or to a definition of `$defs` (or `definitions`) at the root of the schema
+//!   refer to a recursive let-binding.
+//!
+//! Other keywords, such as `title` or `format`, are ignored.
+use crate::{
+    error::ParseError,
+    files::FileId,
+    identifier::LocIdent,
+    mk_app, mk_fun, mk_record,
+    position::TermPos,
+    serialize::json_deser,
+    term::{
+        make as mk_term,
+        record::{Field, FieldMetadata, RecordAttrs, RecordData},
+        BinaryOp, IndexMap, LabeledType, MergePriority, Number, RichTerm, Term, TypeAnnotation,
+    },
+    typ::{DictTypeFlavour, EnumRow, EnumRows, EnumRowsF, Type, TypeF},
+};
+
+/// The name of the let-binding holding the definitions of the schema.
+const DEFS: &str = "defs";
+/// The name of the let-binding holding the schema itself, when it refers to itself with `#`.
+const ROOT: &str = "root";
+
+/// Converts a JSON Schema provided as a string, with the file id of this source, to a Nickel
+/// contract.
+pub fn contract_from_str(s: &str, file_id: FileId) -> Result<RichTerm, ParseError> {
+    let schema = json_deser::from_str(s, file_id)?;
+    let mut converter = Converter {
+        defs: Vec::new(),
+        root_ref: false,
+    };
+
+    if let Term::Record(record) = schema.as_ref() {
+        for keyword in ["$defs", "definitions"] {
+            if let Some(defs) = keyword_value(record, keyword) {
+                let Term::Record(defs) = defs.as_ref() else {
+                    return Err(error(format!("`{keyword}` must be an object"), defs.pos));
+                };
+
+                converter
+                    .defs
+                    .extend(defs.fields.iter().map(|(id, _)| id.label().to_owned()));
+            }
+        }
+    }
+
+    let root = converter.contract(&schema)?;
+    let mut bindings = Vec::new();
+
+    if let Term::Record(record) = schema.as_ref() {
+        let mut defs = IndexMap::new();
+
+        for keyword in ["$defs", "definitions"] {
+            if let Some(Term::Record(record)) = keyword_value(record, keyword).map(AsRef::as_ref) {
+                for (id, field) in record.fields.iter() {
+                    if let Some(def) = &field.value {
+                        defs.insert(*id, converter.contract(def)?);
+                    }
+                }
+            }
+        }
+
+        if !defs.is_empty() {
+            bindings.push((
+                LocIdent::from(DEFS),
+                RichTerm::from(Term::Record(RecordData::with_field_values(defs))),
+            ));
+        }
+    }
+
+    if converter.root_ref {
+        bindings.push((LocIdent::from(ROOT), root));
+        Ok(mk_term::let_in(true, bindings, mk_term::var(ROOT)))
+    } else if bindings.is_empty() {
+        Ok(root)
+    } else {
+        Ok(mk_term::let_in(true, bindings, root))
+    }
+}
+
+struct Converter {
+    /// The names of the definitions at the root of the schema.
+    defs: Vec<String>,
+    /// Whether the schema refers to itself with `#`.
+    root_ref: bool,
+}
+
+impl Converter {
+    /// Converts a schema to a single contract.
+    fn contract(&mut self, schema: &RichTerm) -> Result<RichTerm, ParseError> {
+        let types = self.schema(schema)?;
+        Ok(as_term(all_of(types, schema.pos)))
+    }
+
+    /// Converts a schema to a list of types, which must all be satisfied by a value. An empty list
+    /// accepts any value.
+    fn schema(&mut self, schema: &RichTerm) -> Result<Vec<Type>, ParseError> {
+        let record = match schema.as_ref() {
+            Term::Bool(true) => return Ok(Vec::new()),
+            Term::Bool(false) => {
+                return Ok(vec![validator(
+                    None,
+                    Term::Bool(false).into(),
+                    "no value is allowed here".to_owned(),
+                    schema.pos,
+                )])
+            }
+            Term::Record(record) => record,
+            _ => {
+                return Err(error(
+                    "expected a schema, which must be an object or a boolean",
+                    schema.pos,
+                ))
+            }
+        };
+
+        let mut types = Vec::new();
+
+        if let Some(reference) = keyword_value(record, "$ref") {
+            types.push(self.reference(reference)?);
+        }
+
+        // The contracts specific to records and arrays replace the corresponding `type`, as they
+        // already check that the value is a record or an array.
+        let mut object = self.object(record)?;
+        let mut array = keyword_value(record, "items")
+            .map(|items| -> Result<_, ParseError> {
+                let elts = self.contract_type(items)?;
+                Ok(Type::from(TypeF::Array(Box::new(elts))).with_pos(items.pos))
+            })
+            .transpose()?;
+
+        match keyword_value(record, "type") {
+            Some(typ) => {
+                let mut type_of = |name: &RichTerm| match name.as_ref() {
+                    Term::Str(name_str) => match name_str.as_str() {
+                        "object" => Ok(object.take().unwrap_or_else(|| {
+                            Type::from(TypeF::Dict {
+                                type_fields: Box::new(Type::from(TypeF::Dyn)),
+                                flavour: DictTypeFlavour::Contract,
+                            })
+                            .with_pos(name.pos)
+                        })),
+                        "array" => Ok(array.take().unwrap_or_else(|| {
+                            Type::from(TypeF::Array(Box::new(Type::from(TypeF::Dyn))))
+                                .with_pos(name.pos)
+                        })),
+                        _ => primitive_type(name),
+                    },
+                    _ => Err(error("a type must be a string", name.pos)),
+                };
+
+                match typ.as_ref() {
+                    Term::Array(names, _) => {
+                        let alternatives = names
+                            .iter()
+                            .map(|name| type_of(name).map(as_term))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        types.push(any_of(alternatives, typ.pos));
+                    }
+                    _ => types.push(type_of(typ)?),
+                }
+            }
+            None => types.extend(object.into_iter().chain(array)),
+        }
+
+        types.extend(self.constraints(record)?);
+
+        if let Some(enum_values) = keyword_value(record, "enum") {
+            let Term::Array(values, _) = enum_values.as_ref() else {
+                return Err(error("`enum` must be an array", enum_values.pos));
+            };
+
+            let tags: Option<Vec<_>> = values
+                .iter()
+                .map(|value| match value.as_ref() {
+                    Term::Str(tag) => Some(LocIdent::new_with_pos(tag.as_str(), value.pos)),
+                    _ => None,
+                })
+                .collect();
+
+            match tags {
+                Some(tags) => {
+                    let rows =
+                        tags.into_iter()
+                            .rev()
+                            .fold(EnumRows(EnumRowsF::Empty), |tail, id| {
+                                EnumRows(EnumRowsF::Extend {
+                                    row: EnumRow { id, typ: None },
+                                    tail: Box::new(tail),
+                                })
+                            });
+
+                    types.push(contract(
+                        std_access(["enum", "TagOrString"]),
+                        enum_values.pos,
+                    ));
+                    types.push(Type::from(TypeF::Enum(rows)).with_pos(enum_values.pos));
+                }
+                None => types.push(validator(
+                    None,
+                    mk_app!(
+                        std_access(["array", "elem"]),
+                        mk_term::var("x"),
+                        enum_values.clone()
+                    ),
+                    format!("expected one of {enum_values}"),
+                    enum_values.pos,
+                )),
+            }
+        }
+
+        if let Some(value) = keyword_value(record, "const") {
+            types.push(equal(value));
+        }
+
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(schemas) = keyword_value(record, keyword) {
+                let alternatives = self.schemas(keyword, schemas)?;
+                types.push(any_of(alternatives, schemas.pos));
+            }
+        }
+
+        if let Some(schemas) = keyword_value(record, "allOf") {
+            for schema in schemas_array("allOf", schemas)? {
+                types.extend(self.schema(schema)?);
+            }
+        }
+
+        if let Some(schema) = keyword_value(record, "not") {
+            let negated = mk_app!(std_access(["contract", "not"]), self.contract(schema)?);
+            types.push(contract(negated, schema.pos));
+        }
+
+        Ok(types)
+    }
+
+    /// Converts a schema to a single type.
+    fn contract_type(&mut self, schema: &RichTerm) -> Result<Type, ParseError> {
+        let types = self.schema(schema)?;
+        Ok(all_of(types, schema.pos))
+    }
+
+    fn schemas(&mut self, keyword: &str, schemas: &RichTerm) -> Result<Vec<RichTerm>, ParseError> {
+        schemas_array(keyword, schemas)?
+            .map(|schema| self.contract(schema))
+            .collect()
+    }
+
+    fn reference(&mut self, reference: &RichTerm) -> Result<Type, ParseError> {
+        let Term::Str(target) = reference.as_ref() else {
+            return Err(error("`$ref` must be a string", reference.pos));
+        };
+
+        if target.as_str() == "#" {
+            self.root_ref = true;
+            return Ok(contract(mk_term::var(ROOT), reference.pos));
+        }
+
+        let name = ["#/$defs/", "#/definitions/"]
+            .iter()
+            .find_map(|prefix| target.as_str().strip_prefix(prefix))
+            .filter(|name| !name.contains('/'))
+            .map(|name| name.replace("~1", "/").replace("~0", "~"));
+
+        match name {
+            Some(name) if self.defs.contains(&name) => Ok(contract(
+                mk_term::static_access(mk_term::var(DEFS), [name]),
+                reference.pos,
+            )),
+            Some(name) => Err(error(format!("unknown definition `{name}`"), reference.pos)),
+            None => Err(error(
+                format!(
+                    "unsupported reference `{target}`: only references to `#` and to the \
+                    definitions of the root schema are supported"
+                ),
+                reference.pos,
+            )),
+        }
+    }
+
+    /// Converts the keywords describing the fields of a record.
+    fn object(&mut self, record: &RecordData) -> Result<Option<Type>, ParseError> {
+        let properties = keyword_value(record, "properties");
+        let additional = keyword_value(record, "additionalProperties");
+
+        let Some(properties) = properties else {
+            return match additional {
+                Some(schema) if matches!(schema.as_ref(), Term::Bool(false)) => Ok(Some(contract(
+                    Term::Record(RecordData::empty()).into(),
+                    schema.pos,
+                ))),
+                Some(schema) => {
+                    let type_fields = self.contract_type(schema)?;
+                    Ok(Some(
+                        Type::from(TypeF::Dict {
+                            type_fields: Box::new(type_fields),
+                            flavour: DictTypeFlavour::Contract,
+                        })
+                        .with_pos(schema.pos),
+                    ))
+                }
+                None => Ok(None),
+            };
+        };
+
+        let Term::Record(properties_record) = properties.as_ref() else {
+            return Err(error("`properties` must be an object", properties.pos));
+        };
+
+        let required: Vec<&str> = match keyword_value(record, "required") {
+            Some(required) => match required.as_ref() {
+                Term::Array(names, _) => names
+                    .iter()
+                    .map(|name| match name.as_ref() {
+                        Term::Str(name) => Ok(name.as_str()),
+                        _ => Err(error("a required property must be a string", name.pos)),
+                    })
+                    .collect::<Result<_, _>>()?,
+                _ => return Err(error("`required` must be an array", required.pos)),
+            },
+            None => Vec::new(),
+        };
+
+        let mut fields = IndexMap::new();
+
+        for (id, property) in properties_record.fields.iter() {
+            // unwrap(): the fields of a deserialized JSON object are always defined.
+            let property = property.value.as_ref().unwrap();
+            let field = self.field(property, required.contains(&id.label()))?;
+            fields.insert(*id, field);
+        }
+
+        let attrs = RecordAttrs {
+            open: !additional.is_some_and(|schema| matches!(schema.as_ref(), Term::Bool(false))),
+            ..Default::default()
+        };
+
+        Ok(Some(contract(
+            Term::Record(RecordData::new(fields, attrs, None)).into(),
+            properties.pos,
+        )))
+    }
+
+    fn field(&mut self, schema: &RichTerm, required: bool) -> Result<Field, ParseError> {
+        let contracts = self
+            .schema(schema)?
+            .into_iter()
+            .map(|typ| {
+                let span = typ.pos.unwrap();
+                LabeledType::new(typ, span)
+            })
+            .collect();
+
+        let (doc, default) = match schema.as_ref() {
+            Term::Record(record) => (
+                keyword_value(record, "description").and_then(|doc| match doc.as_ref() {
+                    Term::Str(doc) => Some(doc.to_string()),
+                    _ => None,
+                }),
+                keyword_value(record, "default").cloned(),
+            ),
+            _ => (None, None),
+        };
+
+        let priority = if default.is_some() {
+            MergePriority::Bottom
+        } else {
+            MergePriority::Neutral
+        };
+
+        Ok(Field {
+            metadata: FieldMetadata {
+                doc,
+                annotation: TypeAnnotation {
+                    typ: None,
+                    contracts,
+                },
+                opt: !required && default.is_none(),
+                not_exported: false,
+                priority,
+            },
+            value: default,
+            pending_contracts: Vec::new(),
+        })
+    }
+
+    /// Converts the constraints on numbers, strings and arrays.
+    fn constraints(&mut self, record: &RecordData) -> Result<Vec<Type>, ParseError> {
+        let numeric = [
+            ("minimum", BinaryOp::GreaterOrEq, "greater than or equal to"),
+            ("exclusiveMinimum", BinaryOp::GreaterThan, "greater than"),
+            ("maximum", BinaryOp::LessOrEq, "less than or equal to"),
+            ("exclusiveMaximum", BinaryOp::LessThan, "less than"),
+        ];
+        let sizes = [
+            (
+                "minLength",
+                ["string", "length"],
+                BinaryOp::GreaterOrEq,
+                "at least",
+            ),
+            (
+                "maxLength",
+                ["string", "length"],
+                BinaryOp::LessOrEq,
+                "at most",
+            ),
+            (
+                "minItems",
+                ["array", "length"],
+                BinaryOp::GreaterOrEq,
+                "at least",
+            ),
+            (
+                "maxItems",
+                ["array", "length"],
+                BinaryOp::LessOrEq,
+                "at most",
+            ),
+        ];
+
+        let mut types = Vec::new();
+
+        for (keyword, op, relation) in numeric {
+            if let Some(value) = number_keyword(record, keyword)? {
+                types.push(validator(
+                    Some("is_number"),
+                    mk_term::op2(op, mk_term::var("x"), value.clone()),
+                    format!("expected a number {relation} {value}"),
+                    value.pos,
+                ));
+            }
+        }
+
+        if let Some(value) = number_keyword(record, "multipleOf")? {
+            let remainder = mk_term::op2(BinaryOp::Modulo, mk_term::var("x"), value.clone());
+
+            types.push(validator(
+                Some("is_number"),
+                mk_term::op2(BinaryOp::Eq, remainder, Term::Num(Number::from(0))),
+                format!("expected a multiple of {value}"),
+                value.pos,
+            ));
+        }
+
+        for (keyword, length, op, relation) in sizes {
+            if let Some(value) = number_keyword(record, keyword)? {
+                let (guard, unit) = match length[0] {
+                    "string" => ("is_string", "characters"),
+                    _ => ("is_array", "elements"),
+                };
+
+                types.push(validator(
+                    Some(guard),
+                    mk_term::op2(
+                        op,
+                        mk_app!(std_access(length), mk_term::var("x")),
+                        value.clone(),
+                    ),
+                    format!("expected {relation} {value} {unit}"),
+                    value.pos,
+                ));
+            }
+        }
+
+        if let Some(pattern) = keyword_value(record, "pattern") {
+            let Term::Str(regex) = pattern.as_ref() else {
+                return Err(error("`pattern` must be a string", pattern.pos));
+            };
+
+            types.push(validator(
+                Some("is_string"),
+                mk_app!(
+                    std_access(["string", "is_match"]),
+                    pattern.clone(),
+                    mk_term::var("x")
+                ),
+                format!("expected a string matching `{regex}`"),
+                pattern.pos,
+            ));
+        }
+
+        Ok(types)
+    }
+}
+
+fn error(msg: impl Into<String>, pos: TermPos) -> ParseError {
+    ParseError::ExternalFormatError(String::from("json schema"), msg.into(), pos.into_opt())
+}
+
+fn keyword_value<'a>(record: &'a RecordData, keyword: &str) -> Option<&'a RichTerm> {
+    record
+        .fields
+        .get(&LocIdent::from(keyword))
+        .and_then(|field| field.value.as_ref())
+}
+
+fn number_keyword<'a>(
+    record: &'a RecordData,
+    keyword: &str,
+) -> Result<Option<&'a RichTerm>, ParseError> {
+    match keyword_value(record, keyword) {
+        Some(value) => match value.as_ref() {
+            Term::Num(_) => Ok(Some(value)),
+            _ => Err(error(format!("`{keyword}` must be a number"), value.pos)),
+        },
+        None => Ok(None),
+    }
+}
+
+fn schemas_array<'a>(
+    keyword: &str,
+    schemas: &'a RichTerm,
+) -> Result<impl Iterator<Item = &'a RichTerm>, ParseError> {
+    match schemas.as_ref() {
+        Term::Array(schemas, _) => Ok(schemas.iter()),
+        _ => Err(error(
+            format!("`{keyword}` must be an array of schemas"),
+            schemas.pos,
+        )),
+    }
+}
+
+fn primitive_type(name: &RichTerm) -> Result<Type, ParseError> {
+    let Term::Str(name_str) = name.as_ref() else {
+        return Err(error("a type must be a string", name.pos));
+    };
+
+    let typ = match name_str.as_str() {
+        "string" => Type::from(TypeF::String),
+        "number" => Type::from(TypeF::Number),
+        "boolean" => Type::from(TypeF::Bool),
+        "integer" => return Ok(contract(std_access(["number", "Integer"]), name.pos)),
+        "null" => return Ok(equal(&RichTerm::new(Term::Null, name.pos))),
+        other => return Err(error(format!("unknown type `{other}`"), name.pos)),
+    };
+
+    Ok(typ.with_pos(name.pos))
+}
+
+fn std_access<const N: usize>(path: [&str; N]) -> RichTerm {
+    mk_term::static_access(mk_term::var("std"), path)
+}
+
+fn contract(term: RichTerm, pos: TermPos) -> Type {
+    Type::from(TypeF::Contract(term)).with_pos(pos)
+}
+
+/// Builds a validator accepting only the given value.
+fn equal(value: &RichTerm) -> Type {
+    validator(
+        None,
+        mk_term::op2(BinaryOp::Eq, mk_term::var("x"), value.clone()),
+        format!("expected {value}"),
+        value.pos,
+    )
+}
+
+fn any_of(alternatives: Vec<RichTerm>, pos: TermPos) -> Type {
+    let alternatives = Term::Array(alternatives.into_iter().collect(), Default::default());
+    contract(
+        mk_app!(std_access(["contract", "any_of"]), alternatives),
+        pos,
+    )
+}
+
+/// Combines a list of types into one type, which accepts any value if the list is empty.
+fn all_of(mut types: Vec<Type>, pos: TermPos) -> Type {
+    match types.len() {
+        0 => Type::from(TypeF::Dyn).with_pos(pos),
+        1 => types.pop().unwrap(),
+        _ => {
+            let contracts =
+                Term::Array(types.into_iter().map(as_term).collect(), Default::default());
+            contract(mk_app!(std_access(["contract", "all_of"]), contracts), pos)
+        }
+    }
+}
+
+/// Converts a type to a term which can be used as a contract.
+fn as_term(typ: Type) -> RichTerm {
+    match typ.typ {
+        TypeF::Contract(term) => term,
+        _ => {
+            let pos = typ.pos;
+            // unwrap(): the generated types don't have type variables.
+            let contract = typ.contract().unwrap();
+            RichTerm::new(Term::Type { typ, contract }, pos)
+        }
+    }
+}
+
+/// Builds a validator checking `condition` on the value `x`, if `guard` (a type predicate of the
+/// standard library such as `is_number`) holds.
+fn validator(guard: Option<&str>, condition: RichTerm, message: String, pos: TermPos) -> Type {
+    let error = mk_term::enum_variant("Error", mk_record!(("message", mk_term::string(message))));
+    let check = mk_term::if_then_else(condition, Term::Enum("Ok".into()), error);
+    let body = match guard {
+        Some(guard) => mk_term::if_then_else(
+            mk_app!(std_access([guard]), mk_term::var("x")),
+            check,
+            Term::Enum("Ok".into()),
+        ),
+        None => check,
+    };
+
+    contract(
+        mk_app!(
+            std_access(["contract", "from_validator"]),
+            mk_fun!("x", body)
+        ),
+        pos,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::Files;
+
+    #[test]
+    fn errors() {
+        let mut files = Files::new();
+
+        for schema in [
+            "1",
+            r#"{ "type": "float" }"#,
+            r#"{ "$ref": "other.json" }"#,
+            r##"{ "$ref": "#/$defs/missing" }"##,
+            r#"{ "minimum": "1" }"#,
+            r#"{ "properties": [] }"#,
+        ] {
+            let file_id = files.add("<test>", schema.to_owned());
+            assert!(
+                contract_from_str(schema, file_id).is_err(),
+                "`{schema}` should be rejected"
+            );
+        }
+    }
+}
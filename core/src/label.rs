@@ -4,6 +4,8 @@
 //! information about the context of a contract failure.
 use std::{collections::HashMap, rc::Rc};
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
     eval::cache::{Cache as EvalCache, CacheIndex},
     files::Files,
@@ -53,6 +55,8 @@ pub mod ty_path {
     //! indicating that the path leading to the subtype of interest goes through a record via a
     //! particular field.
 
+    use serde::{Deserialize, Serialize};
+
     use crate::{
         identifier::LocIdent,
         position::RawSpan,
@@ -60,7 +64,7 @@ pub mod ty_path {
     };
 
     /// An element of a path type.
-    #[derive(Debug, Clone, PartialEq, Eq, Copy)]
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, Copy, Serialize, Deserialize)]
     pub enum Elem {
         Domain,
         Codomain,
@@ -270,7 +274,7 @@ pub mod ty_path {
 /// user-written contracts, but is toggled in the argument contract when the interpreter decomposes
 /// an higher order-contract. This also generalizes to higher types such as `((Number -> Number) ->
 /// Number) -> Number` where the polarity alternates each time.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Label {
     /// The type checked by the original contract.
     pub typ: Rc<Type>,
@@ -286,6 +290,7 @@ pub struct Label {
     pub span: RawSpan,
 
     /// The index corresponding to the value being checked. Set at run-time by the interpreter.
+    #[serde(skip)]
     pub arg_idx: Option<CacheIndex>,
 
     /// The original position of the value being checked. Set at run-time by the interpreter.
@@ -300,6 +305,7 @@ pub struct Label {
 
     /// An environment mapping type variables to [`TypeVarData`]. Used by polymorphic contracts to
     /// decide which actions to take when encountering a `forall`.
+    #[serde(skip)]
     pub type_environment: HashMap<SealingKey, TypeVarData>,
 
     /// The name of the record field to report in blame errors. This is set
@@ -341,7 +347,7 @@ impl ReifyAsUnifType for Polarity {
     }
 }
 /// A polarity. See [`Label`]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Polarity {
     Positive,
     Negative,
@@ -379,7 +385,7 @@ impl TryFrom<&Term> for Polarity {
 
 /// Custom reporting diagnostic that can be set by user-code through the `label` API. Used to
 /// customize contract error messages, and provide more context than "a contract has failed".
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct ContractDiagnostic {
     /// The main error message tag to be printed together with the error message.
     pub message: Option<String>,
@@ -538,7 +544,7 @@ impl Default for Label {
 }
 
 /// Possible origins of a merge operation.
-#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub enum MergeKind {
     /// A standard, user-written merge operation (or a merge operation descending from a
     /// user-written merge operation).
@@ -564,7 +570,7 @@ pub enum MergeKind {
 /// Additionally, merging arrays currently generates a contract and its associated label for which
 /// we don't necessarily have a defined span at hand. The merge label makes it possible to fallback
 /// to the original position of the merge.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct MergeLabel {
     /// The span of the original merge (which might then decompose into many others).
     pub span: RawSpan,
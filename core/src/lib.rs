@@ -4,14 +4,18 @@ pub mod cache;
 pub mod closurize;
 pub mod combine;
 pub mod deserialize;
+pub mod diff;
+pub mod disk_cache;
 pub mod environment;
 pub mod error;
 pub mod eval;
 pub mod files;
 pub mod identifier;
+pub mod json_schema;
 pub mod label;
 #[cfg(feature = "nix-experimental")]
 pub mod nix_ffi;
+pub mod package;
 pub mod parser;
 pub mod position;
 pub mod pretty;
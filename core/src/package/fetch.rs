@@ -0,0 +1,270 @@
+//! The local package cache, where git dependencies are fetched.
+//!
+//! Git operations are delegated to the `git` executable, which must be available in `PATH` when
+//! resolving or fetching git dependencies. Evaluation itself only reads the checkouts.
+//!
+//! The layout of the cache directory is the following:
+//!
+//! ```text
+//! <cache dir>/git/db/<url hash>/                 bare mirror of the remote repository
+//! <cache dir>/git/checkouts/<url hash>/<rev>/    checkout of a specific revision
+//! ```
+//!
+//! Urls and revisions come from manifests and lock files, which aren't trusted: they are always
+//! passed to git after `--` or `--end-of-options`, urls starting with `-` are rejected, and locked
+//! revisions must be full commit hashes.
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use sha2::{Digest, Sha256};
+
+use super::Error;
+
+/// A local directory where git dependencies are fetched and checked out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackageCache {
+    pub dir: PathBuf,
+}
+
+impl PackageCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        PackageCache { dir: dir.into() }
+    }
+
+    /// The directory where the revision `rev` of the repository at `url` is checked out. Fails if
+    /// `rev` isn't a full commit hash.
+    pub fn checkout_dir(&self, url: &str, rev: &str) -> Result<PathBuf, Error> {
+        check_rev(url, rev)?;
+
+        Ok(self
+            .dir
+            .join("git")
+            .join("checkouts")
+            .join(url_hash(url))
+            .join(rev))
+    }
+
+    fn db_dir(&self, url: &str) -> PathBuf {
+        self.dir.join("git").join("db").join(url_hash(url))
+    }
+
+    /// Updates the local mirror of the repository at `url`, cloning it if needed, and resolves
+    /// `reference` (a branch, a tag, a possibly abbreviated commit hash, or `HEAD`) to a full
+    /// commit hash.
+    pub fn resolve_rev(&self, url: &str, reference: &str) -> Result<String, Error> {
+        check_url(url)?;
+        let db = self.db_dir(url);
+
+        if db.is_dir() {
+            git(
+                url,
+                Some(&db),
+                [
+                    "fetch",
+                    "--quiet",
+                    "--force",
+                    "--prune",
+                    "--",
+                    url,
+                    "+refs/heads/*:refs/heads/*",
+                    "+refs/tags/*:refs/tags/*",
+                    "+HEAD:refs/remotes/origin/HEAD",
+                ],
+            )?;
+        } else {
+            let parent = db.parent().unwrap_or(&self.dir);
+            fs::create_dir_all(parent).map_err(Error::io(parent))?;
+            git(
+                url,
+                None,
+                [
+                    OsStr::new("clone"),
+                    OsStr::new("--quiet"),
+                    OsStr::new("--bare"),
+                    OsStr::new("--"),
+                    OsStr::new(url),
+                    db.as_os_str(),
+                ],
+            )?;
+        }
+
+        let rev = git(
+            url,
+            Some(&db),
+            [
+                "rev-parse",
+                "--verify",
+                "--end-of-options",
+                &format!("{reference}^{{commit}}"),
+            ],
+        )?;
+
+        Ok(rev.trim().to_owned())
+    }
+
+    /// Makes sure that the revision `rev` of the repository at `url` is checked out in the cache,
+    /// and returns the path of the checkout.
+    pub fn fetch(&self, url: &str, rev: &str) -> Result<PathBuf, Error> {
+        check_url(url)?;
+        let target = self.checkout_dir(url, rev)?;
+
+        if target.is_dir() {
+            return Ok(target);
+        }
+
+        let db = self.db_dir(url);
+        let has_rev = db.is_dir()
+            && git(
+                url,
+                Some(&db),
+                [
+                    "cat-file",
+                    "-e",
+                    "--end-of-options",
+                    &format!("{rev}^{{commit}}"),
+                ],
+            )
+            .is_ok();
+
+        if !has_rev {
+            self.resolve_rev(url, rev)?;
+        }
+
+        let parent = target.parent().unwrap_or(&self.dir);
+        fs::create_dir_all(parent).map_err(Error::io(parent))?;
+
+        // We check out in a temporary directory first, so that an interrupted fetch doesn't leave
+        // a partial checkout behind.
+        let tmp = parent.join(format!(".{rev}.tmp"));
+        if tmp.exists() {
+            fs::remove_dir_all(&tmp).map_err(Error::io(&tmp))?;
+        }
+
+        git(
+            url,
+            None,
+            [
+                OsStr::new("clone"),
+                OsStr::new("--quiet"),
+                OsStr::new("--no-checkout"),
+                OsStr::new("--"),
+                db.as_os_str(),
+                tmp.as_os_str(),
+            ],
+        )?;
+        // `git checkout` doesn't accept `--end-of-options`, but `rev` has been checked to be a
+        // commit hash.
+        git(url, Some(&tmp), ["checkout", "--quiet", "--detach", rev])?;
+        fs::rename(&tmp, &target).map_err(Error::io(&target))?;
+
+        Ok(target)
+    }
+}
+
+/// Runs a git command and returns its standard output.
+fn git<I, S>(url: &str, dir: Option<&Path>, args: I) -> Result<String, Error>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut cmd = Command::new("git");
+
+    if let Some(dir) = dir {
+        cmd.current_dir(dir);
+    }
+
+    let output = cmd.args(args).output().map_err(|err| Error::Git {
+        url: url.to_owned(),
+        message: format!("couldn't run git: {err}"),
+    })?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(Error::Git {
+            url: url.to_owned(),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        })
+    }
+}
+
+/// Rejects urls that git would interpret as options.
+fn check_url(url: &str) -> Result<(), Error> {
+    if url.starts_with('-') {
+        Err(Error::InvalidGitUrl {
+            url: url.to_owned(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks that a locked revision is a full commit hash, either SHA-1 or SHA-256.
+fn check_rev(url: &str, rev: &str) -> Result<(), Error> {
+    if matches!(rev.len(), 40 | 64) && rev.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(Error::InvalidRev {
+            url: url.to_owned(),
+            rev: rev.to_owned(),
+        })
+    }
+}
+
+fn url_hash(url: &str) -> String {
+    let digest = Sha256::digest(url.as_bytes());
+    digest[..8].iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reject_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = PackageCache::new(dir.path());
+        let url = "--upload-pack=touch /tmp/pwned";
+
+        assert!(matches!(
+            cache.resolve_rev(url, "HEAD"),
+            Err(Error::InvalidGitUrl { .. })
+        ));
+        assert!(matches!(
+            cache.fetch(url, &"0".repeat(40)),
+            Err(Error::InvalidGitUrl { .. })
+        ));
+        assert!(!dir.path().join("git").exists());
+    }
+
+    #[test]
+    fn reject_invalid_revs() {
+        let cache = PackageCache::new("/cache");
+        let url = "https://example.com/foo.git";
+
+        for rev in [
+            "/etc",
+            "../../..",
+            "--orphan",
+            "main",
+            "0123abc",
+            &"g".repeat(40),
+        ] {
+            assert!(
+                matches!(cache.checkout_dir(url, rev), Err(Error::InvalidRev { .. })),
+                "{rev}"
+            );
+        }
+
+        let rev = "0123456789abcdef0123456789abcdef01234567";
+        assert!(matches!(
+            cache.checkout_dir(url, rev),
+            Ok(dir) if dir.starts_with("/cache/git/checkouts")
+        ));
+        assert!(cache.checkout_dir(url, &"a".repeat(64)).is_ok());
+    }
+}
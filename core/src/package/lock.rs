@@ -0,0 +1,412 @@
+//! Lock files.
+//!
+//! A lock file records the exact location of every package in the dependency graph of a package:
+//! path dependencies are stored relative to the directory of the lock file, and git dependencies
+//! are pinned to a full commit hash. It's serialized as JSON, and is meant to be checked in
+//! together with the manifest, so that evaluation is reproducible and never needs the network.
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{cache::normalize_path, identifier::Ident};
+
+use super::{
+    fetch::PackageCache,
+    manifest::{Dependency, ManifestFile},
+    Error, PackageMap, MANIFEST_FILE,
+};
+
+/// The exact location of a package.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum LockedSource {
+    /// A package at a local path, relative to the directory of the lock file (unless the
+    /// dependency was declared with an absolute path).
+    Path { path: PathBuf },
+    /// A package in a git repository, at a given revision.
+    Git {
+        url: String,
+        /// The branch that was followed when the revision was locked, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        branch: Option<String>,
+        /// The full commit hash.
+        rev: String,
+        /// The root directory of the package, relative to the root of the repository.
+        #[serde(default, skip_serializing_if = "is_empty_path")]
+        path: PathBuf,
+    },
+}
+
+fn is_empty_path(path: &Path) -> bool {
+    path.as_os_str().is_empty()
+}
+
+/// A package of the dependency graph, together with its own dependencies.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub source: LockedSource,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, LockedSource>,
+}
+
+/// The content of a lock file.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockFile {
+    /// The direct dependencies of the top-level package.
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, LockedSource>,
+    /// All the packages reachable from the top-level package.
+    #[serde(default)]
+    pub packages: Vec<LockedPackage>,
+}
+
+impl LockFile {
+    /// Reads a lock file.
+    pub fn from_path(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path).map_err(Error::io(path))?;
+        serde_json::from_str(&contents).map_err(|error| Error::InvalidLockFile {
+            path: path.to_owned(),
+            error,
+        })
+    }
+
+    /// Writes the lock file to `path`.
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let mut contents =
+            serde_json::to_string_pretty(self).map_err(|error| Error::InvalidLockFile {
+                path: path.to_owned(),
+                error,
+            })?;
+        contents.push('\n');
+        fs::write(path, contents).map_err(Error::io(path))
+    }
+
+    /// Resolves the whole dependency graph of `manifest`.
+    ///
+    /// Git dependencies are fetched into `cache` in the process, as their own manifests need to
+    /// be read. Unless `update` is set, the revisions locked in `previous` are reused for the git
+    /// dependencies that still match their declaration in the manifest.
+    pub fn resolve(
+        manifest: &ManifestFile,
+        previous: Option<&LockFile>,
+        cache: &PackageCache,
+        update: bool,
+    ) -> Result<Self, Error> {
+        let pinned = match previous {
+            Some(previous) if !update => previous
+                .packages
+                .iter()
+                .filter_map(|pkg| match &pkg.source {
+                    LockedSource::Git {
+                        url, branch, rev, ..
+                    } => Some(((url.clone(), branch.clone()), rev.clone())),
+                    LockedSource::Path { .. } => None,
+                })
+                .collect(),
+            _ => HashMap::new(),
+        };
+
+        let mut resolver = Resolver {
+            root: manifest.root_dir(),
+            cache,
+            pinned,
+            visited: HashSet::new(),
+            packages: Vec::new(),
+        };
+
+        let dependencies =
+            resolver.resolve_deps(manifest, &LockedSource::Path { path: "".into() })?;
+
+        Ok(LockFile {
+            dependencies,
+            packages: resolver.packages,
+        })
+    }
+
+    /// Fetches all the git packages of the lock file into `cache`.
+    pub fn fetch(&self, cache: &PackageCache) -> Result<(), Error> {
+        let revs: HashSet<_> = self
+            .packages
+            .iter()
+            .filter_map(|pkg| match &pkg.source {
+                LockedSource::Git { url, rev, .. } => Some((url, rev)),
+                LockedSource::Path { .. } => None,
+            })
+            .collect();
+
+        for (url, rev) in revs {
+            cache.fetch(url, rev)?;
+        }
+
+        Ok(())
+    }
+
+    /// Builds the package map used to resolve package imports. `root` is the directory of the
+    /// lock file. Fails if a git package hasn't been fetched in `cache` yet.
+    pub fn package_map(&self, root: &Path, cache: &PackageCache) -> Result<PackageMap, Error> {
+        let dir = |name: &str, source: &LockedSource| -> Result<PathBuf, Error> {
+            let path = match source {
+                LockedSource::Path { path } => root.join(path),
+                LockedSource::Git { url, rev, path, .. } => {
+                    cache.checkout_dir(url, rev)?.join(path)
+                }
+            };
+            let path = normalize_path(&path).map_err(Error::io(&path))?;
+
+            if path.is_dir() {
+                Ok(path)
+            } else {
+                Err(Error::NotFetched {
+                    name: name.to_owned(),
+                    path,
+                })
+            }
+        };
+
+        let top_level = self
+            .dependencies
+            .iter()
+            .map(|(name, source)| Ok((Ident::new(name), dir(name, source)?)))
+            .collect::<Result<_, Error>>()?;
+
+        let mut packages = HashMap::new();
+
+        for pkg in &self.packages {
+            for (name, source) in &pkg.dependencies {
+                let parent = dir(name, &pkg.source)?;
+                packages.insert((parent, Ident::new(name)), dir(name, source)?);
+            }
+        }
+
+        Ok(PackageMap {
+            root: normalize_path(root).map_err(Error::io(root))?,
+            top_level,
+            packages,
+        })
+    }
+}
+
+struct Resolver<'a> {
+    /// The directory of the top-level package.
+    root: &'a Path,
+    cache: &'a PackageCache,
+    /// Git revisions from a previous lock file, indexed by url and branch.
+    pinned: HashMap<(String, Option<String>), String>,
+    visited: HashSet<LockedSource>,
+    packages: Vec<LockedPackage>,
+}
+
+impl Resolver<'_> {
+    /// Resolves the dependencies of the package described by `manifest`, located at `source`,
+    /// and recursively registers them in `self.packages`.
+    fn resolve_deps(
+        &mut self,
+        manifest: &ManifestFile,
+        source: &LockedSource,
+    ) -> Result<BTreeMap<String, LockedSource>, Error> {
+        let mut result = BTreeMap::new();
+
+        for (name, dep) in &manifest.dependencies {
+            let locked = self.lock(source, dep)?;
+
+            if self.visited.insert(locked.clone()) {
+                let dependencies = match self.manifest(&locked)? {
+                    Some(manifest) => self.resolve_deps(&manifest, &locked)?,
+                    None => BTreeMap::new(),
+                };
+
+                self.packages.push(LockedPackage {
+                    source: locked.clone(),
+                    dependencies,
+                });
+            }
+
+            result.insert(name.clone(), locked);
+        }
+
+        Ok(result)
+    }
+
+    /// Computes the locked source of `dep`, declared by the package at `parent`.
+    fn lock(&self, parent: &LockedSource, dep: &Dependency) -> Result<LockedSource, Error> {
+        match (parent, dep) {
+            (LockedSource::Path { path: parent }, Dependency::Path { path }) => {
+                Ok(LockedSource::Path {
+                    path: normalize_lexically(&parent.join(path)),
+                })
+            }
+            (
+                LockedSource::Git {
+                    url,
+                    branch,
+                    rev,
+                    path: subdir,
+                },
+                Dependency::Path { path },
+            ) => {
+                let subdir = normalize_lexically(&subdir.join(path));
+
+                if path.is_absolute() || subdir.starts_with("..") {
+                    return Err(Error::PathOutsideRepository {
+                        url: url.clone(),
+                        path: path.clone(),
+                    });
+                }
+
+                Ok(LockedSource::Git {
+                    url: url.clone(),
+                    branch: branch.clone(),
+                    rev: rev.clone(),
+                    path: subdir,
+                })
+            }
+            (_, Dependency::Git(git)) => {
+                let pinned = self
+                    .pinned
+                    .get(&(git.url.clone(), git.branch.clone()))
+                    .filter(|rev| git.rev.as_ref().is_none_or(|r| rev.starts_with(r.as_str())));
+
+                let rev = match pinned {
+                    Some(rev) => rev.clone(),
+                    None => self.cache.resolve_rev(&git.url, git.reference())?,
+                };
+
+                Ok(LockedSource::Git {
+                    url: git.url.clone(),
+                    branch: git.branch.clone(),
+                    rev,
+                    path: PathBuf::new(),
+                })
+            }
+        }
+    }
+
+    /// Reads the manifest of the package at `source`, fetching it first if needed. Returns `None`
+    /// if the package doesn't have a manifest, in which case it's considered to have no
+    /// dependencies.
+    fn manifest(&self, source: &LockedSource) -> Result<Option<ManifestFile>, Error> {
+        let dir = match source {
+            LockedSource::Path { path } => self.root.join(path),
+            LockedSource::Git { url, rev, path, .. } => self.cache.fetch(url, rev)?.join(path),
+        };
+        let manifest_path = dir.join(MANIFEST_FILE);
+
+        if manifest_path.is_file() {
+            ManifestFile::from_path(manifest_path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Removes `.` components and resolves `..` components of a path without accessing the file
+/// system. Leading `..` components of a relative path are kept.
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match result.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    result.pop();
+                }
+                Some(Component::RootDir) => {}
+                _ => result.push(".."),
+            },
+            other => result.push(other.as_os_str()),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        assert_eq!(
+            normalize_lexically(Path::new("a/./b/../c")),
+            Path::new("a/c")
+        );
+        assert_eq!(
+            normalize_lexically(Path::new("../a/../../b")),
+            Path::new("../../b")
+        );
+        assert_eq!(
+            normalize_lexically(Path::new("/a/../../b")),
+            Path::new("/b")
+        );
+        assert_eq!(normalize_lexically(Path::new("a/..")), Path::new(""));
+    }
+
+    #[test]
+    fn package_root() {
+        let map = PackageMap {
+            root: "/proj".into(),
+            top_level: HashMap::from([
+                (Ident::new("parent"), "/".into()),
+                (Ident::new("foo"), "/proj/deps/foo".into()),
+            ]),
+            packages: HashMap::new(),
+        };
+
+        assert_eq!(map.package_root(Path::new("/proj/main.ncl")), None);
+        assert_eq!(map.package_root(Path::new("/proj/lib/a.ncl")), None);
+        assert_eq!(
+            map.package_root(Path::new("/proj/deps/foo/main.ncl")),
+            Some(Path::new("/proj/deps/foo"))
+        );
+        assert_eq!(
+            map.package_root(Path::new("/other/main.ncl")),
+            Some(Path::new("/"))
+        );
+    }
+
+    #[test]
+    fn lock_file_roundtrip() {
+        let lock = LockFile {
+            dependencies: BTreeMap::from([(
+                "foo".to_owned(),
+                LockedSource::Path {
+                    path: "deps/foo".into(),
+                },
+            )]),
+            packages: vec![
+                LockedPackage {
+                    source: LockedSource::Path {
+                        path: "deps/foo".into(),
+                    },
+                    dependencies: BTreeMap::from([(
+                        "bar".to_owned(),
+                        LockedSource::Git {
+                            url: "https://example.com/bar.git".to_owned(),
+                            branch: None,
+                            rev: "0123456789abcdef0123456789abcdef01234567".to_owned(),
+                            path: PathBuf::new(),
+                        },
+                    )]),
+                },
+                LockedPackage {
+                    source: LockedSource::Git {
+                        url: "https://example.com/bar.git".to_owned(),
+                        branch: None,
+                        rev: "0123456789abcdef0123456789abcdef01234567".to_owned(),
+                        path: PathBuf::new(),
+                    },
+                    dependencies: BTreeMap::new(),
+                },
+            ],
+        };
+
+        let serialized = serde_json::to_string(&lock).unwrap();
+        assert!(!serialized.contains("branch"));
+        assert_eq!(serde_json::from_str::<LockFile>(&serialized).unwrap(), lock);
+    }
+}
@@ -0,0 +1,217 @@
+//! Package manifests.
+//!
+//! A manifest is a Nickel file, which is evaluated and then converted to a [ManifestFile]. It's
+//! expected to satisfy the `std.package.Manifest` contract, for example:
+//!
+//! ```nickel
+//! {
+//!   name = "demo",
+//!   version = "0.1.0",
+//!   dependencies = {
+//!     contracts = 'Git { url = "https://example.com/contracts.git", branch = "main" },
+//!     shared = 'Path "../shared",
+//!   },
+//! } | std.package.Manifest
+//! ```
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    eval::cache::CacheImpl,
+    program::Program,
+    term::{record::RecordData, RichTerm, Term},
+};
+
+use super::{Error, MANIFEST_FILE};
+
+/// A dependency, as declared in a package manifest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Dependency {
+    /// A package at a local path. Relative paths are relative to the directory of the manifest.
+    Path { path: PathBuf },
+    /// A package fetched from a git repository.
+    Git(GitDependency),
+}
+
+/// A dependency fetched from a git repository.
+///
+/// If neither a branch nor a revision is specified, the default branch of the remote repository
+/// (its `HEAD`) is used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GitDependency {
+    /// The url of the repository, in any format accepted by `git clone`.
+    pub url: String,
+    /// The branch to follow.
+    pub branch: Option<String>,
+    /// A revision (a commit hash or a tag) to use.
+    pub rev: Option<String>,
+}
+
+impl GitDependency {
+    /// The git reference to resolve in order to find the revision to lock.
+    pub fn reference(&self) -> &str {
+        self.rev
+            .as_deref()
+            .or(self.branch.as_deref())
+            .unwrap_or("HEAD")
+    }
+}
+
+/// An evaluated package manifest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestFile {
+    /// The path of the manifest file.
+    pub path: PathBuf,
+    /// The name of the package.
+    pub name: String,
+    /// The version of the package.
+    pub version: String,
+    /// The minimal version of Nickel supported by the package.
+    pub nickel_version: Option<String>,
+    /// The dependencies of the package, indexed by the name used to import them.
+    pub dependencies: BTreeMap<String, Dependency>,
+}
+
+impl ManifestFile {
+    /// Evaluates the manifest at `path` and converts it to a `ManifestFile`.
+    pub fn from_path(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let mut program = Program::<CacheImpl>::new_from_file(&path, std::io::stderr())
+            .map_err(Error::io(&path))?;
+
+        let rt = match program.eval_full_for_export() {
+            Ok(rt) => rt,
+            Err(error) => {
+                return Err(Error::ManifestEval {
+                    path,
+                    program: Box::new(program),
+                    error: Box::new(error),
+                })
+            }
+        };
+
+        Self::from_term(path, &rt)
+    }
+
+    /// Searches for a package manifest in `dir` and then in its parent directories, and returns
+    /// the path of the first one found.
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .map(|ancestor| ancestor.join(MANIFEST_FILE))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// The root directory of the package, which is the directory containing the manifest.
+    pub fn root_dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("."))
+    }
+
+    /// Converts a fully evaluated manifest to a `ManifestFile`.
+    fn from_term(path: PathBuf, rt: &RichTerm) -> Result<Self, Error> {
+        let invalid = |message: String| Error::InvalidManifest {
+            path: path.clone(),
+            message,
+        };
+
+        let Term::Record(record) = rt.as_ref() else {
+            return Err(invalid("the manifest must be a record".into()));
+        };
+
+        let name = get_str(record, "name")
+            .map_err(invalid)?
+            .ok_or_else(|| invalid("missing field `name`".into()))?;
+        let version = get_str(record, "version")
+            .map_err(invalid)?
+            .ok_or_else(|| invalid("missing field `version`".into()))?;
+        let nickel_version = get_str(record, "nickel-version").map_err(invalid)?;
+
+        let dependencies = match get(record, "dependencies") {
+            None => BTreeMap::new(),
+            Some(deps) => {
+                let Term::Record(deps) = deps.as_ref() else {
+                    return Err(invalid("field `dependencies` must be a record".into()));
+                };
+
+                deps.iter_serializable()
+                    .filter_map(Result::ok)
+                    .map(|(id, dep)| {
+                        let dep = dependency_from_term(dep)
+                            .map_err(|msg| invalid(format!("invalid dependency `{id}`: {msg}")))?;
+                        Ok((id.to_string(), dep))
+                    })
+                    .collect::<Result<_, Error>>()?
+            }
+        };
+
+        return Ok(ManifestFile {
+            path,
+            name,
+            version,
+            nickel_version,
+            dependencies,
+        });
+
+        fn get<'a>(record: &'a RecordData, field: &str) -> Option<&'a RichTerm> {
+            record
+                .iter_serializable()
+                .filter_map(Result::ok)
+                .find_map(|(id, value)| (id.label() == field).then_some(value))
+        }
+
+        fn get_str(record: &RecordData, field: &str) -> Result<Option<String>, String> {
+            match get(record, field).map(RichTerm::as_ref) {
+                None => Ok(None),
+                Some(Term::Str(s)) => Ok(Some(s.to_string())),
+                Some(_) => Err(format!("field `{field}` must be a string")),
+            }
+        }
+    }
+}
+
+/// Converts a dependency declaration, which must be either `'Path <path>` or `'Git { url, branch,
+/// rev }`, where `branch` and `rev` are optional.
+fn dependency_from_term(rt: &RichTerm) -> Result<Dependency, String> {
+    match rt.as_ref() {
+        Term::EnumVariant { tag, arg, .. } if tag.label() == "Path" => match arg.as_ref() {
+            Term::Str(path) => Ok(Dependency::Path {
+                path: PathBuf::from(path.as_str()),
+            }),
+            _ => Err("the argument of `'Path` must be a string".into()),
+        },
+        Term::EnumVariant { tag, arg, .. } if tag.label() == "Git" => {
+            let Term::Record(record) = arg.as_ref() else {
+                return Err("the argument of `'Git` must be a record".into());
+            };
+
+            let mut url = None;
+            let mut branch = None;
+            let mut rev = None;
+
+            for binding in record.iter_serializable() {
+                let Ok((id, value)) = binding else {
+                    continue;
+                };
+                let Term::Str(value) = value.as_ref() else {
+                    return Err(format!("field `{id}` must be a string"));
+                };
+                let value = Some(value.to_string());
+
+                match id.label() {
+                    "url" => url = value,
+                    "branch" => branch = value,
+                    "rev" => rev = value,
+                    other => return Err(format!("unexpected field `{other}`")),
+                }
+            }
+
+            Ok(Dependency::Git(GitDependency {
+                url: url.ok_or_else(|| String::from("missing field `url`"))?,
+                branch,
+                rev,
+            }))
+        }
+        _ => Err("expected `'Path <path>` or `'Git { url, .. }`".into()),
+    }
+}
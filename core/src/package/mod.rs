@@ -0,0 +1,228 @@
+//! Package management.
+//!
+//! A package is a directory containing a package manifest, [MANIFEST_FILE], at its root. The
+//! manifest declares the name and the version of the package, together with its dependencies,
+//! which are other packages, either found at a local path or fetched from a git repository. See
+//! [manifest::ManifestFile].
+//!
+//! Dependencies are imported from Nickel code by name, using `import <name>`, which evaluates to
+//! the content of the entry point of the package, [MAIN_FILE]. Only the direct dependencies of the
+//! package doing the import are visible.
+//!
+//! Evaluation never fetches anything: dependencies are pinned to exact revisions by a lock file,
+//! [LOCK_FILE], stored next to the manifest (see [lock::LockFile]), and are read from a local
+//! package cache (see [fetch::PackageCache]). Both the lock file and the package cache are
+//! populated by the `nickel package` subcommand of the CLI. When the lock file is loaded, it is
+//! turned into a [PackageMap], which is what the [crate::cache::Cache] uses to resolve package
+//! imports.
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Diagnostic, Error as CoreError, ImportError, IntoDiagnostics},
+    eval::cache::CacheImpl,
+    files::{FileId, Files},
+    identifier::Ident,
+    position::TermPos,
+    program::Program,
+};
+
+pub mod fetch;
+pub mod lock;
+pub mod manifest;
+
+/// The name of the package manifest file.
+pub const MANIFEST_FILE: &str = "electroplate.ncl";
+/// The name of the lock file, which is stored next to the manifest file.
+pub const LOCK_FILE: &str = "electroplate.lock";
+/// The name of the entry point of a package, relative to its root directory.
+pub const MAIN_FILE: &str = "main.ncl";
+
+/// The location of the packages that can be imported by name, as seen by import resolution.
+///
+/// All paths are normalized (see [crate::cache::normalize_path]) root directories of packages.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PackageMap {
+    /// The root directory of the top-level package.
+    pub root: PathBuf,
+    /// The dependencies of the top-level package, that is the package being evaluated.
+    pub top_level: HashMap<Ident, PathBuf>,
+    /// The dependencies of the other packages, indexed by the root directory of the depending
+    /// package and by the name of the dependency.
+    pub packages: HashMap<(PathBuf, Ident), PathBuf>,
+}
+
+impl PackageMap {
+    /// Returns the root directory of the package containing `path`, if `path` belongs to one of
+    /// the dependencies. If package directories are nested, the innermost one is returned, and
+    /// `None` is returned if it's the top-level package: a dependency whose directory contains the
+    /// top-level package, such as `'Path ".."`, doesn't own the files of the top-level package.
+    pub fn package_root(&self, path: &Path) -> Option<&Path> {
+        let dep_root = self
+            .top_level
+            .values()
+            .chain(self.packages.values())
+            .map(PathBuf::as_path)
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())?;
+
+        let in_top_level = path.starts_with(&self.root)
+            && self.root.components().count() >= dep_root.components().count();
+        (!in_top_level).then_some(dep_root)
+    }
+
+    /// Finds the root directory of the package `name`, as imported from the file at `parent`.
+    /// If `parent` is `None` or doesn't belong to any dependency, the import is considered to
+    /// come from the top-level package.
+    pub fn get(
+        &self,
+        parent: Option<&Path>,
+        name: Ident,
+        pos: TermPos,
+    ) -> Result<&Path, ImportError> {
+        let package_root = parent.and_then(|p| self.package_root(p));
+
+        let result = match package_root {
+            Some(root) => self.packages.get(&(root.to_owned(), name)),
+            None => self.top_level.get(&name),
+        };
+
+        result
+            .map(PathBuf::as_path)
+            .ok_or_else(|| ImportError::MissingDependency {
+                parent: package_root.map(Path::to_owned),
+                missing: name,
+                pos,
+            })
+    }
+}
+
+/// An error occurring during package management.
+pub enum Error {
+    /// An IO error, optionally related to a specific file.
+    Io {
+        path: Option<PathBuf>,
+        error: io::Error,
+    },
+    /// The evaluation of a package manifest failed. The program is kept around to report the
+    /// underlying error.
+    ManifestEval {
+        path: PathBuf,
+        program: Box<Program<CacheImpl>>,
+        error: Box<CoreError>,
+    },
+    /// A package manifest evaluated successfully, but doesn't have the expected shape.
+    InvalidManifest { path: PathBuf, message: String },
+    /// No package manifest was found.
+    ManifestNotFound { path: PathBuf },
+    /// The lock file couldn't be deserialized.
+    InvalidLockFile {
+        path: PathBuf,
+        error: serde_json::Error,
+    },
+    /// A git command failed.
+    Git { url: String, message: String },
+    /// A git url starts with `-`, and would be interpreted as an option by git.
+    InvalidGitUrl { url: String },
+    /// A locked git revision isn't a full commit hash.
+    InvalidRev { url: String, rev: String },
+    /// A locked dependency hasn't been fetched into the package cache yet.
+    NotFetched { name: String, path: PathBuf },
+    /// A git package declared a path dependency pointing outside of its repository.
+    PathOutsideRepository { url: String, path: PathBuf },
+}
+
+impl Error {
+    pub(crate) fn io(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.into();
+        move |error| Error::Io {
+            path: Some(path),
+            error,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io { path: None, error }
+    }
+}
+
+impl IntoDiagnostics for Error {
+    fn into_diagnostics(self, _files: &mut Files) -> Vec<Diagnostic<FileId>> {
+        match self {
+            Error::Io { path: None, error } => {
+                vec![Diagnostic::error().with_message(format!("IO error: {error}"))]
+            }
+            Error::Io {
+                path: Some(path),
+                error,
+            } => vec![Diagnostic::error()
+                .with_message(format!("IO error on `{}`: {error}", path.display()))],
+            Error::ManifestEval {
+                path,
+                mut program,
+                error,
+            } => {
+                let report = program.report_as_str(*error);
+                vec![Diagnostic::error()
+                    .with_message(format!(
+                        "failed to evaluate the package manifest `{}`",
+                        path.display()
+                    ))
+                    .with_notes(vec![report])]
+            }
+            Error::InvalidManifest { path, message } => vec![Diagnostic::error()
+                .with_message(format!("invalid package manifest `{}`", path.display()))
+                .with_notes(vec![
+                    message,
+                    "Package manifests should satisfy the `std.package.Manifest` contract.".into(),
+                ])],
+            Error::ManifestNotFound { path } => vec![Diagnostic::error().with_message(format!(
+                "couldn't find a package manifest `{MANIFEST_FILE}` in `{}` or any of its \
+                    parent directories",
+                path.display()
+            ))],
+            Error::InvalidLockFile { path, error } => vec![Diagnostic::error()
+                .with_message(format!("invalid lock file `{}`: {error}", path.display()))
+                .with_notes(vec![
+                    "Try to delete the lock file and to regenerate it with `nickel package lock`."
+                        .into(),
+                ])],
+            Error::Git { url, message } => vec![Diagnostic::error()
+                .with_message(format!("git operation on `{url}` failed"))
+                .with_notes(vec![message])],
+            Error::InvalidGitUrl { url } => vec![Diagnostic::error()
+                .with_message(format!("invalid git url `{url}`"))
+                .with_notes(vec!["Git urls can't start with `-`.".into()])],
+            Error::InvalidRev { url, rev } => vec![Diagnostic::error()
+                .with_message(format!("invalid locked revision `{rev}` for `{url}`"))
+                .with_notes(vec![format!(
+                    "Git dependencies must be locked to a full commit hash. Run `nickel package \
+                    lock --update` to regenerate `{LOCK_FILE}`."
+                )])],
+            Error::NotFetched { name, path } => vec![Diagnostic::error()
+                .with_message(format!(
+                    "package `{name}` is missing from the package cache (expected at `{}`)",
+                    path.display()
+                ))
+                .with_notes(vec![
+                    "Run `nickel package fetch` to download the locked dependencies.".into(),
+                ])],
+            Error::PathOutsideRepository { url, path } => vec![Diagnostic::error()
+                .with_message(format!(
+                    "path dependency `{}` of the git package `{url}` points outside of its \
+                    repository",
+                    path.display()
+                ))
+                .with_notes(vec![
+                    "Packages fetched from git can only have path dependencies within the same \
+                    repository."
+                        .into(),
+                ])],
+        }
+    }
+}
@@ -5,6 +5,7 @@
 //! indicate that they do not store human friendly data like lines and columns.
 use crate::files::FileId;
 use codespan::{self, ByteIndex};
+use serde::{Deserialize, Serialize};
 use std::cmp::{max, min, Ordering};
 
 /// A position identified by a byte offset in a file.
@@ -23,8 +24,9 @@ impl RawPos {
 /// A position span identified by a starting byte offset and an ending byte offset in a file.
 ///
 /// `end` is the offset of the last character plus one.
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct RawSpan {
+    #[serde(with = "crate::disk_cache::repr::file_id")]
     pub src_id: FileId,
     pub start: ByteIndex,
     pub end: ByteIndex,
@@ -88,7 +90,7 @@ impl From<RawSpan> for codespan::Span {
 }
 
 /// The position span of a term.
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default, Serialize, Deserialize)]
 pub enum TermPos {
     /// The term exactly corresponds to an original expression in the source, or is a construct
     /// introduced by program transformation that corresponds to an original span in the source.
@@ -145,7 +145,7 @@ fn needs_parens_in_type_pos(typ: &Type) -> bool {
                 | Term::Let(..)
                 | Term::LetPattern(..)
                 | Term::Op1(UnaryOp::IfThenElse, _)
-                | Term::Import { .. }
+                | Term::Import(_)
                 | Term::ResolvedImport(..)
         )
     } else {
@@ -1110,7 +1110,7 @@ impl<'a> Pretty<'a, Allocator> for &Term {
             SealingKey(sym) => allocator.text(format!("%<sealing key: {sym}>")),
             Sealed(_i, _rt, _lbl) => allocator.text("%<sealed>"),
             Annotated(annot, rt) => allocator.atom(rt).append(annot.pretty(allocator)),
-            Import { path, format } => {
+            Import(crate::term::Import::Path { path, format }) => {
                 docs![
                     allocator,
                     "import",
@@ -1132,6 +1132,9 @@ impl<'a> Pretty<'a, Allocator> for &Term {
                     },
                 ]
             }
+            Import(crate::term::Import::Package { id }) => {
+                allocator.text("import ").append(id.label().to_string())
+            }
             ResolvedImport(id) => allocator.text(format!("import <file_id: {id:?}>")),
             // This type is in term position, so we don't need to add parentheses.
             Type { typ, contract: _ } => typ.pretty(allocator),
@@ -23,20 +23,32 @@
 use crate::{
     cache::*,
     closurize::Closurize as _,
+    disk_cache::DiskCache,
     error::{
         report::{report, report_to_stdout, report_with, ColorOpt, ErrorFormat},
         Error, EvalError, IOError, IntoDiagnostics, ParseError,
     },
-    eval::{cache::Cache as EvalCache, Closure, VirtualMachine},
-    files::FileId,
+    eval::{
+        cache::Cache as EvalCache,
+        contract_trace::{self, ContractReport},
+        debugger::DebugHook,
+        limits::EvalLimits,
+        profiler::Profiler,
+        Closure, VirtualMachine,
+    },
+    files::{FileId, Files},
     identifier::LocIdent,
     label::Label,
     metrics::increment,
+    package::PackageMap,
+    serialize::value::Value,
     term::{
+        foreign::{self, ForeignBinding},
         make::{self as mk_term, builder},
         record::Field,
-        BinaryOp, MergePriority, RichTerm, Term,
+        BinaryOp, ForeignIdPayload, Import, MergePriority, NativeFunction, RichTerm, Term,
     },
+    typ::{Type, TypeF},
     typecheck::TypecheckMode,
 };
 
@@ -44,13 +56,20 @@ use clap::ColorChoice;
 use codespan_reporting::term::termcolor::{Ansi, NoColor, WriteColor};
 
 use std::{
+    any::Any,
     ffi::OsString,
     fmt,
     io::{self, Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     result::Result,
+    thread,
 };
 
+/// The stack size of the threads of [Program::eval_full_for_export_parallel]. The evaluation is
+/// recursive in places, so we use the usual size of the stack of the main thread rather than the
+/// smaller default of spawned threads.
+const EXPORT_THREAD_STACK_SIZE: usize = 8 * 1024 * 1024;
+
 /// A path of fields, that is a list, locating this field from the root of the configuration.
 #[derive(Clone, Default, PartialEq, Eq, Debug, Hash)]
 pub struct FieldPath(pub Vec<LocIdent>);
@@ -254,19 +273,19 @@ impl<EC: EvalCache> Program<EC> {
         let merge_term = inputs
             .into_iter()
             .map(|input| match input {
-                Input::Path(path) => RichTerm::from(Term::Import {
+                Input::Path(path) => RichTerm::from(Term::Import(Import::Path {
                     path: path.into(),
                     format: InputFormat::Nickel,
-                }),
+                })),
                 Input::Source(source, name) => {
                     let path = PathBuf::from(name.into());
                     cache
                         .add_source(SourcePath::Path(path.clone(), InputFormat::Nickel), source)
                         .unwrap();
-                    RichTerm::from(Term::Import {
+                    RichTerm::from(Term::Import(Import::Path {
                         path: path.into(),
                         format: InputFormat::Nickel,
-                    })
+                    }))
                 }
             })
             .reduce(|acc, f| mk_term::op2(BinaryOp::Merge(Label::default().into()), acc, f))
@@ -375,6 +394,146 @@ impl<EC: EvalCache> Program<EC> {
         self.vm.import_resolver_mut().add_import_paths(paths);
     }
 
+    /// Sets the package map used to resolve package imports, see [crate::package].
+    pub fn set_package_map(&mut self, map: PackageMap) {
+        self.vm.import_resolver_mut().set_package_map(map);
+    }
+
+    /// Sets the policy restricting the files that the program can import, see
+    /// [crate::cache::ImportPolicy].
+    pub fn set_import_policy(&mut self, policy: ImportPolicy) {
+        self.vm.import_resolver_mut().set_import_policy(policy);
+    }
+
+    /// Sets the on-disk cache used to store prepared terms across runs, see [crate::disk_cache].
+    pub fn set_disk_cache(&mut self, disk_cache: Option<DiskCache>) {
+        self.vm.import_resolver_mut().set_disk_cache(disk_cache);
+    }
+
+    /// Binds `name` to an opaque foreign value in the initial environment of this program, see
+    /// [crate::term::foreign]. The value has type `ForeignId` for the typechecker.
+    pub fn add_foreign_value(&mut self, name: &str, value: impl Any) {
+        self.add_foreign_binding(ForeignBinding {
+            id: LocIdent::from(name),
+            value: Term::ForeignId(ForeignIdPayload::new(value)).into(),
+            typ: Type::from(TypeF::ForeignId),
+        });
+    }
+
+    /// Binds `name` to a native function in the initial environment of this program, see
+    /// [crate::term::foreign]. The number of arguments of the function is the number of arrows
+    /// in `typ`, which can be parsed with [Self::parse_type].
+    pub fn add_native_function(
+        &mut self,
+        name: &str,
+        typ: Type,
+        implementation: impl Fn(&[RichTerm]) -> Result<RichTerm, String> + 'static,
+    ) {
+        let native = NativeFunction::new(name, foreign::arity(&typ), implementation);
+
+        self.add_foreign_binding(ForeignBinding {
+            id: LocIdent::from(name),
+            value: native.into_term(),
+            typ,
+        });
+    }
+
+    fn add_foreign_binding(&mut self, binding: ForeignBinding) {
+        self.vm.import_resolver_mut().add_foreign_binding(binding);
+    }
+
+    /// Parses a Nickel type, for example to declare the type of a native function. `ForeignId`
+    /// isn't part of the type syntax: types involving foreign values must be built directly.
+    pub fn parse_type(&mut self, input: String) -> Result<Type, ParseError> {
+        use crate::parser::{grammar::FixedTypeParser, lexer::Lexer, ErrorTolerantParser};
+
+        let cache = self.vm.import_resolver_mut();
+        let input_id = cache.replace_string(SourcePath::Generated("type".into()), input);
+        let s = cache.source(input_id);
+
+        FixedTypeParser::new()
+            .parse_strict(input_id, Lexer::new(s))
+            .map_err(|mut errs| {
+                errs.errors
+                    .pop()
+                    .expect("parsing of the type failed, so the error list must be non-empty")
+            })
+    }
+
+    /// Sets the resource limits of the evaluation of this program, see [crate::eval::limits].
+    pub fn set_eval_limits(&mut self, limits: EvalLimits) {
+        self.vm.set_limits(limits);
+    }
+
+    /// Enables the profiling of the evaluation of this program, see [crate::eval::profiler].
+    pub fn enable_profiling(&mut self) {
+        self.vm.enable_profiling();
+    }
+
+    /// Returns the profile recorded so far, if profiling is enabled. The positions of the profile
+    /// refer to [Self::files].
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.vm.profiler()
+    }
+
+    /// Attaches a debugger to the evaluation of this program, see [crate::eval::debugger].
+    pub fn set_debug_hook(&mut self, hook: impl DebugHook<Cache, EC> + 'static) {
+        self.vm.set_debug_hook(hook);
+    }
+
+    /// Enables the tracing of contract applications during the evaluation of this program, see
+    /// [crate::eval::contract_trace].
+    pub fn enable_contract_tracing(&mut self) {
+        self.vm.enable_contract_tracing();
+    }
+
+    /// Returns the coverage of the contract annotations of the Nickel sources loaded so far,
+    /// excluding the standard library, if contract tracing is enabled. The positions of the report
+    /// refer to [Self::files].
+    pub fn contract_report(&self) -> Option<ContractReport> {
+        let trace = self.vm.contract_trace()?;
+        let cache = self.vm.import_resolver();
+
+        // The terms stored in the cache have been transformed and closurized, which hides the
+        // annotations, so we parse the sources again.
+        let mut annotations: Vec<_> = cache
+            .terms()
+            .keys()
+            .filter(|file_id| {
+                !cache.files().is_stdlib(**file_id)
+                    && InputFormat::from_path(Path::new(cache.name(**file_id))).unwrap_or_default()
+                        == InputFormat::Nickel
+            })
+            .filter_map(|file_id| cache.parse_nocache(*file_id).ok())
+            .flat_map(|(term, _)| contract_trace::annotations(&term))
+            .collect();
+        annotations.sort_by_key(|annotation| (annotation.span.src_id, annotation.span.start));
+
+        Some(trace.report(annotations))
+    }
+
+    /// Reload the source files of the program that have been modified on disk since they were
+    /// loaded, that is the main file and the files it transitively imports, so that the next
+    /// evaluation takes the new content into account. Only the modified files and the files that
+    /// depend on them will be parsed and typechecked again.
+    ///
+    /// Files that can't be read, for example because they're being written by an editor at the
+    /// same time, are left as is. Returns the ids of the reloaded files.
+    pub fn reload_modified_sources(&mut self) -> Vec<FileId> {
+        let cache = self.vm.import_resolver_mut();
+        let mut sources: Vec<_> = cache
+            .get_imports_transitive(self.main_id)
+            .into_iter()
+            .collect();
+        sources.push(self.main_id);
+        // Reloading a file invalidates its importers, which forgets about their imports. We thus
+        // need to compute all the sources before reloading any of them.
+        sources
+            .into_iter()
+            .filter(|file_id| matches!(cache.reload_if_modified(*file_id), Ok(true)))
+            .collect()
+    }
+
     /// Only parse the program, don't typecheck or evaluate. returns the [`RichTerm`] AST
     pub fn parse(&mut self) -> Result<RichTerm, Error> {
         self.vm
@@ -469,6 +628,7 @@ impl<EC: EvalCache> Program<EC> {
 
     /// Parse if necessary, typecheck and then evaluate the program.
     pub fn eval(&mut self) -> Result<RichTerm, Error> {
+        self.vm.reset_budget();
         let prepared = self.prepare_eval()?;
 
         self.vm.reset();
@@ -480,12 +640,14 @@ impl<EC: EvalCache> Program<EC> {
     /// evaluation, with imports resolved and any necessary transformations
     /// applied.
     pub fn eval_closure(&mut self, closure: Closure) -> Result<RichTerm, EvalError> {
+        self.vm.reset_budget();
         self.vm.reset();
         Ok(self.vm.eval_closure(closure)?.body)
     }
 
     /// Same as `eval`, but proceeds to a full evaluation.
     pub fn eval_full(&mut self) -> Result<RichTerm, Error> {
+        self.vm.reset_budget();
         let prepared = self.prepare_eval()?;
 
         self.vm.reset();
@@ -506,14 +668,221 @@ impl<EC: EvalCache> Program<EC> {
     ///   an import referring to the corresponding isolated value. This stub is finally merged with
     ///   the current program before being evaluated for import.
     pub fn eval_full_for_export(&mut self) -> Result<RichTerm, Error> {
+        self.vm.reset_budget();
         let prepared = self.prepare_eval()?;
 
         self.vm.reset();
         Ok(self.vm.eval_full_for_export_closure(prepared)?)
     }
 
+    /// Same as [Self::eval_full_for_export], but evaluates the fields of the program on `jobs`
+    /// threads, each one evaluating a disjoint set of fields.
+    ///
+    /// The program is split into the paths of the values to evaluate: its top-level fields, and
+    /// the fields of the nested records written as record literals without contracts, until there
+    /// are a few paths per thread. Values which are the result of a computation, such as a merge
+    /// or a function call, aren't split: a single large field is evaluated by a single thread.
+    ///
+    /// Terms can't be shared between threads, so each thread evaluates its fields with its own
+    /// program, created by `make_program`, which must return a program identical to this one
+    /// (same sources, import paths, overrides, field, etc.). The sources are thus parsed and
+    /// typechecked once per thread, and the values shared by several fields are evaluated once per
+    /// thread as well: this is only worth it for programs whose fields are expensive to evaluate
+    /// and mostly independent. The evaluation limits apply to each thread separately.
+    ///
+    /// The result is the same as with [Self::eval_full_for_export], up to positions. The program
+    /// is evaluated sequentially instead if it can't be split in at least two paths. The paths
+    /// which a thread failed to evaluate are evaluated again by this program, so that errors are
+    /// reported with its sources.
+    pub fn eval_full_for_export_parallel<F>(
+        &mut self,
+        jobs: usize,
+        make_program: F,
+    ) -> Result<RichTerm, Error>
+    where
+        F: Fn() -> Option<Program<EC>> + Sync,
+    {
+        /// The number of paths per thread, so that a thread which is done with its paths doesn't
+        /// have to wait for the others for too long.
+        const PATHS_PER_JOB: usize = 4;
+
+        if jobs <= 1 {
+            return self.eval_full_for_export();
+        }
+
+        self.vm.reset_budget();
+
+        let paths = match self.export_paths(jobs * PATHS_PER_JOB)? {
+            Some(paths) if paths.len() > 1 => paths,
+            _ => return self.eval_full_for_export(),
+        };
+
+        // Paths are distributed in a round-robin fashion, as neighbouring fields tend to be
+        // similar, and so to take a similar time to evaluate.
+        let jobs = jobs.min(paths.len());
+        let paths = &paths;
+
+        let results: Vec<Vec<(usize, Value)>> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..jobs)
+                .map(|job| {
+                    let make_program = &make_program;
+
+                    thread::Builder::new()
+                        .stack_size(EXPORT_THREAD_STACK_SIZE)
+                        .spawn_scoped(scope, move || {
+                            let mut values = Vec::new();
+                            let Some(mut program) = make_program() else {
+                                return values;
+                            };
+
+                            // A thread stops at its first failure: the remaining paths are
+                            // evaluated by the calling program, which reports the error.
+                            for index in (job..paths.len()).step_by(jobs) {
+                                let value = program
+                                    .eval_full_for_export_path(&paths[index])
+                                    .ok()
+                                    .and_then(|rt| Value::from_term(&rt).ok());
+
+                                match value {
+                                    Some(value) => values.push((index, value)),
+                                    None => break,
+                                }
+                            }
+
+                            values
+                        })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .filter_map(|handle| handle.ok()?.join().ok())
+                .collect()
+        });
+
+        let mut values: Vec<Option<Value>> = vec![None; paths.len()];
+
+        for (index, value) in results.into_iter().flatten() {
+            values[index] = Some(value);
+        }
+
+        let values = paths
+            .iter()
+            .zip(values)
+            .map(|(path, value)| match value {
+                Some(value) => Ok((path.as_slice(), value)),
+                None => {
+                    let rt = self.eval_full_for_export_path(path)?;
+                    Ok((path.as_slice(), Value::from_term(&rt)?))
+                }
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(assemble(values, 0).into())
+    }
+
+    /// Evaluates the program to a record, and splits it into the paths of the values to evaluate
+    /// by [Self::eval_full_for_export_parallel]: its exported fields, and then the fields of the
+    /// nested records level by level, until there are at least `count` paths or no record can be
+    /// split further. The paths are in the order of the fields, so that the paths of the fields of
+    /// a record are contiguous. Returns `None` if the program isn't a record.
+    fn export_paths(&mut self, count: usize) -> Result<Option<Vec<Vec<LocIdent>>>, Error> {
+        let prepared = self.prepare_eval()?;
+
+        self.vm.reset();
+        let root = self.vm.eval_closure(prepared)?;
+
+        let Some(mut paths) = self.split_record(&[], root) else {
+            return Ok(None);
+        };
+
+        while paths.len() < count {
+            let mut split_any = false;
+            let mut next = Vec::with_capacity(paths.len());
+
+            for (path, value) in paths {
+                match value.and_then(|value| self.split_record(&path, value)) {
+                    Some(fields) if !fields.is_empty() => {
+                        split_any = true;
+                        next.extend(fields);
+                    }
+                    _ => next.push((path, None)),
+                }
+            }
+
+            paths = next;
+
+            if !split_any {
+                break;
+            }
+        }
+
+        Ok(Some(paths.into_iter().map(|(path, _)| path).collect()))
+    }
+
+    /// Evaluates `value`, located at `path`, to a weak head normal form, and returns the paths of
+    /// its exported fields if it's a record, or `None` otherwise. Each path comes with the value
+    /// of the field if it's a record literal without contracts, and can thus be split further
+    /// without changing the result of the evaluation.
+    #[allow(clippy::type_complexity)]
+    fn split_record(
+        &mut self,
+        path: &[LocIdent],
+        value: Closure,
+    ) -> Option<Vec<(Vec<LocIdent>, Option<Closure>)>> {
+        let Closure { body, env } = self.vm.eval_closure(value).ok()?;
+
+        let Term::Record(record) = body.as_ref() else {
+            return None;
+        };
+
+        let fields = record
+            .fields
+            .iter()
+            .filter(|(_, field)| {
+                !field.metadata.not_exported && (field.value.is_some() || !field.metadata.opt)
+            })
+            .map(|(id, field)| {
+                let is_literal = |value: &RichTerm| match value.as_ref() {
+                    Term::Closure(idx) => matches!(
+                        self.vm.cache.get(idx.clone()).body.as_ref(),
+                        Term::Record(_) | Term::RecRecord(..)
+                    ),
+                    Term::Record(_) | Term::RecRecord(..) => true,
+                    _ => false,
+                };
+
+                let value = field
+                    .value
+                    .as_ref()
+                    .filter(|value| field.pending_contracts.is_empty() && is_literal(value))
+                    .map(|value| Closure {
+                        body: value.clone(),
+                        env: env.clone(),
+                    });
+
+                let mut field_path = path.to_vec();
+                field_path.push(*id);
+                (field_path, value)
+            })
+            .collect();
+
+        Some(fields)
+    }
+
+    /// Same as [Self::eval_full_for_export], but only evaluates the value at the given path,
+    /// relative to `self.field`.
+    fn eval_full_for_export_path(&mut self, path: &[LocIdent]) -> Result<RichTerm, Error> {
+        let len = self.field.0.len();
+        self.field.0.extend_from_slice(path);
+        let result = self.eval_full_for_export();
+        self.field.0.truncate(len);
+        result
+    }
+
     /// Same as `eval_full`, but does not substitute all variables.
     pub fn eval_deep(&mut self) -> Result<RichTerm, Error> {
+        self.vm.reset_budget();
         let prepared = self.prepare_eval()?;
 
         self.vm.reset();
@@ -523,6 +892,7 @@ impl<EC: EvalCache> Program<EC> {
     /// Prepare for evaluation, then fetch the metadata of `self.field`, or list the fields of the
     /// whole program if `self.field` is empty.
     pub fn query(&mut self) -> Result<Field, Error> {
+        self.vm.reset_budget();
         let prepared = self.prepare_query()?;
 
         Ok(self.vm.query_closure(prepared, &self.field)?)
@@ -530,6 +900,23 @@ impl<EC: EvalCache> Program<EC> {
 
     /// Load, parse, and typecheck the program and the standard library, if not already done.
     pub fn typecheck(&mut self, initial_mode: TypecheckMode) -> Result<(), Error> {
+        // The entries of the disk cache have only been typechecked in walk mode, so they can't be
+        // used to check the program in enforce mode.
+        let disk_cache = match initial_mode {
+            TypecheckMode::Enforce => self.vm.import_resolver_mut().take_disk_cache(),
+            TypecheckMode::Walk => None,
+        };
+
+        let result = self.typecheck_(initial_mode);
+
+        if disk_cache.is_some() {
+            self.set_disk_cache(disk_cache);
+        }
+
+        result
+    }
+
+    fn typecheck_(&mut self, initial_mode: TypecheckMode) -> Result<(), Error> {
         self.vm
             .import_resolver_mut()
             .parse(self.main_id, InputFormat::Nickel)?;
@@ -553,6 +940,12 @@ impl<EC: EvalCache> Program<EC> {
         Ok(())
     }
 
+    /// Returns the files loaded by this program, which the positions of terms and diagnostics
+    /// refer to.
+    pub fn files(&self) -> &Files {
+        self.vm.import_resolver().files()
+    }
+
     /// Wrapper for [`report`].
     pub fn report<E>(&mut self, error: E, format: ErrorFormat)
     where
@@ -638,7 +1031,7 @@ impl<EC: EvalCache> Program<EC> {
     /// [crate::error::EvalError::MissingFieldDef] errors are _ignored_: if this is encountered
     /// when evaluating a field, this field is just left as it is and the evaluation proceeds.
     pub fn eval_record_spine(&mut self) -> Result<RichTerm, Error> {
-        self.maybe_closurized_eval_record_spine(false)
+        self.eval_record_spine_impl(false, false)
     }
 
     /// Evaluate a program into a record spine, while closurizing all the
@@ -661,18 +1054,63 @@ impl<EC: EvalCache> Program<EC> {
     /// further evaluate any record fields, while the non-closurized version is
     /// more useful if you intend to do further static analysis.
     pub fn eval_closurized_record_spine(&mut self) -> Result<RichTerm, Error> {
-        self.maybe_closurized_eval_record_spine(true)
+        self.eval_record_spine_impl(true, false)
+    }
+
+    /// Evaluate a program into a record spine, as [`Program::eval_record_spine`], and additionally
+    /// evaluate the user-defined contracts appearing in the type and contract annotations of
+    /// fields. Contracts which evaluate to a record are replaced by their record spine in the
+    /// annotations, while other contracts are left untouched.
+    ///
+    /// Annotations usually refer to record contracts by name, as in `server | Server`. This
+    /// function makes it possible to inspect the fields of such contracts, for example to
+    /// generate a JSON schema.
+    pub fn eval_record_spine_with_contracts(&mut self) -> Result<RichTerm, Error> {
+        self.eval_record_spine_impl(false, true)
     }
 
-    fn maybe_closurized_eval_record_spine(&mut self, closurize: bool) -> Result<RichTerm, Error> {
+    fn eval_record_spine_impl(
+        &mut self,
+        closurize: bool,
+        resolve_contracts: bool,
+    ) -> Result<RichTerm, Error> {
         use crate::{
             eval::Environment,
-            match_sharedterm,
-            term::{record::RecordData, RuntimeContract},
+            json_schema, match_sharedterm,
+            term::{
+                record::{FieldMetadata, RecordData},
+                RuntimeContract, SharedTerm, Traverse, TraverseOrder,
+            },
+            typ::{Type, TypeF},
         };
+        use std::convert::Infallible;
 
+        self.vm.reset_budget();
         let prepared = self.prepare_eval()?;
 
+        // The values of the contracts of the standard library which are recognized by the JSON
+        // schema generation, together with their path. Evaluated contracts are compared to them,
+        // so that aliases of these contracts are resolved to their path as well.
+        let std_contracts: Vec<(RichTerm, RichTerm)> = if resolve_contracts {
+            json_schema::STD_CONTRACTS
+                .iter()
+                .filter_map(|path| {
+                    let term = json_schema::std_contract_term(path);
+                    let value = self
+                        .vm
+                        .eval_closure(Closure {
+                            body: term.clone(),
+                            env: self.vm.initial_env().clone(),
+                        })
+                        .ok()?;
+                    Some((term, value.body))
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let resolve_contracts = resolve_contracts.then_some(std_contracts.as_slice());
+
         // Naively evaluating some legit recursive structures might lead to an infinite loop. Take
         // for example this simple contract definition:
         //
@@ -700,6 +1138,7 @@ impl<EC: EvalCache> Program<EC> {
             mut pending_contracts: Vec<RuntimeContract>,
             current_env: Environment,
             closurize: bool,
+            resolve_contracts: Option<&[(RichTerm, RichTerm)]>,
         ) -> Result<Vec<RuntimeContract>, Error> {
             vm.reset();
 
@@ -708,12 +1147,80 @@ impl<EC: EvalCache> Program<EC> {
                 // Note that contracts can't be referred to recursively, as they aren't binding
                 // anything. Only fields are. This is why we pass `None` for `self_idx`: there is
                 // no locking required here.
-                ctr.contract = eval_guarded(vm, rt, current_env.clone(), closurize)?;
+                ctr.contract =
+                    eval_guarded(vm, rt, current_env.clone(), closurize, resolve_contracts)?;
             }
 
             Ok(pending_contracts)
         }
 
+        // Evaluate the user-defined contracts of the annotations of a field. Contrary to pending
+        // contracts, annotations aren't closurized, but the pending contracts are generated from
+        // the annotations and thus share their environment, which we fetch from the first
+        // closurized one.
+        //
+        // Errors are ignored: the corresponding contracts are just left unevaluated. Contracts which
+        // evaluate to one of `std_contracts` are replaced with their path.
+        fn resolve_annotation_contracts<EC: EvalCache>(
+            vm: &mut VirtualMachine<Cache, EC>,
+            mut metadata: FieldMetadata,
+            pending_contracts: &[RuntimeContract],
+            std_contracts: &[(RichTerm, RichTerm)],
+        ) -> FieldMetadata {
+            let env = pending_contracts
+                .iter()
+                .find_map(|ctr| match ctr.contract.as_ref() {
+                    Term::Closure(idx) => Some(vm.cache.get(idx.clone()).env),
+                    _ => None,
+                })
+                .unwrap_or_default();
+
+            let mut resolve = |ty: Type| -> Result<Type, Infallible> {
+                let TypeF::Contract(ctr) = ty.typ else {
+                    return Ok(ty);
+                };
+
+                // We go through the thunk of contracts referred to by name, so that
+                // `eval_guarded` can lock it and stop on recursive contracts.
+                let to_eval = match ctr.as_ref() {
+                    Term::Var(id) => env
+                        .get(&id.ident())
+                        .map(|idx| RichTerm::new(Term::Closure(idx.clone()), ctr.pos))
+                        .unwrap_or_else(|| ctr.clone()),
+                    _ => ctr.clone(),
+                };
+
+                let resolved =
+                    match eval_guarded(vm, to_eval, env.clone(), false, Some(std_contracts)) {
+                        Ok(rt) if matches!(rt.as_ref(), Term::Record(_)) => rt,
+                        Ok(rt) => std_contracts
+                            .iter()
+                            .find(|(_, value)| SharedTerm::ptr_eq(&value.term, &rt.term))
+                            .map(|(path, _)| path.clone().with_pos(ctr.pos))
+                            .unwrap_or(ctr),
+                        _ => ctr,
+                    };
+
+                Ok(Type {
+                    typ: TypeF::Contract(resolved),
+                    pos: ty.pos,
+                })
+            };
+
+            for labeled_ty in metadata
+                .annotation
+                .typ
+                .iter_mut()
+                .chain(metadata.annotation.contracts.iter_mut())
+            {
+                let typ = std::mem::replace(&mut labeled_ty.typ, Type::from(TypeF::Dyn));
+                let Ok(typ) = typ.traverse(&mut resolve, TraverseOrder::TopDown);
+                labeled_ty.typ = typ;
+            }
+
+            metadata
+        }
+
         // Handles thunk locking (and unlocking upon errors) to detect infinite recursion, but
         // hands over the meat of the work to `do_eval`.
         fn eval_guarded<EC: EvalCache>(
@@ -721,6 +1228,7 @@ impl<EC: EvalCache> Program<EC> {
             term: RichTerm,
             env: Environment,
             closurize: bool,
+            resolve_contracts: Option<&[(RichTerm, RichTerm)]>,
         ) -> Result<RichTerm, Error> {
             vm.reset();
 
@@ -734,7 +1242,7 @@ impl<EC: EvalCache> Program<EC> {
                 }
             }
 
-            let result = do_eval(vm, term.clone(), env, closurize);
+            let result = do_eval(vm, term.clone(), env, closurize, resolve_contracts);
 
             // Once we're done evaluating all the children, or if there was an error, we unlock the
             // current thunk
@@ -767,6 +1275,7 @@ impl<EC: EvalCache> Program<EC> {
             term: RichTerm,
             env: Environment,
             closurize: bool,
+            resolve_contracts: Option<&[(RichTerm, RichTerm)]>,
         ) -> Result<RichTerm, Error> {
             let evaled = vm.eval_closure(Closure { body: term, env })?;
 
@@ -776,13 +1285,30 @@ impl<EC: EvalCache> Program<EC> {
                         .fields
                         .into_iter()
                         .map(|(id, field)| -> Result<_, Error> {
+                            let metadata = if let Some(std_contracts) = resolve_contracts {
+                                resolve_annotation_contracts(
+                                    vm,
+                                    field.metadata,
+                                    &field.pending_contracts,
+                                    std_contracts,
+                                )
+                            } else {
+                                field.metadata
+                            };
+
                             Ok((
                                 id,
                                 Field {
                                     value: field
                                         .value
                                         .map(|value| {
-                                            eval_guarded(vm, value, evaled.env.clone(), closurize)
+                                            eval_guarded(
+                                                vm,
+                                                value,
+                                                evaled.env.clone(),
+                                                closurize,
+                                                resolve_contracts,
+                                            )
                                         })
                                         .transpose()?,
                                     pending_contracts: eval_contracts(
@@ -790,8 +1316,9 @@ impl<EC: EvalCache> Program<EC> {
                                         field.pending_contracts,
                                         evaled.env.clone(),
                                         closurize,
+                                        resolve_contracts,
                                     )?,
-                                    ..field
+                                    metadata,
                                 },
                             ))
                         })
@@ -811,7 +1338,13 @@ impl<EC: EvalCache> Program<EC> {
             })
         }
 
-        eval_guarded(&mut self.vm, prepared.body, prepared.env, closurize)
+        eval_guarded(
+            &mut self.vm,
+            prepared.body,
+            prepared.env,
+            closurize,
+            resolve_contracts,
+        )
     }
 
     /// Extract documentation from the program
@@ -825,6 +1358,13 @@ impl<EC: EvalCache> Program<EC> {
         ))
     }
 
+    /// Generate a JSON schema from the record contract defined by the program. See
+    /// [crate::json_schema].
+    pub fn json_schema(&mut self) -> Result<crate::json_schema::JsonSchema, Error> {
+        let term = self.eval_record_spine_with_contracts()?;
+        Ok(crate::json_schema::from_record_contract(&term)?)
+    }
+
     #[cfg(debug_assertions)]
     pub fn set_skip_stdlib(&mut self) {
         self.vm.import_resolver_mut().skip_stdlib = true;
@@ -1143,6 +1683,32 @@ mod doc {
     }
 }
 
+/// Rebuilds a record from the values of the paths returned by [Program::export_paths], which
+/// share their first `depth` fields.
+fn assemble(values: Vec<(&[LocIdent], Value)>, depth: usize) -> Value {
+    let mut fields = Vec::new();
+    let mut values = values.into_iter().peekable();
+
+    while let Some((path, value)) = values.next() {
+        let id = path[depth].ident();
+
+        if path.len() == depth + 1 {
+            fields.push((id, value));
+            continue;
+        }
+
+        let mut nested = vec![(path, value)];
+
+        while let Some(next) = values.next_if(|(path, _)| path[depth].ident() == id) {
+            nested.push(next);
+        }
+
+        fields.push((id, assemble(nested, depth + 1)));
+    }
+
+    Value::Record(fields)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1213,6 +1779,331 @@ mod tests {
         eval_full("{y = fun x => x, x = fun y => y}").unwrap();
     }
 
+    #[test]
+    fn eval_limits() {
+        let mut p: Program<CacheImpl> = Program::new_from_source(
+            Cursor::new("let rec f = fun x => if x < 0 then 0 else f (x + 1) in f 0"),
+            "<test>",
+            std::io::sink(),
+        )
+        .unwrap();
+
+        p.set_eval_limits(EvalLimits {
+            max_steps: Some(10_000),
+            ..Default::default()
+        });
+        // Only the innermost part of the call stack of the runaway recursion is kept.
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::EvalError(EvalError::StepLimitExceeded {
+                limit: 10_000,
+                call_stack,
+                elided_frames,
+                ..
+            })) if call_stack.len() == 64 && elided_frames > 0
+        );
+
+        let mut p: Program<CacheImpl> = Program::new_from_source(
+            Cursor::new(
+                "let rec sum = fun n => if n == 0 then 0 else n + sum (n - 1) in sum 100000",
+            ),
+            "<test>",
+            std::io::sink(),
+        )
+        .unwrap();
+
+        p.set_eval_limits(EvalLimits {
+            max_stack_depth: Some(1_000),
+            ..Default::default()
+        });
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::EvalError(EvalError::StackLimitExceeded {
+                limit: 1_000,
+                ..
+            }))
+        );
+    }
+
+    #[test]
+    fn eval_limits_span_record_spine() {
+        let record_spine = |fields: usize| {
+            let fields: Vec<_> = (0..fields).map(|i| format!("f{i} = count 200")).collect();
+            let mut p: Program<CacheImpl> = Program::new_from_source(
+                Cursor::new(format!(
+                    "let rec count = fun n => if n == 0 then 0 else count (n - 1) in {{ {} }}",
+                    fields.join(", ")
+                )),
+                "<test>",
+                std::io::sink(),
+            )
+            .unwrap();
+
+            p.set_eval_limits(EvalLimits {
+                max_steps: Some(10_000),
+                ..Default::default()
+            });
+            p.eval_record_spine()
+        };
+
+        // Each field fits in the budget, but not all of them together: the budget must not be
+        // reset between the fields.
+        assert_matches!(record_spine(1), Ok(_));
+        assert_matches!(
+            record_spine(50),
+            Err(Error::EvalError(EvalError::StepLimitExceeded {
+                limit: 10_000,
+                ..
+            }))
+        );
+    }
+
+    #[test]
+    fn native_functions() {
+        let program = |src: &str| {
+            let mut p: Program<CacheImpl> =
+                Program::new_from_source(Cursor::new(src.to_owned()), "<test>", std::io::sink())
+                    .unwrap();
+
+            p.add_foreign_value("handle", vec![1, 2, 3]);
+
+            let typ = p.parse_type("Number -> Number -> Number".into()).unwrap();
+            p.add_native_function("add", typ, |args| {
+                match (args[0].as_ref(), args[1].as_ref()) {
+                    (Term::Num(n1), Term::Num(n2)) => Ok(Term::Num(n1 + n2).into()),
+                    _ => Err("expected two numbers".into()),
+                }
+            });
+
+            let typ = Type::from(TypeF::Arrow(
+                Box::new(Type::from(TypeF::ForeignId)),
+                Box::new(Type::from(TypeF::Number)),
+            ));
+            p.add_native_function("count", typ, |args| match args[0].as_ref() {
+                Term::ForeignId(id) => id
+                    .downcast_ref::<Vec<i32>>()
+                    .map(|v| Term::Num(v.len().into()).into())
+                    .ok_or_else(|| "unexpected foreign value".into()),
+                _ => Err("expected a handle".into()),
+            });
+
+            p
+        };
+
+        let mut p = program(
+            "let x : Number = add 1 (count handle) in \
+             x + std.array.fold_left (+) 0 (std.array.map (add 10) [1, 2])",
+        );
+        p.typecheck(TypecheckMode::Walk).unwrap();
+        assert_matches!(
+            p.eval_full().unwrap().as_ref(),
+            Term::Num(n) if *n == 27
+        );
+
+        let mut p = program("let x : Number = count 5 in x");
+        assert_matches!(
+            p.typecheck(TypecheckMode::Walk),
+            Err(Error::TypecheckError(_))
+        );
+
+        let mut p = program("{ f = count }.f 5");
+        assert_matches!(
+            p.eval_full(),
+            Err(Error::EvalError(EvalError::NativeFunctionError { name, message, .. }))
+                if name == "count" && message == "expected a handle"
+        );
+    }
+
+    #[test]
+    fn contract_report() {
+        use crate::eval::contract_trace::CoverageStatus;
+
+        let report = |src: &str| {
+            let mut p: Program<CacheImpl> =
+                Program::new_from_source(Cursor::new(src.to_owned()), "<test>", std::io::sink())
+                    .unwrap();
+            p.enable_contract_tracing();
+            let result = p.eval_full_for_export();
+            let statuses: Vec<_> = p
+                .contract_report()
+                .unwrap()
+                .entries
+                .into_iter()
+                .map(|entry| {
+                    (
+                        entry.annotation.field.unwrap().label().to_owned(),
+                        entry.status,
+                    )
+                })
+                .collect();
+
+            (result, statuses)
+        };
+
+        let (result, statuses) =
+            report("{ a | Number = 1, b | not_exported | String = 1, c | Array Number = [1, 2] }");
+        assert!(result.is_ok());
+        assert_eq!(
+            statuses,
+            vec![
+                ("a".to_owned(), CoverageStatus::Passed),
+                ("b".to_owned(), CoverageStatus::Unchecked),
+                ("c".to_owned(), CoverageStatus::Passed),
+            ]
+        );
+
+        let (result, statuses) =
+            report("{ b | not_exported | Number = 1, c | Array Number = [1, \"2\"] }");
+        assert_matches!(result, Err(Error::EvalError(EvalError::BlameError { .. })));
+        assert_eq!(
+            statuses,
+            vec![
+                ("b".to_owned(), CoverageStatus::Unchecked),
+                ("c".to_owned(), CoverageStatus::Failed),
+            ]
+        );
+    }
+
+    #[test]
+    fn eval_full_for_export_parallel() {
+        use crate::serialize::{self, ExportFormat};
+
+        let export = |src: &str, jobs: usize| {
+            let make_program = || {
+                Program::<CacheImpl>::new_from_source(
+                    Cursor::new(src.to_owned()),
+                    "<test>",
+                    std::io::sink(),
+                )
+                .ok()
+            };
+
+            make_program()
+                .unwrap()
+                .eval_full_for_export_parallel(jobs, make_program)
+                .map(|rt| serialize::to_string(ExportFormat::Json, &rt).unwrap())
+        };
+
+        let src = "let shared = std.array.map (fun x => x * 2) [1, 2, 3] in {
+            a = shared,
+            b = { c = 'Foo, d = null, e | optional },
+            f | not_exported = 1,
+            g = std.string.join \",\" [\"x\", \"y\"],
+            h = 1 / 3,
+        }";
+
+        let expected = export(src, 1).unwrap();
+        assert_eq!(export(src, 2).unwrap(), expected);
+        assert_eq!(export(src, 8).unwrap(), expected);
+
+        // Nested records are split across threads, except when they have contracts, which could
+        // add fields, or when they are computed.
+        let nested = "{
+            a = { b = { c = 1, d | not_exported = 2 }, e = {}, f | optional },
+            g | { h | default = 3 } = {},
+            i = { j = 4 } & { k = 5 },
+            l = [{ m = 6 }],
+        }";
+
+        let paths = |src: &str, count: usize| {
+            let mut program =
+                Program::<CacheImpl>::new_from_source(Cursor::new(src), "<test>", std::io::sink())
+                    .unwrap();
+            program
+                .export_paths(count)
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .map(|path| {
+                    path.iter()
+                        .map(|id| id.label())
+                        .collect::<Vec<_>>()
+                        .join(".")
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(paths(nested, 2), ["a", "g", "i", "l"]);
+        assert_eq!(paths(nested, 5), ["a.b", "a.e", "g", "i", "l"]);
+        assert_eq!(paths(nested, 100), ["a.b.c", "a.e", "g", "i", "l"]);
+
+        let expected = export(nested, 1).unwrap();
+        assert_eq!(export(nested, 2).unwrap(), expected);
+        assert_eq!(export(nested, 16).unwrap(), expected);
+
+        // Not a record: evaluated sequentially.
+        assert_eq!(export("[1, 2]", 4).unwrap(), export("[1, 2]", 1).unwrap());
+
+        // Errors are reported as without parallelism.
+        assert_matches!(
+            export("{ a = 1, b | Number = \"b\", c = 3 }", 2),
+            Err(Error::EvalError(EvalError::BlameError { .. }))
+        );
+        assert_matches!(
+            export("{ a = { b = 1, c | Number = \"c\" }, d = 2 }", 8),
+            Err(Error::EvalError(EvalError::BlameError { .. }))
+        );
+        assert_matches!(
+            export("{ a = { b = 1, c | String }, d = 2 }", 8),
+            Err(Error::EvalError(EvalError::MissingFieldDef { .. }))
+        );
+    }
+
+    #[test]
+    fn reload_modified_sources() {
+        use std::{
+            fs,
+            time::{Duration, SystemTime},
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name);
+        // Sets the modification time explicitly, so that successive writes are seen as
+        // modifications regardless of the precision of the filesystem timestamps.
+        let write_at = |name: &str, contents: &str, secs: u64| {
+            fs::write(path(name), contents).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(path(name))
+                .unwrap()
+                .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+                .unwrap();
+        };
+        write_at("main.ncl", "(import \"a.ncl\") + (import \"b.ncl\")", 1);
+        write_at("a.ncl", "import \"c.ncl\"", 1);
+        write_at("b.ncl", "1", 1);
+        write_at("c.ncl", "1", 1);
+
+        let mut p: Program<CacheImpl> =
+            Program::new_from_file(path("main.ncl"), std::io::sink()).unwrap();
+        assert_eq!(p.eval_full().unwrap().term.as_ref(), &Term::Num(2.into()));
+        assert_eq!(p.reload_modified_sources(), Vec::new());
+
+        let cache = p.vm.import_resolver();
+        let [a, b, c] = ["a.ncl", "b.ncl", "c.ncl"].map(|name| {
+            cache
+                .id_of(&SourcePath::Path(path(name), InputFormat::Nickel))
+                .unwrap()
+        });
+
+        write_at("c.ncl", "2", 2);
+        assert_eq!(p.reload_modified_sources(), vec![c]);
+
+        // Only the modified file and the files that depend on it have to be prepared again.
+        let cache = p.vm.import_resolver();
+        for file_id in [p.main_id, a, c] {
+            assert_eq!(cache.entry_state(file_id), None);
+        }
+        assert_matches!(cache.entry_state(b), Some(EntryState::Closurized));
+
+        assert_eq!(p.eval_full().unwrap().term.as_ref(), &Term::Num(3.into()));
+        assert_eq!(p.reload_modified_sources(), Vec::new());
+
+        write_at("main.ncl", "import \"b.ncl\"", 3);
+        assert_eq!(p.reload_modified_sources(), vec![p.main_id]);
+        assert_eq!(p.eval_full().unwrap().term.as_ref(), &Term::Num(1.into()));
+    }
+
     #[test]
     // Regression test for issue 715 (https://github.com/tweag/nickel/issues/715)
     // Check that program::typecheck() fail on parse error
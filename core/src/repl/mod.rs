@@ -12,7 +12,7 @@ use crate::error::{
     Error, EvalError, IOError, IntoDiagnostics, ParseError, ParseErrors, ReplError,
 };
 use crate::eval::cache::Cache as EvalCache;
-use crate::eval::{Closure, VirtualMachine};
+use crate::eval::{limits::EvalLimits, Closure, VirtualMachine};
 use crate::files::FileId;
 use crate::identifier::LocIdent;
 use crate::parser::{grammar, lexer, ErrorTolerantParser, ExtendedTerm};
@@ -102,6 +102,11 @@ impl<EC: EvalCache> ReplImpl<EC> {
         }
     }
 
+    /// Sets the resource limits of the evaluation of each input, see [crate::eval::limits].
+    pub fn set_eval_limits(&mut self, limits: EvalLimits) {
+        self.vm.set_limits(limits);
+    }
+
     /// Load and process the stdlib, and use it to populate the eval environment as well as the
     /// typing environment.
     pub fn load_stdlib(&mut self) -> Result<(), Error> {
@@ -174,6 +179,7 @@ impl<EC: EvalCache> ReplImpl<EC> {
     }
 
     fn eval_(&mut self, exp: &str, eval_full: bool) -> Result<EvalResult, Error> {
+        self.vm.reset_budget();
         self.vm.reset();
 
         let eval_function = if eval_full {
@@ -326,6 +332,7 @@ impl<EC: EvalCache> Repl for ReplImpl<EC> {
     }
 
     fn query(&mut self, path: String) -> Result<Field, Error> {
+        self.vm.reset_budget();
         self.vm.reset();
 
         let mut query_path = FieldPath::parse(self.vm.import_resolver_mut(), path)?;
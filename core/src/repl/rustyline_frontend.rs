@@ -3,7 +3,10 @@ use std::path::PathBuf;
 
 use super::{command::Command, *};
 
-use crate::{error::report::ColorOpt, eval::cache::CacheImpl};
+use crate::{
+    error::report::ColorOpt,
+    eval::{cache::CacheImpl, limits::EvalLimits},
+};
 
 use ansi_term::Style;
 use rustyline::{error::ReadlineError, Config, EditMode, Editor};
@@ -30,8 +33,9 @@ impl From<ColorOpt> for rustyline::config::ColorMode {
 }
 
 /// Main loop of the REPL.
-pub fn repl(histfile: PathBuf, color_opt: ColorOpt) -> Result<(), InitError> {
+pub fn repl(histfile: PathBuf, color_opt: ColorOpt, limits: EvalLimits) -> Result<(), InitError> {
     let mut repl = ReplImpl::<CacheImpl>::new(std::io::stderr());
+    repl.set_eval_limits(limits);
 
     match repl.load_stdlib() {
         Ok(()) => (),
@@ -0,0 +1,86 @@
+//! Serialization to dotenv files, as read by most `.env` loaders and by `docker --env-file`.
+//!
+//! The exported value must be a flat record of scalar values. Each field becomes a `KEY=value`
+//! line. Values are written as is when they only contain safe characters, and are double-quoted
+//! otherwise.
+use super::{scalar_to_string, sorted_fields, with_elem, ExportFormat, NickelPointerElem};
+use crate::{
+    error::{ExportError, ExportErrorData},
+    term::{RichTerm, Term},
+};
+
+/// Check that a term is a flat record of scalars whose fields are valid environment variable
+/// names.
+pub(super) fn validate_shape(t: &RichTerm) -> Result<(), ExportError> {
+    let Term::Record(record) = t.as_ref() else {
+        return Err(ExportErrorData::NotARecord(ExportFormat::Dotenv, t.clone()).into());
+    };
+
+    for (id, value) in sorted_fields(record)? {
+        if !is_valid_key(id.label()) {
+            return Err(ExportErrorData::InvalidKey(ExportFormat::Dotenv, id.to_string()).into());
+        }
+
+        if scalar_to_string(value).is_none() {
+            return Err(with_elem(
+                ExportErrorData::UnsupportedNesting(ExportFormat::Dotenv, value.clone()).into(),
+                NickelPointerElem::Field(id),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) fn to_string(t: &RichTerm) -> Result<String, ExportError> {
+    validate_shape(t)?;
+
+    let Term::Record(record) = t.as_ref() else {
+        unreachable!("validate_shape ensures the exported value is a record")
+    };
+
+    let mut result = String::new();
+
+    for (id, value) in sorted_fields(record)? {
+        // unwrap(): validate_shape ensures that all values are scalars
+        let value = scalar_to_string(value).unwrap();
+        result.push_str(&format!("{id}={}\n", quote(&value)));
+    }
+
+    Ok(result)
+}
+
+fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn quote(value: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./:@%+,".contains(c);
+
+    if !value.is_empty() && value.chars().all(is_safe) {
+        return value.to_owned();
+    }
+
+    let mut result = String::from('"');
+
+    for c in value.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '"' => result.push_str("\\\""),
+            '$' => result.push_str("\\$"),
+            '`' => result.push_str("\\`"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
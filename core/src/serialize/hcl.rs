@@ -0,0 +1,64 @@
+//! Serialization to the JSON syntax of HCL, as used by Terraform (`.tf.json` files).
+//!
+//! The exported value must be a record, which is written as the body of the configuration file.
+//! The output is plain JSON, except for strings: HCL interprets strings as templates, so the
+//! template sequences `${` and `%{` are escaped as `$${` and `%%{`, for the strings to be read
+//! back literally. Object keys are left as is, since they are also used for block types and
+//! labels, which aren't templates.
+use serde_json::Value;
+
+use super::ExportFormat;
+use crate::{
+    error::{ExportError, ExportErrorData},
+    term::{RichTerm, Term},
+};
+
+/// Check that a term is a record.
+pub(super) fn validate_shape(t: &RichTerm) -> Result<(), ExportError> {
+    if let Term::Record(_) = t.as_ref() {
+        Ok(())
+    } else {
+        Err(ExportErrorData::NotARecord(ExportFormat::Hcl, t.clone()).into())
+    }
+}
+
+pub(super) fn to_string(t: &RichTerm) -> Result<String, ExportError> {
+    validate_shape(t)?;
+
+    let mut value =
+        serde_json::to_value(t).map_err(|err| ExportErrorData::Other(err.to_string()))?;
+    escape_templates(&mut value);
+
+    let mut result = serde_json::to_string_pretty(&value)
+        .map_err(|err| ExportErrorData::Other(err.to_string()))?;
+    result.push('\n');
+
+    Ok(result)
+}
+
+/// Escape the template sequences of all the strings of a value, but not of object keys.
+fn escape_templates(value: &mut Value) {
+    match value {
+        Value::String(s) => *s = escape(s),
+        Value::Array(array) => array.iter_mut().for_each(escape_templates),
+        Value::Object(object) => object.values_mut().for_each(escape_templates),
+        Value::Null | Value::Bool(_) | Value::Number(_) => (),
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        result.push(c);
+
+        // `${` and `%{` start template sequences, which are escaped by doubling the first
+        // character.
+        if matches!(c, '$' | '%') && chars.peek() == Some(&'{') {
+            result.push(c);
+        }
+    }
+
+    result
+}
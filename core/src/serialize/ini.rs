@@ -0,0 +1,122 @@
+//! Serialization to INI files.
+//!
+//! The exported value must be a record. Fields with a scalar value are written first, as global
+//! keys, and fields whose value is a record of scalars are written as sections. Values are written
+//! as is, unless they contain characters that INI parsers commonly interpret (comment markers,
+//! quotes, surrounding whitespace or newlines), in which case they're double-quoted and escaped.
+use super::{scalar_to_string, sorted_fields, with_elem, ExportFormat, NickelPointerElem};
+use crate::{
+    error::{ExportError, ExportErrorData},
+    term::{RichTerm, Term},
+};
+
+/// Check that a term is a record whose fields are either scalars or records of scalars.
+pub(super) fn validate_shape(t: &RichTerm) -> Result<(), ExportError> {
+    let Term::Record(record) = t.as_ref() else {
+        return Err(ExportErrorData::NotARecord(ExportFormat::Ini, t.clone()).into());
+    };
+
+    for (id, value) in sorted_fields(record)? {
+        check_key(id.label())?;
+
+        let result = match value.as_ref() {
+            Term::Record(section) => {
+                sorted_fields(section)?
+                    .into_iter()
+                    .try_for_each(|(key, value)| {
+                        check_key(key.label())?;
+
+                        if scalar_to_string(value).is_some() {
+                            Ok(())
+                        } else {
+                            Err(with_elem(
+                                ExportErrorData::UnsupportedNesting(
+                                    ExportFormat::Ini,
+                                    value.clone(),
+                                )
+                                .into(),
+                                NickelPointerElem::Field(key),
+                            ))
+                        }
+                    })
+            }
+            _ if scalar_to_string(value).is_some() => Ok(()),
+            _ => Err(ExportErrorData::UnsupportedNesting(ExportFormat::Ini, value.clone()).into()),
+        };
+
+        result.map_err(|err| with_elem(err, NickelPointerElem::Field(id)))?;
+    }
+
+    Ok(())
+}
+
+pub(super) fn to_string(t: &RichTerm) -> Result<String, ExportError> {
+    validate_shape(t)?;
+
+    let Term::Record(record) = t.as_ref() else {
+        unreachable!("validate_shape ensures the exported value is a record")
+    };
+
+    let fields = sorted_fields(record)?;
+    let mut result = String::new();
+
+    // Global keys must come before the first section, otherwise they would be part of it.
+    for (id, value) in &fields {
+        if let Some(value) = scalar_to_string(value) {
+            result.push_str(&format!("{id} = {}\n", quote(&value)));
+        }
+    }
+
+    for (id, value) in &fields {
+        if let Term::Record(section) = value.as_ref() {
+            if !result.is_empty() {
+                result.push('\n');
+            }
+
+            result.push_str(&format!("[{id}]\n"));
+
+            for (key, value) in sorted_fields(section)? {
+                // unwrap(): validate_shape ensures that section values are scalars
+                let value = scalar_to_string(value).unwrap();
+                result.push_str(&format!("{key} = {}\n", quote(&value)));
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+fn check_key(key: &str) -> Result<(), ExportError> {
+    let is_valid = !key.is_empty()
+        && key.trim() == key
+        && !key.contains(['=', ';', '#', '[', ']', '\n', '\r']);
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(ExportErrorData::InvalidKey(ExportFormat::Ini, key.to_owned()).into())
+    }
+}
+
+fn quote(value: &str) -> String {
+    let needs_quotes = value.trim() != value || value.contains([';', '#', '"', '\\', '\n', '\r']);
+
+    if !needs_quotes {
+        return value.to_owned();
+    }
+
+    let mut result = String::from('"');
+
+    for c in value.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '"' => result.push_str("\\\""),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
@@ -0,0 +1,449 @@
+//! Deserialization of JSON sources with position information.
+//!
+//! Going through `serde_json` loses all the positions, and blame errors on imported values can't
+//! point back to the data file. Instead, we use a small hand-written parser which attaches to
+//! each value (and to each record field name) the span of its source. The resulting terms are
+//! otherwise the same as with `serde_json`: in particular, numbers are converted from 64 bits
+//! floats, and when a key appears several times in an object, the last value wins.
+use codespan::ByteIndex;
+
+use crate::{
+    error::ParseError,
+    files::FileId,
+    identifier::LocIdent,
+    position::RawSpan,
+    term::{record::RecordData, IndexMap, Number, RichTerm, Term},
+};
+
+/// The maximum nesting of arrays and objects. The parser is recursive, so deeper sources would
+/// overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Deserialize a Nickel term with position information from a JSON source provided as a string
+/// and the file id of this source.
+pub fn from_str(s: &str, file_id: FileId) -> Result<RichTerm, ParseError> {
+    let mut parser = Parser {
+        src: s,
+        pos: 0,
+        depth: 0,
+        file_id,
+    };
+
+    parser.skip_whitespace();
+    let value = parser.value()?;
+    parser.skip_whitespace();
+
+    if parser.pos < s.len() {
+        return Err(parser.error("trailing characters"));
+    }
+
+    Ok(value)
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    /// The current byte offset in `src`.
+    pos: usize,
+    /// The number of arrays and objects enclosing the current position.
+    depth: usize,
+    file_id: FileId,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn span(&self, start: usize, end: usize) -> RawSpan {
+        RawSpan {
+            src_id: self.file_id,
+            start: ByteIndex(start as u32),
+            end: ByteIndex(end as u32),
+        }
+    }
+
+    /// Build an error pointing at the current position.
+    fn error(&self, msg: &str) -> ParseError {
+        let start = self.pos.min(self.src.len());
+        let end = (start + 1).min(self.src.len());
+
+        ParseError::ExternalFormatError(
+            String::from("json"),
+            msg.to_owned(),
+            Some(self.span(start, end)),
+        )
+    }
+
+    fn expect(&mut self, byte: u8, msg: &str) -> Result<(), ParseError> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(msg))
+        }
+    }
+
+    fn value(&mut self) -> Result<RichTerm, ParseError> {
+        let start = self.pos;
+
+        let term = match self.peek() {
+            None => return Err(self.error("EOF while parsing a value")),
+            Some(b'{') => self.nested(Self::object)?,
+            Some(b'[') => self.nested(Self::array)?,
+            Some(b'"') => Term::Str(self.string()?.into()),
+            Some(b't') => self.keyword("true", Term::Bool(true))?,
+            Some(b'f') => self.keyword("false", Term::Bool(false))?,
+            Some(b'n') => self.keyword("null", Term::Null)?,
+            Some(b'-' | b'0'..=b'9') => self.number()?,
+            Some(_) => return Err(self.error("expected value")),
+        };
+
+        Ok(RichTerm::new(term, self.span(start, self.pos).into()))
+    }
+
+    /// Parse an array or an object, checking that the nesting limit isn't exceeded.
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Term, ParseError>,
+    ) -> Result<Term, ParseError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("recursion limit exceeded"));
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn keyword(&mut self, keyword: &str, term: Term) -> Result<Term, ParseError> {
+        if self.src[self.pos..].starts_with(keyword) {
+            self.pos += keyword.len();
+            Ok(term)
+        } else {
+            Err(self.error("expected value"))
+        }
+    }
+
+    fn object(&mut self) -> Result<Term, ParseError> {
+        // Skip the opening brace.
+        self.pos += 1;
+        self.skip_whitespace();
+
+        let mut fields = IndexMap::new();
+
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Term::Record(RecordData::with_field_values(fields)));
+        }
+
+        loop {
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(b'"') => (),
+                Some(b'}') => return Err(self.error("trailing comma")),
+                None => return Err(self.error("EOF while parsing an object")),
+                Some(_) => return Err(self.error("key must be a string")),
+            }
+
+            let key_start = self.pos;
+            let key = self.string()?;
+            let id = LocIdent::new_with_pos(key, self.span(key_start, self.pos).into());
+
+            self.skip_whitespace();
+            self.expect(b':', "expected `:`")?;
+            self.skip_whitespace();
+
+            let value = self.value()?;
+            fields.insert(id, value);
+
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Term::Record(RecordData::with_field_values(fields)));
+                }
+                None => return Err(self.error("EOF while parsing an object")),
+                Some(_) => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Term, ParseError> {
+        // Skip the opening bracket.
+        self.pos += 1;
+        self.skip_whitespace();
+
+        let mut elts = Vec::new();
+
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Term::Array(elts.into_iter().collect(), Default::default()));
+        }
+
+        loop {
+            self.skip_whitespace();
+
+            if self.peek() == Some(b']') {
+                return Err(self.error("trailing comma"));
+            }
+
+            elts.push(self.value()?);
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Term::Array(elts.into_iter().collect(), Default::default()));
+                }
+                None => return Err(self.error("EOF while parsing a list")),
+                Some(_) => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Term, ParseError> {
+        let start = self.pos;
+        let digits = |parser: &mut Self| {
+            let digits_start = parser.pos;
+
+            while let Some(b'0'..=b'9') = parser.peek() {
+                parser.pos += 1;
+            }
+
+            parser.pos > digits_start
+        };
+
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+
+        if self.peek() == Some(b'0') {
+            self.pos += 1;
+        } else if !digits(self) {
+            return Err(self.error("invalid number"));
+        }
+
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+
+        if let Some(b'e' | b'E') = self.peek() {
+            self.pos += 1;
+
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+
+        // unwrap(): we just checked that the input is a valid JSON number, which is also a valid
+        // float literal for Rust.
+        let float: f64 = self.src[start..self.pos].parse().unwrap();
+
+        Number::try_from_float_simplest(float)
+            .map(Term::Num)
+            .map_err(|_| {
+                self.pos = start;
+                self.error("number out of range")
+            })
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        // Skip the opening quote.
+        self.pos += 1;
+
+        let mut result = String::new();
+        let mut chunk_start = self.pos;
+
+        loop {
+            match self.peek() {
+                None => return Err(self.error("EOF while parsing a string")),
+                Some(b'"') => {
+                    result.push_str(&self.src[chunk_start..self.pos]);
+                    self.pos += 1;
+                    return Ok(result);
+                }
+                Some(b'\\') => {
+                    result.push_str(&self.src[chunk_start..self.pos]);
+                    self.pos += 1;
+                    self.escape(&mut result)?;
+                    chunk_start = self.pos;
+                }
+                Some(0x00..=0x1F) => {
+                    return Err(self
+                        .error("control character (\\u0000-\\u001F) found while parsing a string"))
+                }
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
+    /// Parse an escape sequence, the backslash being already consumed.
+    fn escape(&mut self, out: &mut String) -> Result<(), ParseError> {
+        let escaped = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\x08',
+            Some(b'f') => '\x0C',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.pos += 1;
+                let high = self.hex_escape()?;
+
+                let c = if (0xD800..0xDC00).contains(&high) {
+                    // A high surrogate must be followed by an escaped low surrogate.
+                    if !self.src[self.pos..].starts_with("\\u") {
+                        return Err(self.error("unexpected end of hex escape"));
+                    }
+
+                    self.pos += 2;
+                    let low = self.hex_escape()?;
+
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(self.error("lone leading surrogate in hex escape"));
+                    }
+
+                    char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+                } else {
+                    char::from_u32(high)
+                };
+
+                out.push(c.ok_or_else(|| self.error("lone leading surrogate in hex escape"))?);
+                return Ok(());
+            }
+            None => return Err(self.error("EOF while parsing a string")),
+            Some(_) => return Err(self.error("invalid escape")),
+        };
+
+        self.pos += 1;
+        out.push(escaped);
+        Ok(())
+    }
+
+    /// Parse the four hexadecimal digits of a `\u` escape.
+    fn hex_escape(&mut self) -> Result<u32, ParseError> {
+        let digits = self
+            .src
+            .get(self.pos..self.pos + 4)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("invalid escape"))?;
+
+        // unwrap(): we just checked that these are four hexadecimal digits
+        let code = u32::from_str_radix(digits, 16).unwrap();
+        self.pos += 4;
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{files::Files, position::TermPos};
+
+    fn parse(s: &str) -> Result<RichTerm, ParseError> {
+        let mut files = Files::new();
+        let file_id = files.add("<test>", s.to_owned());
+        from_str(s, file_id)
+    }
+
+    /// Check that the parser produces the same term as `std.deserialize`, which goes through
+    /// `serde_json`.
+    #[track_caller]
+    fn assert_same_as_serde(s: &str) {
+        let expected: RichTerm = serde_json::from_str(s).unwrap();
+        assert_eq!(parse(s).unwrap().without_pos(), expected.without_pos());
+    }
+
+    #[test]
+    fn values() {
+        assert_same_as_serde("null");
+        assert_same_as_serde(" [true, false, 1, -2.5e3, 0.125, \"\"] ");
+        assert_same_as_serde(
+            r#"{"a": {"b": [1, {"c": null}]}, "d": "e\n\u00e9\ud83d\ude00\"\\/"}"#,
+        );
+        assert_same_as_serde(r#"{"a": 1, "a": 2}"#);
+        assert_same_as_serde("{}");
+        assert_same_as_serde("[]");
+    }
+
+    #[test]
+    fn positions() {
+        let rt = parse("{\n  \"a\": [1, \"x\"]\n}").unwrap();
+        let Term::Record(record) = rt.as_ref() else {
+            panic!("expected a record");
+        };
+        let (id, field) = record.fields.iter().next().unwrap();
+
+        let span = |pos: TermPos| {
+            let span = pos.unwrap();
+            (span.start.0, span.end.0)
+        };
+
+        assert_eq!(span(rt.pos), (0, 19));
+        assert_eq!(span(id.pos), (4, 7));
+
+        let value = field.value.as_ref().unwrap();
+        assert_eq!(span(value.pos), (9, 17));
+
+        let Term::Array(array, _) = value.as_ref() else {
+            panic!("expected an array");
+        };
+        assert_eq!(span(array.get(1).unwrap().pos), (13, 16));
+    }
+
+    #[test]
+    fn errors() {
+        for s in [
+            "",
+            "{",
+            "[1,]",
+            "{\"a\" 1}",
+            "{1: 2}",
+            "01",
+            "1.",
+            "\"\\x\"",
+            "tru",
+            "1 2",
+            "\"\u{1}\"",
+            "1e400",
+        ] {
+            assert!(parse(s).is_err(), "`{s}` should fail to parse");
+        }
+    }
+
+    #[test]
+    fn recursion_limit() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+
+        let err = parse(&nested(MAX_DEPTH + 1)).unwrap_err();
+        assert!(
+            matches!(&err, ParseError::ExternalFormatError(_, msg, _) if msg == "recursion limit exceeded")
+        );
+
+        // Used to overflow the stack.
+        assert!(parse(&"[".repeat(200_000)).is_err());
+        assert!(parse(&"{\"a\":".repeat(200_000)).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! Deserialization of line-based formats: CSV and JSON Lines.
+//!
+//! Both formats are deserialized as arrays:
+//!
+//! - a CSV file must start with a header row, and each following row is deserialized as a record
+//!   whose field names are the column names of the header. All values are strings, as CSV doesn't
+//!   have a notion of data types.
+//! - a JSON Lines file contains one JSON value per line, and each line is deserialized as an
+//!   element of the array. Blank lines are ignored.
+//!
+//! Errors always refer to the line that failed, which is usually more helpful than a byte offset
+//! when working with large data files.
+use std::ops::Range;
+
+use crate::{
+    identifier::LocIdent,
+    term::{array::Array, record::RecordData, RichTerm, Term},
+};
+
+/// An error on a specific line of a line-based format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineError {
+    /// The number of the line, starting at 1.
+    pub line: usize,
+    /// The byte range of the line in the source, excluding the line terminator.
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+impl LineError {
+    /// Creates an error for the line starting at the given byte offset of `source`.
+    fn new(source: &str, line: usize, offset: usize, message: impl Into<String>) -> Self {
+        let offset = offset.min(source.len());
+        let end = source[offset..]
+            .find(['\n', '\r'])
+            .map_or(source.len(), |len| offset + len);
+
+        LineError {
+            line,
+            span: offset..end,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for LineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Deserialize a CSV source, with a header row, to an array of records.
+pub fn csv_from_str(s: &str) -> Result<RichTerm, LineError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .from_reader(s.as_bytes());
+
+    let csv_error = |err: csv::Error| {
+        let (line, offset) = err
+            .position()
+            .map_or((1, 0), |pos| (pos.line() as usize, pos.byte() as usize));
+        let message = match err.kind() {
+            csv::ErrorKind::UnequalLengths {
+                expected_len, len, ..
+            } => format!("expected {expected_len} fields, as in the header row, but found {len}"),
+            _ => err.to_string(),
+        };
+
+        LineError::new(s, line, offset, message)
+    };
+
+    let headers: Vec<LocIdent> = reader
+        .headers()
+        .map_err(csv_error)?
+        .iter()
+        .map(LocIdent::from)
+        .collect();
+
+    for (index, header) in headers.iter().enumerate() {
+        if headers[..index].contains(header) {
+            return Err(LineError::new(
+                s,
+                1,
+                0,
+                format!("duplicate column name `{header}` in the header row"),
+            ));
+        }
+    }
+
+    let rows = reader
+        .records()
+        .map(|row| {
+            let row = row.map_err(csv_error)?;
+            let fields = headers
+                .iter()
+                .zip(row.iter())
+                .map(|(header, value)| (*header, RichTerm::from(Term::Str(value.into()))));
+
+            Ok(RichTerm::from(Term::Record(RecordData::with_field_values(
+                fields,
+            ))))
+        })
+        .collect::<Result<Array, _>>()?;
+
+    Ok(Term::Array(rows, Default::default()).into())
+}
+
+/// Deserialize a JSON Lines source to an array of values.
+pub fn jsonl_from_str(s: &str) -> Result<RichTerm, LineError> {
+    let mut offset = 0;
+    let mut values = Vec::new();
+
+    for (index, line) in s.split('\n').enumerate() {
+        let line_offset = offset;
+        offset += line.len() + 1;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let value = serde_json::from_str(line).map_err(|err| {
+            // serde_json reports a position relative to the line being parsed, which would be
+            // confusing: we only keep the column.
+            let mut message = err.to_string();
+
+            if let Some(location) = message.rfind(" at line ") {
+                message.truncate(location);
+                message.push_str(&format!(" at column {}", err.column()));
+            }
+
+            LineError::new(s, index + 1, line_offset, message)
+        })?;
+        values.push(value);
+    }
+
+    Ok(Term::Array(values.into_iter().collect(), Default::default()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv() {
+        let rt = csv_from_str("name,count\nbolt,12\n\"nut, hex\",3\n").unwrap();
+        assert_eq!(
+            serde_json::to_value(&rt).unwrap(),
+            serde_json::json!([
+                {"name": "bolt", "count": "12"},
+                {"name": "nut, hex", "count": "3"},
+            ])
+        );
+
+        assert_eq!(
+            serde_json::to_value(csv_from_str("").unwrap()).unwrap(),
+            serde_json::json!([])
+        );
+
+        let err = csv_from_str("a,b\n1,2\n3\n").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(err.span, 8..9);
+
+        assert_eq!(csv_from_str("a,b,a\n1,2,3\n").unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn jsonl() {
+        let rt = jsonl_from_str("{\"a\": 1}\n\n[true, null]\n\"x\"").unwrap();
+        assert_eq!(
+            serde_json::to_value(&rt).unwrap(),
+            serde_json::json!([{"a": 1}, [true, null], "x"])
+        );
+
+        let err = jsonl_from_str("1\n2\n{\"a\":}\n4\n").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(err.span, 4..10);
+    }
+}
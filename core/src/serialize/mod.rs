@@ -24,7 +24,18 @@ use malachite::{
 };
 use once_cell::sync::Lazy;
 
-use std::{fmt, io};
+use std::{ffi::OsStr, fmt, io, path::Path};
+
+mod dotenv;
+mod hcl;
+mod ini;
+pub mod json_deser;
+pub mod line_based;
+mod properties;
+pub mod tree;
+pub mod value;
+mod xml;
+pub mod yaml_deser;
 
 /// Available export formats.
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, clap::ValueEnum)]
@@ -36,7 +47,19 @@ pub enum ExportFormat {
     #[default]
     Json,
     Yaml,
+    /// Serialize a top-level array as a stream of YAML documents, one document per element
+    YamlDocuments,
     Toml,
+    /// Serialize a record with a single field, the root element, as an XML document
+    Xml,
+    /// Serialize a record of values and sections (records of values) as an INI file
+    Ini,
+    /// Serialize a record as a Java properties file, flattening nested records with dotted keys
+    Properties,
+    /// Serialize a flat record of values as a dotenv file
+    Dotenv,
+    /// Serialize a record as a Terraform configuration file in the JSON syntax of HCL
+    Hcl,
 }
 
 impl fmt::Display for ExportFormat {
@@ -45,7 +68,58 @@ impl fmt::Display for ExportFormat {
             Self::Text => write!(f, "text"),
             Self::Json => write!(f, "json"),
             Self::Yaml => write!(f, "yaml"),
+            Self::YamlDocuments => write!(f, "yaml-documents"),
             Self::Toml => write!(f, "toml"),
+            Self::Xml => write!(f, "xml"),
+            Self::Ini => write!(f, "ini"),
+            Self::Properties => write!(f, "properties"),
+            Self::Dotenv => write!(f, "dotenv"),
+            Self::Hcl => write!(f, "hcl"),
+        }
+    }
+}
+
+impl ExportFormat {
+    /// Returns the export format corresponding to an enum tag, as used by `std.serialize`.
+    pub fn from_tag(tag: &str) -> Option<ExportFormat> {
+        Some(match tag {
+            "Text" => ExportFormat::Text,
+            "Json" => ExportFormat::Json,
+            "Yaml" => ExportFormat::Yaml,
+            "YamlDocuments" => ExportFormat::YamlDocuments,
+            "Toml" => ExportFormat::Toml,
+            "Xml" => ExportFormat::Xml,
+            "Ini" => ExportFormat::Ini,
+            "Properties" => ExportFormat::Properties,
+            "Dotenv" => ExportFormat::Dotenv,
+            "Hcl" => ExportFormat::Hcl,
+            _ => return None,
+        })
+    }
+
+    /// Returns an export format based on the file extension of a path, or on its file name for
+    /// dotenv files and Terraform JSON files.
+    pub fn from_path(path: &Path) -> Option<ExportFormat> {
+        let name = path.file_name().and_then(OsStr::to_str);
+
+        if name.is_some_and(|name| name == ".env" || name.starts_with(".env.")) {
+            return Some(ExportFormat::Dotenv);
+        }
+
+        if name.is_some_and(|name| name.ends_with(".tf.json") || name.ends_with(".tfvars.json")) {
+            return Some(ExportFormat::Hcl);
+        }
+
+        match path.extension().and_then(OsStr::to_str) {
+            Some("txt") => Some(ExportFormat::Text),
+            Some("json") => Some(ExportFormat::Json),
+            Some("yaml") | Some("yml") => Some(ExportFormat::Yaml),
+            Some("toml") => Some(ExportFormat::Toml),
+            Some("xml") => Some(ExportFormat::Xml),
+            Some("ini") => Some(ExportFormat::Ini),
+            Some("properties") => Some(ExportFormat::Properties),
+            Some("env") => Some(ExportFormat::Dotenv),
+            _ => None,
         }
     }
 }
@@ -207,12 +281,17 @@ where
 }
 
 impl Serialize for RichTerm {
-    /// Serialize the underlying term.
+    /// Serialize the underlying term. Binary formats (see [crate::disk_cache]) also store the
+    /// position and the terms which can't be exported.
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        (*self.term).serialize(serializer)
+        if serializer.is_human_readable() {
+            (*self.term).serialize(serializer)
+        } else {
+            crate::disk_cache::repr::serialize_rich_term(self, serializer)
+        }
     }
 }
 
@@ -221,8 +300,12 @@ impl<'de> Deserialize<'de> for RichTerm {
     where
         D: Deserializer<'de>,
     {
-        let t: Term = Term::deserialize(deserializer)?;
-        Ok(RichTerm::from(t))
+        if deserializer.is_human_readable() {
+            let t: Term = Term::deserialize(deserializer)?;
+            Ok(RichTerm::from(t))
+        } else {
+            crate::disk_cache::repr::deserialize_rich_term(deserializer)
+        }
     }
 }
 
@@ -294,8 +377,43 @@ impl fmt::Display for NickelPointer {
     }
 }
 
+// Push an NickelPoinerElem to the end of the path of an ExportError
+fn with_elem(mut err: ExportError, elem: NickelPointerElem) -> ExportError {
+    err.path.0.push(elem);
+    err
+}
+
+/// Return the fields of an evaluated record sorted by name, to get a deterministic output.
+fn sorted_fields(record: &RecordData) -> Result<Vec<(Ident, &RichTerm)>, ExportError> {
+    let mut entries = record
+        .iter_serializable()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| {
+            ExportErrorData::Other(format!("missing field definition for `{}`", err.id))
+        })?;
+
+    entries.sort_by(|(id1, _), (id2, _)| id1.label().cmp(id2.label()));
+    Ok(entries)
+}
+
+/// Render a scalar value, that is a string, a boolean, a number or an enum tag, as text. This is
+/// used by the formats which don't distinguish between strings and other scalar values. Return
+/// `None` if the value isn't a scalar.
+fn scalar_to_string(t: &RichTerm) -> Option<String> {
+    match t.as_ref() {
+        Term::Str(s) => Some(s.to_string()),
+        Term::Bool(b) => Some(b.to_string()),
+        Term::Enum(id) => Some(id.label().to_owned()),
+        Term::Num(n) => serialize_num(n, serde_json::value::Serializer)
+            .ok()
+            .map(|value| value.to_string()),
+        _ => None,
+    }
+}
+
 /// Check that a term is serializable. Serializable terms are booleans, numbers, strings, enum,
-/// arrays of serializable terms or records of serializable terms.
+/// arrays of serializable terms or records of serializable terms. Some formats further restrict
+/// the shape of serializable terms, for example by forbidding nested records.
 pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
     use Term::*;
 
@@ -306,12 +424,6 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
     static NUMBER_MIN: Lazy<Number> = Lazy::new(|| Number::try_from(f64::MIN).unwrap());
     static NUMBER_MAX: Lazy<Number> = Lazy::new(|| Number::try_from(f64::MAX).unwrap());
 
-    // Push an NickelPoinerElem to the end of the path of an ExportError
-    fn with_elem(mut err: ExportError, elem: NickelPointerElem) -> ExportError {
-        err.path.0.push(elem);
-        err
-    }
-
     // We need to build a field path locating a potential export error. One way would be to pass a
     // context storing the current path to recursive calls of `validate`. However, representing
     // this context isn't entirely trivial: using an owned `Vec` will incur a lot of copying, even
@@ -333,7 +445,17 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
     fn do_validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
         match t.as_ref() {
             // TOML doesn't support null values
-            Null if format == ExportFormat::Json || format == ExportFormat::Yaml => Ok(()),
+            Null if matches!(
+                format,
+                ExportFormat::Json
+                    | ExportFormat::Yaml
+                    | ExportFormat::YamlDocuments
+                    | ExportFormat::Xml
+                    | ExportFormat::Hcl
+            ) =>
+            {
+                Ok(())
+            }
             Null => Err(ExportErrorData::UnsupportedNull(format, t.clone()).into()),
             Bool(_) | Str(_) | Enum(_) => Ok(()),
             Num(n) => {
@@ -381,8 +503,19 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), ExportError> {
         } else {
             Err(ExportErrorData::NotAString(t.clone()).into())
         }
+    } else if format == ExportFormat::YamlDocuments && !matches!(t.term.as_ref(), Term::Array(..)) {
+        Err(ExportErrorData::NotAnArray(format, t.clone()).into())
     } else {
-        let mut result = do_validate(format, t);
+        // Format-specific restrictions are checked on top of the general serializability of the
+        // term, and their errors are also built bottom-up.
+        let mut result = do_validate(format, t).and_then(|()| match format {
+            ExportFormat::Xml => xml::validate_shape(t),
+            ExportFormat::Ini => ini::validate_shape(t),
+            ExportFormat::Properties => properties::validate_shape(t),
+            ExportFormat::Dotenv => dotenv::validate_shape(t),
+            ExportFormat::Hcl => hcl::validate_shape(t),
+            _ => Ok(()),
+        });
 
         if let Err(ExportError { path, .. }) = &mut result {
             path.0.reverse();
@@ -432,6 +565,19 @@ where
             .map_err(|err| ExportErrorData::Other(err.to_string())),
         ExportFormat::Yaml => serde_yaml::to_writer(writer, &rt)
             .map_err(|err| ExportErrorData::Other(err.to_string())),
+        ExportFormat::YamlDocuments => match rt.as_ref() {
+            Term::Array(array, _) => array.iter().enumerate().try_for_each(|(index, doc)| {
+                if index > 0 {
+                    writer
+                        .write_all(b"---\n")
+                        .map_err(|err| ExportErrorData::Other(err.to_string()))?;
+                }
+
+                serde_yaml::to_writer(&mut writer, doc)
+                    .map_err(|err| ExportErrorData::Other(err.to_string()))
+            }),
+            _ => Err(ExportErrorData::NotAnArray(format, rt.clone())),
+        },
         ExportFormat::Toml => toml::to_string_pretty(rt)
             .map_err(|err| ExportErrorData::Other(err.to_string()))
             .and_then(|s| {
@@ -439,6 +585,23 @@ where
                     .write_all(s.as_bytes())
                     .map_err(|err| ExportErrorData::Other(err.to_string()))
             }),
+        ExportFormat::Xml
+        | ExportFormat::Ini
+        | ExportFormat::Properties
+        | ExportFormat::Dotenv
+        | ExportFormat::Hcl => {
+            let s = match format {
+                ExportFormat::Xml => xml::to_string(rt),
+                ExportFormat::Ini => ini::to_string(rt),
+                ExportFormat::Properties => properties::to_string(rt),
+                ExportFormat::Dotenv => dotenv::to_string(rt),
+                _ => hcl::to_string(rt),
+            }?;
+
+            writer
+                .write_all(s.as_bytes())
+                .map_err(|err| ExportErrorData::Other(err.to_string()))
+        }
         ExportFormat::Text => match rt.as_ref() {
             Term::Str(s) => writer
                 .write_all(s.as_bytes())
@@ -587,6 +750,7 @@ mod tests {
     use crate::eval::VirtualMachine;
     use crate::program::Program;
     use crate::term::{make as mk_term, BinaryOp};
+    use assert_matches::assert_matches;
     use serde_json::json;
     use std::io::Cursor;
 
@@ -718,6 +882,161 @@ mod tests {
         );
         assert_pass_validation(ExportFormat::Json, "{foo = null}");
         assert_fail_validation(ExportFormat::Toml, "{foo = null}");
+        assert_pass_validation(ExportFormat::YamlDocuments, "[{foo = null}, 1]");
+        assert_fail_validation(ExportFormat::YamlDocuments, "{foo = 1}");
+    }
+
+    #[test]
+    fn restricted_formats_validation() {
+        assert_pass_validation(ExportFormat::Dotenv, "{FOO = 1, BAR_2 = \"a b\"}");
+        assert_fail_validation(ExportFormat::Dotenv, "{foo = {bar = 1}}");
+        assert_fail_validation(ExportFormat::Dotenv, "{\"1foo\" = 1}");
+        assert_fail_validation(ExportFormat::Dotenv, "[1]");
+        assert_pass_validation(ExportFormat::Ini, "{a = 1, section = {b = true}}");
+        assert_fail_validation(ExportFormat::Ini, "{section = {b = {c = 1}}}");
+        assert_pass_validation(ExportFormat::Properties, "{a.b.c = 1}");
+        assert_fail_validation(ExportFormat::Properties, "{a = [1]}");
+        assert_pass_validation(ExportFormat::Properties, "{a.b = 1, \"a.c\" = 2}");
+        assert_fail_validation(ExportFormat::Properties, "{a.b = 1, \"a.b\" = 2}");
+        assert_fail_validation(ExportFormat::Properties, "{a.\"b.c\" = 1, \"a.b\".c = 2}");
+        assert_pass_validation(ExportFormat::Xml, "{root = {\"@id\" = 1, item = [1, 2]}}");
+        assert_fail_validation(ExportFormat::Xml, "{a = 1, b = 2}");
+        assert_fail_validation(ExportFormat::Xml, "{root = {\"@id\" = {}}}");
+        assert_pass_validation(ExportFormat::Hcl, "{\"a b\" = {\"c d\" = [null]}}");
+        assert_fail_validation(ExportFormat::Hcl, "[1]");
+    }
+
+    #[test]
+    fn restricted_formats() {
+        let value = eval("{b = {c = \"x y\", d = 1}, a = true}");
+
+        assert_eq!(
+            to_string(ExportFormat::Properties, &value).unwrap(),
+            "a=true\nb.c=x y\nb.d=1\n"
+        );
+        assert_matches!(
+            to_string(ExportFormat::Properties, &eval("{a.b = 1, \"a.b\" = 2}")),
+            Err(ExportError {
+                data: ExportErrorData::DuplicateKey(ExportFormat::Properties, key),
+                ..
+            }) if key == "a.b"
+        );
+        assert_eq!(
+            to_string(ExportFormat::Ini, &value).unwrap(),
+            "a = true\n\n[b]\nc = x y\nd = 1\n"
+        );
+        assert_eq!(
+            to_string(ExportFormat::Hcl, &value).unwrap(),
+            "{\n  \"a\": true,\n  \"b\": {\n    \"c\": \"x y\",\n    \"d\": 1\n  }\n}\n"
+        );
+        assert_eq!(
+            to_string(ExportFormat::Dotenv, &eval("{A = \"x y\", B = \"z\"}")).unwrap(),
+            "A=\"x y\"\nB=z\n"
+        );
+        assert_eq!(
+            to_string(ExportFormat::Xml, &eval("{a = {\"@k\" = \"<\", b = [1, 2]}}")).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<a k=\"&lt;\">\n  <b>1</b>\n  <b>2</b>\n</a>\n"
+        );
+    }
+
+    #[test]
+    fn hcl_json() {
+        let value = eval(
+            r#"{
+                resource.aws_instance."${name}" = {
+                    ami = "ami-${id}",
+                    user_data = "\%{if} $${x} 100%",
+                    tags = ["${a}", null],
+                },
+            }"#,
+        );
+
+        // Only strings are escaped, not the keys, which are also used for block labels.
+        assert_eq!(
+            to_string(ExportFormat::Hcl, &value).unwrap(),
+            r#"{
+  "resource": {
+    "aws_instance": {
+      "${name}": {
+        "ami": "ami-$${id}",
+        "tags": [
+          "$${a}",
+          null
+        ],
+        "user_data": "%%{if} $$${x} 100%"
+      }
+    }
+  }
+}
+"#
+        );
+
+        assert_eq!(
+            ExportFormat::from_path(Path::new("dir/main.tf.json")),
+            Some(ExportFormat::Hcl)
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("prod.tfvars.json")),
+            Some(ExportFormat::Hcl)
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("main.json")),
+            Some(ExportFormat::Json)
+        );
+    }
+
+    #[test]
+    fn multi_file() {
+        let files = tree::files(
+            &eval("{\"a/b.yaml\" = {x = 1}, c = {format = 'Toml, content = {y = 2}}, d = 3}"),
+            ExportFormat::Json,
+        )
+        .unwrap();
+        let formats: Vec<_> = files.iter().map(|f| (f.path.clone(), f.format)).collect();
+
+        assert_eq!(
+            formats,
+            vec![
+                ("a/b.yaml".into(), ExportFormat::Yaml),
+                ("c".into(), ExportFormat::Toml),
+                ("d".into(), ExportFormat::Json),
+            ]
+        );
+
+        assert!(tree::files(&eval("{\"../a.json\" = 1}"), ExportFormat::Json).is_err());
+        assert!(tree::files(&eval("{\"/a.json\" = 1}"), ExportFormat::Json).is_err());
+        assert!(tree::files(&eval("{\"a.txt\" = 1}"), ExportFormat::Json).is_err());
+
+        // Paths are normalized, and can't refer to the same file or to the directory of another
+        // file.
+        let files = tree::files(&eval("{\"./a//b.json\" = 1}"), ExportFormat::Json).unwrap();
+        assert_eq!(files[0].path, std::path::Path::new("a/b.json"));
+
+        for conflicting in [
+            "{\"a.json\" = 1, \"./a.json\" = 2}",
+            "{\"a/b.json\" = 1, \"a//b.json\" = 2}",
+            "{a = 1, \"a/b.json\" = 2}",
+        ] {
+            assert_matches!(
+                tree::files(&eval(conflicting), ExportFormat::Json),
+                Err(ExportError {
+                    data: ExportErrorData::ConflictingOutputPaths(..),
+                    ..
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn yaml_documents() {
+        assert_eq!(
+            to_string(ExportFormat::YamlDocuments, &eval("[{a = 1}, [2], \"b\"]")).unwrap(),
+            "a: 1\n---\n- 2\n---\nb\n"
+        );
+        assert_eq!(
+            to_string(ExportFormat::YamlDocuments, &eval("[]")).unwrap(),
+            ""
+        );
     }
 
     #[test]
@@ -0,0 +1,120 @@
+//! Serialization to Java properties files.
+//!
+//! The exported value must be a record. Nested records are flattened, using dotted keys: `{ a.b =
+//! 1 }` is exported as `a.b=1`. Field names containing a dot are written as is, and are rejected
+//! if they collide with the key of a nested field. Arrays aren't supported. Keys and values are
+//! escaped following the format accepted by `java.util.Properties.load`, and non-ASCII characters
+//! are written as unicode escapes, so that the output is also valid ISO 8859-1.
+use super::{scalar_to_string, sorted_fields, with_elem, ExportFormat, NickelPointerElem};
+use crate::{
+    error::{ExportError, ExportErrorData},
+    term::{RichTerm, Term},
+};
+
+/// Check that a term is a record whose leaves (after flattening nested records) are scalars.
+pub(super) fn validate_shape(t: &RichTerm) -> Result<(), ExportError> {
+    fn do_validate(t: &RichTerm) -> Result<(), ExportError> {
+        match t.as_ref() {
+            Term::Record(record) => {
+                sorted_fields(record)?
+                    .into_iter()
+                    .try_for_each(|(id, value)| {
+                        do_validate(value)
+                            .map_err(|err| with_elem(err, NickelPointerElem::Field(id)))
+                    })
+            }
+            _ if scalar_to_string(t).is_some() => Ok(()),
+            _ => {
+                Err(ExportErrorData::UnsupportedNesting(ExportFormat::Properties, t.clone()).into())
+            }
+        }
+    }
+
+    if let Term::Record(_) = t.as_ref() {
+        do_validate(t)?;
+        flatten(t).map(|_| ())
+    } else {
+        Err(ExportErrorData::NotARecord(ExportFormat::Properties, t.clone()).into())
+    }
+}
+
+/// Flattens a record whose leaves are scalars into the sorted list of its dotted keys and values.
+/// Fails if two different fields end up with the same key, as in `{ a.b = 1, "a.b" = 2 }`.
+fn flatten(t: &RichTerm) -> Result<Vec<(String, String)>, ExportError> {
+    fn do_flatten(
+        prefix: &str,
+        t: &RichTerm,
+        lines: &mut Vec<(String, String)>,
+    ) -> Result<(), ExportError> {
+        match t.as_ref() {
+            Term::Record(record) => {
+                for (id, value) in sorted_fields(record)? {
+                    let key = if prefix.is_empty() {
+                        id.to_string()
+                    } else {
+                        format!("{prefix}.{id}")
+                    };
+
+                    do_flatten(&key, value, lines)?;
+                }
+            }
+            _ => {
+                // unwrap(): validate_shape ensures that all the leaves are scalars
+                lines.push((prefix.to_owned(), scalar_to_string(t).unwrap()));
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut lines = Vec::new();
+    do_flatten("", t, &mut lines)?;
+    // Flattening can mix up the order of keys, as `.` isn't the smallest character.
+    lines.sort();
+
+    if let Some(pair) = lines.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(
+            ExportErrorData::DuplicateKey(ExportFormat::Properties, pair[0].0.clone()).into(),
+        );
+    }
+
+    Ok(lines)
+}
+
+pub(super) fn to_string(t: &RichTerm) -> Result<String, ExportError> {
+    validate_shape(t)?;
+
+    Ok(flatten(t)?
+        .into_iter()
+        .map(|(key, value)| format!("{}={}\n", escape(&key, true), escape(&value, false)))
+        .collect())
+}
+
+fn escape(s: &str, is_key: bool) -> String {
+    let mut result = String::with_capacity(s.len());
+
+    for (index, c) in s.chars().enumerate() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\x0C' => result.push_str("\\f"),
+            // Leading whitespace is always skipped, and whitespace terminates a key.
+            ' ' if is_key || index == 0 => result.push_str("\\ "),
+            '=' | ':' | '#' | '!' if is_key || index == 0 => {
+                result.push('\\');
+                result.push(c);
+            }
+            c if c.is_ascii() && !c.is_ascii_control() => result.push(c),
+            c => {
+                let mut buf = [0u16; 2];
+                for unit in c.encode_utf16(&mut buf) {
+                    result.push_str(&format!("\\u{unit:04X}"));
+                }
+            }
+        }
+    }
+
+    result
+}
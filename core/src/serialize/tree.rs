@@ -0,0 +1,200 @@
+//! Multi-file export: serialization of a record mapping relative file paths to values.
+//!
+//! For example, the following value describes two files, `app/config.yaml` and `app/.env`:
+//!
+//! ```nickel
+//! {
+//!   "app/config.yaml" = { port = 80 },
+//!   "app/.env" = { PORT = "80" },
+//! }
+//! ```
+//!
+//! The format of each file is inferred from its extension (see [ExportFormat::from_path]), and
+//! defaults to a format provided by the caller. It can also be given explicitly by using a record
+//! with exactly two fields, `format`, an enum tag as accepted by `std.serialize` (or `'Text`), and
+//! `content`, as the value of the file:
+//!
+//! ```nickel
+//! {
+//!   "run" = { format = 'Text, content = "#!/bin/sh\necho hello\n" },
+//! }
+//! ```
+//!
+//! Paths must be relative and can't contain `..` components, so that the files are always written
+//! within the output directory. They are normalized, so that `a.json` and `./a.json` are detected
+//! as the same file. Symbolic links can't be checked before the files are written, as they depend
+//! on the content of the output directory: see [check_output_dir].
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use super::{sorted_fields, validate, ExportFormat, NickelPointerElem};
+use crate::{
+    error::{Error, ExportError, ExportErrorData, IOError},
+    identifier::Ident,
+    term::{RichTerm, Term},
+};
+
+/// The name of the field holding the format of a file, when given explicitly.
+pub const FORMAT_FIELD: &str = "format";
+/// The name of the field holding the content of a file, when the format is given explicitly.
+pub const CONTENT_FIELD: &str = "content";
+
+/// A file of a multi-file export.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportedFile {
+    /// The path of the file, relative to the output directory.
+    pub path: PathBuf,
+    /// The format used to serialize the file.
+    pub format: ExportFormat,
+    /// The content of the file.
+    pub value: RichTerm,
+}
+
+/// Extracts and validates the files of a multi-file export from a fully evaluated term.
+/// `default_format` is used for files whose format can't be inferred from their extension.
+///
+/// All the files are validated for serialization (see [validate]), and their paths are checked
+/// not to conflict with each other, so that an error is reported before any file is written.
+pub fn files(
+    rt: &RichTerm,
+    default_format: ExportFormat,
+) -> Result<Vec<ExportedFile>, ExportError> {
+    let Term::Record(record) = rt.as_ref() else {
+        return Err(ExportErrorData::NotAFileTree(rt.clone()).into());
+    };
+
+    let files = sorted_fields(record)?
+        .into_iter()
+        .map(|(id, value)| {
+            let path = PathBuf::from(id.label());
+
+            if !is_safe(&path) {
+                return Err(ExportErrorData::InvalidOutputPath(id.to_string()).into());
+            }
+
+            let path = normalize(&path);
+
+            let (format, value) = match explicit_format(value) {
+                Some((format, content)) => (format, content),
+                None => (
+                    ExportFormat::from_path(&path).unwrap_or(default_format),
+                    value,
+                ),
+            };
+
+            validate(format, value).map_err(|mut err| {
+                // `validate` returns a fully reconstructed path, to which we prepend the file.
+                err.path.0.insert(0, NickelPointerElem::Field(id));
+                err
+            })?;
+
+            Ok((
+                id,
+                ExportedFile {
+                    path,
+                    format,
+                    value: value.clone(),
+                },
+            ))
+        })
+        .collect::<Result<Vec<_>, ExportError>>()?;
+
+    check_conflicts(&files)?;
+
+    Ok(files.into_iter().map(|(_, file)| file).collect())
+}
+
+/// Checks that no two files have the same path, and that no file is written at the path of a
+/// directory containing other files. `files` are given with the field defining them.
+fn check_conflicts(files: &[(Ident, ExportedFile)]) -> Result<(), ExportError> {
+    let conflict = |field1: &Ident, field2: &Ident| {
+        ExportErrorData::ConflictingOutputPaths(field1.to_string(), field2.to_string()).into()
+    };
+
+    let mut paths = BTreeMap::new();
+
+    for (field, file) in files {
+        if let Some(other) = paths.insert(file.path.as_path(), field) {
+            return Err(conflict(other, field));
+        }
+    }
+
+    for (field, file) in files {
+        if let Some(other) = file.path.ancestors().skip(1).find_map(|dir| paths.get(dir)) {
+            return Err(conflict(other, field));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that writing the files under `output_dir` doesn't follow a symbolic link outside of
+/// it. This depends on the content of the output directory, and is thus checked right before
+/// writing the files.
+pub fn check_output_dir(output_dir: &Path, files: &[ExportedFile]) -> Result<(), Error> {
+    let root = fs::canonicalize(output_dir).map_err(IOError::from)?;
+
+    for file in files {
+        let mut current = output_dir.to_path_buf();
+
+        for component in file.path.components() {
+            current.push(component);
+
+            // If this part of the path doesn't exist, the rest of it doesn't either.
+            let Ok(metadata) = fs::symlink_metadata(&current) else {
+                break;
+            };
+
+            // A dangling symbolic link can't be resolved, but writing the file would create its
+            // target.
+            if metadata.is_symlink()
+                && !fs::canonicalize(&current).is_ok_and(|target| target.starts_with(&root))
+            {
+                return Err(ExportError::from(ExportErrorData::OutputPathEscapes(
+                    file.path.to_string_lossy().into_owned(),
+                ))
+                .into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// If a file is given as `{ format = <tag>, content = <value> }`, returns the format and the
+/// content.
+fn explicit_format(value: &RichTerm) -> Option<(ExportFormat, &RichTerm)> {
+    let Term::Record(record) = value.as_ref() else {
+        return None;
+    };
+
+    match sorted_fields(record).ok()?.as_slice() {
+        [(content_id, content), (format_id, format)]
+            if content_id.label() == CONTENT_FIELD && format_id.label() == FORMAT_FIELD =>
+        {
+            match format.as_ref() {
+                Term::Enum(tag) => ExportFormat::from_tag(tag.label()).map(|f| (f, *content)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Removes the `.` components of a safe path (see [is_safe]), as well as redundant separators.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect()
+}
+
+/// A path is safe if it's a non-empty relative path which doesn't go up the directory tree.
+fn is_safe(path: &Path) -> bool {
+    path.components().any(|c| matches!(c, Component::Normal(_)))
+        && path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
@@ -0,0 +1,99 @@
+//! A plain representation of fully evaluated values.
+//!
+//! Terms are reference-counted with [std::rc::Rc], so they can't be sent to another thread. A
+//! [Value] holds the same data as an exportable term, that is a tree of records, arrays and
+//! scalars, but owns it, so that values evaluated by different virtual machines on different
+//! threads can be gathered and serialized together. See
+//! [crate::program::Program::eval_full_for_export_parallel].
+use super::{with_elem, NickelPointerElem};
+use crate::{
+    error::{ExportError, ExportErrorData},
+    identifier::Ident,
+    term::{
+        array::{Array, ArrayAttrs},
+        make as mk_term,
+        record::RecordData,
+        Number, RichTerm, Term,
+    },
+};
+
+/// A fully evaluated value which can be serialized. See the module documentation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Num(Number),
+    Str(String),
+    Enum(Ident),
+    Array(Vec<Value>),
+    /// The fields of a record, in the order of the original record.
+    Record(Vec<(Ident, Value)>),
+}
+
+impl Value {
+    /// Converts a fully evaluated term, as returned by
+    /// [crate::program::Program::eval_full_for_export], to a value. Optional fields without
+    /// definition and fields marked `not_exported` are skipped, as when serializing. Fails on
+    /// terms that can't be serialized, such as functions.
+    pub fn from_term(rt: &RichTerm) -> Result<Self, ExportError> {
+        let value = match rt.as_ref() {
+            Term::Null => Value::Null,
+            Term::Bool(b) => Value::Bool(*b),
+            Term::Num(n) => Value::Num(n.clone()),
+            Term::Str(s) => Value::Str(s.to_string()),
+            Term::Enum(id) => Value::Enum(id.ident()),
+            Term::Array(elts, _) => Value::Array(
+                elts.iter()
+                    .enumerate()
+                    .map(|(index, elt)| {
+                        Value::from_term(elt)
+                            .map_err(|err| with_elem(err, NickelPointerElem::Index(index)))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Term::Record(record) => Value::Record(
+                record
+                    .iter_serializable()
+                    .map(|field| {
+                        let (id, value) = field.map_err(|err| {
+                            ExportErrorData::Other(format!(
+                                "missing field definition for `{}`",
+                                err.id
+                            ))
+                        })?;
+
+                        Value::from_term(value)
+                            .map(|value| (id, value))
+                            .map_err(|err| with_elem(err, NickelPointerElem::Field(id)))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            _ => return Err(ExportErrorData::NonSerializable(rt.clone()).into()),
+        };
+
+        Ok(value)
+    }
+}
+
+impl From<Value> for RichTerm {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => Term::Null.into(),
+            Value::Bool(b) => Term::Bool(b).into(),
+            Value::Num(n) => Term::Num(n).into(),
+            Value::Str(s) => mk_term::string(s),
+            Value::Enum(id) => Term::Enum(id.into()).into(),
+            Value::Array(elts) => Term::Array(
+                elts.into_iter().map(RichTerm::from).collect::<Array>(),
+                ArrayAttrs::new(),
+            )
+            .into(),
+            Value::Record(fields) => Term::Record(RecordData::with_field_values(
+                fields
+                    .into_iter()
+                    .map(|(id, value)| (id.into(), RichTerm::from(value))),
+            ))
+            .into(),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Serialization to XML documents.
+//!
+//! The exported value must be a record with exactly one field, which is the root element of the
+//! document. The value of an element is mapped as follows:
+//!
+//! - a scalar value is the text content of the element;
+//! - `null` is an empty element;
+//! - in a record, fields whose name starts with `@` are attributes, the field `$text` is the text
+//!   content, and the other fields are child elements;
+//! - an array is a sequence of elements with the same name, one for each item.
+//!
+//! For example, `{ server = { "@port" = 80, host = ["a", "b"] } }` is exported as:
+//!
+//! ```xml
+//! <?xml version="1.0" encoding="UTF-8"?>
+//! <server port="80">
+//!   <host>a</host>
+//!   <host>b</host>
+//! </server>
+//! ```
+use super::{scalar_to_string, sorted_fields, with_elem, ExportFormat, NickelPointerElem};
+use crate::{
+    error::{ExportError, ExportErrorData},
+    identifier::Ident,
+    term::{record::RecordData, RichTerm, Term},
+};
+
+/// The prefix of fields representing attributes.
+const ATTRIBUTE_PREFIX: char = '@';
+/// The name of the field representing the text content of an element.
+const TEXT_FIELD: &str = "$text";
+
+/// Check that a term is a record with exactly one field, and that all element and attribute
+/// names are valid.
+pub(super) fn validate_shape(t: &RichTerm) -> Result<(), ExportError> {
+    let (name, value) = root(t)?;
+    validate_element(name, value).map_err(|err| with_elem(err, NickelPointerElem::Field(name)))
+}
+
+pub(super) fn to_string(t: &RichTerm) -> Result<String, ExportError> {
+    validate_shape(t)?;
+
+    let (name, value) = root(t)?;
+    let mut result = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    write_element(&mut result, name.label(), value, 0)?;
+
+    Ok(result)
+}
+
+fn root(t: &RichTerm) -> Result<(Ident, &RichTerm), ExportError> {
+    let Term::Record(record) = t.as_ref() else {
+        return Err(ExportErrorData::NotARecord(ExportFormat::Xml, t.clone()).into());
+    };
+
+    match sorted_fields(record)?.as_slice() {
+        [(name, value)] if !matches!(value.as_ref(), Term::Array(..)) => Ok((*name, *value)),
+        _ => Err(ExportErrorData::InvalidXmlRoot(t.clone()).into()),
+    }
+}
+
+/// Validate the value of an element. `name` is the name of the element, or of the field holding
+/// an array of elements.
+fn validate_element(name: Ident, value: &RichTerm) -> Result<(), ExportError> {
+    check_name(name.label())?;
+
+    match value.as_ref() {
+        Term::Array(array, _) => array.iter().enumerate().try_for_each(|(index, item)| {
+            if let Term::Array(..) = item.as_ref() {
+                Err(with_elem(
+                    ExportErrorData::UnsupportedNesting(ExportFormat::Xml, item.clone()).into(),
+                    NickelPointerElem::Index(index),
+                ))
+            } else {
+                validate_element(name, item)
+                    .map_err(|err| with_elem(err, NickelPointerElem::Index(index)))
+            }
+        }),
+        Term::Record(record) => sorted_fields(record)?
+            .into_iter()
+            .try_for_each(|(id, value)| {
+                let result = match id.label().strip_prefix(ATTRIBUTE_PREFIX) {
+                    Some(attr) => check_name(attr).and_then(|()| check_scalar(value)),
+                    None if id.label() == TEXT_FIELD => check_scalar(value),
+                    None => validate_element(id, value),
+                };
+
+                result.map_err(|err| with_elem(err, NickelPointerElem::Field(id)))
+            }),
+        _ => Ok(()),
+    }
+}
+
+fn check_scalar(value: &RichTerm) -> Result<(), ExportError> {
+    if scalar_to_string(value).is_some() {
+        Ok(())
+    } else {
+        Err(ExportErrorData::UnsupportedNesting(ExportFormat::Xml, value.clone()).into())
+    }
+}
+
+fn check_name(name: &str) -> Result<(), ExportError> {
+    let mut chars = name.chars();
+    let is_valid = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'));
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(ExportErrorData::InvalidKey(ExportFormat::Xml, name.to_owned()).into())
+    }
+}
+
+/// Write the element(s) `name` with the given value, indented by `indent` levels. Arrays are
+/// written as several elements.
+fn write_element(
+    out: &mut String,
+    name: &str,
+    value: &RichTerm,
+    indent: usize,
+) -> Result<(), ExportError> {
+    let padding = "  ".repeat(indent);
+
+    match value.as_ref() {
+        Term::Array(array, _) => {
+            for item in array.iter() {
+                write_element(out, name, item, indent)?;
+            }
+        }
+        Term::Null => out.push_str(&format!("{padding}<{name}/>\n")),
+        Term::Record(record) => write_record(out, name, record, indent)?,
+        _ => {
+            // unwrap(): validate_shape ensures that other values are scalars
+            let text = scalar_to_string(value).unwrap();
+            out.push_str(&format!(
+                "{padding}<{name}>{}</{name}>\n",
+                escape(&text, false)
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn write_record(
+    out: &mut String,
+    name: &str,
+    record: &RecordData,
+    indent: usize,
+) -> Result<(), ExportError> {
+    let padding = "  ".repeat(indent);
+    let mut attributes = String::new();
+    let mut text = None;
+    let mut children = Vec::new();
+
+    for (id, value) in sorted_fields(record)? {
+        // unwrap()s: validate_shape ensures that attributes and text content are scalars
+        match id.label().strip_prefix(ATTRIBUTE_PREFIX) {
+            Some(attr) => {
+                let value = scalar_to_string(value).unwrap();
+                attributes.push_str(&format!(" {attr}=\"{}\"", escape(&value, true)));
+            }
+            None if id.label() == TEXT_FIELD => text = Some(scalar_to_string(value).unwrap()),
+            None => children.push((id, value)),
+        }
+    }
+
+    out.push_str(&format!("{padding}<{name}{attributes}"));
+
+    match (text, children.is_empty()) {
+        (None, true) => out.push_str("/>\n"),
+        (Some(text), true) => out.push_str(&format!(">{}</{name}>\n", escape(&text, false))),
+        (text, false) => {
+            out.push_str(">\n");
+
+            if let Some(text) = text {
+                out.push_str(&format!("{padding}  {}\n", escape(&text, false)));
+            }
+
+            for (id, value) in children {
+                write_element(out, id.label(), value, indent + 1)?;
+            }
+
+            out.push_str(&format!("{padding}</{name}>\n"));
+        }
+    }
+
+    Ok(())
+}
+
+fn escape(s: &str, is_attribute: bool) -> String {
+    let mut result = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' if is_attribute => result.push_str("&quot;"),
+            '\n' if is_attribute => result.push_str("&#10;"),
+            '\r' => result.push_str("&#13;"),
+            '\t' if is_attribute => result.push_str("&#9;"),
+            c => result.push(c),
+        }
+    }
+
+    result
+}
@@ -0,0 +1,625 @@
+//! Deserialization of YAML sources with position information.
+//!
+//! `serde_yaml` doesn't expose the location of deserialized values. We drive the event-based
+//! parser of `libyaml` that `serde_yaml` is built on instead, which provides the start and the end
+//! of each node, and build the Nickel terms ourselves. Plain scalars are resolved to null,
+//! booleans, numbers or strings following the same rules as `serde_yaml`, so that the resulting
+//! terms don't depend on the path used to deserialize a YAML value.
+use std::{collections::HashMap, ffi::CStr, mem::MaybeUninit, ptr::addr_of_mut};
+
+use codespan::ByteIndex;
+
+use crate::{
+    error::ParseError,
+    files::FileId,
+    identifier::LocIdent,
+    position::RawSpan,
+    term::{record::RecordData, IndexMap, Number, RichTerm, Term},
+};
+
+/// The maximum nesting of sequences and mappings. The loader is recursive, so deeper sources would
+/// overflow the stack. This is the same limit as `serde_yaml`.
+const MAX_DEPTH: usize = 128;
+
+/// The tags of the YAML core schema which change how a scalar is resolved.
+const BOOL_TAG: &str = "tag:yaml.org,2002:bool";
+const INT_TAG: &str = "tag:yaml.org,2002:int";
+const FLOAT_TAG: &str = "tag:yaml.org,2002:float";
+const NULL_TAG: &str = "tag:yaml.org,2002:null";
+
+/// Deserialize the documents of a YAML source provided as a string, together with the file id
+/// of this source. As with `serde_yaml`, a source without any document is deserialized as a single
+/// `null` document.
+pub fn from_str(s: &str, file_id: FileId) -> Result<Vec<RichTerm>, ParseError> {
+    let mut loader = Loader {
+        src: s,
+        file_id,
+        parser: Parser::new(s),
+        anchors: HashMap::new(),
+        depth: 0,
+    };
+
+    let mut documents = Vec::new();
+
+    loop {
+        let event = loader.next_event()?;
+
+        match event.kind {
+            EventKind::StreamStart | EventKind::DocumentEnd => (),
+            EventKind::DocumentStart => {
+                let event = loader.next_event()?;
+                documents.push(loader.node(event)?);
+            }
+            EventKind::StreamEnd => break,
+            _ => return Err(loader.error("unexpected event", event.start)),
+        }
+    }
+
+    if documents.is_empty() {
+        documents.push(RichTerm::new(Term::Null, loader.span(0, 0).into()));
+    }
+
+    Ok(documents)
+}
+
+/// A parsing event, together with the byte offsets of the start and the end of the
+/// corresponding piece of source.
+struct Event {
+    kind: EventKind,
+    start: usize,
+    end: usize,
+}
+
+enum EventKind {
+    StreamStart,
+    StreamEnd,
+    DocumentStart,
+    DocumentEnd,
+    Alias(String),
+    Scalar {
+        value: String,
+        style: unsafe_libyaml::yaml_scalar_style_t,
+        tag: Option<String>,
+        anchor: Option<String>,
+    },
+    SequenceStart {
+        anchor: Option<String>,
+        flow: bool,
+    },
+    SequenceEnd,
+    MappingStart {
+        anchor: Option<String>,
+        flow: bool,
+    },
+    MappingEnd,
+}
+
+/// A safe wrapper around the event-based parser of `libyaml`.
+struct Parser<'a> {
+    /// The parser is boxed because it holds a pointer to itself once the input is set, so it must
+    /// not move.
+    sys: Box<unsafe_libyaml::yaml_parser_t>,
+    /// The parser holds a pointer to the source, which must outlive it.
+    _src: std::marker::PhantomData<&'a str>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str) -> Self {
+        let mut parser = Box::new(MaybeUninit::<unsafe_libyaml::yaml_parser_t>::uninit());
+
+        // Safety: `yaml_parser_initialize` initializes the whole structure, and only fails if it
+        // can't allocate its buffers. The source outlives the parser thanks to `_src`.
+        let sys = unsafe {
+            let ptr = parser.as_mut_ptr();
+
+            if unsafe_libyaml::yaml_parser_initialize(ptr).fail {
+                panic!("libyaml couldn't allocate a parser");
+            }
+
+            unsafe_libyaml::yaml_parser_set_encoding(ptr, unsafe_libyaml::YAML_UTF8_ENCODING);
+            unsafe_libyaml::yaml_parser_set_input_string(ptr, src.as_ptr(), src.len() as u64);
+            Box::from_raw(Box::into_raw(parser).cast::<unsafe_libyaml::yaml_parser_t>())
+        };
+
+        Parser {
+            sys,
+            _src: std::marker::PhantomData,
+        }
+    }
+
+    /// Return the next event, or an error message together with the byte offset of the error.
+    fn next(&mut self) -> Result<Event, (String, usize)> {
+        let mut event = MaybeUninit::<unsafe_libyaml::yaml_event_t>::uninit();
+
+        // Safety: the parser is initialized, and the event is initialized by `yaml_parser_parse`
+        // when it succeeds. The strings of the event are copied before it is deleted.
+        unsafe {
+            let parser = addr_of_mut!(*self.sys);
+
+            if self.sys.error != unsafe_libyaml::YAML_NO_ERROR
+                || unsafe_libyaml::yaml_parser_parse(parser, event.as_mut_ptr()).fail
+            {
+                return Err(self.error());
+            }
+
+            let event = event.as_mut_ptr();
+            let result = Event {
+                kind: event_kind(&*event),
+                start: (*event).start_mark.index as usize,
+                end: (*event).end_mark.index as usize,
+            };
+            unsafe_libyaml::yaml_event_delete(event);
+
+            Ok(result)
+        }
+    }
+
+    fn error(&self) -> (String, usize) {
+        // Safety: the problem and the context are either null or static C strings.
+        let (problem, context) = unsafe {
+            (
+                c_string(self.sys.problem.cast()),
+                c_string(self.sys.context.cast()),
+            )
+        };
+
+        let msg = match (problem, context) {
+            (Some(problem), Some(context)) => format!("{problem}, {context}"),
+            (Some(problem), None) => problem,
+            (None, _) => String::from("invalid YAML"),
+        };
+
+        (msg, self.sys.problem_mark.index as usize)
+    }
+}
+
+impl Drop for Parser<'_> {
+    fn drop(&mut self) {
+        // Safety: the parser was initialized in `Parser::new`.
+        unsafe { unsafe_libyaml::yaml_parser_delete(addr_of_mut!(*self.sys)) }
+    }
+}
+
+/// Convert a `libyaml` event to an owned [EventKind].
+///
+/// # Safety
+///
+/// `event` must have been initialized by `yaml_parser_parse` and not yet deleted.
+unsafe fn event_kind(event: &unsafe_libyaml::yaml_event_t) -> EventKind {
+    match event.type_ {
+        unsafe_libyaml::YAML_STREAM_START_EVENT => EventKind::StreamStart,
+        unsafe_libyaml::YAML_DOCUMENT_START_EVENT => EventKind::DocumentStart,
+        unsafe_libyaml::YAML_DOCUMENT_END_EVENT => EventKind::DocumentEnd,
+        unsafe_libyaml::YAML_ALIAS_EVENT => {
+            EventKind::Alias(c_string(event.data.alias.anchor).unwrap_or_default())
+        }
+        unsafe_libyaml::YAML_SCALAR_EVENT => {
+            let scalar = event.data.scalar;
+            let value = std::slice::from_raw_parts(scalar.value, scalar.length as usize);
+
+            EventKind::Scalar {
+                value: String::from_utf8_lossy(value).into_owned(),
+                style: scalar.style,
+                tag: c_string(scalar.tag),
+                anchor: c_string(scalar.anchor),
+            }
+        }
+        unsafe_libyaml::YAML_SEQUENCE_START_EVENT => EventKind::SequenceStart {
+            anchor: c_string(event.data.sequence_start.anchor),
+            flow: event.data.sequence_start.style == unsafe_libyaml::YAML_FLOW_SEQUENCE_STYLE,
+        },
+        unsafe_libyaml::YAML_SEQUENCE_END_EVENT => EventKind::SequenceEnd,
+        unsafe_libyaml::YAML_MAPPING_START_EVENT => EventKind::MappingStart {
+            anchor: c_string(event.data.mapping_start.anchor),
+            flow: event.data.mapping_start.style == unsafe_libyaml::YAML_FLOW_MAPPING_STYLE,
+        },
+        unsafe_libyaml::YAML_MAPPING_END_EVENT => EventKind::MappingEnd,
+        // `yaml_parser_parse` only produces an empty event after the end of the stream, which we
+        // never ask for.
+        _ => EventKind::StreamEnd,
+    }
+}
+
+/// Copy a nullable, null-terminated C string.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a valid null-terminated string.
+unsafe fn c_string(ptr: *const u8) -> Option<String> {
+    (!ptr.is_null()).then(|| CStr::from_ptr(ptr.cast()).to_string_lossy().into_owned())
+}
+
+struct Loader<'a> {
+    src: &'a str,
+    file_id: FileId,
+    parser: Parser<'a>,
+    /// The values of the anchors defined so far.
+    anchors: HashMap<String, RichTerm>,
+    /// The number of sequences and mappings enclosing the current node.
+    depth: usize,
+}
+
+impl Loader<'_> {
+    fn span(&self, start: usize, end: usize) -> RawSpan {
+        RawSpan {
+            src_id: self.file_id,
+            start: ByteIndex(start as u32),
+            end: ByteIndex(end as u32),
+        }
+    }
+
+    fn error(&self, msg: &str, start: usize) -> ParseError {
+        let start = start.min(self.src.len());
+        let end = self.src[start..]
+            .chars()
+            .next()
+            .map_or(start, |c| start + c.len_utf8());
+
+        ParseError::ExternalFormatError(
+            String::from("yaml"),
+            msg.to_owned(),
+            Some(self.span(start, end)),
+        )
+    }
+
+    fn next_event(&mut self) -> Result<Event, ParseError> {
+        self.parser
+            .next()
+            .map_err(|(msg, offset)| self.error(&msg, offset))
+    }
+
+    /// Build the term corresponding to the node starting with `event`.
+    fn node(&mut self, event: Event) -> Result<RichTerm, ParseError> {
+        let Event { kind, start, end } = event;
+
+        let (term, end, anchor) = match kind {
+            EventKind::Scalar {
+                value,
+                style,
+                tag,
+                anchor,
+            } => {
+                let end = self.scalar_end(style, start, end);
+                let term = scalar(value, style, tag).map_err(|msg| self.error(&msg, start))?;
+                (term, end, anchor)
+            }
+            EventKind::Alias(name) => {
+                let value = self
+                    .anchors
+                    .get(&name)
+                    .ok_or_else(|| self.error("unknown anchor", start))?;
+
+                return Ok(value.clone().with_pos(self.span(start, end).into()));
+            }
+            EventKind::SequenceStart { anchor, flow } => {
+                self.enter(start)?;
+
+                let mut elts = Vec::new();
+                let mut last_end = start;
+
+                let seq_end = loop {
+                    let event = self.next_event()?;
+
+                    if let EventKind::SequenceEnd = event.kind {
+                        break event.end;
+                    }
+
+                    let elt = self.node(event)?;
+                    last_end = elt.pos.unwrap().end.to_usize();
+                    elts.push(elt);
+                };
+
+                self.depth -= 1;
+
+                (
+                    Term::Array(elts.into_iter().collect(), Default::default()),
+                    if flow { seq_end } else { last_end },
+                    anchor,
+                )
+            }
+            EventKind::MappingStart { anchor, flow } => {
+                self.enter(start)?;
+
+                let mut fields = IndexMap::new();
+                let mut last_end = start;
+
+                let map_end = loop {
+                    let event = self.next_event()?;
+
+                    let key = match event.kind {
+                        EventKind::MappingEnd => break event.end,
+                        EventKind::Scalar { value, style, .. } => {
+                            let key_end = self.scalar_end(style, event.start, event.end);
+                            LocIdent::new_with_pos(value, self.span(event.start, key_end).into())
+                        }
+                        _ => return Err(self.error("mapping keys must be strings", event.start)),
+                    };
+
+                    let event = self.next_event()?;
+                    let value = self.node(event)?;
+                    last_end = value.pos.unwrap().end.to_usize();
+                    fields.insert(key, value);
+                };
+
+                self.depth -= 1;
+
+                (
+                    Term::Record(RecordData::with_field_values(fields)),
+                    if flow { map_end } else { last_end },
+                    anchor,
+                )
+            }
+            _ => return Err(self.error("unexpected event", start)),
+        };
+
+        let rt = RichTerm::new(term, self.span(start, end).into());
+
+        if let Some(anchor) = anchor {
+            self.anchors.insert(anchor, rt.clone());
+        }
+
+        Ok(rt)
+    }
+
+    /// Enter a sequence or a mapping starting at `start`, checking that the nesting limit isn't
+    /// exceeded.
+    fn enter(&mut self, start: usize) -> Result<(), ParseError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("recursion limit exceeded", start));
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// The end of a scalar. The end reported by `libyaml` for block scalars includes the trailing
+    /// line breaks and indentation, which we don't consider to be part of the value.
+    fn scalar_end(
+        &self,
+        style: unsafe_libyaml::yaml_scalar_style_t,
+        start: usize,
+        end: usize,
+    ) -> usize {
+        match style {
+            unsafe_libyaml::YAML_LITERAL_SCALAR_STYLE
+            | unsafe_libyaml::YAML_FOLDED_SCALAR_STYLE => {
+                start + self.src[start..end].trim_end().len()
+            }
+            _ => end,
+        }
+    }
+}
+
+/// Convert a scalar to a Nickel term. Plain scalars (i.e. unquoted and not in block style) are
+/// resolved to null, booleans, numbers or strings, unless they are tagged. Tags of the YAML core
+/// schema force the resolution of any scalar, local tags are rejected, and other scalars are
+/// strings.
+fn scalar(
+    value: String,
+    style: unsafe_libyaml::yaml_scalar_style_t,
+    tag: Option<String>,
+) -> Result<Term, String> {
+    let expected = |what: &str| format!("invalid value `{value}`, expected {what}");
+
+    match tag.as_deref() {
+        Some(BOOL_TAG) => parse_bool(&value)
+            .map(Term::Bool)
+            .ok_or_else(|| expected("a boolean")),
+        Some(INT_TAG) => parse_int(&value)
+            .ok_or_else(|| expected("an integer"))
+            .and_then(number),
+        Some(FLOAT_TAG) => parse_float(&value)
+            .ok_or_else(|| expected("a float"))
+            .and_then(number),
+        Some(NULL_TAG) if is_null(&value) => Ok(Term::Null),
+        Some(NULL_TAG) => Err(expected("null")),
+        // `serde_yaml` deserializes scalars with a local tag, such as `!foo`, as enum variants,
+        // which Nickel doesn't support.
+        Some(tag) if tag.starts_with('!') => Err(format!("unsupported tag `{tag}`")),
+        None if style == unsafe_libyaml::YAML_PLAIN_SCALAR_STYLE => untagged_scalar(value),
+        _ => Ok(Term::Str(value.into())),
+    }
+}
+
+/// Resolve a plain, untagged scalar.
+fn untagged_scalar(value: String) -> Result<Term, String> {
+    if value.is_empty() || is_null(&value) {
+        return Ok(Term::Null);
+    }
+
+    if let Some(b) = parse_bool(&value) {
+        return Ok(Term::Bool(b));
+    }
+
+    let float = if digits_but_not_number(&value) {
+        None
+    } else {
+        parse_int(&value).or_else(|| parse_float(&value))
+    };
+
+    match float {
+        Some(float) => number(float),
+        None => Ok(Term::Str(value.into())),
+    }
+}
+
+fn number(float: f64) -> Result<Term, String> {
+    Number::try_from_float_simplest(float)
+        .map(Term::Num)
+        .map_err(|_| {
+            format!(
+                "couldn't convert {float} to a Nickel number: \
+                Nickel doesn't support NaN nor infinity"
+            )
+        })
+}
+
+fn is_null(value: &str) -> bool {
+    matches!(value, "~" | "null" | "Null" | "NULL")
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "True" | "TRUE" => Some(true),
+        "false" | "False" | "FALSE" => Some(false),
+        _ => None,
+    }
+}
+
+/// Leading zeros followed by digits denote a string according to the YAML 1.2 spec.
+fn digits_but_not_number(value: &str) -> bool {
+    let value = value.strip_prefix(['-', '+']).unwrap_or(value);
+    value.len() > 1 && value.starts_with('0') && value[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Parse a decimal, hexadecimal (`0x`), octal (`0o`) or binary (`0b`) integer, with an optional
+/// sign. As for other formats, the result is converted to a 64 bits float.
+fn parse_int(value: &str) -> Option<f64> {
+    let (negative, unsigned) = match value.as_bytes().first() {
+        Some(b'-') => (true, &value[1..]),
+        Some(b'+') => (false, &value[1..]),
+        _ => (false, value),
+    };
+
+    let (radix, digits) = [("0x", 16), ("0o", 8), ("0b", 2)]
+        .into_iter()
+        .find_map(|(prefix, radix)| unsigned.strip_prefix(prefix).map(|digits| (radix, digits)))
+        .unwrap_or((10, unsigned));
+
+    // `from_str_radix` accepts a sign, which must not be repeated.
+    if digits.starts_with(['+', '-']) {
+        return None;
+    }
+
+    let magnitude = u128::from_str_radix(digits, radix).ok()? as f64;
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+fn parse_float(value: &str) -> Option<f64> {
+    let unsigned = match value.strip_prefix('+') {
+        Some(unsigned) if unsigned.starts_with(['+', '-']) => return None,
+        Some(unsigned) => unsigned,
+        None => value,
+    };
+
+    match unsigned {
+        ".inf" | ".Inf" | ".INF" => Some(f64::INFINITY),
+        "-.inf" | "-.Inf" | "-.INF" => Some(f64::NEG_INFINITY),
+        ".nan" | ".NaN" | ".NAN" => Some(f64::NAN),
+        _ => unsigned
+            .parse::<f64>()
+            .ok()
+            .filter(|float| float.is_finite()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{files::Files, position::TermPos};
+
+    fn parse(s: &str) -> Result<Vec<RichTerm>, ParseError> {
+        let mut files = Files::new();
+        let file_id = files.add("<test>", s.to_owned());
+        from_str(s, file_id)
+    }
+
+    /// Check that the loader produces the same term as `std.deserialize`, which goes through
+    /// `serde_yaml`.
+    #[track_caller]
+    fn assert_same_as_serde(s: &str) {
+        let expected: RichTerm = serde_yaml::from_str(s).unwrap();
+        let mut documents = parse(s).unwrap();
+        assert_eq!(documents.len(), 1);
+        assert_eq!(
+            documents.pop().unwrap().without_pos(),
+            expected.without_pos()
+        );
+    }
+
+    fn span(pos: TermPos) -> (u32, u32) {
+        let span = pos.unwrap();
+        (span.start.0, span.end.0)
+    }
+
+    #[test]
+    fn values() {
+        assert_same_as_serde("");
+        assert_same_as_serde(
+            "a: 1\nb: [true, ~, 'x', \"y\\n\"]\nc:\n  - 0x1f\n  - -2.5e3\n  - 012",
+        );
+        assert_same_as_serde("a: &anchor {b: null, c: +12}\nd: *anchor\ne: !!str 1\nf:");
+        assert_same_as_serde("text: |\n  first\n  second\nfolded: >\n  a\n  b\n");
+        assert_same_as_serde("- yes\n- no\n- 1_000\n- .5\n- é");
+        assert_same_as_serde("- !!int '12'\n- !!float 1\n- !!bool 'true'\n- !!null ~");
+    }
+
+    #[test]
+    fn documents() {
+        assert_eq!(parse("a: 1\n---\nb: 2\n").unwrap().len(), 2);
+        assert_eq!(parse("# just a comment\n").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn positions() {
+        let documents = parse("é: 'it''s'\nb:\n  - [1, \"x\"]\n  - 2\n").unwrap();
+        let Term::Record(record) = documents[0].as_ref() else {
+            panic!("expected a record");
+        };
+        let fields: Vec<_> = record.fields.iter().collect();
+
+        let (id, field) = fields[0];
+        assert_eq!(span(id.pos), (0, 2));
+        assert_eq!(span(field.value.as_ref().unwrap().pos), (4, 11));
+
+        let (_, field) = fields[1];
+        let value = field.value.as_ref().unwrap();
+        assert_eq!(span(value.pos), (17, 33));
+
+        let Term::Array(array, _) = value.as_ref() else {
+            panic!("expected an array");
+        };
+        assert_eq!(span(array.get(0).unwrap().pos), (19, 27));
+        assert_eq!(span(array.get(1).unwrap().pos), (32, 33));
+    }
+
+    #[test]
+    fn block_scalar_positions() {
+        let documents = parse("a: |\n  x\n  y\n\nb: 1\n").unwrap();
+        let Term::Record(record) = documents[0].as_ref() else {
+            panic!("expected a record");
+        };
+        let (_, field) = record.fields.iter().next().unwrap();
+        assert_eq!(span(field.value.as_ref().unwrap().pos), (3, 12));
+    }
+
+    #[test]
+    fn recursion_limit() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+
+        let err = parse(&nested(MAX_DEPTH + 1)).unwrap_err();
+        assert!(
+            matches!(&err, ParseError::ExternalFormatError(_, msg, _) if msg == "recursion limit exceeded")
+        );
+
+        // Used to overflow the stack.
+        assert!(parse(&"[".repeat(200_000)).is_err());
+        assert!(parse(&"- ".repeat(200_000)).is_err());
+    }
+
+    #[test]
+    fn errors() {
+        for s in [
+            "a: [1, 2",
+            "[1]: 2",
+            "a: .nan",
+            "a: *unknown",
+            "a: 'x",
+            "a: !!int x",
+            "a: !local 1",
+        ] {
+            assert!(parse(s).is_err(), "`{s}` should fail to parse");
+        }
+    }
+}
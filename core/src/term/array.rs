@@ -2,7 +2,7 @@ use nickel_lang_vector::Slice;
 
 use super::*;
 
-#[derive(Debug, Default, PartialEq, Clone)]
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ArrayAttrs {
     /// An array is closurized when each element is a [crate::term::Term::Closure] or a constant.
     ///
@@ -0,0 +1,155 @@
+//! Values provided by a program embedding Nickel.
+//!
+//! An embedder can extend the initial environment of a Nickel program with its own bindings (see
+//! [crate::program::Program::add_foreign_value] and
+//! [crate::program::Program::add_native_function]):
+//!
+//! - foreign values are opaque host values wrapped in a [Term::ForeignId]. They can't be
+//!   constructed, inspected or compared from Nickel code, but they can be stored in data
+//!   structures and passed back to native functions.
+//! - native functions are Rust callbacks that Nickel code calls like any other function. Their
+//!   arguments are fully evaluated before the callback is invoked.
+//!
+//! Each binding has a declared type, which the typechecker trusts: it isn't enforced at runtime,
+//! so native functions must check their arguments themselves.
+use std::{any::Any, fmt, rc::Rc};
+
+use super::{make as mk_term, Array, ArrayAttrs, RichTerm, Term, UnaryOp};
+use crate::{
+    identifier::LocIdent,
+    typ::{Type, TypeF},
+};
+
+/// The payload of a [Term::ForeignId]: an opaque value provided by the program embedding Nickel.
+///
+/// Foreign values are reference-counted, so that copying the term doesn't copy the value. Two
+/// payloads are equal only if they're the same value.
+#[derive(Clone)]
+pub struct ForeignIdPayload(Rc<dyn Any>);
+
+impl ForeignIdPayload {
+    pub fn new<T: Any>(value: T) -> Self {
+        ForeignIdPayload(Rc::new(value))
+    }
+
+    /// Returns the foreign value if it has type `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+}
+
+impl fmt::Debug for ForeignIdPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ForeignIdPayload({:p})", Rc::as_ptr(&self.0))
+    }
+}
+
+impl PartialEq for ForeignIdPayload {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// The Rust implementation of a native function. It's given the fully evaluated arguments, and
+/// returns either the result or an error message.
+pub type NativeFunctionImpl = dyn Fn(&[RichTerm]) -> Result<RichTerm, String>;
+
+/// A function implemented in Rust, which can be called from Nickel code.
+#[derive(Clone)]
+pub struct NativeFunction {
+    name: String,
+    arity: usize,
+    implementation: Rc<NativeFunctionImpl>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: impl Into<String>,
+        arity: usize,
+        implementation: impl Fn(&[RichTerm]) -> Result<RichTerm, String> + 'static,
+    ) -> Self {
+        NativeFunction {
+            name: name.into(),
+            arity,
+            implementation: Rc::new(implementation),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn call(&self, args: &[RichTerm]) -> Result<RichTerm, String> {
+        (self.implementation)(args)
+    }
+
+    /// Returns a Nickel function calling this native function, that is
+    ///
+    /// ```text
+    /// fun x1 .. xn => %native_call% (%force% [x1, .., xn])
+    /// ```
+    ///
+    /// A native function without arguments is evaluated once, when it's first accessed.
+    pub fn into_term(self) -> RichTerm {
+        let params: Vec<_> = (0..self.arity).map(|_| LocIdent::fresh()).collect();
+        let args = Term::Array(
+            params
+                .iter()
+                .map(|param| mk_term::var(*param))
+                .collect::<Array>(),
+            ArrayAttrs::default(),
+        );
+        let body = mk_term::op1(
+            UnaryOp::NativeCall(self),
+            mk_term::op1(
+                UnaryOp::Force {
+                    ignore_not_exported: false,
+                },
+                args,
+            ),
+        );
+
+        params
+            .into_iter()
+            .rev()
+            .fold(body, |body, param| RichTerm::from(Term::Fun(param, body)))
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Rc::ptr_eq(&self.implementation, &other.implementation)
+    }
+}
+
+/// A binding added by the embedder to the initial environment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForeignBinding {
+    pub id: LocIdent,
+    pub value: RichTerm,
+    /// The declared type of the binding, used by the typechecker.
+    pub typ: Type,
+}
+
+/// Returns the number of arguments of a function of type `typ`, that is the number of arrows at
+/// the top-level of the type, possibly under a `forall`.
+pub fn arity(typ: &Type) -> usize {
+    match &typ.typ {
+        TypeF::Arrow(_, codomain) => 1 + arity(codomain),
+        TypeF::Forall { body, .. } => arity(body),
+        _ => 0,
+    }
+}
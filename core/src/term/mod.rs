@@ -10,6 +10,7 @@
 //! It also features types and type annotations, and other typechecking or contracts-related
 //! constructs (label, symbols, etc.).
 pub mod array;
+pub mod foreign;
 pub mod pattern;
 pub mod record;
 pub mod string;
@@ -25,7 +26,7 @@ use crate::{
     error::{EvalError, ParseError},
     eval::{cache::CacheIndex, Environment},
     files::FileId,
-    identifier::LocIdent,
+    identifier::{Ident, LocIdent},
     impl_display_from_pretty,
     label::{Label, MergeLabel},
     match_sharedterm,
@@ -46,7 +47,7 @@ pub use malachite::{
     Integer, Rational,
 };
 
-use serde::{Deserialize, Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 // Because we use `IndexMap` for recors, consumer of Nickel (as a library) might have to
 // manipulate values of this type, so we re-export this type.
@@ -61,8 +62,7 @@ use std::{
     rc::Rc,
 };
 
-/// The payload of a `Term::ForeignId`.
-pub type ForeignIdPayload = u64;
+pub use foreign::{ForeignIdPayload, NativeFunction};
 
 /// The AST of a Nickel expression.
 ///
@@ -207,7 +207,7 @@ pub enum Term {
 
     /// An unresolved import.
     #[serde(skip)]
-    Import { path: OsString, format: InputFormat },
+    Import(Import),
 
     /// A resolved import (which has already been loaded and parsed).
     #[serde(skip)]
@@ -365,16 +365,7 @@ impl PartialEq for Term {
                 l0 == r0 && l1 == r1 && l2 == r2
             }
             (Self::Annotated(l0, l1), Self::Annotated(r0, r1)) => l0 == r0 && l1 == r1,
-            (
-                Self::Import {
-                    path: l0,
-                    format: l1,
-                },
-                Self::Import {
-                    path: r0,
-                    format: r1,
-                },
-            ) => l0 == r0 && l1 == r1,
+            (Self::Import(l0), Self::Import(r0)) => l0 == r0,
             (Self::ResolvedImport(l0), Self::ResolvedImport(r0)) => l0 == r0,
             (
                 Self::Type {
@@ -422,7 +413,7 @@ pub type Number = Rational;
 /// revertible cache elements at evaluation, which are devices used for the implementation of
 /// recursive records merging. See the [`crate::eval::merge`] and [`crate::eval`] modules for more
 /// details.
-#[derive(Debug, Eq, PartialEq, Clone, Default)]
+#[derive(Debug, Eq, PartialEq, Clone, Default, Serialize, Deserialize)]
 pub enum BindingType {
     #[default]
     Normal,
@@ -436,7 +427,7 @@ pub struct CustomContract(pub RichTerm);
 
 /// A runtime representation of a contract, as a term and a label ready to be applied via
 /// [BinaryOp::ContractApply].
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct RuntimeContract {
     /// The pending contract, which can be a function, a type, a [CustomContract] or a record.
     pub contract: RichTerm,
@@ -550,8 +541,19 @@ impl std::convert::TryFrom<LabeledType> for RuntimeContract {
     }
 }
 
+/// The target of an unresolved import.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Import {
+    /// An import of a file, given as a path relative to the importing file or to one of the
+    /// import paths, together with the format used to interpret the file.
+    Path { path: OsString, format: InputFormat },
+    /// An import of a package, given by the name under which it is declared in the dependencies
+    /// of the package manifest. See [crate::package].
+    Package { id: Ident },
+}
+
 /// The attributes of a enum variant.
-#[derive(Debug, Default, Eq, PartialEq, Clone)]
+#[derive(Debug, Default, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct EnumVariantAttrs {
     /// An enum variant is closurized if its argument is a [crate::term::Term::Closure] or a
     /// constant.
@@ -581,7 +583,7 @@ impl EnumVariantAttrs {
 }
 
 /// The attributes of a let binding.
-#[derive(Debug, Default, Eq, PartialEq, Clone)]
+#[derive(Debug, Default, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct LetAttrs {
     /// The type of a let binding. See the documentation of [`BindingType`].
     pub binding_type: BindingType,
@@ -689,17 +691,32 @@ impl fmt::Display for MergePriority {
     }
 }
 
+/// Human-readable formats represent a priority as it would be written in a Nickel source, while
+/// binary formats (see [crate::disk_cache]) use a compact representation.
 impl Serialize for MergePriority {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_str(&self.to_string())
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_string())
+        } else {
+            crate::disk_cache::repr::MergePriorityRepr::serialize(self, serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MergePriority {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        crate::disk_cache::repr::MergePriorityRepr::deserialize(deserializer)
     }
 }
 
 /// A branch of a match expression.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct MatchBranch {
     /// The pattern on the left hand side of `=>`.
     pub pattern: Pattern,
@@ -711,7 +728,7 @@ pub struct MatchBranch {
 }
 
 /// Content of a match expression.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct MatchData {
     /// Branches of the match expression, where the first component is the pattern on the left hand
     /// side of `=>` and the second component is the body of the branch.
@@ -748,12 +765,27 @@ impl LabeledType {
     }
 }
 
+/// Human-readable formats represent a labeled type as the type itself, while binary formats (see
+/// [crate::disk_cache]) store the whole label.
 impl Serialize for LabeledType {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_str(&self.label.typ.to_string())
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.label.typ.to_string())
+        } else {
+            crate::disk_cache::repr::LabeledTypeRepr::serialize(self, serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LabeledType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        crate::disk_cache::repr::LabeledTypeRepr::deserialize(deserializer)
     }
 }
 
@@ -779,7 +811,7 @@ impl Traverse<RichTerm> for LabeledType {
 }
 
 /// A type and/or contract annotation.
-#[derive(Debug, PartialEq, Clone, Default)]
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
 pub struct TypeAnnotation {
     /// The type annotation (using `:`).
     pub typ: Option<LabeledType>,
@@ -945,7 +977,7 @@ impl Traverse<RichTerm> for TypeAnnotation {
 
 /// A chunk of a string with interpolated expressions inside. Can be either a string literal or an
 /// interpolated expression.
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum StrChunk<E> {
     /// A string literal.
     Literal(String),
@@ -1000,7 +1032,7 @@ impl Term {
             | Term::Op1(_, _)
             | Term::Op2(_, _, _)
             | Term::OpN(..)
-            | Term::Import { .. }
+            | Term::Import(_)
             | Term::ResolvedImport(_)
             | Term::StrChunks(_)
             | Term::ParseError(_)
@@ -1053,7 +1085,7 @@ impl Term {
             | Term::OpN(..)
             | Term::Sealed(..)
             | Term::Annotated(..)
-            | Term::Import{..}
+            | Term::Import(_)
             | Term::ResolvedImport(_)
             | Term::StrChunks(_)
             | Term::RecRecord(..)
@@ -1115,7 +1147,7 @@ impl Term {
             | Term::OpN(..)
             | Term::Sealed(..)
             | Term::Annotated(..)
-            | Term::Import { .. }
+            | Term::Import(_)
             | Term::ResolvedImport(_)
             | Term::StrChunks(_)
             | Term::RecRecord(..)
@@ -1171,7 +1203,7 @@ impl Term {
             | Term::OpN(..)
             | Term::Sealed(..)
             | Term::Annotated(..)
-            | Term::Import{..}
+            | Term::Import(_)
             | Term::ResolvedImport(..)
             | Term::Closure(_)
             | Term::ParseError(_)
@@ -1267,7 +1299,7 @@ impl Deref for SharedTerm {
 /// elseBlock`, `if-then-else` can be seen as a unary operator taking a `Bool` argument and
 /// evaluating to either the first projection `fun x y => x` or the second projection `fun x y =>
 /// y`.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum UnaryOp {
     /// If-then-else.
     IfThenElse,
@@ -1518,6 +1550,16 @@ pub enum UnaryOp {
     /// contract application.
     LabelPushDiag,
 
+    /// Call a native function provided by the program embedding Nickel, see
+    /// [foreign::NativeFunction]. The argument is the array of the arguments of the call, which
+    /// must have been fully evaluated. This primop can't be written in Nickel code: it's only
+    /// generated by [foreign::NativeFunction::into_term].
+    #[serde(
+        skip_serializing,
+        deserialize_with = "crate::disk_cache::repr::unsupported"
+    )]
+    NativeCall(NativeFunction),
+
     /// Evaluate a string of nix code into a resulting nickel value. Currently completely
     /// (strictly) evaluates the nix code, and must result in a value serializable into JSON.
     #[cfg(feature = "nix-experimental")]
@@ -1558,6 +1600,12 @@ pub enum UnaryOp {
     /// the second.
     ContractPostprocessResult,
 
+    /// Records the result `'Ok value` or `'Error err_data` of a contract checked with
+    /// `%contract/check%` in the contract trace, and returns it unchanged. The label of the
+    /// contract is the second argument, taken from the stack. This operation is only used when
+    /// contract tracing is enabled, see [crate::eval::contract_trace].
+    ContractTraceResult,
+
     /// The cosinus function.
     NumberArcCos,
 
@@ -1626,6 +1674,7 @@ impl fmt::Display for UnaryOp {
             RecordEmptyWithTail => write!(f, "record/empty_with_tail"),
             Trace => write!(f, "trace"),
             LabelPushDiag => write!(f, "label/push_diag"),
+            NativeCall(native) => write!(f, "native_call[{}]", native.name()),
 
             #[cfg(feature = "nix-experimental")]
             EvalNix => write!(f, "eval_nix"),
@@ -1638,6 +1687,7 @@ impl fmt::Display for UnaryOp {
             PatternBranch => write!(f, "pattern_branch"),
             ContractCustom => write!(f, "contract/custom"),
             ContractPostprocessResult => write!(f, "contract/postprocess_result"),
+            ContractTraceResult => write!(f, "contract/trace_result"),
 
             NumberArcCos => write!(f, "number/arccos"),
             NumberArcSin => write!(f, "number/arcsin"),
@@ -1674,6 +1724,27 @@ impl From<regex::Regex> for CompiledRegex {
     }
 }
 
+impl Serialize for CompiledRegex {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for CompiledRegex {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let regex = String::deserialize(deserializer)?;
+        regex::Regex::new(&regex)
+            .map(CompiledRegex)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 /// Position of a unary operator
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OpPos {
@@ -1698,7 +1769,7 @@ impl UnaryOp {
 
 /// The kind of a dynamic record extension. Kind indicates if a definition is expected for the
 /// field being inserted, or if the inserted field doesn't have a definition.
-#[derive(Clone, Debug, PartialEq, Eq, Copy)]
+#[derive(Clone, Debug, PartialEq, Eq, Copy, Serialize, Deserialize)]
 pub enum RecordExtKind {
     WithValue,
     WithoutValue,
@@ -1710,7 +1781,7 @@ pub enum RecordExtKind {
 ///
 /// However, it's sometimes useful and even necessary to take them into account. This behavior is
 /// controlled by [RecordOpKind].
-#[derive(Clone, Debug, PartialEq, Eq, Copy, Default)]
+#[derive(Clone, Debug, PartialEq, Eq, Copy, Default, Serialize, Deserialize)]
 pub enum RecordOpKind {
     #[default]
     IgnoreEmptyOpt,
@@ -1718,7 +1789,7 @@ pub enum RecordOpKind {
 }
 
 /// Primitive binary operators
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum BinaryOp {
     /// Addition of numerals.
     Plus,
@@ -1992,7 +2063,7 @@ impl fmt::Display for BinaryOp {
 
 /// Primitive n-ary operators. Unary and binary operator make up for most of operators and are
 /// hence special cased. `NAryOp` handles strict operations of arity greater than 2.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NAryOp {
     /// Replace a substring by another one in a string.
     StringReplace,
@@ -2418,7 +2489,7 @@ impl Traverse<RichTerm> for RichTerm {
             | Term::Var(_)
             | Term::Closure(_)
             | Term::Enum(_)
-            | Term::Import { .. }
+            | Term::Import(_)
             | Term::ResolvedImport(_)
             | Term::SealingKey(_)
             | Term::ForeignId(_)
@@ -2889,10 +2960,10 @@ pub mod make {
     where
         S: Into<OsString>,
     {
-        Term::Import {
+        Term::Import(Import::Path {
             path: path.into(),
             format,
-        }
+        })
         .into()
     }
 
@@ -1,6 +1,8 @@
 //! Pattern matching and destructuring of Nickel values.
 use std::collections::{hash_map::Entry, HashMap};
 
+use serde::{Deserialize, Serialize};
+
 use super::{
     record::{Field, RecordData},
     NickelString, Number, RichTerm, TypeAnnotation,
@@ -14,7 +16,7 @@ use crate::{
 pub mod bindings;
 pub mod compile;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum PatternData {
     /// A wildcard pattern, matching any value. As opposed to any, this pattern doesn't bind any
     /// variable.
@@ -36,7 +38,7 @@ pub enum PatternData {
 
 /// A generic pattern, that can appear in a match expression (not yet implemented) or in a
 /// destructuring let-binding.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Pattern {
     /// The content of this pattern
     pub data: PatternData,
@@ -48,7 +50,7 @@ pub struct Pattern {
 }
 
 /// An enum pattern, including both an enum tag and an enum variant.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct EnumPattern {
     pub tag: LocIdent,
     pub pattern: Option<Box<Pattern>>,
@@ -57,7 +59,7 @@ pub struct EnumPattern {
 
 /// A field pattern inside a record pattern. Every field can be annotated with a type, contracts or
 /// with a default value.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct FieldPattern {
     /// The name of the matched field. For example, in `{..., foo = {bar, baz}, ...}`, the matched
     /// identifier is `foo`.
@@ -96,7 +98,7 @@ pub enum LastPattern<P> {
 }
 
 /// A record pattern.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct RecordPattern {
     /// The patterns for each field in the record.
     pub patterns: Vec<FieldPattern>,
@@ -107,7 +109,7 @@ pub struct RecordPattern {
 }
 
 /// An array pattern.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ArrayPattern {
     /// The patterns of the elements of the array.
     pub patterns: Vec<Pattern>,
@@ -126,13 +128,13 @@ impl ArrayPattern {
 }
 
 /// A constant pattern, matching a constant value.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ConstantPattern {
     pub data: ConstantPatternData,
     pub pos: TermPos,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum ConstantPatternData {
     Bool(bool),
     Number(Number),
@@ -140,7 +142,7 @@ pub enum ConstantPatternData {
     Null,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct OrPattern {
     pub patterns: Vec<Pattern>,
     pub pos: TermPos,
@@ -148,7 +150,7 @@ pub struct OrPattern {
 
 /// The tail of a data structure pattern (record or array) which might capture the rest of said
 /// data structure.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum TailPattern {
     /// The pattern is closed, i.e. it doesn't allow more fields. For example, `{foo, bar}`.
     Empty,
@@ -8,7 +8,7 @@ use crate::{
 use std::{collections::HashSet, rc::Rc};
 
 /// Additional attributes for record.
-#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct RecordAttrs {
     /// If the record is an open record, ie ending with `..`. Open records have a different
     /// behavior when used as a record contract: they allow additional fields to be present.
@@ -48,7 +48,7 @@ impl Combine for RecordAttrs {
 
 /// Dependencies of a field or a cache element over the other recursive fields of a recursive
 /// record.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FieldDeps {
     /// The set of dependencies is fixed and has been computed. When attached to an element, an
     /// empty set of dependency means that the element isn't revertible, but standard.
@@ -95,7 +95,7 @@ impl From<HashSet<Ident>> for FieldDeps {
 
 /// Store field interdependencies in a recursive record. Map each static and dynamic field to the
 /// set of recursive fields that syntactically appears in their definition as free variables.
-#[derive(Debug, Default, Eq, PartialEq, Clone)]
+#[derive(Debug, Default, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct RecordDeps {
     /// Must have exactly the same keys as the static fields map of the recursive record.
     pub stat_fields: IndexMap<Ident, FieldDeps>,
@@ -104,7 +104,7 @@ pub struct RecordDeps {
 }
 
 /// The metadata attached to record fields.
-#[derive(Debug, PartialEq, Clone, Default)]
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
 pub struct FieldMetadata {
     pub doc: Option<String>,
     pub annotation: TypeAnnotation,
@@ -139,7 +139,7 @@ impl From<TypeAnnotation> for FieldMetadata {
 }
 
 /// A record field with its metadata.
-#[derive(Clone, Default, PartialEq, Debug)]
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Field {
     /// The value is optional because record field may not have a definition (e.g. optional fields).
     pub value: Option<RichTerm>,
@@ -267,13 +267,14 @@ impl Traverse<RichTerm> for Field {
 ///
 /// Used to group together fields common to both the [super::Term::Record] and
 /// [super::Term::RecRecord] terms.
-#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct RecordData {
     /// Fields whose names are known statically.
     pub fields: IndexMap<LocIdent, Field>,
     /// Attributes which may be applied to a record.
     pub attrs: RecordAttrs,
     /// The hidden part of a record under a polymorphic contract.
+    #[serde(skip)]
     pub sealed_tail: Option<SealedTail>,
 }
 
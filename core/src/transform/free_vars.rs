@@ -42,7 +42,7 @@ impl CollectFreeVars for RichTerm {
             | Term::ForeignId(_)
             | Term::SealingKey(_)
             | Term::Enum(_)
-            | Term::Import { .. }
+            | Term::Import(_)
             | Term::ResolvedImport(_) => (),
             Term::Fun(id, t) => {
                 let mut fresh = HashSet::new();
@@ -60,6 +60,8 @@ use crate::{
 
 use std::{collections::HashSet, convert::Infallible};
 
+use serde::{Deserialize, Serialize};
+
 /// A record row, mapping an identifier to a type. A record type is a dictionary mapping
 /// identifiers to Nickel type. Record types are represented as sequences of `RecordRowF`, ending
 /// potentially with a type variable or `Dyn` in tail position.
@@ -68,7 +70,7 @@ use std::{collections::HashSet, convert::Infallible};
 ///
 /// As other types with the `F` suffix, this type is parametrized by one or more recursive
 /// unfoldings (here, `Ty` for `TypeF`). See [`TypeF`] for more details.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct RecordRowF<Ty> {
     pub id: LocIdent,
     pub typ: Ty,
@@ -83,7 +85,7 @@ pub struct RecordRowF<Ty> {
 ///
 /// As other types with the `F` suffix, this type is parametrized by one or more recursive
 /// unfoldings (here, `Ty` for `TypeF`). See [`TypeF`] for more details.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct EnumRowF<Ty> {
     pub id: LocIdent,
     pub typ: Option<Ty>,
@@ -100,7 +102,7 @@ pub struct EnumRowF<Ty> {
 ///   wrapper around an instantiation of `TypeF`.
 /// - `RRows` is the recursive unfolding of record rows (the tail of this row sequence). In
 ///   practice, a wrapper around an instantiation of `RecordRowsF`.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum RecordRowsF<Ty, RRows> {
     Empty,
     Extend { row: RecordRowF<Ty>, tail: RRows },
@@ -117,7 +119,7 @@ pub enum RecordRowsF<Ty, RRows> {
 ///
 /// - `ERows` is the recursive unfolding of enum rows (the tail of this row sequence). In practice,
 ///   a wrapper around `EnumRowsF`.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum EnumRowsF<Ty, ERows> {
     Empty,
     Extend { row: EnumRowF<Ty>, tail: ERows },
@@ -132,7 +134,7 @@ pub enum EnumRowsF<Ty, ERows> {
 /// users to write e.g. `forall a :: Type` or `forall a :: Rows`. But the kind of a variable is
 /// required for the typechecker. It is thus determined during parsing and stored as `VarKind` where
 /// type variables are introduced, that is, on forall quantifiers.
-#[derive(Clone, PartialEq, Eq, Debug, Default)]
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
 pub enum VarKind {
     #[default]
     Type,
@@ -178,7 +180,7 @@ impl From<&VarKind> for VarKindDiscriminant {
 /// blame, etc.).
 ///
 /// Dictionary contracts might get a proper AST node later on.
-#[derive(Clone, Debug, Copy, Eq, PartialEq)]
+#[derive(Clone, Debug, Copy, Eq, PartialEq, Serialize, Deserialize)]
 pub enum DictTypeFlavour {
     /// Dictionary type (`{_ : T}`)
     Type,
@@ -258,7 +260,7 @@ pub enum DictTypeFlavour {
 /// - `RRows`: the recursive unfolding of record rows
 /// - `ERows`: the recursive unfolding of enum rows
 /// - `Te`: the type of a term (used to store contracts)
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum TypeF<Ty, RRows, ERows, Te> {
     /// The dynamic type, or unitype. Assigned to values whose actual type is not statically known
     /// or checked.
@@ -314,16 +316,16 @@ pub enum TypeF<Ty, RRows, ERows, Te> {
 /// Concrete, recursive definition for an enum row.
 pub type EnumRow = EnumRowF<Box<Type>>;
 /// Concrete, recursive definition for enum rows.
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct EnumRows(pub EnumRowsF<Box<Type>, Box<EnumRows>>);
 /// Concrete, recursive definition for a record row.
 pub type RecordRow = RecordRowF<Box<Type>>;
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 /// Concrete, recursive definition for record rows.
 pub struct RecordRows(pub RecordRowsF<Box<Type>, Box<RecordRows>>);
 
 /// Concrete, recursive type for a Nickel type.
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Type {
     pub typ: TypeF<Box<Type>, RecordRows, EnumRows, RichTerm>,
     pub pos: TermPos,
@@ -638,6 +638,7 @@ fn type_eq_bounded<E: TermEnvironment>(
                 | (TypeF::Number, TypeF::Number)
                 | (TypeF::Bool, TypeF::Bool)
                 | (TypeF::Symbol, TypeF::Symbol)
+                | (TypeF::ForeignId, TypeF::ForeignId)
                 | (TypeF::String, TypeF::String) => true,
                 (
                     TypeF::Dict {
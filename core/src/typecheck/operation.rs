@@ -240,6 +240,9 @@ pub fn get_uop_type(
         // Morally: Lbl -> Lbl
         // Actual: Dyn -> Dyn
         UnaryOp::LabelPushDiag => (mk_uniftype::dynamic(), mk_uniftype::dynamic()),
+        // The type of a native function is declared separately, and the primop itself is never
+        // typechecked.
+        UnaryOp::NativeCall(_) => (mk_uniftype::dynamic(), mk_uniftype::dynamic()),
         // Str -> Dyn
         #[cfg(feature = "nix-experimental")]
         UnaryOp::EvalNix => (mk_uniftype::str(), mk_uniftype::dynamic()),
@@ -278,6 +281,11 @@ pub fn get_uop_type(
             custom_contract_ret_type(),
             mk_uty_arrow!(mk_uniftype::dynamic(), mk_uniftype::dynamic()),
         ),
+        // <custom_contract_ret_type()> -> Dyn -> <custom_contract_ret_type()>
+        UnaryOp::ContractTraceResult => (
+            custom_contract_ret_type(),
+            mk_uty_arrow!(mk_uniftype::dynamic(), custom_contract_ret_type()),
+        ),
         // Number -> Number
         UnaryOp::NumberCos
         | UnaryOp::NumberSin
@@ -438,14 +446,24 @@ pub fn get_bop_type(
         BinaryOp::Serialize => {
             let ty_input = state.table.fresh_type_uvar(var_level);
             (
-                mk_uty_enum!("Json", "Yaml", "Toml"),
+                mk_uty_enum!(
+                    "Json",
+                    "Yaml",
+                    "YamlDocuments",
+                    "Toml",
+                    "Xml",
+                    "Ini",
+                    "Properties",
+                    "Dotenv",
+                    "Hcl"
+                ),
                 ty_input,
                 mk_uniftype::str(),
             )
         }
         // <Json, Yaml, Toml> -> Str -> Dyn
         BinaryOp::Deserialize => (
-            mk_uty_enum!("Json", "Yaml", "Toml"),
+            mk_uty_enum!("Json", "Yaml", "YamlDocuments", "Toml", "Csv", "JsonLines"),
             mk_uniftype::str(),
             mk_uniftype::dynamic(),
         ),
@@ -1210,7 +1210,8 @@ impl Unify for UnifType {
                 | (TypeF::Number, TypeF::Number)
                 | (TypeF::Bool, TypeF::Bool)
                 | (TypeF::String, TypeF::String)
-                | (TypeF::Symbol, TypeF::Symbol) => Ok(()),
+                | (TypeF::Symbol, TypeF::Symbol)
+                | (TypeF::ForeignId, TypeF::ForeignId) => Ok(()),
                 (TypeF::Array(uty1), TypeF::Array(uty2)) => uty1.unify(*uty2, state, ctxt),
                 (TypeF::Arrow(s1s, s1t), TypeF::Arrow(s2s, s2t)) => {
                     s1s.clone()
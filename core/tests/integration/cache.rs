@@ -0,0 +1,165 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use nickel_lang_core::cache::{
+    Cache, CacheOp, EntryState, ErrorTolerance, InputFormat, SourcePath,
+};
+use nickel_lang_utils::project_root::project_root;
+
+fn imports_dir() -> PathBuf {
+    project_root().join("core/tests/integration/inputs/imports")
+}
+
+#[test]
+fn get_or_add_file_normalizes_paths() {
+    let mut cache = Cache::new(ErrorTolerance::Strict);
+    let unnormalized = imports_dir().join("imported/../nested.ncl");
+
+    let CacheOp::Done(id) = cache
+        .get_or_add_file(&unnormalized, InputFormat::Nickel)
+        .unwrap()
+    else {
+        panic!("the file should have been added to the cache");
+    };
+
+    assert_eq!(
+        cache
+            .get_or_add_file(&unnormalized, InputFormat::Nickel)
+            .unwrap(),
+        CacheOp::Cached(id)
+    );
+    assert_eq!(
+        cache
+            .get_or_add_file(imports_dir().join("nested.ncl"), InputFormat::Nickel)
+            .unwrap(),
+        CacheOp::Cached(id)
+    );
+}
+
+/// Writes a file and sets its modification time to `secs` seconds after the epoch, so that
+/// successive writes are seen as modifications regardless of the precision of the filesystem
+/// timestamps.
+fn write_at(path: &Path, contents: &str, secs: u64) {
+    fs::write(path, contents).unwrap();
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+        .unwrap();
+}
+
+#[test]
+fn reload_if_modified_invalidates_dependents() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name);
+    write_at(
+        &path("main.ncl"),
+        "(import \"a.ncl\") + (import \"b.ncl\")",
+        1,
+    );
+    write_at(&path("a.ncl"), "import \"c.ncl\"", 1);
+    write_at(&path("b.ncl"), "1", 1);
+    write_at(&path("c.ncl"), "1", 1);
+
+    let mut cache = Cache::new(ErrorTolerance::Strict);
+    let main = cache
+        .get_or_add_file(path("main.ncl"), InputFormat::Nickel)
+        .unwrap()
+        .inner();
+    cache.parse(main, InputFormat::Nickel).unwrap();
+    cache.resolve_imports(main).unwrap();
+
+    let id = |cache: &Cache, name: &str| {
+        cache
+            .id_of(&SourcePath::Path(path(name), InputFormat::Nickel))
+            .unwrap()
+    };
+    let [a, b, c] = ["a.ncl", "b.ncl", "c.ncl"].map(|name| id(&cache, name));
+
+    for file_id in [main, a, b, c] {
+        assert!(!cache.reload_if_modified(file_id).unwrap());
+    }
+
+    write_at(&path("c.ncl"), "2", 2);
+    assert!(cache.reload_if_modified(c).unwrap());
+    // The file id is reused, and the entry is up to date again.
+    assert_eq!(id(&cache, "c.ncl"), c);
+    assert!(!cache.reload_if_modified(c).unwrap());
+    assert_eq!(cache.source(c), "2");
+
+    // The modified file and the files which transitively import it are invalidated, while the
+    // other entries are kept.
+    for file_id in [main, a, c] {
+        assert_eq!(cache.entry_state(file_id), None);
+    }
+    assert_eq!(cache.entry_state(b), Some(EntryState::ImportsResolved));
+}
+
+// Entries are written to a temporary file which is then renamed, while loading an entry only
+// touches it, so inode numbers tell whether an entry has been reused or written again.
+#[cfg(unix)]
+mod disk_cache {
+    use std::{collections::BTreeMap, fs, os::unix::fs::MetadataExt, path::Path};
+
+    use nickel_lang_core::{disk_cache::DiskCache, term::Term};
+    use nickel_lang_utils::test_program::TestProgram;
+
+    fn eval(dir: &Path, cache_dir: &Path) -> Term {
+        let mut program =
+            TestProgram::new_from_file(dir.join("main.ncl"), std::io::stderr()).unwrap();
+        program.set_disk_cache(Some(DiskCache::new(cache_dir)));
+        program.eval_full().unwrap().term.into_owned()
+    }
+
+    /// The entries of the cache, with their inode number.
+    fn entries(cache_dir: &Path) -> BTreeMap<String, u64> {
+        fs::read_dir(cache_dir)
+            .unwrap()
+            .filter_map(|build| {
+                let build = build.unwrap();
+                build.file_type().unwrap().is_dir().then(|| build.path())
+            })
+            .flat_map(|build| fs::read_dir(build).unwrap())
+            .map(|entry| {
+                let entry = entry.unwrap();
+                (
+                    entry.file_name().to_string_lossy().into_owned(),
+                    entry.metadata().unwrap().ino(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reuses_and_invalidates_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("main.ncl"), "(import \"dep.ncl\").x + 1").unwrap();
+        fs::write(dir.path().join("dep.ncl"), "{ x | Number = 1 }").unwrap();
+
+        assert_eq!(eval(dir.path(), cache_dir.path()), Term::Num(2.into()));
+        let stored = entries(cache_dir.path());
+        // The standard library modules, the main file and the imported file.
+        assert!(stored.len() > 2);
+
+        assert_eq!(eval(dir.path(), cache_dir.path()), Term::Num(2.into()));
+        assert_eq!(entries(cache_dir.path()), stored);
+
+        fs::write(dir.path().join("dep.ncl"), "{ x | Number = 2 }").unwrap();
+        assert_eq!(eval(dir.path(), cache_dir.path()), Term::Num(3.into()));
+
+        let updated = entries(cache_dir.path());
+        let added = updated.keys().filter(|name| !stored.contains_key(*name));
+        let rewritten = stored
+            .iter()
+            .filter(|(name, ino)| updated.get(*name) != Some(*ino));
+        // The imported file has a new entry, and the entry of the main file is written again with
+        // the new key of its import. The other entries are reused.
+        assert_eq!(added.count(), 1);
+        assert_eq!(rewritten.count(), 1);
+    }
+}
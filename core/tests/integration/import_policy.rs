@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use assert_matches::assert_matches;
+use nickel_lang_core::{
+    cache::{ImportPolicy, InputFormat},
+    error::{Error, ImportError, ImportPolicyViolation},
+};
+use nickel_lang_utils::{project_root::project_root, test_program::TestProgram};
+
+fn imports_dir() -> PathBuf {
+    project_root().join("core/tests/integration/inputs/imports")
+}
+
+fn eval_with_policy(file: &str, policy: ImportPolicy) -> Result<(), Error> {
+    let mut program =
+        TestProgram::new_from_file(imports_dir().join(file), std::io::stderr()).unwrap();
+    program.set_import_policy(policy);
+    program.eval_full().map(|_| ())
+}
+
+#[test]
+fn sandbox_allows_nested_imports() {
+    assert_matches!(
+        eval_with_policy("nested.ncl", ImportPolicy::sandbox([imports_dir()])),
+        Ok(())
+    );
+}
+
+#[test]
+fn sandbox_forbids_parent_escape() {
+    assert_matches!(
+        eval_with_policy(
+            "imported/import_parent.ncl",
+            ImportPolicy::sandbox([imports_dir().join("imported")])
+        ),
+        Err(Error::ImportError(ImportError::Forbidden {
+            violation: ImportPolicyViolation::OutsideAllowedDirs(_),
+            ..
+        }))
+    );
+}
+
+#[test]
+fn sandbox_forbids_absolute_paths() {
+    let path = imports_dir().join("imported/two.ncl");
+    let mut program = TestProgram::new_from_source(
+        format!("import \"{}\"", path.display()).as_bytes(),
+        "<test>",
+        std::io::stderr(),
+    )
+    .unwrap();
+    program.set_import_policy(ImportPolicy::sandbox([imports_dir()]));
+
+    assert_matches!(
+        program.eval_full(),
+        Err(Error::ImportError(ImportError::Forbidden {
+            violation: ImportPolicyViolation::AbsolutePath,
+            ..
+        }))
+    );
+}
+
+#[test]
+fn forbidden_formats() {
+    let policy = ImportPolicy {
+        forbidden_formats: vec![InputFormat::Yaml],
+        ..Default::default()
+    };
+
+    assert_matches!(
+        eval_with_policy("yaml_import.ncl", policy),
+        Err(Error::ImportError(ImportError::Forbidden {
+            violation: ImportPolicyViolation::ForbiddenFormat(InputFormat::Yaml),
+            ..
+        }))
+    );
+}
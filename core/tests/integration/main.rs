@@ -15,8 +15,10 @@ use nickel_lang_utils::{
 use serde::Deserialize;
 use test_generator::test_resources;
 
+mod cache;
 mod contract_label_path;
 mod free_vars;
+mod import_policy;
 mod pretty;
 mod query;
 mod stdlib_typecheck;
@@ -0,0 +1,207 @@
+//! A minimal client of the Debug Adapter Protocol, for testing debug adapters.
+//!
+//! This is not a production-grade implementation.
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, Command, Stdio},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::debug;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+pub struct Client {
+    child: Child,
+    /// For sending messages to the debug adapter.
+    write: Box<dyn Write>,
+    /// For reading messages from the debug adapter.
+    read: Box<dyn BufRead>,
+    /// The sequence number of the last request sent.
+    seq: i64,
+    /// Events that have been received from the adapter but not yet delivered to the client.
+    pending_events: VecDeque<Event>,
+}
+
+/// An event sent by the debug adapter.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Event {
+    pub event: String,
+    #[serde(default)]
+    pub body: Value,
+}
+
+/// A response sent by the debug adapter.
+#[derive(Deserialize, Debug)]
+struct Response {
+    request_seq: i64,
+    success: bool,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    body: Value,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message {
+    Response(Response),
+    Event(Event),
+}
+
+impl Client {
+    /// Launch a debug adapter by running the given command, and initialize the session.
+    ///
+    /// The command's stdin and stdout will be overridden to "piped".
+    pub fn new(mut cmd: Command) -> Result<Client> {
+        let mut child = cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+
+        let mut client = Client {
+            write: Box::new(child.stdin.take().unwrap()),
+            read: Box::new(BufReader::new(child.stdout.take().unwrap())),
+            child,
+            seq: 0,
+            pending_events: VecDeque::new(),
+        };
+
+        client.request(
+            "initialize",
+            json!({ "adapterID": "nickel", "linesStartAt1": true, "columnsStartAt1": true }),
+        )?;
+        client.wait_for_event("initialized")?;
+
+        Ok(client)
+    }
+
+    /// Sends a request and waits for the response, returning its body. Fails if the adapter
+    /// reports an error.
+    pub fn request(&mut self, command: &str, arguments: Value) -> Result<Value> {
+        self.seq += 1;
+        let seq = self.seq;
+
+        self.send(json!({
+            "seq": seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        }))?;
+
+        loop {
+            match self.recv()? {
+                Message::Response(resp) if resp.request_seq == seq => {
+                    if resp.success {
+                        return Ok(resp.body);
+                    } else {
+                        bail!(
+                            "{command} failed: {}",
+                            resp.message.unwrap_or_else(|| "unknown error".to_owned())
+                        );
+                    }
+                }
+                Message::Response(resp) => {
+                    bail!("unexpected response to request {}", resp.request_seq)
+                }
+                Message::Event(event) => self.pending_events.push_back(event),
+            }
+        }
+    }
+
+    /// Waits for an event with the given name, returning its body. Events with other names
+    /// received in the meantime are discarded.
+    pub fn wait_for_event(&mut self, name: &str) -> Result<Value> {
+        while let Some(event) = self.pending_events.pop_front() {
+            if event.event == name {
+                return Ok(event.body);
+            }
+        }
+
+        loop {
+            match self.recv()? {
+                Message::Event(event) if event.event == name => return Ok(event.body),
+                Message::Event(_) => (),
+                Message::Response(resp) => {
+                    bail!("unexpected response to request {}", resp.request_seq)
+                }
+            }
+        }
+    }
+
+    /// Waits for the end of the debugging session, returning the text of the `output` events
+    /// received in the meantime with the given category.
+    pub fn wait_for_output(&mut self, category: &str) -> Result<String> {
+        let mut output = String::new();
+        let mut events = std::mem::take(&mut self.pending_events);
+
+        loop {
+            let event = match events.pop_front() {
+                Some(event) => event,
+                None => match self.recv()? {
+                    Message::Event(event) => event,
+                    Message::Response(resp) => {
+                        bail!("unexpected response to request {}", resp.request_seq)
+                    }
+                },
+            };
+
+            match event.event.as_str() {
+                "output" if event.body["category"] == category => {
+                    output.push_str(event.body["output"].as_str().unwrap_or_default());
+                }
+                "terminated" => return Ok(output),
+                _ => (),
+            }
+        }
+    }
+
+    fn send(&mut self, message: Value) -> Result<()> {
+        let content = serde_json::to_string(&message)?;
+        debug!("sending {content}");
+
+        write!(
+            self.write,
+            "Content-Length: {}\r\n\r\n{content}",
+            content.len()
+        )?;
+        self.write.flush()?;
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Message> {
+        let mut content_length = None;
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+
+            if self.read.read_line(&mut line)? == 0 {
+                bail!("the debug adapter closed the connection");
+            }
+
+            let header = line.trim_end();
+
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some(length) = header.strip_prefix("Content-Length:") {
+                content_length = Some(length.trim().parse::<usize>()?);
+            }
+        }
+
+        let content_length = content_length.ok_or_else(|| anyhow!("missing Content-Length"))?;
+        let mut content = vec![0; content_length];
+        self.read.read_exact(&mut content)?;
+        debug!("received {}", String::from_utf8_lossy(&content));
+
+        serde_json::from_slice(&content).context("invalid message")
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // The adapter may be stuck waiting for a request, so we don't bother waiting for it.
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,203 @@
+//! The transport of the Debug Adapter Protocol.
+//!
+//! Messages are JSON objects preceded by a `Content-Length` header, as in the Language Server
+//! Protocol. Requests are read on a separate thread, so that the debugger can check for incoming
+//! requests (such as `pause`) while the program is running.
+use std::{
+    cell::RefCell,
+    io::{self, BufRead, Write},
+    rc::Rc,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A request from the client.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Request {
+    pub seq: i64,
+    pub command: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Message<'a> {
+    Response {
+        seq: i64,
+        request_seq: i64,
+        success: bool,
+        command: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        body: Option<Value>,
+    },
+    Event {
+        seq: i64,
+        event: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        body: Option<Value>,
+    },
+}
+
+struct Output {
+    writer: Box<dyn Write>,
+    /// The sequence number of the last message sent.
+    seq: i64,
+}
+
+/// A connection to a client. Cloning a connection gives another handle to the same client.
+#[derive(Clone)]
+pub struct Connection {
+    requests: Rc<Receiver<Request>>,
+    output: Rc<RefCell<Output>>,
+}
+
+impl Connection {
+    /// Creates a connection reading requests from `reader` and writing responses and events to
+    /// `writer`.
+    pub fn new(mut reader: impl BufRead + Send + 'static, writer: impl Write + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || loop {
+            match read_request(&mut reader) {
+                Ok(Some(request)) => {
+                    if sender.send(request).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(err) => warn!("failed to read a request: {err:#}"),
+            }
+        });
+
+        Connection {
+            requests: Rc::new(receiver),
+            output: Rc::new(RefCell::new(Output {
+                writer: Box::new(writer),
+                seq: 0,
+            })),
+        }
+    }
+
+    /// Creates a connection on the standard input and output.
+    pub fn stdio() -> Self {
+        Connection::new(io::BufReader::new(io::stdin()), io::stdout())
+    }
+
+    /// Waits for the next request. Returns `None` if the client closed the connection.
+    pub fn recv(&self) -> Option<Request> {
+        self.requests.recv().ok()
+    }
+
+    /// Returns the next request if there is one, without waiting.
+    pub fn try_recv(&self) -> Option<Request> {
+        self.requests.try_recv().ok()
+    }
+
+    /// Sends a successful response to `request`.
+    pub fn respond(&self, request: &Request, body: Value) {
+        self.send(|seq| Message::Response {
+            seq,
+            request_seq: request.seq,
+            success: true,
+            command: &request.command,
+            message: None,
+            body: (!body.is_null()).then_some(body),
+        });
+    }
+
+    /// Sends an error response to `request`.
+    pub fn respond_error(&self, request: &Request, message: &str) {
+        self.send(|seq| Message::Response {
+            seq,
+            request_seq: request.seq,
+            success: false,
+            command: &request.command,
+            message: Some(message),
+            body: Some(serde_json::json!({ "error": { "id": 1, "format": message } })),
+        });
+    }
+
+    /// Sends an event.
+    pub fn event(&self, event: &str, body: Value) {
+        self.send(|seq| Message::Event {
+            seq,
+            event,
+            body: (!body.is_null()).then_some(body),
+        });
+    }
+
+    /// Sends an `output` event, which is displayed in the debug console of the client.
+    pub fn output(&self, category: &str, output: &str) {
+        self.event(
+            "output",
+            serde_json::json!({ "category": category, "output": output }),
+        );
+    }
+
+    fn send<'a>(&self, message: impl FnOnce(i64) -> Message<'a>) {
+        let mut output = self.output.borrow_mut();
+        output.seq += 1;
+
+        let message = message(output.seq);
+        // unwrap(): the messages only contain strings and JSON values, which always serialize.
+        let content = serde_json::to_string(&message).unwrap();
+        debug!("sending {content}");
+
+        let result = write!(
+            output.writer,
+            "Content-Length: {}\r\n\r\n{content}",
+            content.len()
+        )
+        .and_then(|()| output.writer.flush());
+
+        // If the client is gone, the next read will notice it, and the session will end.
+        if let Err(err) = result {
+            warn!("failed to send a message: {err}");
+        }
+    }
+}
+
+/// Reads a request. Returns `None` at the end of the input.
+fn read_request(reader: &mut impl BufRead) -> Result<Option<Request>> {
+    let mut content_length = None;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let header = line.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+
+    let Some(content_length) = content_length else {
+        bail!("missing Content-Length header");
+    };
+
+    let mut content = vec![0; content_length];
+    reader.read_exact(&mut content)?;
+    debug!("received {}", String::from_utf8_lossy(&content));
+
+    let request = serde_json::from_slice(&content).context("invalid request")?;
+    Ok(Some(request))
+}
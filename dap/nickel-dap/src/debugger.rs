@@ -0,0 +1,501 @@
+//! The debugger proper, which is called by the virtual machine before each evaluation step.
+//!
+//! Nickel is lazy, so the evaluation doesn't follow the order of the source. The debugger stops on
+//! the expressions of the program (excluding the standard library) as they get evaluated:
+//! breakpoints are hit when the evaluation enters a line with a breakpoint, and steps go to the
+//! next line being evaluated. A step over skips the lines evaluated in the body of the functions
+//! called from the current line.
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use codespan_reporting::term::termcolor::NoColor;
+use log::debug;
+use nickel_lang_core::{
+    cache::{Cache as ImportCache, SourcePath},
+    error::{
+        report::{report_with, ErrorFormat},
+        Error, IntoDiagnostics,
+    },
+    eval::{
+        cache::Cache,
+        callstack::{CallDescr, StackElem},
+        debugger::DebugHook,
+        Closure, VirtualMachine,
+    },
+    files::{FileId, Files},
+    position::{RawSpan, TermPos},
+    transform,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    connection::{Connection, Request},
+    inspect::{self, Description},
+};
+
+/// The debugger only checks for incoming requests every so many steps while the program runs, as
+/// it would otherwise spend most of its time polling.
+const POLL_INTERVAL: u64 = 256;
+
+/// Nickel is single-threaded, but the protocol needs a thread to attach the stack to.
+pub const THREAD_ID: i64 = 1;
+
+/// The breakpoints set by the client, as line numbers starting from 1, by canonical path.
+pub type Breakpoints = HashMap<PathBuf, HashSet<usize>>;
+
+/// Handles a `setBreakpoints` request, replacing the breakpoints of a source.
+pub fn set_breakpoints(breakpoints: &mut Breakpoints, connection: &Connection, req: &Request) {
+    let Some(path) = req.arguments["source"]["path"].as_str() else {
+        connection.respond_error(req, "setBreakpoints: missing source path");
+        return;
+    };
+
+    let lines: Vec<usize> = req.arguments["breakpoints"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|bp| bp["line"].as_u64())
+        .map(|line| line as usize)
+        .collect();
+
+    let path = canonicalize(Path::new(path));
+    debug!("breakpoints in {}: {lines:?}", path.display());
+
+    let response: Vec<_> = lines
+        .iter()
+        .map(|line| json!({ "verified": true, "line": line }))
+        .collect();
+    breakpoints.insert(path, lines.into_iter().collect());
+
+    connection.respond(req, json!({ "breakpoints": response }));
+}
+
+fn canonicalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
+
+/// A location in the program: a source file and a line, starting from 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Location {
+    file: FileId,
+    line: usize,
+}
+
+/// What the debugger is doing.
+#[derive(Copy, Clone, Debug)]
+enum Mode {
+    /// Run until a breakpoint is hit or the client pauses the evaluation.
+    Run,
+    /// Stop at the next location, reporting the given reason.
+    Pause(&'static str),
+    /// Stop at the next location different from `from`.
+    StepIn { from: Location },
+    /// Stop at the next location different from `from`, outside of the functions called from it.
+    StepOver { from: Location, depth: usize },
+    /// Stop at the next location outside of the current function.
+    StepOut { depth: usize },
+}
+
+/// What to do after handling a request while stopped.
+enum Next {
+    Wait,
+    Resume(Mode),
+}
+
+pub struct Debugger {
+    connection: Connection,
+    breakpoints: Breakpoints,
+    mode: Mode,
+    /// The canonical paths of the sources met so far, or `None` for sources which aren't files of
+    /// the program.
+    paths: HashMap<FileId, Option<PathBuf>>,
+    /// The span of the expression of the last breakpoint hit. A line can hold several
+    /// expressions, which are evaluated in an order that depends on laziness: a breakpoint stops
+    /// at each of them, but not at their subexpressions.
+    breakpoint_hit: Option<RawSpan>,
+    steps: u64,
+    /// The children of the values shown while stopped, indexed by their variables reference minus
+    /// one. References are only valid until the evaluation resumes.
+    variables: Vec<Vec<(String, Option<Closure>)>>,
+    /// The number of expressions evaluated from the debug console, used to name their sources.
+    evaluations: usize,
+}
+
+impl Debugger {
+    pub fn new(connection: Connection, breakpoints: Breakpoints, stop_on_entry: bool) -> Self {
+        Debugger {
+            connection,
+            breakpoints,
+            mode: if stop_on_entry {
+                Mode::Pause("entry")
+            } else {
+                Mode::Run
+            },
+            paths: HashMap::new(),
+            breakpoint_hit: None,
+            steps: 0,
+            variables: Vec::new(),
+            evaluations: 0,
+        }
+    }
+
+    /// Returns the location of a span, if it's in a file of the program.
+    fn location(&mut self, files: &Files, span: RawSpan) -> Option<Location> {
+        let path = self.paths.entry(span.src_id).or_insert_with(|| {
+            (!files.is_stdlib(span.src_id))
+                .then(|| files.name(span.src_id))
+                .filter(|name| Path::new(name).is_file())
+                .map(|name| canonicalize(Path::new(name)))
+        });
+
+        path.as_ref()?;
+
+        let location = files.location(span.src_id, span.start).ok()?;
+
+        Some(Location {
+            file: span.src_id,
+            line: location.line.to_usize() + 1,
+        })
+    }
+
+    fn is_breakpoint(&self, location: Location) -> bool {
+        self.paths[&location.file]
+            .as_ref()
+            .and_then(|path| self.breakpoints.get(path))
+            .is_some_and(|lines| lines.contains(&location.line))
+    }
+
+    /// Decides whether to stop at `location`, and returns the reason if so.
+    fn stop_reason(&self, span: RawSpan, location: Location, depth: usize) -> Option<&'static str> {
+        let nested = self.breakpoint_hit.is_some_and(|hit| span < hit);
+
+        if !nested && self.is_breakpoint(location) {
+            return Some("breakpoint");
+        }
+
+        match self.mode {
+            Mode::Run => None,
+            Mode::Pause(reason) => Some(reason),
+            Mode::StepIn { from } => (location != from).then_some("step"),
+            Mode::StepOver {
+                from,
+                depth: from_depth,
+            } => (location != from && depth <= from_depth).then_some("step"),
+            Mode::StepOut { depth: from_depth } => (depth < from_depth).then_some("step"),
+        }
+    }
+
+    /// Handles the requests received while the program is running.
+    fn poll(&mut self) {
+        while let Some(req) = self.connection.try_recv() {
+            match req.command.as_str() {
+                "pause" => {
+                    self.connection.respond(&req, Value::Null);
+                    self.mode = Mode::Pause("pause");
+                }
+                "setBreakpoints" => set_breakpoints(&mut self.breakpoints, &self.connection, &req),
+                "threads" => self.connection.respond(&req, threads()),
+                "disconnect" | "terminate" => self.exit(&req),
+                _ => self
+                    .connection
+                    .respond_error(&req, "the program is running"),
+            }
+        }
+    }
+
+    /// Waits for the client to resume the evaluation, answering its requests in the meantime.
+    fn stop<C: Cache>(
+        &mut self,
+        vm: &mut VirtualMachine<ImportCache, C>,
+        closure: &Closure,
+        span: RawSpan,
+        location: Location,
+        reason: &'static str,
+    ) {
+        debug!("stopped at {location:?} ({reason})");
+
+        if reason == "breakpoint" {
+            self.breakpoint_hit = Some(span);
+        }
+
+        self.connection.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        );
+
+        while let Some(req) = self.connection.recv() {
+            let depth = depth(vm);
+
+            let next = match req.command.as_str() {
+                "threads" => self.respond(&req, threads()),
+                "stackTrace" => {
+                    let frames = stack_trace(vm, span);
+                    self.respond(&req, frames)
+                }
+                "scopes" => {
+                    let scopes = if req.arguments["frameId"].as_i64() == Some(0) {
+                        let locals = inspect::variables(&vm.cache, &closure.env);
+                        vec![json!({
+                            "name": "Locals",
+                            "presentationHint": "locals",
+                            "variablesReference": self.reference(locals),
+                            "expensive": false,
+                        })]
+                    } else {
+                        // The environments of the callers aren't kept around.
+                        Vec::new()
+                    };
+                    self.respond(&req, json!({ "scopes": scopes }))
+                }
+                "variables" => {
+                    let children = req.arguments["variablesReference"]
+                        .as_u64()
+                        .and_then(|reference| (reference as usize).checked_sub(1))
+                        .and_then(|index| self.variables.get(index))
+                        .cloned()
+                        .unwrap_or_default();
+
+                    let variables: Vec<_> = children
+                        .into_iter()
+                        .map(|(name, child)| self.variable(vm, name, child))
+                        .collect();
+                    self.respond(&req, json!({ "variables": variables }))
+                }
+                "evaluate" => {
+                    let expression = req.arguments["expression"].as_str().unwrap_or_default();
+
+                    match self.evaluate(vm, closure, expression) {
+                        Ok(description) => {
+                            let body = json!({
+                                "result": description.value,
+                                "type": description.typ,
+                                "variablesReference": self.reference(description.children),
+                            });
+                            self.respond(&req, body)
+                        }
+                        Err(message) => {
+                            self.connection.respond_error(&req, &message);
+                            Next::Wait
+                        }
+                    }
+                }
+                "setBreakpoints" => {
+                    set_breakpoints(&mut self.breakpoints, &self.connection, &req);
+                    Next::Wait
+                }
+                "continue" => {
+                    self.connection
+                        .respond(&req, json!({ "allThreadsContinued": true }));
+                    Next::Resume(Mode::Run)
+                }
+                "next" => self.resume(
+                    &req,
+                    Mode::StepOver {
+                        from: location,
+                        depth,
+                    },
+                ),
+                "stepIn" => self.resume(&req, Mode::StepIn { from: location }),
+                "stepOut" => self.resume(&req, Mode::StepOut { depth }),
+                "pause" => self.respond(&req, Value::Null),
+                "disconnect" | "terminate" => self.exit(&req),
+                _ => {
+                    self.connection
+                        .respond_error(&req, &format!("unsupported request {}", req.command));
+                    Next::Wait
+                }
+            };
+
+            if let Next::Resume(mode) = next {
+                self.mode = mode;
+                self.variables.clear();
+                return;
+            }
+        }
+
+        // The client is gone.
+        std::process::exit(0);
+    }
+
+    fn respond(&self, req: &Request, body: Value) -> Next {
+        self.connection.respond(req, body);
+        Next::Wait
+    }
+
+    fn resume(&self, req: &Request, mode: Mode) -> Next {
+        self.connection.respond(req, Value::Null);
+        Next::Resume(mode)
+    }
+
+    /// Ends the debugging session. The evaluation can't be interrupted, so we exit directly.
+    fn exit(&self, req: &Request) -> ! {
+        self.connection.respond(req, Value::Null);
+        self.connection.event("terminated", Value::Null);
+        std::process::exit(0);
+    }
+
+    /// Returns a variables reference for the given children, or 0 if there are none.
+    fn reference(&mut self, children: Vec<(String, Option<Closure>)>) -> usize {
+        if children.is_empty() {
+            0
+        } else {
+            self.variables.push(children);
+            self.variables.len()
+        }
+    }
+
+    fn variable<C: Cache>(
+        &mut self,
+        vm: &VirtualMachine<ImportCache, C>,
+        name: String,
+        child: Option<Closure>,
+    ) -> Value {
+        let Some(child) = child else {
+            return json!({
+                "name": name,
+                "value": "<no definition>",
+                "variablesReference": 0,
+            });
+        };
+
+        let description = inspect::describe(&vm.cache, child);
+
+        json!({
+            "name": name,
+            "value": description.value,
+            "type": description.typ,
+            "variablesReference": self.reference(description.children),
+        })
+    }
+
+    /// Evaluates an expression in the environment of the current closure.
+    fn evaluate<C: Cache>(
+        &mut self,
+        vm: &mut VirtualMachine<ImportCache, C>,
+        closure: &Closure,
+        expression: &str,
+    ) -> Result<Description, String> {
+        self.evaluations += 1;
+
+        let file_id = vm.import_resolver_mut().add_string(
+            SourcePath::Generated(format!("debugger-input-{}", self.evaluations)),
+            expression.to_owned(),
+        );
+
+        let result = vm
+            .import_resolver()
+            .parse_nocache(file_id)
+            .map_err(Error::from)
+            .and_then(|(term, errors)| {
+                if errors.no_errors() {
+                    Ok(term)
+                } else {
+                    Err(errors.into())
+                }
+            })
+            .and_then(|term| {
+                transform::transform(term, None).map_err(|err| Error::ParseErrors(err.into()))
+            })
+            .and_then(|term| {
+                vm.eval_in_env(term, closure.env.clone())
+                    .map_err(Error::from)
+            });
+
+        match result {
+            Ok(value) => Ok(inspect::describe_value(&vm.cache, value)),
+            Err(error) => Err(render_error(vm.import_resolver().files(), error)),
+        }
+    }
+}
+
+impl<C: Cache> DebugHook<ImportCache, C> for Debugger {
+    fn before_step(&mut self, vm: &mut VirtualMachine<ImportCache, C>, closure: &Closure) {
+        self.steps += 1;
+
+        if matches!(self.mode, Mode::Run) && self.steps.is_multiple_of(POLL_INTERVAL) {
+            self.poll();
+        }
+
+        // Inherited positions point to the source of a value which is being used elsewhere, so
+        // they would make the debugger jump around.
+        let TermPos::Original(span) = closure.body.pos else {
+            return;
+        };
+
+        let Some(location) = self.location(vm.import_resolver().files(), span) else {
+            return;
+        };
+
+        if let Some(reason) = self.stop_reason(span, location, depth(vm)) {
+            self.stop(vm, closure, span, location, reason);
+        }
+    }
+}
+
+/// The depth of the function calls, which is used to step over or out of functions.
+fn depth<C: Cache>(vm: &VirtualMachine<ImportCache, C>) -> usize {
+    vm.call_stack()
+        .0
+        .iter()
+        .filter(|elem| matches!(elem, StackElem::Fun { .. }))
+        .count()
+}
+
+pub fn threads() -> Value {
+    json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })
+}
+
+/// Returns the stack frames of the evaluation stopped at `span`. The topmost frame is the current
+/// expression, and the others are the call sites of the enclosing functions, innermost first.
+fn stack_trace<C: Cache>(vm: &VirtualMachine<ImportCache, C>, span: RawSpan) -> Value {
+    let files = vm.import_resolver().files();
+    let (calls, _) = vm.call_stack().group_by_calls(files);
+    let name = |call: Option<&CallDescr>| {
+        call.and_then(|call| call.head)
+            .map(|head| head.to_string())
+            .unwrap_or_else(|| "<top-level>".to_owned())
+    };
+
+    let current = frame(files, 0, name(calls.first()), span);
+    let callers = calls
+        .iter()
+        .enumerate()
+        .map(|(i, call)| frame(files, i + 1, name(calls.get(i + 1)), call.span));
+
+    let frames: Vec<_> = std::iter::once(current).chain(callers).collect();
+    json!({ "stackFrames": frames, "totalFrames": frames.len() })
+}
+
+fn frame(files: &Files, id: usize, name: String, span: RawSpan) -> Value {
+    let path = files.name(span.src_id).to_string_lossy();
+    let source_name = Path::new(&*path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.clone().into_owned());
+    let (line, column) = files
+        .location(span.src_id, span.start)
+        .map(|loc| (loc.line.to_usize() + 1, loc.column.to_usize() + 1))
+        .unwrap_or((1, 1));
+
+    json!({
+        "id": id,
+        "name": name,
+        "source": { "name": source_name, "path": path },
+        "line": line,
+        "column": column,
+    })
+}
+
+/// Renders an error as plain text.
+pub fn render_error(files: &Files, error: impl IntoDiagnostics) -> String {
+    let mut buffer = Vec::new();
+    report_with(
+        &mut NoColor::new(&mut buffer),
+        &mut files.clone(),
+        error,
+        ErrorFormat::Text,
+    );
+    String::from_utf8_lossy(&buffer).into_owned()
+}
@@ -0,0 +1,152 @@
+//! Rendering of Nickel values for the variables view and the debug console.
+use nickel_lang_core::{
+    eval::{cache::Cache, Closure, Environment},
+    pretty::PrettyPrintCap,
+    term::{RichTerm, Term},
+};
+
+/// The maximum length of a rendered value, in characters.
+const MAX_VALUE_WIDTH: usize = 80;
+
+/// The maximum number of field names shown in the summary of a record.
+const MAX_FIELD_NAMES: usize = 8;
+
+/// The maximum number of indirections followed by [resolve]. Cache elements normally point to
+/// values directly, but nothing prevents chains of variables.
+const MAX_INDIRECTIONS: usize = 32;
+
+/// A value as displayed by the client.
+pub struct Description {
+    pub value: String,
+    pub typ: String,
+    /// The children of the value, for records and arrays. A child without value is a record field
+    /// without definition.
+    pub children: Vec<(String, Option<Closure>)>,
+}
+
+/// Follows the cache indices and the variables of a closure, to get to the actual expression
+/// stored in the cache.
+pub fn resolve<C: Cache>(cache: &C, mut closure: Closure) -> Closure {
+    for _ in 0..MAX_INDIRECTIONS {
+        closure = match closure.body.as_ref() {
+            Term::Closure(idx) => cache.get(idx.clone()),
+            Term::Var(id) => match closure.env.get(&id.ident()) {
+                Some(idx) => cache.get(idx.clone()),
+                None => break,
+            },
+            _ => break,
+        };
+    }
+
+    closure
+}
+
+/// Describes a closure. The closure isn't evaluated: an expression that hasn't been forced yet is
+/// shown as is.
+pub fn describe<C: Cache>(cache: &C, closure: Closure) -> Description {
+    let Closure { body, env } = resolve(cache, closure);
+
+    match body.as_ref() {
+        Term::Record(data) => {
+            let mut fields: Vec<_> = data.fields.iter().collect();
+            fields.sort_by(|(id1, _), (id2, _)| id1.label().cmp(id2.label()));
+
+            let mut names: Vec<_> = fields
+                .iter()
+                .take(MAX_FIELD_NAMES)
+                .map(|(id, _)| id.label())
+                .collect();
+            if fields.len() > MAX_FIELD_NAMES {
+                names.push("…");
+            }
+
+            let value = if names.is_empty() {
+                "{}".to_owned()
+            } else {
+                format!("{{ {} }}", names.join(", "))
+            };
+
+            let children = fields
+                .into_iter()
+                .map(|(id, field)| {
+                    let child = field.value.as_ref().map(|value| Closure {
+                        body: value.clone(),
+                        env: env.clone(),
+                    });
+                    (id.to_string(), child)
+                })
+                .collect();
+
+            Description {
+                value,
+                typ: "Record".to_owned(),
+                children,
+            }
+        }
+        Term::Array(elts, _) => {
+            let value = match elts.len() {
+                0 => "[]".to_owned(),
+                1 => "[…] (1 element)".to_owned(),
+                n => format!("[…] ({n} elements)"),
+            };
+
+            let children = elts
+                .iter()
+                .enumerate()
+                .map(|(i, elt)| {
+                    let child = Closure {
+                        body: elt.clone(),
+                        env: env.clone(),
+                    };
+                    (format!("[{i}]"), Some(child))
+                })
+                .collect();
+
+            Description {
+                value,
+                typ: "Array".to_owned(),
+                children,
+            }
+        }
+        Term::Fun(..) | Term::FunPattern(..) | Term::Match(..) => Description {
+            value: "<function>".to_owned(),
+            typ: "Function".to_owned(),
+            children: Vec::new(),
+        },
+        term => Description {
+            value: body.pretty_print_cap(MAX_VALUE_WIDTH),
+            typ: term
+                .type_of()
+                .filter(|_| term.is_whnf())
+                .unwrap_or_else(|| "unevaluated".to_owned()),
+            children: Vec::new(),
+        },
+    }
+}
+
+/// Describes a value which has just been evaluated by the debugger.
+pub fn describe_value<C: Cache>(cache: &C, value: RichTerm) -> Description {
+    describe(
+        cache,
+        Closure {
+            body: value,
+            env: Environment::new(),
+        },
+    )
+}
+
+/// Returns the variables bound in an environment, sorted by name. Variables introduced by program
+/// transformations are left out.
+pub fn variables<C: Cache>(cache: &C, env: &Environment) -> Vec<(String, Option<Closure>)> {
+    let mut variables: Vec<_> = env
+        .iter()
+        .map(|(id, idx)| (id.label().to_owned(), idx.clone()))
+        .filter(|(name, _)| !name.starts_with(nickel_lang_core::identifier::GEN_PREFIX))
+        .collect();
+    variables.sort_by(|(name1, _), (name2, _)| name1.cmp(name2));
+
+    variables
+        .into_iter()
+        .map(|(name, idx)| (name, Some(cache.get(idx))))
+        .collect()
+}
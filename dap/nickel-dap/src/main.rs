@@ -0,0 +1,25 @@
+use anyhow::Result;
+
+mod connection;
+mod debugger;
+mod inspect;
+mod server;
+
+use connection::Connection;
+use server::Server;
+
+#[derive(clap::Parser, Debug)]
+/// A debug adapter for the Nickel language, speaking the Debug Adapter Protocol on its standard
+/// input and output.
+#[command(author, about, long_about = None, version)]
+struct Options {}
+
+fn main() -> Result<()> {
+    use clap::Parser;
+
+    env_logger::init();
+
+    let _options = Options::parse();
+
+    Server::new(Connection::stdio()).run()
+}
@@ -0,0 +1,172 @@
+//! The session with a client: initialization, configuration and launch of the program.
+use std::{io, path::PathBuf};
+
+use anyhow::Result;
+use log::debug;
+use nickel_lang_core::{
+    error::Error,
+    eval::cache::CacheImpl,
+    program::Program,
+    serialize::{self, ExportFormat},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    connection::{Connection, Request},
+    debugger::{self, Breakpoints, Debugger},
+};
+
+/// The arguments of the `launch` request.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LaunchArgs {
+    /// The Nickel file to evaluate.
+    program: PathBuf,
+    /// Pause before evaluating the first expression of the program.
+    #[serde(default)]
+    stop_on_entry: bool,
+    /// Evaluate the program without debugging it.
+    #[serde(default)]
+    no_debug: bool,
+}
+
+pub struct Server {
+    connection: Connection,
+    breakpoints: Breakpoints,
+    launch: Option<LaunchArgs>,
+    /// Whether the client is done sending the initial configuration, such as breakpoints.
+    configured: bool,
+    launched: bool,
+}
+
+impl Server {
+    pub fn new(connection: Connection) -> Self {
+        Server {
+            connection,
+            breakpoints: Breakpoints::new(),
+            launch: None,
+            configured: false,
+            launched: false,
+        }
+    }
+
+    /// Serves requests until the client disconnects.
+    pub fn run(mut self) -> Result<()> {
+        while let Some(req) = self.connection.recv() {
+            match req.command.as_str() {
+                "initialize" => {
+                    self.connection.respond(
+                        &req,
+                        json!({
+                            "supportsConfigurationDoneRequest": true,
+                            "supportsEvaluateForHovers": true,
+                            "supportsTerminateRequest": true,
+                        }),
+                    );
+                    self.connection.event("initialized", Value::Null);
+                }
+                "setBreakpoints" => {
+                    debugger::set_breakpoints(&mut self.breakpoints, &self.connection, &req)
+                }
+                "setExceptionBreakpoints" => self.connection.respond(&req, Value::Null),
+                "configurationDone" => {
+                    self.connection.respond(&req, Value::Null);
+                    self.configured = true;
+                }
+                "launch" => self.handle_launch(&req),
+                "threads" => self.connection.respond(&req, debugger::threads()),
+                "disconnect" | "terminate" => {
+                    self.connection.respond(&req, Value::Null);
+                    return Ok(());
+                }
+                _ => self
+                    .connection
+                    .respond_error(&req, &format!("unsupported request {}", req.command)),
+            }
+
+            if self.configured && !self.launched {
+                if let Some(args) = self.launch.take() {
+                    self.launched = true;
+                    self.evaluate(args);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_launch(&mut self, req: &Request) {
+        match serde_json::from_value::<LaunchArgs>(req.arguments.clone()) {
+            Ok(args) => {
+                debug!("launching {args:?}");
+                self.launch = Some(args);
+                self.connection.respond(req, Value::Null);
+            }
+            Err(err) => self
+                .connection
+                .respond_error(req, &format!("invalid launch arguments: {err}")),
+        }
+    }
+
+    /// Evaluates the program, as `nickel export` would, and reports the result.
+    fn evaluate(&mut self, args: LaunchArgs) {
+        let trace = TraceWriter(self.connection.clone());
+
+        let mut program = match Program::<CacheImpl>::new_from_file(&args.program, trace) {
+            Ok(program) => program,
+            Err(err) => {
+                let message = format!("cannot open {}: {err}\n", args.program.display());
+                self.connection.output("stderr", &message);
+                self.finish(1);
+                return;
+            }
+        };
+
+        program.color_opt = clap::ColorChoice::Never.into();
+
+        if !args.no_debug {
+            program.set_debug_hook(Debugger::new(
+                self.connection.clone(),
+                self.breakpoints.clone(),
+                args.stop_on_entry,
+            ));
+        }
+
+        let result = program.eval_full_for_export().and_then(|value| {
+            serialize::to_string(ExportFormat::Json, &value).map_err(Error::from)
+        });
+
+        match result {
+            Ok(output) => {
+                self.connection.output("stdout", &format!("{output}\n"));
+                self.finish(0);
+            }
+            Err(error) => {
+                self.connection
+                    .output("stderr", &program.report_as_str(error));
+                self.finish(1);
+            }
+        }
+    }
+
+    fn finish(&self, exit_code: i32) {
+        self.connection
+            .event("exited", json!({ "exitCode": exit_code }));
+        self.connection.event("terminated", Value::Null);
+    }
+}
+
+/// Forwards the output of `std.trace` to the debug console of the client.
+struct TraceWriter(Connection);
+
+impl io::Write for TraceWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.output("stderr", &String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+use std::{path::PathBuf, process::Command};
+
+use assert_cmd::prelude::CommandCargoExt;
+use dap_harness::Client;
+use pretty_assertions::assert_eq;
+use serde_json::{json, Value};
+
+const PROGRAM: &str = r#"let double = fun x =>
+  x * 2
+in
+let base = 20 in
+{
+  value = double base,
+  name = "config",
+}
+"#;
+
+/// Writes a program to a file named after the test, and returns its path.
+fn program_file(name: &str, contents: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.ncl"));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Starts a debugging session of `program` with breakpoints on the given lines.
+fn launch(program: &PathBuf, lines: &[usize], stop_on_entry: bool) -> Client {
+    let mut client = Client::new(Command::cargo_bin("nickel-dap").unwrap()).unwrap();
+    let breakpoints: Vec<_> = lines.iter().map(|line| json!({ "line": line })).collect();
+
+    let response = client
+        .request(
+            "setBreakpoints",
+            json!({ "source": { "path": program }, "breakpoints": breakpoints }),
+        )
+        .unwrap();
+    assert_eq!(
+        response["breakpoints"].as_array().unwrap().len(),
+        lines.len()
+    );
+
+    client
+        .request(
+            "launch",
+            json!({ "program": program, "stopOnEntry": stop_on_entry }),
+        )
+        .unwrap();
+    client.request("configurationDone", json!({})).unwrap();
+    client
+}
+
+fn stack_trace(client: &mut Client) -> Vec<(String, u64)> {
+    let response = client
+        .request("stackTrace", json!({ "threadId": 1 }))
+        .unwrap();
+
+    response["stackFrames"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|frame| {
+            (
+                frame["name"].as_str().unwrap().to_owned(),
+                frame["line"].as_u64().unwrap(),
+            )
+        })
+        .collect()
+}
+
+fn locals(client: &mut Client) -> Vec<(String, String)> {
+    let scopes = client.request("scopes", json!({ "frameId": 0 })).unwrap();
+    let reference = scopes["scopes"][0]["variablesReference"].clone();
+    variables(client, reference)
+}
+
+fn variables(client: &mut Client, reference: Value) -> Vec<(String, String)> {
+    let response = client
+        .request("variables", json!({ "variablesReference": reference }))
+        .unwrap();
+
+    response["variables"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|var| {
+            (
+                var["name"].as_str().unwrap().to_owned(),
+                var["value"].as_str().unwrap().to_owned(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn run_without_breakpoints() {
+    let program = program_file("run_without_breakpoints", PROGRAM);
+    let mut client = launch(&program, &[], false);
+
+    let output = client.wait_for_output("stdout").unwrap();
+    let value: Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(value, json!({ "name": "config", "value": 40 }));
+}
+
+#[test]
+fn breakpoint_in_function() {
+    let program = program_file("breakpoint_in_function", PROGRAM);
+    let mut client = launch(&program, &[2], false);
+
+    let stopped = client.wait_for_event("stopped").unwrap();
+    assert_eq!(stopped["reason"], "breakpoint");
+
+    assert_eq!(
+        stack_trace(&mut client),
+        vec![("double".to_owned(), 2), ("<top-level>".to_owned(), 6)]
+    );
+    assert_eq!(locals(&mut client), vec![("x".to_owned(), "20".to_owned())]);
+
+    let result = client
+        .request(
+            "evaluate",
+            json!({ "expression": "x + base_offset", "frameId": 0 }),
+        )
+        .unwrap_err();
+    assert!(result.to_string().contains("unbound identifier"));
+
+    let result = client
+        .request("evaluate", json!({ "expression": "x + 1", "frameId": 0 }))
+        .unwrap();
+    assert_eq!(result["result"], "21");
+
+    client
+        .request("continue", json!({ "threadId": 1 }))
+        .unwrap();
+    let output = client.wait_for_output("stdout").unwrap();
+    let value: Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(value, json!({ "name": "config", "value": 40 }));
+}
+
+#[test]
+fn inspect_records() {
+    let program = program_file(
+        "inspect_records",
+        "let server = { host = \"localhost\", ports = [80, 443] } in\n\
+         let url = server.host in\n\
+         url\n",
+    );
+    let mut client = launch(&program, &[3], false);
+
+    client.wait_for_event("stopped").unwrap();
+
+    let response = client
+        .request("evaluate", json!({ "expression": "server", "frameId": 0 }))
+        .unwrap();
+    assert_eq!(response["result"], "{ host, ports }");
+
+    let fields = variables(&mut client, response["variablesReference"].clone());
+    assert_eq!(
+        fields,
+        vec![
+            ("host".to_owned(), "\"localhost\"".to_owned()),
+            ("ports".to_owned(), "[…] (2 elements)".to_owned()),
+        ]
+    );
+
+    // A reference of 0 means that a variable has no children.
+    assert_eq!(variables(&mut client, json!(0)), Vec::new());
+    let response = client
+        .request("evaluate", json!({ "expression": "url", "frameId": 0 }))
+        .unwrap();
+    assert_eq!(response["result"], "\"localhost\"");
+}
+
+#[test]
+fn step_in_and_over() {
+    let program = program_file("step_in_and_over", PROGRAM);
+    let mut client = launch(&program, &[6], false);
+
+    let stopped = client.wait_for_event("stopped").unwrap();
+    assert_eq!(stopped["reason"], "breakpoint");
+    assert_eq!(stack_trace(&mut client)[0].1, 6);
+
+    // Stepping in goes to the definition of `double`, then enters its body.
+    client.request("stepIn", json!({ "threadId": 1 })).unwrap();
+    let stopped = client.wait_for_event("stopped").unwrap();
+    assert_eq!(stopped["reason"], "step");
+    assert_eq!(stack_trace(&mut client)[0].1, 1);
+
+    client.request("stepIn", json!({ "threadId": 1 })).unwrap();
+    client.wait_for_event("stopped").unwrap();
+    assert_eq!(stack_trace(&mut client)[0], ("double".to_owned(), 2));
+
+    client
+        .request("continue", json!({ "threadId": 1 }))
+        .unwrap();
+    client.wait_for_output("stdout").unwrap();
+
+    // Stepping over the definition of `double` doesn't stop in its body.
+    let mut client = launch(&program, &[6], false);
+    client.wait_for_event("stopped").unwrap();
+
+    client.request("stepIn", json!({ "threadId": 1 })).unwrap();
+    client.wait_for_event("stopped").unwrap();
+    client.request("next", json!({ "threadId": 1 })).unwrap();
+
+    let output = client.wait_for_output("stdout").unwrap();
+    let value: Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(value, json!({ "name": "config", "value": 40 }));
+}
+
+#[test]
+fn stop_on_entry() {
+    let program = program_file("stop_on_entry", PROGRAM);
+    let mut client = launch(&program, &[], true);
+
+    let stopped = client.wait_for_event("stopped").unwrap();
+    assert_eq!(stopped["reason"], "entry");
+    assert_eq!(stack_trace(&mut client)[0].0, "<top-level>");
+}
+
+#[test]
+fn evaluation_error() {
+    let program = program_file("evaluation_error", "{ port | Number = \"80\" }\n");
+    let mut client = launch(&program, &[], false);
+
+    let output = client.wait_for_output("stderr").unwrap();
+    assert!(output.contains("contract broken"), "{output}");
+}
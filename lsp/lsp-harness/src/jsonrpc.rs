@@ -14,7 +14,7 @@ use lsp_types::{
     ClientCapabilities, DidChangeTextDocumentParams, DidOpenTextDocumentParams,
     GotoDefinitionParams, GotoDefinitionResponse, InitializeParams, InitializedParams, Position,
     TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentPositionParams, Url,
-    VersionedTextDocumentIdentifier, WorkDoneProgressParams,
+    VersionedTextDocumentIdentifier, WorkDoneProgressParams, WorkspaceFolder,
 };
 use std::{
     io::{BufRead, BufReader, Read, Write},
@@ -92,8 +92,17 @@ pub struct Response {
 impl Server {
     /// Similar to `new`, but allows passing custom stuff
     pub fn new_with_options(
+        cmd: std::process::Command,
+        initialization_options: Option<serde_json::Value>,
+    ) -> Result<Server> {
+        Server::new_with_workspace(cmd, initialization_options, None)
+    }
+
+    /// Similar to `new_with_options`, but also sends some workspace folders
+    pub fn new_with_workspace(
         mut cmd: std::process::Command,
         initialization_options: Option<serde_json::Value>,
+        workspace_folders: Option<Vec<WorkspaceFolder>>,
     ) -> Result<Server> {
         let lsp = cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
 
@@ -104,7 +113,7 @@ impl Server {
             id: 0,
         };
 
-        lsp.initialize(initialization_options)?;
+        lsp.initialize(initialization_options, workspace_folders)?;
 
         Ok(lsp)
     }
@@ -159,7 +168,11 @@ impl Server {
         self.send_notification::<Exit>(())
     }
 
-    fn initialize(&mut self, initialization_options: Option<serde_json::Value>) -> Result<()> {
+    fn initialize(
+        &mut self,
+        initialization_options: Option<serde_json::Value>,
+        workspace_folders: Option<Vec<WorkspaceFolder>>,
+    ) -> Result<()> {
         // `root_path` is deprecated, but we need ot initialize the struct
         // somehow. There is no `Default` implementation for `InitilizeParams`
         // in versions of `lsp-types` compatible with `codespan-lsp`
@@ -171,7 +184,7 @@ impl Server {
             initialization_options,
             capabilities: ClientCapabilities::default(),
             trace: None,
-            workspace_folders: None,
+            workspace_folders,
             client_info: None,
             locale: None,
             work_done_progress_params: WorkDoneProgressParams::default(),
@@ -8,11 +8,15 @@ pub use jsonrpc::Server;
 use lsp_types::{
     notification::{Notification, PublishDiagnostics},
     request::{
-        Completion, DocumentSymbolRequest, Formatting, GotoDefinition, HoverRequest, References,
-        Rename, Request as LspRequest,
+        CodeActionRequest, Completion, DocumentSymbolRequest, Formatting, GotoDefinition,
+        HoverRequest, InlayHintRequest, References, Rename, Request as LspRequest,
+        SemanticTokensFullRequest, SemanticTokensRangeRequest, SignatureHelpRequest,
+        WorkspaceSymbolRequest,
     },
-    CompletionParams, DocumentFormattingParams, DocumentSymbolParams, GotoDefinitionParams,
-    HoverParams, PublishDiagnosticsParams, ReferenceParams, RenameParams, Url,
+    CodeActionParams, CompletionParams, DocumentFormattingParams, DocumentSymbolParams,
+    GotoDefinitionParams, HoverParams, InlayHintParams, PublishDiagnosticsParams, ReferenceParams,
+    RenameParams, SemanticTokensParams, SemanticTokensRangeParams, SignatureHelpParams, Url,
+    WorkspaceFolder, WorkspaceSymbolParams,
 };
 pub use output::LspDebug;
 use serde::Deserialize;
@@ -45,6 +49,12 @@ pub enum Request {
     Hover(HoverParams),
     Rename(RenameParams),
     Symbols(DocumentSymbolParams),
+    SemanticTokens(SemanticTokensParams),
+    SemanticTokensRange(SemanticTokensRangeParams),
+    InlayHints(InlayHintParams),
+    SignatureHelp(SignatureHelpParams),
+    CodeAction(CodeActionParams),
+    WorkspaceSymbols(WorkspaceSymbolParams),
 }
 
 #[derive(Deserialize, Debug, Default)]
@@ -126,6 +136,23 @@ fn modify_request_uri(req: &mut Request) {
         Request::Symbols(params) => {
             params.text_document.uri = file_url(&params.text_document.uri);
         }
+        Request::SemanticTokens(params) => {
+            params.text_document.uri = file_url(&params.text_document.uri);
+        }
+        Request::SemanticTokensRange(params) => {
+            params.text_document.uri = file_url(&params.text_document.uri);
+        }
+        Request::InlayHints(params) => {
+            params.text_document.uri = file_url(&params.text_document.uri);
+        }
+        Request::SignatureHelp(params) => {
+            params.text_document_position_params.text_document.uri =
+                file_url(&params.text_document_position_params.text_document.uri);
+        }
+        Request::CodeAction(params) => {
+            params.text_document.uri = file_url(&params.text_document.uri);
+        }
+        Request::WorkspaceSymbols(_) => {}
     }
 }
 
@@ -207,6 +234,16 @@ impl TestHarness {
             out: Vec::new(),
         }
     }
+
+    /// Starts the language server with some workspace folders, which it indexes on startup.
+    pub fn new_with_workspace(folders: Vec<WorkspaceFolder>) -> Self {
+        let cmd = std::process::Command::cargo_bin("nls").unwrap();
+        let srv = Server::new_with_workspace(cmd, None, Some(folders)).unwrap();
+        Self {
+            srv,
+            out: Vec::new(),
+        }
+    }
     pub fn new() -> Self {
         Self::new_with_options(None)
     }
@@ -229,6 +266,12 @@ impl TestHarness {
             Request::References(r) => self.request::<References>(r),
             Request::Rename(r) => self.request::<Rename>(r),
             Request::Symbols(s) => self.request::<DocumentSymbolRequest>(s),
+            Request::SemanticTokens(s) => self.request::<SemanticTokensFullRequest>(s),
+            Request::SemanticTokensRange(s) => self.request::<SemanticTokensRangeRequest>(s),
+            Request::InlayHints(h) => self.request::<InlayHintRequest>(h),
+            Request::SignatureHelp(s) => self.request::<SignatureHelpRequest>(s),
+            Request::CodeAction(a) => self.request::<CodeActionRequest>(a),
+            Request::WorkspaceSymbols(s) => self.request::<WorkspaceSymbolRequest>(s),
         }
     }
 
@@ -4,7 +4,11 @@
 
 use std::io::Write;
 
-use lsp_types::{Diagnostic, DocumentSymbolResponse, GotoDefinitionResponse, WorkspaceEdit};
+use lsp_types::{
+    CodeActionOrCommand, Diagnostic, DocumentSymbolResponse, GotoDefinitionResponse, InlayHint,
+    InlayHintLabel, ParameterLabel, SemanticTokens, SemanticTokensRangeResult,
+    SemanticTokensResult, WorkspaceEdit, WorkspaceSymbolResponse,
+};
 
 pub trait LspDebug {
     fn debug(&self, w: impl Write) -> std::io::Result<()>;
@@ -193,7 +197,28 @@ impl LspDebug for lsp_types::SymbolInformation {
     fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
         let name = &self.name;
         let kind = self.kind;
-        write!(w, "{name} ({kind:?})@{}", self.location.debug_str())
+        write!(w, "{name} ({kind:?})@{}", self.location.debug_str())?;
+        if let Some(container) = &self.container_name {
+            write!(w, " in {container}")?;
+        }
+        Ok(())
+    }
+}
+
+impl LspDebug for lsp_types::WorkspaceSymbol {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        let name = &self.name;
+        let kind = self.kind;
+        write!(w, "{name} ({kind:?})")
+    }
+}
+
+impl LspDebug for WorkspaceSymbolResponse {
+    fn debug(&self, w: impl Write) -> std::io::Result<()> {
+        match self {
+            WorkspaceSymbolResponse::Flat(symbols) => symbols.debug(w),
+            WorkspaceSymbolResponse::Nested(symbols) => symbols.debug(w),
+        }
     }
 }
 
@@ -232,6 +257,113 @@ impl LspDebug for WorkspaceEdit {
     }
 }
 
+impl LspDebug for CodeActionOrCommand {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        match self {
+            CodeActionOrCommand::Command(command) => write!(w, "command \"{}\"", command.title),
+            CodeActionOrCommand::CodeAction(action) => {
+                let kind = action.kind.as_ref().map_or("none", |kind| kind.as_str());
+                write!(
+                    w,
+                    "{kind} \"{}\": {}",
+                    action.title,
+                    action.edit.debug_str()
+                )
+            }
+        }
+    }
+}
+
+// Semantic tokens are delta-encoded on the wire. We decode them to absolute positions, and print
+// one token per line as `line:start+length type modifiers`, with the type and the modifiers given
+// as indices in the legend.
+impl LspDebug for SemanticTokens {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        let mut line = 0;
+        let mut start = 0;
+        for token in &self.data {
+            if token.delta_line > 0 {
+                start = 0;
+            }
+            line += token.delta_line;
+            start += token.delta_start;
+            writeln!(
+                w,
+                "{line}:{start}+{} {} {:#06b}",
+                token.length, token.token_type, token.token_modifiers_bitset
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl LspDebug for SemanticTokensResult {
+    fn debug(&self, w: impl Write) -> std::io::Result<()> {
+        match self {
+            SemanticTokensResult::Tokens(tokens) => tokens.debug(w),
+            SemanticTokensResult::Partial(partial) => SemanticTokens {
+                result_id: None,
+                data: partial.data.clone(),
+            }
+            .debug(w),
+        }
+    }
+}
+
+impl LspDebug for SemanticTokensRangeResult {
+    fn debug(&self, w: impl Write) -> std::io::Result<()> {
+        match self {
+            SemanticTokensRangeResult::Tokens(tokens) => tokens.debug(w),
+            SemanticTokensRangeResult::Partial(partial) => SemanticTokens {
+                result_id: None,
+                data: partial.data.clone(),
+            }
+            .debug(w),
+        }
+    }
+}
+
+impl LspDebug for InlayHint {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        let label = match &self.label {
+            InlayHintLabel::String(s) => s.clone(),
+            InlayHintLabel::LabelParts(parts) => {
+                parts.iter().map(|part| part.value.as_str()).collect()
+            }
+        };
+        write!(
+            w,
+            "{}:{} {label:?}",
+            self.position.line, self.position.character
+        )
+    }
+}
+
+impl LspDebug for lsp_types::SignatureHelp {
+    fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
+        for sig in &self.signatures {
+            write!(w, "{}", sig.label)?;
+
+            let param = self
+                .active_parameter
+                .and_then(|idx| sig.parameters.as_ref()?.get(idx as usize));
+            if let Some(param) = param {
+                let label = match &param.label {
+                    ParameterLabel::Simple(s) => s.clone(),
+                    // Offsets are in UTF-16 code units.
+                    ParameterLabel::LabelOffsets([start, end]) => String::from_utf16_lossy(
+                        &sig.label.encode_utf16().collect::<Vec<_>>()
+                            [*start as usize..*end as usize],
+                    ),
+                };
+                write!(w, " (active parameter: {label})")?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
+
 impl LspDebug for Diagnostic {
     fn debug(&self, mut w: impl Write) -> std::io::Result<()> {
         write!(w, "{}: {}", self.range.debug_str(), self.message)
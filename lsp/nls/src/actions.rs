@@ -1,7 +1,405 @@
+use std::collections::HashMap;
+
 use lsp_server::{RequestId, Response, ResponseError};
-use lsp_types::{CodeActionOrCommand, CodeActionParams};
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams, Diagnostic, Range,
+    TextDocumentPositionParams, TextEdit, Url, WorkspaceEdit,
+};
+use nickel_lang_core::{
+    error::{suggest, Error, EvalError, TypecheckError},
+    files::FileId,
+    identifier::{Ident, LocIdent},
+    position::{RawSpan, TermPos},
+    pretty::ident_quoted,
+    term::{record::RecordData, RichTerm, Term, Traverse, TraverseControl, TypeAnnotation},
+    typ::{RecordRowsIteratorItem, Type, TypeF},
+};
+
+use crate::{
+    cache::CacheExt,
+    diagnostic::{LocationCompat, OrdRange, QuickFix},
+    field_walker::{Def, FieldResolver, Record},
+    server::Server,
+    usage::UsageLookup,
+    world::World,
+};
+
+/// Computes the quick fixes for an error found in `file_id`.
+pub fn quick_fixes(world: &World, file_id: FileId, err: &Error) -> Vec<QuickFix> {
+    let fix = match err {
+        Error::TypecheckError(TypecheckError::UnboundIdentifier { id, pos })
+        | Error::EvalError(EvalError::UnboundIdentifier(id, pos)) => {
+            unbound_identifier(world, file_id, *id, *pos)
+        }
+        Error::TypecheckError(TypecheckError::MissingRow {
+            id, expected, pos, ..
+        }) => {
+            let field = match row_type(expected, id.ident()) {
+                Some(typ) => format!("{} | {typ} = null", ident_quoted(id)),
+                None => format!("{} = null", ident_quoted(id)),
+            };
+            add_field(world, file_id, *pos, &field).map(|edit| QuickFix {
+                title: format!("Add the missing field `{id}`"),
+                edits: vec![edit],
+            })
+        }
+        Error::TypecheckError(TypecheckError::ExtraRow { id, pos, .. }) => {
+            remove_field(world, file_id, *pos, id.ident()).map(|edit| QuickFix {
+                title: format!("Remove the field `{id}`"),
+                edits: vec![edit],
+            })
+        }
+        _ => None,
+    };
+
+    fix.into_iter().collect()
+}
+
+fn edit(world: &World, span: RawSpan, text: String) -> (OrdRange, String) {
+    (OrdRange(Range::from_span(&span, world.cache.files())), text)
+}
+
+/// Returns the span of `pos` if it's in `file_id`.
+fn span_in(pos: TermPos, file_id: FileId) -> Option<RawSpan> {
+    pos.into_opt().filter(|span| span.src_id == file_id)
+}
+
+/// Suggests to replace an unbound identifier with the closest name in scope.
+fn unbound_identifier(
+    world: &World,
+    file_id: FileId,
+    id: LocIdent,
+    pos: TermPos,
+) -> Option<QuickFix> {
+    let span = span_in(pos, file_id)?;
+    // The file failed to typecheck, so it has no analysis, and we compute the names in scope
+    // ourselves.
+    let rt = world.cache.get_ref(file_id)?;
+    let var = rt.find_map(|rt: &RichTerm| {
+        (matches!(rt.as_ref(), Term::Var(_)) && rt.pos.into_opt() == Some(span)).then(|| rt.clone())
+    })?;
+
+    let usages = UsageLookup::new(rt, &world.initial_term_env);
+    let mut names: Vec<_> = usages
+        .env(&var)?
+        .iter_elems()
+        .map(|(name, _)| name.label().to_owned())
+        .collect();
+    names.sort();
+    names.dedup();
+
+    let name = suggest::find_best_match(&names, &id.label())?;
+    Some(QuickFix {
+        title: format!("Replace with `{name}`"),
+        edits: vec![edit(world, span, name.to_owned())],
+    })
+}
+
+/// The type of the field `id` in a record type.
+fn row_type(typ: &Type, id: Ident) -> Option<&Type> {
+    let TypeF::Record(rows) = &typ.typ else {
+        return None;
+    };
+    rows.iter().find_map(|item| match item {
+        RecordRowsIteratorItem::Row(row) if row.id.ident() == id => Some(row.typ),
+        _ => None,
+    })
+}
 
-use crate::{cache::CacheExt, server::Server};
+/// Computes the fixes adding a definition for the fields that the record literal under the
+/// cursor misses, that is, the fields declared without a value by a record that is merged with,
+/// or annotated by, this literal.
+///
+/// Evaluation reports missing definitions, but they are expected in partial configurations, so
+/// they aren't published as diagnostics and we look for them on demand instead.
+fn missing_definitions(
+    world: &World,
+    params: &CodeActionParams,
+) -> Result<Vec<QuickFix>, ResponseError> {
+    let pos = world.cache.position(&TextDocumentPositionParams {
+        text_document: params.text_document.clone(),
+        position: params.range.start,
+    })?;
+    let Some(rt) = world.cache.get_ref(pos.src_id) else {
+        return Ok(Vec::new());
+    };
+
+    // The innermost record literal containing the cursor.
+    let mut record = None;
+    rt.traverse_ref(
+        &mut |rt: &RichTerm, _: &()| {
+            if !rt.pos.contains(pos) {
+                return TraverseControl::SkipBranch;
+            }
+            if let Term::Record(_) | Term::RecRecord(..) = rt.as_ref() {
+                record = Some(rt.clone());
+            }
+            TraverseControl::<(), ()>::Continue
+        },
+        &(),
+    );
+    let Some(record) = record else {
+        return Ok(Vec::new());
+    };
+    let (Term::Record(data) | Term::RecRecord(data, ..)) = record.as_ref() else {
+        return Ok(Vec::new());
+    };
+
+    let mut missing: Vec<(LocIdent, String)> = Vec::new();
+    for cousin in FieldResolver::new(world).cousin_records(&record) {
+        let Record::RecordTerm(cousin) = cousin else {
+            continue;
+        };
+        for (id, field) in &cousin.fields {
+            let defined = field.value.is_some()
+                || data.fields.contains_key(id)
+                || missing.iter().any(|(other, _)| other == id);
+            if !defined {
+                let contracts: String = field
+                    .metadata
+                    .annotation
+                    .iter()
+                    .map(|contract| format!(" | {}", contract.typ))
+                    .collect();
+                missing.push((*id, contracts));
+            }
+        }
+    }
+    missing.sort_by(|(a, _), (b, _)| a.label().cmp(b.label()));
+
+    Ok(missing
+        .into_iter()
+        .filter_map(|(id, contracts)| {
+            let field = format!("{}{contracts} = null", ident_quoted(&id));
+            add_field(world, pos.src_id, record.pos, &field).map(|edit| QuickFix {
+                title: format!("Add a definition for `{id}`"),
+                edits: vec![edit],
+            })
+        })
+        .collect())
+}
+
+/// Builds an edit adding `field` at the end of the record literal at `pos`. The new field goes
+/// on its own line if the record spans several lines.
+fn add_field(
+    world: &World,
+    file_id: FileId,
+    pos: TermPos,
+    field: &str,
+) -> Option<(OrdRange, String)> {
+    // Inherited positions can span more than the record literal itself.
+    let span = match pos {
+        TermPos::Original(span) if span.src_id == file_id => span,
+        _ => return None,
+    };
+    let source = world.cache.files().source(file_id);
+    let text = source.get(span.start.to_usize()..span.end.to_usize())?;
+    if !text.starts_with('{') || !text.ends_with('}') {
+        return None;
+    }
+
+    let close = span.end.to_usize() - 1;
+    let last_end = source[..close].trim_end().len();
+    let last = source[..last_end].chars().next_back()?;
+    let sep = if matches!(last, '{' | ',') { "" } else { "," };
+
+    let (end, new_text) = if text.contains('\n') {
+        let line_start = source[..last_end].rfind('\n').map_or(0, |i| i + 1);
+        let mut indent: String = source[line_start..]
+            .chars()
+            .take_while(|c| matches!(c, ' ' | '\t'))
+            .collect();
+        if last == '{' {
+            indent.push_str("  ");
+        }
+        (last_end, format!("{sep}\n{indent}{field},"))
+    } else {
+        (close, format!("{sep} {field} "))
+    };
+
+    let span = RawSpan {
+        src_id: file_id,
+        start: (last_end as u32).into(),
+        end: (end as u32).into(),
+    };
+    Some(edit(world, span, new_text))
+}
+
+/// Builds an edit removing the field `id` from the record literal at `pos`, together with its
+/// separating comma.
+fn remove_field(
+    world: &World,
+    file_id: FileId,
+    pos: TermPos,
+    id: Ident,
+) -> Option<(OrdRange, String)> {
+    let span = span_in(pos, file_id)?;
+    let rt = world.cache.get_ref(file_id)?;
+    let data: RecordData = rt.find_map(|rt: &RichTerm| match rt.as_ref() {
+        Term::Record(data) | Term::RecRecord(data, ..) if rt.pos.into_opt() == Some(span) => {
+            Some(data.clone())
+        }
+        _ => None,
+    })?;
+
+    let (name, field) = data.fields.iter().find(|(name, _)| name.ident() == id)?;
+    let name_span = span_in(name.pos, file_id)?;
+    let mut start = name_span.start.to_usize();
+    let mut end = field
+        .value
+        .iter()
+        .filter_map(|value| span_in(value.pos, file_id))
+        .chain(
+            field
+                .metadata
+                .annotation
+                .iter()
+                .map(|contract| contract.label.span),
+        )
+        .map(|span| span.end.to_usize())
+        .fold(name_span.end.to_usize(), usize::max);
+
+    let source = world.cache.files().source(file_id);
+    let is_blank = |c: char| matches!(c, ' ' | '\t');
+    let after = source[end..].trim_start_matches(is_blank);
+
+    if let Some(rest) = after.strip_prefix(',') {
+        // Remove the comma after the field, and the whole line if the field is alone on it.
+        let rest = rest.trim_start_matches(is_blank);
+        end = source.len() - rest.len();
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        if source[line_start..start].trim().is_empty() && rest.starts_with('\n') {
+            start = line_start;
+            end += 1;
+        }
+    } else {
+        // The last field has no comma after it, so we remove the comma before it instead.
+        let before = source[..start].trim_end();
+        if before.ends_with(',') {
+            start = before.len() - 1;
+        }
+    }
+
+    let span = RawSpan {
+        src_id: file_id,
+        start: (start as u32).into(),
+        end: (end as u32).into(),
+    };
+    Some(edit(world, span, String::new()))
+}
+
+/// Computes the actions changing the annotation of the binding under the cursor: adding the
+/// inferred type as a type annotation, or turning the binding into statically typed code.
+fn annotation_actions(
+    world: &World,
+    params: &CodeActionParams,
+) -> Result<Vec<(String, TextEdit)>, ResponseError> {
+    let pos = world.cache.position(&TextDocumentPositionParams {
+        text_document: params.text_document.clone(),
+        position: params.range.start,
+    })?;
+
+    // Files that failed to typecheck have no analysis, and only get quick fixes.
+    let Ok(Some(ident)) = world.lookup_ident_by_position(pos) else {
+        return Ok(Vec::new());
+    };
+    let Some(def) = world.analysis.get_def(&ident) else {
+        return Ok(Vec::new());
+    };
+    // Only offer the actions on the binding itself, not on its usages.
+    if def.ident().pos != ident.pos {
+        return Ok(Vec::new());
+    }
+
+    let empty = TypeAnnotation::default();
+    let annot = match def {
+        Def::Let { value, path, .. } if path.is_empty() => match value.as_ref() {
+            Term::Annotated(annot, _) => annot,
+            _ => &empty,
+        },
+        Def::Field { metadata, .. } => &metadata.annotation,
+        _ => return Ok(Vec::new()),
+    };
+    let Some(span) = ident.pos.into_opt() else {
+        return Ok(Vec::new());
+    };
+    let files = world.cache.files();
+    let mut actions = Vec::new();
+
+    if annot.typ.is_some() {
+        return Ok(actions);
+    }
+
+    if let Some(contract) = annot.contracts.first() {
+        // Turn the first contract annotation `| T` into a type annotation `: T`.
+        let source = files.source(span.src_id);
+        let before = source[..contract.label.span.start.to_usize()].trim_end();
+        if before.ends_with('|') {
+            let bar = RawSpan {
+                src_id: span.src_id,
+                start: ((before.len() - 1) as u32).into(),
+                end: (before.len() as u32).into(),
+            };
+            actions.push((
+                "Convert to static typing".to_owned(),
+                TextEdit {
+                    range: Range::from_span(&bar, files),
+                    new_text: ":".to_owned(),
+                },
+            ));
+        }
+        return Ok(actions);
+    }
+
+    let end = Range::from_span(&span, files).end;
+    let insert = |text: String| TextEdit {
+        range: Range::new(end, end),
+        new_text: text,
+    };
+
+    match world.analysis.get_type_for_ident(&ident) {
+        Some(typ) if !matches!(typ.typ, TypeF::Dyn | TypeF::Wildcard(_)) => {
+            actions.push((
+                format!("Add type annotation `: {typ}`"),
+                insert(format!(" : {typ}")),
+            ));
+        }
+        _ => {
+            actions.push((
+                "Convert to static typing".to_owned(),
+                insert(" : _".to_owned()),
+            ));
+        }
+    }
+
+    Ok(actions)
+}
+
+fn workspace_edit(uri: &Url, edits: Vec<TextEdit>) -> WorkspaceEdit {
+    WorkspaceEdit {
+        changes: Some(HashMap::from([(uri.clone(), edits)])),
+        ..Default::default()
+    }
+}
+
+fn quick_fix(uri: &Url, fix: QuickFix, diagnostic: Option<Diagnostic>) -> CodeAction {
+    let edits = fix
+        .edits
+        .into_iter()
+        .map(|(range, new_text)| TextEdit {
+            range: range.0,
+            new_text,
+        })
+        .collect();
+
+    CodeAction {
+        title: fix.title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: diagnostic.map(|diagnostic| vec![diagnostic]),
+        edit: Some(workspace_edit(uri, edits)),
+        ..Default::default()
+    }
+}
 
 pub fn handle_code_action(
     params: CodeActionParams,
@@ -9,13 +407,38 @@ pub fn handle_code_action(
     server: &mut Server,
 ) -> Result<(), ResponseError> {
     let mut actions = Vec::new();
+    let uri = &params.text_document.uri;
+
+    for diagnostic in &params.context.diagnostics {
+        let fixes: Vec<QuickFix> = diagnostic
+            .data
+            .clone()
+            .and_then(|data| serde_json::from_value(data).ok())
+            .unwrap_or_default();
+
+        for fix in fixes {
+            actions.push(CodeActionOrCommand::CodeAction(quick_fix(
+                uri,
+                fix,
+                Some(diagnostic.clone()),
+            )));
+        }
+    }
+
+    if server.world.cache.file_id(uri)?.is_some() {
+        for fix in missing_definitions(&server.world, &params)? {
+            actions.push(CodeActionOrCommand::CodeAction(quick_fix(uri, fix, None)));
+        }
+
+        for (title, edit) in annotation_actions(&server.world, &params)? {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title,
+                kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                edit: Some(workspace_edit(uri, vec![edit])),
+                ..Default::default()
+            }));
+        }
 
-    if server
-        .world
-        .cache
-        .file_id(&params.text_document.uri)?
-        .is_some()
-    {
         actions.push(CodeActionOrCommand::Command(lsp_types::Command {
             title: "evaluate term".to_owned(),
             command: "eval".to_owned(),
@@ -118,7 +118,7 @@ pub fn worker_main() -> anyhow::Result<()> {
                             nickel_lang_core::error::EvalError::MissingFieldDef { .. }
                         )
                     })
-                    .flat_map(|e| world.lsp_diagnostics(file_id, e)),
+                    .flat_map(|e| world.lsp_diagnostics_with_fixes(file_id, e.into())),
             );
         }
 
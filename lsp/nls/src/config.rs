@@ -44,9 +44,31 @@ impl Default for LspEvalConfig {
     }
 }
 
+/// The kinds of inlay hints reported by the LSP
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LspInlayHintsConfig {
+    /// Show the types inferred by the typechecker for let-bindings and function parameters in
+    /// statically typed code
+    pub inferred_types: bool,
+    /// Show the contracts that record fields inherit from the records they are merged with
+    pub inherited_contracts: bool,
+}
+
+impl Default for LspInlayHintsConfig {
+    fn default() -> Self {
+        LspInlayHintsConfig {
+            inferred_types: true,
+            inherited_contracts: true,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Default)]
 #[serde(default)]
 pub struct LspConfig {
     /// Configuration for the background evaluator in the LSP
     pub eval_config: LspEvalConfig,
+    /// Configuration for the inlay hints
+    pub inlay_hints: LspInlayHintsConfig,
 }
@@ -24,6 +24,16 @@ pub struct SerializableDiagnostic {
     pub code: Option<String>,
     pub message: String,
     pub related_information: Option<Vec<OrdDiagnosticRelatedInformation>>,
+    /// The quick fixes for the error behind this diagnostic. They are sent to the client in the
+    /// `data` field of the diagnostic, and come back with code action requests.
+    pub fixes: Vec<QuickFix>,
+}
+
+/// A fix for the error behind a diagnostic, as a list of edits to the file of the diagnostic.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct QuickFix {
+    pub title: String,
+    pub edits: Vec<(OrdRange, String)>,
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Default, Deserialize, Serialize)]
@@ -77,6 +87,7 @@ impl From<SerializableDiagnostic> for lsp_types::Diagnostic {
             related_information: d
                 .related_information
                 .map(|xs| xs.into_iter().map(|x| x.0).collect()),
+            data: (!d.fixes.is_empty()).then(|| serde_json::to_value(&d.fixes).unwrap()),
             ..Default::default()
         }
     }
@@ -190,6 +201,7 @@ impl DiagnosticCompat for SerializableDiagnostic {
                             })
                             .collect(),
                     ),
+                    fixes: Vec::new(),
                 });
             }
         }
@@ -203,6 +215,7 @@ impl DiagnosticCompat for SerializableDiagnostic {
                 severity: Some(lsp_types::DiagnosticSeverity::HINT),
                 code: code.clone(),
                 related_information: None,
+                fixes: Vec::new(),
             }
         }));
         diagnostics
@@ -1,13 +1,16 @@
-use std::path::PathBuf;
+use std::{collections::HashSet, path::PathBuf};
 
 use anyhow::Result;
 use lsp_server::RequestId;
 use lsp_types::{
     notification::{DidOpenTextDocument, Notification},
-    DidChangeTextDocumentParams, DidOpenTextDocumentParams, Url,
+    DidChangeTextDocumentParams, DidChangeWatchedFilesParams, DidOpenTextDocumentParams,
+    FileChangeType, Url,
 };
+use nickel_lang_core::files::FileId;
 
 use crate::{
+    cache::CacheExt as _,
     error::Error,
     trace::{param::FileUpdate, Enrich, Trace},
 };
@@ -44,10 +47,11 @@ pub fn handle_open(server: &mut Server, params: DidOpenTextDocumentParams) -> Re
 
     let diags = server.world.parse_and_typecheck(file_id);
     server.issue_diagnostics(file_id, diags);
+    server.world.index_file(file_id);
 
-    for rev_dep in &invalid {
-        let diags = server.world.parse_and_typecheck(*rev_dep);
-        server.issue_diagnostics(*rev_dep, diags);
+    for rev_dep in open_files(server, invalid.iter().copied()) {
+        let diags = server.world.parse_and_typecheck(rev_dep);
+        server.issue_diagnostics(rev_dep, diags);
     }
     Trace::reply(id);
     Ok(server.world.uris(invalid).cloned().collect())
@@ -76,11 +80,52 @@ pub fn handle_save(server: &mut Server, params: DidChangeTextDocumentParams) ->
 
     let diags = server.world.parse_and_typecheck(file_id);
     server.issue_diagnostics(file_id, diags);
+    server.world.index_file(file_id);
 
+    for f in open_files(server, invalid.iter().copied()) {
+        let errors = server.world.parse_and_typecheck(f);
+        server.issue_diagnostics(f, errors);
+    }
+    Trace::reply(id);
+    Ok(server.world.uris(invalid).cloned().collect())
+}
+
+/// Returns a list of open files that were potentially invalidated by the changes.
+///
+/// Changes to the files that are open are ignored, because the editor has the latest version of
+/// them.
+pub fn handle_watched_files(
+    server: &mut Server,
+    params: DidChangeWatchedFilesParams,
+) -> Result<Vec<Url>> {
+    let mut invalid = HashSet::new();
+
+    for change in params.changes {
+        let file_id = server.world.cache.file_id(&change.uri)?;
+        if file_id.is_some_and(|id| server.world.file_uris.contains_key(&id)) {
+            continue;
+        }
+
+        match (change.typ, file_id) {
+            (FileChangeType::DELETED, Some(id)) => server.world.remove_from_disk(id),
+            (FileChangeType::DELETED, None) => {}
+            _ => invalid.extend(server.world.load_from_disk(uri_to_path(&change.uri)?)?),
+        }
+    }
+
+    let invalid = open_files(server, invalid);
     for f in &invalid {
         let errors = server.world.parse_and_typecheck(*f);
         server.issue_diagnostics(*f, errors);
     }
-    Trace::reply(id);
     Ok(server.world.uris(invalid).cloned().collect())
 }
+
+/// Keeps the files that are open. The other files of the workspace are only analyzed again when
+/// a request needs it, instead of every time one of their imports changes.
+fn open_files(server: &Server, files: impl IntoIterator<Item = FileId>) -> Vec<FileId> {
+    files
+        .into_iter()
+        .filter(|f| server.world.file_uris.contains_key(f))
+        .collect()
+}
@@ -27,6 +27,7 @@ mod term;
 mod trace;
 mod usage;
 mod utils;
+mod workspace;
 mod world;
 
 use crate::{config::LspConfig, trace::Trace};
@@ -93,7 +94,11 @@ fn main() -> Result<()> {
 
     debug!("Parsed InitializeParams: {:?}", config);
 
-    let _server = Server::new(connection, config).run();
+    // We only need the workspace folders and the capabilities of the client, which are optional,
+    // so we don't fail on parameters that we can't parse.
+    let client = serde_json::from_value(initialize_params).unwrap_or_default();
+
+    let _server = Server::new(connection, config, &client).run();
 
     Ok(())
 }
@@ -62,6 +62,11 @@ pub fn handle_references(
         .position(&params.text_document_position)?;
     let ident = server.world.lookup_ident_by_position(pos)?;
 
+    // Record fields can be accessed from any file of the workspace.
+    if let Some(ident) = ident {
+        server.world.analyze_accesses(ident.ident);
+    }
+
     // The "references" of a symbol are all the usages of its definitions,
     // so first find the definitions and then find their usages.
     let term = server.world.lookup_term_by_position(pos)?;
@@ -20,11 +20,32 @@ use crate::{
 };
 
 #[derive(Debug, Default)]
-struct HoverData {
-    values: Vec<RichTerm>,
-    metadata: Vec<FieldMetadata>,
-    span: Option<RawSpan>,
-    ty: Option<Type>,
+pub(crate) struct HoverData {
+    pub(crate) values: Vec<RichTerm>,
+    pub(crate) metadata: Vec<FieldMetadata>,
+    pub(crate) span: Option<RawSpan>,
+    pub(crate) ty: Option<Type>,
+}
+
+impl HoverData {
+    /// All the type and contract annotations we can find, possibly with duplicates.
+    pub(crate) fn annotations(&self) -> impl Iterator<Item = &Type> {
+        self.metadata
+            .iter()
+            .flat_map(|m| m.annotation.iter().map(|typ| &typ.typ))
+            .chain(
+                self.values
+                    .iter()
+                    .flat_map(annotated_contracts)
+                    .map(|contract| contract.label.typ.as_ref()),
+            )
+    }
+
+    /// The documentation, if any.
+    pub(crate) fn doc(&self) -> Option<&str> {
+        // Not sure how to do documentation merging yet, so pick the first non-empty one.
+        self.metadata.iter().find_map(|m| m.doc.as_deref())
+    }
 }
 
 impl Combine for HoverData {
@@ -66,7 +87,7 @@ fn values_and_metadata_from_field(
     (values, metadata)
 }
 
-fn ident_hover(ident: LocIdent, world: &World) -> Option<HoverData> {
+pub(crate) fn ident_hover(ident: LocIdent, world: &World) -> Option<HoverData> {
     let ty = world.analysis.get_type_for_ident(&ident).cloned();
     let span = ident.pos.into_opt()?;
     let mut ret = HoverData {
@@ -105,7 +126,7 @@ fn ident_hover(ident: LocIdent, world: &World) -> Option<HoverData> {
     Some(ret)
 }
 
-fn term_hover(rt: &RichTerm, world: &World) -> Option<HoverData> {
+pub(crate) fn term_hover(rt: &RichTerm, world: &World) -> Option<HoverData> {
     let ty = world.analysis.get_type(rt).cloned();
     let span = rt.pos.into_opt();
 
@@ -164,18 +185,7 @@ pub fn handle(
         // Collect all the type and contract annotations we can find. We don't distinguish between them
         // (and we deduplicate annotations if they're present as both types and contracts). However, we
         // do give some special attention to the inferred static type if there is one: we list it first.
-        let mut annotations: Vec<_> = hover
-            .metadata
-            .iter()
-            .flat_map(|m| m.annotation.iter().map(|typ| typ.typ.to_string()))
-            .chain(
-                hover
-                    .values
-                    .iter()
-                    .flat_map(annotated_contracts)
-                    .map(|contract| contract.label.typ.to_string()),
-            )
-            .collect();
+        let mut annotations: Vec<_> = hover.annotations().map(Type::to_string).collect();
         dedup(&mut annotations);
 
         let ty = hover
@@ -196,9 +206,7 @@ pub fn handle(
 
         contents.extend(annotations.into_iter().map(nickel_string));
 
-        // Not sure how to do documentation merging yet, so pick the first non-empty one.
-        let doc = hover.metadata.iter().find_map(|m| m.doc.as_ref());
-        if let Some(doc) = doc {
+        if let Some(doc) = hover.doc() {
             contents.push(MarkedString::String(doc.to_owned()));
         }
 
@@ -0,0 +1,266 @@
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{InlayHint, InlayHintKind, InlayHintLabel, InlayHintParams, Range};
+use nickel_lang_core::{
+    identifier::{Ident, LocIdent},
+    term::{
+        pattern::{bindings::Bindings as _, Pattern},
+        record::{Field, RecordData},
+        RichTerm, Term, Traverse, TraverseControl,
+    },
+    typ::{EnumRows, EnumRowsF, RecordRows, RecordRowsF, Type, TypeF},
+};
+
+use crate::{
+    cache::CacheExt as _, config::LspInlayHintsConfig, diagnostic::LocationCompat,
+    field_walker::FieldResolver, server::Server, utils::dedup, world::World,
+};
+
+struct HintCollector<'a> {
+    world: &'a World,
+    config: &'a LspInlayHintsConfig,
+    range: Range,
+    hints: Vec<InlayHint>,
+}
+
+impl<'a> HintCollector<'a> {
+    /// Adds a hint right after `id`, if it's in the requested range.
+    fn push(&mut self, id: LocIdent, label: String, kind: Option<InlayHintKind>) {
+        let Some(span) = id.pos.into_opt() else {
+            return;
+        };
+        let position = Range::from_span(&span, self.world.cache.files()).end;
+        if position < self.range.start || position > self.range.end {
+            return;
+        }
+
+        self.hints.push(InlayHint {
+            position,
+            label: InlayHintLabel::String(label),
+            kind,
+            text_edits: None,
+            tooltip: None,
+            padding_left: Some(true),
+            padding_right: None,
+            data: None,
+        });
+    }
+
+    /// Adds a hint with the inferred type of a binding. Bindings that are already annotated, and
+    /// generated bindings, don't get a hint. Neither do bindings whose type has free type
+    /// variables: those are unification variables that weren't generalized, or type constants,
+    /// which are given arbitrary names such as `_a`.
+    fn inferred_type(&mut self, id: LocIdent, annotated: bool) {
+        if !self.config.inferred_types || annotated || id.is_generated() {
+            return;
+        }
+
+        let ty = self.world.analysis.get_type_for_ident(&id.into());
+        if let Some(ty) = ty.filter(|ty| !has_free_vars(ty, &mut Vec::new())) {
+            let label = format!(": {ty}");
+            self.push(id, label, Some(InlayHintKind::TYPE));
+        }
+    }
+
+    fn pattern(&mut self, pat: &Pattern) {
+        for (_path, id, field) in pat.bindings() {
+            self.inferred_type(id, !field.metadata.annotation.is_empty());
+        }
+    }
+
+    /// Adds a hint with the contracts that a record field gets from its cousins (see
+    /// [FieldResolver::cousin_defs]), but that aren't already on the field itself.
+    fn inherited_contracts(&mut self, id: LocIdent, field: &Field) {
+        if !self.config.inherited_contracts {
+            return;
+        }
+
+        let Some(def) = self.world.analysis.get_def(&id.into()) else {
+            return;
+        };
+
+        let own: Vec<_> = field
+            .metadata
+            .annotation
+            .iter()
+            .map(|annot| annot.typ.to_string())
+            .collect();
+
+        let mut contracts: Vec<_> = FieldResolver::new(self.world)
+            .cousin_defs(def)
+            .into_iter()
+            .filter(|(cousin_id, _)| cousin_id.pos != id.pos)
+            .flat_map(|(_, cousin)| {
+                cousin
+                    .metadata
+                    .annotation
+                    .iter()
+                    .map(|annot| annot.typ.to_string())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|contract| !own.contains(contract))
+            .collect();
+        dedup(&mut contracts);
+
+        if !contracts.is_empty() {
+            let label = contracts
+                .iter()
+                .map(|contract| format!("| {contract}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            self.push(id, label, None);
+        }
+    }
+
+    fn record(&mut self, data: &RecordData, dyn_fields: &[(RichTerm, Field)], typed: bool) {
+        for (id, field) in &data.fields {
+            self.inherited_contracts(*id, field);
+            self.field_value(field, typed);
+        }
+
+        for (name, field) in dyn_fields {
+            self.visit(name, typed);
+            self.field_value(field, typed);
+        }
+    }
+
+    fn field_value(&mut self, field: &Field, typed: bool) {
+        if let Some(value) = &field.value {
+            self.visit(value, field_is_typed(field, typed));
+        }
+    }
+
+    /// Collects the hints of `rt`. `typed` is true if `rt` is statically typed, in which case the
+    /// bindings it introduces get type hints.
+    fn visit(&mut self, rt: &RichTerm, typed: bool) {
+        rt.traverse_ref(
+            &mut |term: &RichTerm, typed: &bool| {
+                let typed = *typed;
+
+                match term.as_ref() {
+                    // A type annotation starts a statically typed block, while a contract
+                    // annotation alone ends it.
+                    Term::Annotated(annot, inner) => {
+                        self.visit(inner, annot.typ.is_some());
+                        return TraverseControl::SkipBranch;
+                    }
+                    Term::Record(data) => {
+                        self.record(data, &[], typed);
+                        return TraverseControl::SkipBranch;
+                    }
+                    Term::RecRecord(data, dyn_fields, _) => {
+                        self.record(data, dyn_fields, typed);
+                        return TraverseControl::SkipBranch;
+                    }
+                    Term::Let(bindings, _, _) if typed => {
+                        for (id, value) in bindings {
+                            let annotated = matches!(value.as_ref(), Term::Annotated(..));
+                            self.inferred_type(*id, annotated);
+                        }
+                    }
+                    Term::LetPattern(bindings, _, _) if typed => {
+                        for (pat, _) in bindings {
+                            self.pattern(pat);
+                        }
+                    }
+                    Term::Fun(id, _) if typed => self.inferred_type(*id, false),
+                    Term::FunPattern(pat, _) if typed => self.pattern(pat),
+                    _ => {}
+                }
+
+                TraverseControl::<bool, ()>::ContinueWithScope(typed)
+            },
+            &typed,
+        );
+    }
+}
+
+/// Whether the value of a field is statically typed, given whether the enclosing record is.
+fn field_is_typed(field: &Field, typed: bool) -> bool {
+    let annot = &field.metadata.annotation;
+    if annot.typ.is_some() {
+        true
+    } else if !annot.contracts.is_empty() {
+        false
+    } else {
+        typed
+    }
+}
+
+/// Whether a type has type variables that aren't bound by a `forall` of the type itself. `bound`
+/// holds the variables bound by the enclosing `forall`s.
+fn has_free_vars(ty: &Type, bound: &mut Vec<Ident>) -> bool {
+    match &ty.typ {
+        TypeF::Dyn
+        | TypeF::Number
+        | TypeF::Bool
+        | TypeF::String
+        | TypeF::Symbol
+        | TypeF::ForeignId
+        | TypeF::Contract(_)
+        | TypeF::Wildcard(_) => false,
+        TypeF::Var(var) => !bound.contains(var),
+        TypeF::Arrow(dom, codom) => has_free_vars(dom, bound) || has_free_vars(codom, bound),
+        TypeF::Forall { var, body, .. } => {
+            bound.push(var.ident());
+            let result = has_free_vars(body, bound);
+            bound.pop();
+            result
+        }
+        TypeF::Enum(erows) => enum_rows_have_free_vars(erows, bound),
+        TypeF::Record(rrows) => record_rows_have_free_vars(rrows, bound),
+        TypeF::Dict { type_fields, .. } => has_free_vars(type_fields, bound),
+        TypeF::Array(elts) => has_free_vars(elts, bound),
+    }
+}
+
+fn record_rows_have_free_vars(rrows: &RecordRows, bound: &mut Vec<Ident>) -> bool {
+    match &rrows.0 {
+        RecordRowsF::Empty | RecordRowsF::TailDyn => false,
+        RecordRowsF::TailVar(var) => !bound.contains(&var.ident()),
+        RecordRowsF::Extend { row, tail } => {
+            has_free_vars(&row.typ, bound) || record_rows_have_free_vars(tail, bound)
+        }
+    }
+}
+
+fn enum_rows_have_free_vars(erows: &EnumRows, bound: &mut Vec<Ident>) -> bool {
+    match &erows.0 {
+        EnumRowsF::Empty => false,
+        EnumRowsF::TailVar(var) => !bound.contains(&var.ident()),
+        EnumRowsF::Extend { row, tail } => {
+            row.typ
+                .as_ref()
+                .is_some_and(|typ| has_free_vars(typ, bound))
+                || enum_rows_have_free_vars(tail, bound)
+        }
+    }
+}
+
+pub fn handle_inlay_hints(
+    params: InlayHintParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let file_id = server
+        .world
+        .cache
+        .file_id(&params.text_document.uri)?
+        .ok_or_else(|| crate::error::Error::FileNotFound(params.text_document.uri.clone()))?;
+
+    let mut collector = HintCollector {
+        world: &server.world,
+        config: &server.inlay_hints_config,
+        range: params.range,
+        hints: Vec::new(),
+    };
+
+    if let Some(rt) = server.world.cache.get_ref(file_id) {
+        // The top-level of a file is never statically typed.
+        collector.visit(rt, false);
+    }
+
+    let mut hints = collector.hints;
+    hints.sort_by_key(|hint| hint.position);
+    server.reply(Response::new_ok(id, hints));
+    Ok(())
+}
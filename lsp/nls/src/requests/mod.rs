@@ -2,5 +2,8 @@ pub mod completion;
 pub mod formatting;
 pub mod goto;
 pub mod hover;
+pub mod inlay_hints;
 pub mod rename;
+pub mod semantic_tokens;
+pub mod signature_help;
 pub mod symbols;
@@ -18,6 +18,11 @@ pub fn handle_rename(
         .position(&params.text_document_position)?;
 
     let ident = server.world.lookup_ident_by_position(pos)?;
+
+    // Record fields can be accessed from any file of the workspace.
+    if let Some(ident) = ident {
+        server.world.analyze_accesses(ident.ident);
+    }
     let term = server.world.lookup_term_by_position(pos)?;
     let mut def_locs = term
         .map(|term| server.world.get_defs(term, ident))
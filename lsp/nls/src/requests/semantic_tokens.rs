@@ -0,0 +1,467 @@
+use std::collections::HashSet;
+
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{
+    Range, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens,
+    SemanticTokensLegend, SemanticTokensParams, SemanticTokensRangeParams,
+};
+use nickel_lang_core::{
+    files::FileId,
+    identifier::LocIdent,
+    position::{RawSpan, TermPos},
+    term::{
+        pattern::{bindings::Bindings as _, Pattern, PatternData},
+        record::FieldMetadata,
+        RichTerm, Term, Traverse, TraverseControl, TypeAnnotation, UnaryOp,
+    },
+    typ::{RecordRowsIteratorItem, Type, TypeF},
+};
+
+use crate::{
+    cache::CacheExt as _,
+    diagnostic::LocationCompat,
+    field_walker::{Def, FieldResolver},
+    server::Server,
+    world::World,
+};
+
+/// The kinds of tokens we report. The discriminant of each kind is its index in the legend, see
+/// [legend].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenKind {
+    Variable,
+    Property,
+    Parameter,
+    TypeParameter,
+    Contract,
+    EnumTag,
+}
+
+// The modifiers of tokens, as bits of `SemanticToken::token_modifiers_bitset`. The index of each
+// bit is the index of the modifier in the legend, see [legend].
+const DECLARATION: u32 = 1 << 0;
+const DEFAULT_LIBRARY: u32 = 1 << 1;
+const DEPRECATED: u32 = 1 << 2;
+const UNUSED: u32 = 1 << 3;
+
+/// The legend of the semantic tokens, advertised in the server capabilities.
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: vec![
+            SemanticTokenType::VARIABLE,
+            SemanticTokenType::PROPERTY,
+            SemanticTokenType::PARAMETER,
+            SemanticTokenType::TYPE_PARAMETER,
+            SemanticTokenType::TYPE,
+            SemanticTokenType::ENUM_MEMBER,
+        ],
+        token_modifiers: vec![
+            SemanticTokenModifier::DECLARATION,
+            SemanticTokenModifier::DEFAULT_LIBRARY,
+            SemanticTokenModifier::DEPRECATED,
+            // There's no standard modifier for unused bindings.
+            SemanticTokenModifier::new("unused"),
+        ],
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Token {
+    span: RawSpan,
+    kind: TokenKind,
+    modifiers: u32,
+}
+
+/// A field is deprecated if its documentation starts with "deprecated", possibly formatted as a
+/// Markdown heading or in bold (such as `**Deprecated**: use foo instead`).
+fn is_deprecated(metadata: &FieldMetadata) -> bool {
+    metadata.doc.as_deref().is_some_and(|doc| {
+        doc.trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '#' | '*' | '_'))
+            .get(.."deprecated".len())
+            .is_some_and(|start| start.eq_ignore_ascii_case("deprecated"))
+    })
+}
+
+struct TokenCollector<'a> {
+    world: &'a World,
+    resolver: FieldResolver<'a>,
+    file_id: FileId,
+    /// The spans of the terms used as contracts in a type, such as `Foo` in `x | Foo`.
+    contracts: HashSet<RawSpan>,
+    tokens: Vec<Token>,
+}
+
+impl<'a> TokenCollector<'a> {
+    fn new(world: &'a World, file_id: FileId) -> Self {
+        TokenCollector {
+            world,
+            resolver: FieldResolver::new(world),
+            file_id,
+            contracts: HashSet::new(),
+            tokens: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, pos: TermPos, kind: TokenKind, modifiers: u32) {
+        if let Some(span) = pos.into_opt().filter(|span| span.src_id == self.file_id) {
+            self.tokens.push(Token {
+                span,
+                kind,
+                modifiers,
+            });
+        }
+    }
+
+    fn is_stdlib(&self, pos: TermPos) -> bool {
+        pos.into_opt()
+            .is_some_and(|span| self.world.cache.files().is_stdlib(span.src_id))
+    }
+
+    /// The kind and the modifiers of an identifier bound by `def`.
+    fn classify_def(&self, def: &Def) -> (TokenKind, u32) {
+        let (kind, mut modifiers) = match def {
+            Def::Fn { .. } => (TokenKind::Parameter, 0),
+            Def::Let { .. } => (TokenKind::Variable, 0),
+            Def::Field { metadata, .. } if is_deprecated(metadata) => {
+                (TokenKind::Property, DEPRECATED)
+            }
+            Def::Field { .. } => (TokenKind::Property, 0),
+        };
+
+        // Identifiers without position are bound in the initial environment (that is, `std`).
+        if !def.ident().pos.is_def() || self.is_stdlib(def.ident().pos) {
+            modifiers |= DEFAULT_LIBRARY;
+        }
+
+        (kind, modifiers)
+    }
+
+    /// Adds the token of an identifier bound by a let, a function or a pattern.
+    fn declaration(&mut self, id: LocIdent, default: TokenKind) {
+        let ident = crate::identifier::LocIdent::from(id);
+        let kind = self
+            .world
+            .analysis
+            .get_def(&ident)
+            .map_or(default, |def| self.classify_def(def).0);
+
+        let unused = !id.label().starts_with('_')
+            && id
+                .pos
+                .as_opt_ref()
+                .is_some_and(|span| self.world.analysis.get_usages(span).next().is_none());
+
+        let modifiers = DECLARATION | if unused { UNUSED } else { 0 };
+        self.push(id.pos, kind, modifiers);
+    }
+
+    fn usage(&mut self, id: LocIdent, term: &RichTerm) {
+        let ident = crate::identifier::LocIdent::from(id);
+        let (kind, modifiers) = self
+            .world
+            .analysis
+            .get_def(&ident)
+            .map_or((TokenKind::Variable, 0), |def| self.classify_def(def));
+
+        self.push(id.pos, self.contract_or(term, kind), modifiers);
+    }
+
+    /// Returns [TokenKind::Contract] if `term` is used as a contract, and `kind` otherwise.
+    fn contract_or(&self, term: &RichTerm, kind: TokenKind) -> TokenKind {
+        if term
+            .pos
+            .as_opt_ref()
+            .is_some_and(|span| self.contracts.contains(span))
+        {
+            TokenKind::Contract
+        } else {
+            kind
+        }
+    }
+
+    fn field_access(&mut self, id: LocIdent, record: &RichTerm, term: &RichTerm) {
+        let mut modifiers = 0;
+
+        let field = self
+            .resolver
+            .resolve_record(record)
+            .into_iter()
+            .find_map(|record| {
+                record
+                    .field_and_loc(id.ident())
+                    .map(|(loc, field)| (loc, field.cloned()))
+            });
+
+        if let Some((loc, field)) = field {
+            if self.is_stdlib(loc.pos) {
+                modifiers |= DEFAULT_LIBRARY;
+            }
+
+            if field.is_some_and(|field| is_deprecated(&field.metadata)) {
+                modifiers |= DEPRECATED;
+            }
+        }
+
+        self.push(
+            id.pos,
+            self.contract_or(term, TokenKind::Property),
+            modifiers,
+        );
+    }
+
+    /// Adds the token of an enum tag, given the position of a term or a pattern starting with the
+    /// tag. Tags don't have a position of their own, so we find the end of the tag in the source.
+    fn enum_tag(&mut self, pos: TermPos) {
+        let Some(span) = pos.into_opt() else {
+            return;
+        };
+        let source = self.world.cache.files().source(span.src_id);
+        let Some(text) = source.get(span.start.to_usize()..span.end.to_usize()) else {
+            return;
+        };
+        let Some(tag) = text.strip_prefix('\'') else {
+            return;
+        };
+
+        let len = if let Some(quoted) = tag.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => end + 2,
+                None => return,
+            }
+        } else {
+            tag.find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '\'')))
+                .unwrap_or(tag.len())
+        };
+
+        let tag_span = RawSpan {
+            end: (span.start.0 + 1 + len as u32).into(),
+            ..span
+        };
+        self.push(tag_span.into(), TokenKind::EnumTag, 0);
+    }
+
+    fn annotation(&mut self, annot: &TypeAnnotation) {
+        for labeled in annot.iter() {
+            self.typ(&labeled.typ);
+        }
+    }
+
+    fn typ(&mut self, typ: &Type) {
+        typ.traverse_ref(
+            &mut |ty: &Type, _: &()| {
+                match &ty.typ {
+                    TypeF::Var(_) => self.push(ty.pos, TokenKind::TypeParameter, 0),
+                    TypeF::Forall { var, .. } => {
+                        self.push(var.pos, TokenKind::TypeParameter, DECLARATION)
+                    }
+                    TypeF::Record(rows) => {
+                        for item in rows.iter() {
+                            match item {
+                                RecordRowsIteratorItem::Row(row) => {
+                                    self.push(row.id.pos, TokenKind::Property, 0)
+                                }
+                                RecordRowsIteratorItem::TailVar(id) => {
+                                    self.push(id.pos, TokenKind::TypeParameter, 0)
+                                }
+                                RecordRowsIteratorItem::TailDyn => (),
+                            }
+                        }
+                    }
+                    TypeF::Contract(rt) => {
+                        if let Some(span) = rt.pos.into_opt() {
+                            self.contracts.insert(span);
+                        }
+                    }
+                    _ => (),
+                }
+                TraverseControl::<(), ()>::Continue
+            },
+            &(),
+        );
+    }
+
+    fn pattern(&mut self, pat: &Pattern) {
+        match &pat.data {
+            PatternData::Wildcard | PatternData::Any(_) | PatternData::Constant(_) => (),
+            PatternData::Record(record_pat) => {
+                for field_pat in &record_pat.patterns {
+                    self.push(field_pat.matched_id.pos, TokenKind::Property, 0);
+                    self.annotation(&field_pat.annotation);
+                    self.pattern(&field_pat.pattern);
+                }
+            }
+            PatternData::Array(array_pat) => {
+                for pat in &array_pat.patterns {
+                    self.pattern(pat);
+                }
+            }
+            PatternData::Enum(enum_pat) => {
+                self.enum_tag(enum_pat.pos);
+                if let Some(pat) = &enum_pat.pattern {
+                    self.pattern(pat);
+                }
+            }
+            PatternData::Or(or_pat) => {
+                for pat in &or_pat.patterns {
+                    self.pattern(pat);
+                }
+            }
+        }
+    }
+
+    /// Adds the tokens of a pattern: first the bindings, then the other parts.
+    fn pattern_with_bindings(&mut self, pat: &Pattern, kind: TokenKind) {
+        for (_path, id, _field) in pat.bindings() {
+            self.declaration(id, kind);
+        }
+        self.pattern(pat);
+    }
+
+    fn term(&mut self, term: &RichTerm) {
+        match term.as_ref() {
+            Term::Var(id) => self.usage(*id, term),
+            Term::Fun(id, _) => self.declaration(*id, TokenKind::Parameter),
+            Term::FunPattern(pat, _) => self.pattern_with_bindings(pat, TokenKind::Parameter),
+            Term::Let(bindings, _, _) => {
+                for (id, _) in bindings {
+                    self.declaration(*id, TokenKind::Variable);
+                }
+            }
+            Term::LetPattern(bindings, _, _) => {
+                for (pat, _) in bindings {
+                    self.pattern_with_bindings(pat, TokenKind::Variable);
+                }
+            }
+            Term::Match(data) => {
+                for branch in &data.branches {
+                    self.pattern_with_bindings(&branch.pattern, TokenKind::Variable);
+                }
+            }
+            Term::Record(data) | Term::RecRecord(data, ..) => {
+                for (id, field) in &data.fields {
+                    let deprecated = if is_deprecated(&field.metadata) {
+                        DEPRECATED
+                    } else {
+                        0
+                    };
+                    self.push(id.pos, TokenKind::Property, DECLARATION | deprecated);
+                    self.annotation(&field.metadata.annotation);
+                }
+            }
+            Term::Op1(UnaryOp::RecordAccess(id), record) => self.field_access(*id, record, term),
+            Term::Enum(_) | Term::EnumVariant { .. } => self.enum_tag(term.pos),
+            Term::Annotated(annot, _) => self.annotation(annot),
+            Term::Type { typ, .. } => self.typ(typ),
+            _ => (),
+        }
+    }
+
+    /// Collects the tokens of `rt`, sorted by position and without overlaps.
+    fn collect(mut self, rt: &RichTerm) -> Vec<Token> {
+        rt.traverse_ref(
+            &mut |term: &RichTerm, _: &()| {
+                self.term(term);
+                TraverseControl::<(), ()>::Continue
+            },
+            &(),
+        );
+
+        // The same identifier can be reached several times, for example as a field of a record
+        // pattern and as the variable it binds. The first token wins, which is why bindings are
+        // added first.
+        let mut tokens = self.tokens;
+        tokens.sort_by_key(|token| token.span.start);
+
+        let mut end = 0.into();
+        tokens.retain(|token| {
+            let keep = token.span.start >= end;
+            if keep {
+                end = token.span.end;
+            }
+            keep
+        });
+
+        tokens
+    }
+}
+
+/// Computes the semantic tokens of `file_id` overlapping `range`, or of the whole file if `range`
+/// is `None`.
+fn semantic_tokens(world: &World, file_id: FileId, range: Option<Range>) -> SemanticTokens {
+    let Some(rt) = world.cache.get_ref(file_id) else {
+        return SemanticTokens::default();
+    };
+
+    let mut data = Vec::new();
+    let mut prev_line = 0;
+    let mut prev_start = 0;
+
+    for token in TokenCollector::new(world, file_id).collect(rt) {
+        let token_range = Range::from_span(&token.span, world.cache.files());
+
+        // Tokens can't span several lines unless the client supports it explicitly.
+        if token_range.start.line != token_range.end.line
+            || token_range.start == token_range.end
+            || range.is_some_and(|range| {
+                token_range.end <= range.start || token_range.start >= range.end
+            })
+        {
+            continue;
+        }
+
+        let line = token_range.start.line;
+        let start = token_range.start.character;
+
+        data.push(SemanticToken {
+            delta_line: line - prev_line,
+            delta_start: if line == prev_line {
+                start - prev_start
+            } else {
+                start
+            },
+            length: token_range.end.character - start,
+            token_type: token.kind as u32,
+            token_modifiers_bitset: token.modifiers,
+        });
+
+        prev_line = line;
+        prev_start = start;
+    }
+
+    SemanticTokens {
+        result_id: None,
+        data,
+    }
+}
+
+pub fn handle_semantic_tokens_full(
+    params: SemanticTokensParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let file_id = server
+        .world
+        .cache
+        .file_id(&params.text_document.uri)?
+        .ok_or_else(|| crate::error::Error::FileNotFound(params.text_document.uri.clone()))?;
+
+    let tokens = semantic_tokens(&server.world, file_id, None);
+    server.reply(Response::new_ok(id, tokens));
+    Ok(())
+}
+
+pub fn handle_semantic_tokens_range(
+    params: SemanticTokensRangeParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let file_id = server
+        .world
+        .cache
+        .file_id(&params.text_document.uri)?
+        .ok_or_else(|| crate::error::Error::FileNotFound(params.text_document.uri.clone()))?;
+
+    let tokens = semantic_tokens(&server.world, file_id, Some(params.range));
+    server.reply(Response::new_ok(id, tokens));
+    Ok(())
+}
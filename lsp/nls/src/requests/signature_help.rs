@@ -0,0 +1,240 @@
+use codespan::ByteIndex;
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{
+    Documentation, MarkupContent, MarkupKind, ParameterInformation, ParameterLabel, SignatureHelp,
+    SignatureHelpParams, SignatureInformation,
+};
+use nickel_lang_core::{
+    combine::Combine,
+    position::RawPos,
+    term::{RichTerm, SharedTerm, Term, UnaryOp},
+    typ::{Type, TypeF},
+};
+use serde_json::Value;
+
+use crate::{
+    cache::CacheExt as _,
+    requests::hover::{ident_hover, term_hover, HoverData},
+    server::Server,
+    world::World,
+};
+
+/// A function application, as seen by the user: Nickel functions are curried, so `f x y` is
+/// parsed as `(f x) y`.
+struct Call {
+    head: RichTerm,
+    /// The index of the argument being filled.
+    active: usize,
+}
+
+/// Splits a chain of applications `((head a1) a2) .. an` into `head` and `[a1, .., an]`.
+fn flatten_app(rt: &RichTerm) -> (RichTerm, Vec<RichTerm>) {
+    let mut args = Vec::new();
+    let mut head = rt.clone();
+
+    while let Term::App(fun, arg) = head.as_ref() {
+        args.push(arg.clone());
+        head = fun.clone();
+    }
+
+    args.reverse();
+    (head, args)
+}
+
+/// Extends an application to the enclosing applications it's the function of, that is, goes up
+/// from `f x` to `f x y z`.
+fn outermost_app(world: &World, mut app: RichTerm) -> RichTerm {
+    while let Some(parent) = world.analysis.get_parent(&app) {
+        match parent.term.as_ref() {
+            Term::App(fun, _) if SharedTerm::ptr_eq(&fun.term, &app.term) => {
+                app = parent.term.clone();
+            }
+            _ => break,
+        }
+    }
+    app
+}
+
+fn ends_at(rt: &RichTerm, index: usize) -> bool {
+    rt.pos
+        .into_opt()
+        .is_some_and(|span| span.end.to_usize() == index)
+}
+
+/// Finds the function call whose arguments the cursor is in.
+///
+/// When the cursor is preceded by whitespace, as in `f x |`, the user is about to write the next
+/// argument of the application that ends right before the cursor (which might only consist of a
+/// function, as in `f |`). Otherwise, the cursor is within an argument, and we look for the
+/// closest application that has it as argument.
+fn find_call(world: &World, pos: RawPos) -> Result<Option<Call>, ResponseError> {
+    let source = world.cache.files().source(pos.src_id);
+    let Some(before) = source.get(..pos.index.to_usize()) else {
+        return Ok(None);
+    };
+    let anchor = before.trim_end().len();
+    let Some(last_char) = before[..anchor].chars().next_back() else {
+        return Ok(None);
+    };
+    let after_space = anchor < before.len();
+
+    let lookup_pos = RawPos::new(
+        pos.src_id,
+        ByteIndex((anchor - last_char.len_utf8()) as u32),
+    );
+    let Some(term) = world.lookup_term_by_position(lookup_pos)?.cloned() else {
+        return Ok(None);
+    };
+
+    if after_space {
+        if !ends_at(&term, anchor) {
+            return Ok(None);
+        }
+
+        // Go up to the largest application ending right before the cursor.
+        let mut current = term;
+        while let Some(parent) = world.analysis.get_parent(&current) {
+            if !matches!(parent.term.as_ref(), Term::App(..)) || !ends_at(&parent.term, anchor) {
+                break;
+            }
+            current = parent.term.clone();
+        }
+
+        let (head, args) = flatten_app(&current);
+        if !matches!(
+            head.as_ref(),
+            Term::Var(_) | Term::Op1(UnaryOp::RecordAccess(_), _)
+        ) {
+            return Ok(None);
+        }
+
+        let active = args.len();
+        return Ok(Some(Call { head, active }));
+    }
+
+    let mut current = term;
+    while let Some(parent) = world.analysis.get_parent(&current) {
+        if let Term::App(_, arg) = parent.term.as_ref() {
+            if SharedTerm::ptr_eq(&arg.term, &current.term) {
+                let (head, args) = flatten_app(&outermost_app(world, parent.term.clone()));
+                let active = args
+                    .iter()
+                    .position(|arg| SharedTerm::ptr_eq(&arg.term, &current.term))
+                    .unwrap_or_default();
+                return Ok(Some(Call { head, active }));
+            }
+        }
+        current = parent.term.clone();
+    }
+
+    Ok(None)
+}
+
+/// Gathers the types, contracts and documentation of the function of a call.
+fn head_data(world: &World, head: &RichTerm) -> Option<HoverData> {
+    match head.as_ref() {
+        Term::Var(id) => {
+            Combine::combine(ident_hover((*id).into(), world), term_hover(head, world))
+        }
+        _ => term_hover(head, world),
+    }
+}
+
+/// Builds the signature of a function of type `typ`. Each argument of the type (that is, each
+/// domain of an arrow type) is a parameter of the signature.
+fn signature(name: &str, typ: &Type, doc: Option<&str>) -> Option<SignatureInformation> {
+    let mut label = format!("{name} : ");
+    let mut parameters = Vec::new();
+    let mut typ = typ;
+
+    let mut vars = Vec::new();
+    while let TypeF::Forall { var, body, .. } = &typ.typ {
+        vars.push(var.to_string());
+        typ = body;
+    }
+    if !vars.is_empty() {
+        label.push_str(&format!("forall {}. ", vars.join(" ")));
+    }
+
+    while let TypeF::Arrow(dom, codom) = &typ.typ {
+        let dom = match dom.typ {
+            TypeF::Arrow(..) | TypeF::Forall { .. } => format!("({dom})"),
+            _ => dom.to_string(),
+        };
+
+        // Offsets are counted in UTF-16 code units.
+        let start = label.encode_utf16().count() as u32;
+        let end = start + dom.encode_utf16().count() as u32;
+        parameters.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([start, end]),
+            documentation: None,
+        });
+
+        label.push_str(&dom);
+        label.push_str(" -> ");
+        typ = codom;
+    }
+
+    if parameters.is_empty() {
+        return None;
+    }
+    label.push_str(&typ.to_string());
+
+    Some(SignatureInformation {
+        label,
+        documentation: doc.map(|doc| {
+            Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: doc.to_owned(),
+            })
+        }),
+        parameters: Some(parameters),
+        active_parameter: None,
+    })
+}
+
+fn signature_help(world: &World, pos: RawPos) -> Result<Option<SignatureHelp>, ResponseError> {
+    let Some(call) = find_call(world, pos)? else {
+        return Ok(None);
+    };
+    let Some(data) = head_data(world, &call.head) else {
+        return Ok(None);
+    };
+    let Some(span) = call.head.pos.into_opt() else {
+        return Ok(None);
+    };
+    let name = world.cache.files().source(span.src_id)[span.start.to_usize()..span.end.to_usize()]
+        .to_owned();
+
+    // Prefer the static type when it's more informative than `Dyn`, and otherwise the first
+    // annotation that is a function type or contract.
+    let signature = data
+        .ty
+        .iter()
+        .filter(|ty| !matches!(ty.typ, TypeF::Dyn))
+        .chain(data.annotations())
+        .find_map(|typ| signature(&name, typ, data.doc()));
+
+    Ok(signature.map(|signature| SignatureHelp {
+        signatures: vec![signature],
+        active_signature: Some(0),
+        active_parameter: Some(call.active as u32),
+    }))
+}
+
+pub fn handle_signature_help(
+    params: SignatureHelpParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let pos = server
+        .world
+        .cache
+        .position(&params.text_document_position_params)?;
+
+    match signature_help(&server.world, pos)? {
+        Some(help) => server.reply(Response::new_ok(id, help)),
+        None => server.reply(Response::new_ok(id, Value::Null)),
+    }
+    Ok(())
+}
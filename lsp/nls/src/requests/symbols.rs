@@ -1,10 +1,14 @@
 use lsp_server::{RequestId, Response, ResponseError};
-use lsp_types::{DocumentSymbol, DocumentSymbolParams, SymbolKind};
+use lsp_types::{
+    DocumentSymbol, DocumentSymbolParams, Location, SymbolInformation, SymbolKind,
+    WorkspaceSymbolParams, WorkspaceSymbolResponse,
+};
 use nickel_lang_core::term::RichTerm;
 use nickel_lang_core::typ::Type;
 
 use crate::analysis::CollectedTypes;
 use crate::cache::CacheExt as _;
+use crate::diagnostic::LocationCompat;
 use crate::field_walker::{FieldResolver, Record};
 use crate::server::Server;
 use crate::term::RawSpanExt;
@@ -112,3 +116,43 @@ pub fn handle_document_symbols(
 
     Ok(())
 }
+
+pub fn handle_workspace_symbols(
+    params: WorkspaceSymbolParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    server.world.finish_indexing();
+
+    let files = server.world.cache.files();
+    let mut symbols: Vec<_> = server
+        .world
+        .workspace
+        .symbols(&params.query)
+        .filter_map(|def| {
+            let span = def.ident.pos.into_opt()?;
+
+            #[allow(deprecated)]
+            Some(SymbolInformation {
+                name: def.ident.label().to_owned(),
+                kind: def.kind,
+                tags: None,
+                deprecated: None,
+                location: Location::from_span(&span, files),
+                container_name: def.container.clone(),
+            })
+        })
+        .collect();
+    // Sort so the response is deterministic.
+    symbols.sort_by(|a, b| {
+        (&a.name, a.location.uri.as_str(), a.location.range.start).cmp(&(
+            &b.name,
+            b.location.uri.as_str(),
+            b.location.range.start,
+        ))
+    });
+
+    server.reply(Response::new_ok(id, WorkspaceSymbolResponse::Flat(symbols)));
+
+    Ok(())
+}
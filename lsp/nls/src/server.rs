@@ -4,14 +4,18 @@ use log::{debug, trace, warn};
 use lsp_server::{Connection, ErrorCode, Message, Notification, RequestId, Response};
 use lsp_types::{
     notification::Notification as _,
-    notification::{DidChangeTextDocument, DidOpenTextDocument},
+    notification::{DidChangeTextDocument, DidChangeWatchedFiles, DidOpenTextDocument},
     request::{Request as RequestTrait, *},
     CodeActionParams, CompletionOptions, CompletionParams, DidChangeTextDocumentParams,
+    DidChangeWatchedFilesParams, DidChangeWatchedFilesRegistrationOptions,
     DidOpenTextDocumentParams, DocumentFormattingParams, DocumentSymbolParams,
-    ExecuteCommandParams, GotoDefinitionParams, HoverOptions, HoverParams, HoverProviderCapability,
-    OneOf, PublishDiagnosticsParams, ReferenceParams, RenameParams, ServerCapabilities,
+    ExecuteCommandParams, FileSystemWatcher, GlobPattern, GotoDefinitionParams, HoverOptions,
+    HoverParams, HoverProviderCapability, InitializeParams, InlayHintParams, OneOf,
+    PublishDiagnosticsParams, ReferenceParams, Registration, RegistrationParams, RenameParams,
+    SemanticTokensFullOptions, SemanticTokensOptions, SemanticTokensParams,
+    SemanticTokensRangeParams, ServerCapabilities, SignatureHelpOptions, SignatureHelpParams,
     TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions, Url,
-    WorkDoneProgressOptions,
+    WorkDoneProgressOptions, WorkspaceSymbolParams,
 };
 use nickel_lang_core::files::FileId;
 
@@ -19,9 +23,13 @@ use crate::{
     actions,
     background::BackgroundJobs,
     command,
-    config::LspConfig,
-    requests::{completion, formatting, goto, hover, rename, symbols},
+    config::{LspConfig, LspInlayHintsConfig},
+    requests::{
+        completion, formatting, goto, hover, inlay_hints, rename, semantic_tokens, signature_help,
+        symbols,
+    },
     trace::Trace,
+    workspace,
     world::World,
 };
 
@@ -37,6 +45,7 @@ pub struct Server {
     pub connection: Connection,
     pub world: World,
     pub background_jobs: BackgroundJobs,
+    pub inlay_hints_config: LspInlayHintsConfig,
 }
 
 impl Server {
@@ -70,16 +79,76 @@ impl Server {
                 ..Default::default()
             }),
             rename_provider: Some(OneOf::Left(true)),
+            workspace_symbol_provider: Some(OneOf::Left(true)),
+            inlay_hint_provider: Some(OneOf::Left(true)),
+            signature_help_provider: Some(SignatureHelpOptions {
+                trigger_characters: Some(vec![" ".to_owned()]),
+                ..Default::default()
+            }),
+            semantic_tokens_provider: Some(
+                SemanticTokensOptions {
+                    legend: semantic_tokens::legend(),
+                    range: Some(true),
+                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                    ..Default::default()
+                }
+                .into(),
+            ),
             ..ServerCapabilities::default()
         }
     }
 
-    pub fn new(connection: Connection, config: LspConfig) -> Server {
-        Server {
+    pub fn new(connection: Connection, config: LspConfig, client: &InitializeParams) -> Server {
+        let mut server = Server {
             connection,
             world: World::default(),
             background_jobs: BackgroundJobs::new(config.eval_config),
+            inlay_hints_config: config.inlay_hints,
+        };
+
+        server
+            .world
+            .index_workspace(workspace::workspace_roots(client));
+
+        let watched_files = client
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|ws| ws.did_change_watched_files)
+            .and_then(|caps| caps.dynamic_registration);
+        if watched_files == Some(true) {
+            server.watch_files();
         }
+
+        server
+    }
+
+    /// Asks the client to notify us of the changes to the Nickel files of the workspace, so that
+    /// we can keep the index of the files that aren't open up to date.
+    fn watch_files(&mut self) {
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/*.ncl".to_owned()),
+                kind: None,
+            }],
+        };
+        let params = RegistrationParams {
+            registrations: vec![Registration {
+                id: DidChangeWatchedFiles::METHOD.to_owned(),
+                method: DidChangeWatchedFiles::METHOD.to_owned(),
+                register_options: Some(serde_json::to_value(options).unwrap()),
+            }],
+        };
+
+        // We ignore the responses of the client, so the id doesn't matter.
+        self.connection
+            .sender
+            .send(Message::Request(lsp_server::Request::new(
+                RegisterCapability::METHOD.to_owned().into(),
+                RegisterCapability::METHOD.to_owned(),
+                params,
+            )))
+            .unwrap();
     }
 
     pub(crate) fn reply(&mut self, response: Response) {
@@ -119,6 +188,16 @@ impl Server {
     pub fn run(&mut self) -> Result<()> {
         trace!("Running...");
         loop {
+            // Index the workspace while there are no pending messages, so that indexing a large
+            // workspace doesn't delay the requests.
+            if self.world.is_indexing()
+                && self.connection.receiver.is_empty()
+                && self.background_jobs.receiver().is_empty()
+            {
+                self.world.index_next_file();
+                continue;
+            }
+
             select! {
                 recv(self.connection.receiver) -> msg => {
                     // Failure here means the connection was closed, so exit quietly.
@@ -212,6 +291,18 @@ impl Server {
                 }
                 Ok(())
             }
+            DidChangeWatchedFiles::METHOD => {
+                trace!("handle watched files notification");
+                let params =
+                    serde_json::from_value::<DidChangeWatchedFilesParams>(notification.params)?;
+                let invalid = crate::files::handle_watched_files(self, params)?;
+                for uri in invalid {
+                    self.background_jobs
+                        .update_file_deps(uri.clone(), &self.world);
+                    self.background_jobs.eval_file(uri);
+                }
+                Ok(())
+            }
             _ => Ok(()),
         }
     }
@@ -273,6 +364,36 @@ impl Server {
                 rename::handle_rename(params, req.id.clone(), self)
             }
 
+            WorkspaceSymbolRequest::METHOD => {
+                debug!("workspace symbols");
+                let params: WorkspaceSymbolParams = serde_json::from_value(req.params).unwrap();
+                symbols::handle_workspace_symbols(params, req.id.clone(), self)
+            }
+
+            InlayHintRequest::METHOD => {
+                debug!("inlay hints");
+                let params: InlayHintParams = serde_json::from_value(req.params).unwrap();
+                inlay_hints::handle_inlay_hints(params, req.id.clone(), self)
+            }
+
+            SignatureHelpRequest::METHOD => {
+                debug!("signature help");
+                let params: SignatureHelpParams = serde_json::from_value(req.params).unwrap();
+                signature_help::handle_signature_help(params, req.id.clone(), self)
+            }
+
+            SemanticTokensFullRequest::METHOD => {
+                debug!("semantic tokens");
+                let params: SemanticTokensParams = serde_json::from_value(req.params).unwrap();
+                semantic_tokens::handle_semantic_tokens_full(params, req.id.clone(), self)
+            }
+
+            SemanticTokensRangeRequest::METHOD => {
+                debug!("semantic tokens in range");
+                let params: SemanticTokensRangeParams = serde_json::from_value(req.params).unwrap();
+                semantic_tokens::handle_semantic_tokens_range(params, req.id.clone(), self)
+            }
+
             _ => Ok(()),
         };
 
@@ -0,0 +1,234 @@
+//! An index of the definitions of the Nickel files in the workspace.
+//!
+//! nls only analyzes the files that are open, together with the files they import. To search
+//! symbols in the whole project, and to find the references to a record field from files that
+//! aren't open, we scan the workspace folders sent by the client on initialization and parse every
+//! Nickel file in them. This happens one file at a time while the server is idle, so that the first
+//! requests aren't delayed (see [`crate::world::World::index_workspace`]). For each file, the index
+//! records its top-level let-bindings and its record fields, together with the fields that it
+//! accesses statically. The index is updated whenever a file is changed, either in the editor or on
+//! the disk.
+//!
+//! Parsing is cheap enough to do for every file, but typechecking isn't: the files of the workspace
+//! are only analyzed when a request needs it (see [`crate::world::World::analyze_accesses`]).
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use lsp_types::{InitializeParams, SymbolKind};
+use nickel_lang_core::{
+    files::FileId,
+    identifier::{Ident, LocIdent},
+    term::{pattern::bindings::Bindings as _, RichTerm, Term, Traverse, TraverseControl, UnaryOp},
+};
+
+use crate::files::uri_to_path;
+
+/// A definition found in a file of the workspace.
+#[derive(Clone, Debug)]
+pub struct IndexedDef {
+    pub ident: LocIdent,
+    pub kind: SymbolKind,
+    /// The path of the definition in the file, as in `server.http` for the field `port` of
+    /// `{ server.http.port = 80 }`, or the name of the top-level let-binding for the fields of
+    /// the record it's bound to.
+    pub container: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct FileIndex {
+    defs: Vec<IndexedDef>,
+    /// The names of the fields accessed statically in the file, as in `x.foo`.
+    accesses: HashSet<Ident>,
+}
+
+#[derive(Debug, Default)]
+pub struct WorkspaceIndex {
+    files: HashMap<FileId, FileIndex>,
+}
+
+impl WorkspaceIndex {
+    /// Indexes (or re-indexes) the parsed term of a file.
+    pub fn update(&mut self, file_id: FileId, rt: &RichTerm) {
+        let mut index = FileIndex::default();
+        index.top_level(rt);
+        index.accesses = static_accesses(rt);
+        self.files.insert(file_id, index);
+    }
+
+    pub fn remove(&mut self, file_id: FileId) {
+        self.files.remove(&file_id);
+    }
+
+    /// Returns the definitions whose name matches `query`.
+    pub fn symbols<'a>(&'a self, query: &'a str) -> impl Iterator<Item = &'a IndexedDef> {
+        self.files
+            .values()
+            .flat_map(|index| &index.defs)
+            .filter(move |def| fuzzy_match(query, def.ident.label()))
+    }
+
+    /// Returns the files that access a field named `id`.
+    pub fn files_accessing(&self, id: Ident) -> Vec<FileId> {
+        self.files
+            .iter()
+            .filter(|(_, index)| index.accesses.contains(&id))
+            .map(|(file_id, _)| *file_id)
+            .collect()
+    }
+}
+
+impl FileIndex {
+    /// Indexes the let-bindings at the top of a file, and then the record fields.
+    fn top_level(&mut self, rt: &RichTerm) {
+        let mut body = rt;
+        loop {
+            match body.as_ref() {
+                Term::Let(bindings, next, _) => {
+                    for (id, value) in bindings {
+                        self.push(*id, SymbolKind::VARIABLE, None);
+                        self.fields(value, Some(id.label()));
+                    }
+                    body = next;
+                }
+                Term::LetPattern(bindings, next, _) => {
+                    for (pat, value) in bindings {
+                        for (_path, id, _field) in pat.bindings() {
+                            self.push(id, SymbolKind::VARIABLE, None);
+                        }
+                        self.fields(value, None);
+                    }
+                    body = next;
+                }
+                _ => break,
+            }
+        }
+
+        self.fields(body, None);
+    }
+
+    /// Indexes the fields of the record literals in `rt`.
+    fn fields(&mut self, rt: &RichTerm, container: Option<&str>) {
+        rt.traverse_ref(
+            &mut |term: &RichTerm, _: &()| {
+                let (data, dyn_fields) = match term.as_ref() {
+                    Term::Record(data) => (data, &[][..]),
+                    Term::RecRecord(data, dyn_fields, _) => (data, &dyn_fields[..]),
+                    _ => return TraverseControl::Continue,
+                };
+
+                for (id, field) in &data.fields {
+                    self.push(*id, SymbolKind::FIELD, container);
+                    if let Some(value) = &field.value {
+                        let path = match container {
+                            Some(container) => format!("{container}.{id}"),
+                            None => id.to_string(),
+                        };
+                        self.fields(value, Some(&path));
+                    }
+                }
+
+                for (_, field) in dyn_fields {
+                    if let Some(value) = &field.value {
+                        self.fields(value, container);
+                    }
+                }
+
+                TraverseControl::<(), ()>::SkipBranch
+            },
+            &(),
+        );
+    }
+
+    fn push(&mut self, ident: LocIdent, kind: SymbolKind, container: Option<&str>) {
+        // Generated identifiers don't appear in the source.
+        if ident.pos.into_opt().is_some() {
+            self.defs.push(IndexedDef {
+                ident,
+                kind,
+                container: container.map(str::to_owned),
+            });
+        }
+    }
+}
+
+fn static_accesses(rt: &RichTerm) -> HashSet<Ident> {
+    let mut accesses = HashSet::new();
+    rt.traverse_ref(
+        &mut |term: &RichTerm, _: &()| {
+            if let Term::Op1(UnaryOp::RecordAccess(id), _) = term.as_ref() {
+                accesses.insert(id.ident());
+            }
+            TraverseControl::<(), ()>::Continue
+        },
+        &(),
+    );
+    accesses
+}
+
+/// Whether the characters of `query` appear in `name` in the same order, ignoring case. This is
+/// the usual matching of symbol searches, where `srvport` finds `server_port`.
+fn fuzzy_match(query: &str, name: &str) -> bool {
+    let mut name = name.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|c| name.any(|n| n == c))
+}
+
+/// Returns the workspace folders of the client, or its root folder if it doesn't support
+/// workspace folders.
+pub fn workspace_roots(params: &InitializeParams) -> Vec<PathBuf> {
+    let uris: Vec<_> = match &params.workspace_folders {
+        Some(folders) => folders.iter().map(|folder| &folder.uri).collect(),
+        None => params.root_uri.iter().collect(),
+    };
+
+    uris.into_iter()
+        .filter_map(|uri| uri_to_path(uri).ok())
+        .collect()
+}
+
+/// Finds the Nickel files in a directory and its subdirectories, skipping the hidden ones (such
+/// as `.git`).
+pub fn nickel_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return files;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if hidden {
+            continue;
+        } else if file_type.is_dir() {
+            files.extend(nickel_files(&path));
+        } else if file_type.is_file() && path.extension().is_some_and(|ext| ext == "ncl") {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fuzzy_match;
+
+    #[test]
+    fn fuzzy() {
+        assert!(fuzzy_match("", "port"));
+        assert!(fuzzy_match("srvport", "server_port"));
+        assert!(fuzzy_match("Port", "http_port"));
+        assert!(!fuzzy_match("ports", "port"));
+        assert!(!fuzzy_match("tp", "port"));
+    }
+}
@@ -1,6 +1,7 @@
 use std::{
     collections::{HashMap, HashSet},
     ffi::OsString,
+    fs,
     path::PathBuf,
 };
 
@@ -11,6 +12,7 @@ use nickel_lang_core::{
     cache::{Cache, CacheError, ErrorTolerance, InputFormat, SourcePath},
     error::{ImportError, IntoDiagnostics},
     files::FileId,
+    identifier::Ident,
     position::{RawPos, RawSpan},
     term::{pattern::bindings::Bindings, record::FieldMetadata, RichTerm, Term, UnaryOp},
     typecheck::Context,
@@ -23,6 +25,7 @@ use crate::{
     field_walker::{Def, FieldResolver},
     files::uri_to_path,
     identifier::LocIdent,
+    workspace::{self, WorkspaceIndex},
 };
 
 /// All the state associated with the files we know about.
@@ -43,6 +46,14 @@ pub struct World {
     /// files that failed to import, and the values in this map are the file ids that tried
     /// to import it.
     pub failed_imports: HashMap<OsString, HashSet<FileId>>,
+
+    /// The definitions of the files of the workspace, including the ones that aren't open.
+    pub workspace: WorkspaceIndex,
+
+    /// The workspace folders which haven't been scanned for Nickel files yet.
+    unscanned_roots: Vec<PathBuf>,
+    /// The Nickel files of the workspace which haven't been indexed yet, in reverse order.
+    unindexed_files: Vec<PathBuf>,
 }
 
 impl Default for World {
@@ -65,6 +76,9 @@ impl Default for World {
             initial_term_env,
             file_uris: HashMap::default(),
             failed_imports: HashMap::default(),
+            workspace: WorkspaceIndex::default(),
+            unscanned_roots: Vec::new(),
+            unindexed_files: Vec::new(),
         }
     }
 }
@@ -133,6 +147,99 @@ impl World {
         Ok((file_id, invalid))
     }
 
+    /// Schedules the indexing of the Nickel files of the workspace folders.
+    ///
+    /// Scanning and parsing a large workspace takes a while, so it's done one file at a time by
+    /// [`World::index_next_file`], when the server has nothing else to do.
+    pub fn index_workspace(&mut self, roots: Vec<PathBuf>) {
+        self.unscanned_roots.extend(roots.into_iter().rev());
+    }
+
+    /// Whether some files of the workspace haven't been indexed yet.
+    pub fn is_indexing(&self) -> bool {
+        !self.unscanned_roots.is_empty() || !self.unindexed_files.is_empty()
+    }
+
+    /// Loads and indexes the next file of the workspace, if any, or scans the next workspace
+    /// folder for Nickel files.
+    pub fn index_next_file(&mut self) {
+        let Some(path) = self.unindexed_files.pop() else {
+            if let Some(root) = self.unscanned_roots.pop() {
+                self.unindexed_files = workspace::nickel_files(&root);
+                self.unindexed_files.reverse();
+            }
+            return;
+        };
+
+        // Open files are indexed as they're edited, and their contents may differ from the disk.
+        let open = self
+            .cache
+            .id_of(&SourcePath::Path(path.clone(), InputFormat::Nickel))
+            .is_some_and(|file_id| self.file_uris.contains_key(&file_id));
+
+        if !open {
+            if let Err(e) = self.load_from_disk(path.clone()) {
+                warn!("failed to index {}: {e}", path.display());
+            }
+        }
+    }
+
+    /// Indexes all the files of the workspace which haven't been indexed yet. Requests that
+    /// depend on the whole workspace call this first, so they don't miss any file.
+    pub fn finish_indexing(&mut self) {
+        while self.is_indexing() {
+            self.index_next_file();
+        }
+    }
+
+    /// Loads a file that isn't open from the disk, replacing the previous version if there is
+    /// one, and indexes it.
+    ///
+    /// Returns a list of files that were invalidated by this change.
+    pub fn load_from_disk(&mut self, path: PathBuf) -> anyhow::Result<Vec<FileId>> {
+        let contents = fs::read_to_string(&path)?;
+        let file_id = self
+            .cache
+            .replace_string(SourcePath::Path(path, InputFormat::Nickel), contents);
+
+        let invalid = self.cache.invalidate_cache(file_id);
+        self.analysis.remove(file_id);
+        for f in &invalid {
+            self.analysis.remove(*f);
+        }
+
+        // Errors are reported when the file is opened.
+        let _ = self.parse(file_id);
+        self.index_file(file_id);
+        Ok(invalid)
+    }
+
+    /// Removes a file that was deleted from the disk from the index.
+    pub fn remove_from_disk(&mut self, file_id: FileId) {
+        self.workspace.remove(file_id);
+        self.analysis.remove(file_id);
+    }
+
+    /// Updates the index of a file, which must have been parsed.
+    pub fn index_file(&mut self, file_id: FileId) {
+        if let Some(rt) = self.cache.get_ref(file_id) {
+            self.workspace.update(file_id, rt);
+        }
+    }
+
+    /// Analyzes the files of the workspace that access a field named `id`, so that we can find
+    /// the references to this field in files that aren't open.
+    pub fn analyze_accesses(&mut self, id: Ident) {
+        self.finish_indexing();
+
+        for file_id in self.workspace.files_accessing(id) {
+            if !self.analysis.analysis.contains_key(&file_id) {
+                // Files that fail to typecheck have no analysis, and we skip their references.
+                let _ = self.parse_and_typecheck(file_id);
+            }
+        }
+    }
+
     pub fn lsp_diagnostics(
         &mut self,
         file_id: FileId,
@@ -145,6 +252,21 @@ impl World {
             .collect()
     }
 
+    /// Converts an error to diagnostics like [`World::lsp_diagnostics`], and attaches the quick
+    /// fixes for this error (if any) to the main diagnostic.
+    pub fn lsp_diagnostics_with_fixes(
+        &mut self,
+        file_id: FileId,
+        err: nickel_lang_core::error::Error,
+    ) -> Vec<SerializableDiagnostic> {
+        let fixes = crate::actions::quick_fixes(self, file_id, &err);
+        let mut diagnostics = self.lsp_diagnostics(file_id, err);
+        if let Some(main) = diagnostics.first_mut() {
+            main.fixes = fixes;
+        }
+        diagnostics
+    }
+
     // Make a record of I/O errors in imports so that we can retry them when appropriate.
     fn associate_failed_import(&mut self, err: &nickel_lang_core::error::Error) {
         if let nickel_lang_core::error::Error::ImportError(ImportError::IOError(name, _, pos)) =
@@ -187,7 +309,7 @@ impl World {
                     .into_iter()
                     .flat_map(|err| {
                         self.associate_failed_import(&err);
-                        self.lsp_diagnostics(file_id, err)
+                        self.lsp_diagnostics_with_fixes(file_id, err)
                     })
                     .collect::<Vec<_>>(),
                 CacheError::NotParsed => panic!("must parse first!"),
@@ -4,6 +4,12 @@ use serde_json::json;
 use test_generator::test_resources;
 
 use lsp_harness::{file_url_from_path, TestFixture, TestHarness};
+use lsp_types::{
+    request::{CodeActionRequest, InlayHintRequest, References, WorkspaceSymbolRequest},
+    CodeActionContext, CodeActionParams, InlayHintParams, Position, Range, ReferenceContext,
+    ReferenceParams, TextDocumentIdentifier, TextDocumentPositionParams, Url, WorkspaceFolder,
+    WorkspaceSymbolParams,
+};
 
 #[test_resources("lsp/nls/tests/inputs/*.ncl")]
 fn check_snapshots(path: &str) {
@@ -149,3 +155,110 @@ fn apply_client_options() {
     let diags = harness.wait_for_diagnostics();
     assert!(diags.diagnostics.is_empty());
 }
+
+#[test]
+fn configure_inlay_hints() {
+    let _ = env_logger::try_init();
+    let lsp_options = json!({
+        "inlay_hints": {
+            "inferred_types": false
+        }
+    });
+    let mut harness = TestHarness::new_with_options(Some(lsp_options));
+    let test_uri = file_url_from_path("/test.ncl").unwrap();
+    harness.send_file(
+        test_uri.clone(),
+        "{ foo = (let x = 1 in x) : _ } & { foo | Number }",
+    );
+
+    harness.request::<InlayHintRequest>(InlayHintParams {
+        text_document: TextDocumentIdentifier { uri: test_uri },
+        range: Range {
+            start: Position::new(0, 0),
+            end: Position::new(1, 0),
+        },
+        work_done_progress_params: Default::default(),
+    });
+
+    // Only the inherited contract is reported, as type hints are disabled.
+    let output = String::from_utf8(harness.out).unwrap();
+    assert_eq!(output, "[0:5 \"| Number\"]\n");
+}
+
+#[test]
+fn quick_fix_unbound_identifier() {
+    let _ = env_logger::try_init();
+    let mut harness = TestHarness::new();
+    let test_uri = file_url_from_path("/test.ncl").unwrap();
+    harness.send_file(test_uri.clone(), "let value = 1 in valeu + 1");
+
+    // The quick fixes are attached to the diagnostics, which the client sends back to request
+    // the code actions.
+    let diags = harness.wait_for_diagnostics();
+    harness.request::<CodeActionRequest>(CodeActionParams {
+        text_document: TextDocumentIdentifier { uri: test_uri },
+        range: Range {
+            start: Position::new(0, 17),
+            end: Position::new(0, 17),
+        },
+        context: CodeActionContext {
+            diagnostics: diags.diagnostics,
+            ..Default::default()
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    });
+
+    let output = String::from_utf8(harness.out).unwrap();
+    assert_eq!(
+        output,
+        "[quickfix \"Replace with `value`\": [(file:///test.ncl, [<0:17-0:22> value])], \
+         command \"evaluate term\"]\n"
+    );
+}
+
+#[test]
+fn workspace_symbols_and_references() {
+    let _ = env_logger::try_init();
+    let root = project_root()
+        .join("lsp/nls/tests/workspace")
+        .canonicalize()
+        .unwrap();
+    let root_uri = Url::from_directory_path(&root).unwrap();
+    let mut harness = TestHarness::new_with_workspace(vec![WorkspaceFolder {
+        uri: root_uri.clone(),
+        name: "workspace".to_owned(),
+    }]);
+
+    // The files of the workspace are indexed even if they aren't open.
+    harness.request::<WorkspaceSymbolRequest>(WorkspaceSymbolParams {
+        query: "host".to_owned(),
+        ..Default::default()
+    });
+
+    // `clients/client.ncl` isn't open, but it accesses the field `server.port` of `config.ncl`.
+    let config_uri = root_uri.join("config.ncl").unwrap();
+    let config = std::fs::read_to_string(root.join("config.ncl")).unwrap();
+    harness.send_file(config_uri.clone(), &config);
+    harness.request::<References>(ReferenceParams {
+        text_document_position: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: config_uri },
+            position: Position::new(4, 4),
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+        context: ReferenceContext {
+            include_declaration: true,
+        },
+    });
+
+    let output = String::from_utf8(harness.out).unwrap();
+    assert_eq!(
+        output,
+        format!(
+            "[host (Field)@{root_uri}clients/client.ncl:2:2-2:6, \
+             host (Field)@{root_uri}config.ncl:3:4-3:8 in server]\n\
+             [{root_uri}clients/client.ncl:3:30-3:34, {root_uri}config.ncl:4:4-4:8]\n"
+        )
+    );
+}
@@ -0,0 +1,179 @@
+//! Conversion of Nickel errors to Python exceptions.
+//!
+//! Each kind of error is raised as a subclass of `NickelException`, whose message is the rendered
+//! diagnostics, as printed by the command-line interface. The structured diagnostics are also
+//! available as the `diagnostics` attribute of the exception.
+use codespan_reporting::{
+    diagnostic::{self, LabelStyle, Severity},
+    term::{self, termcolor::NoColor},
+};
+use nickel_lang_core::{
+    error::{Error, IntoDiagnostics},
+    eval::cache::CacheImpl,
+    files::{FileId, Files},
+    program::Program,
+};
+use pyo3::{create_exception, exceptions::PyException, prelude::*};
+
+create_exception!(pyckel, NickelException, PyException);
+create_exception!(pyckel, ParseError, NickelException);
+create_exception!(pyckel, TypecheckError, NickelException);
+create_exception!(pyckel, EvalError, NickelException);
+create_exception!(pyckel, ImportError, NickelException);
+create_exception!(pyckel, ExportError, NickelException);
+
+/// A diagnostic reported by Nickel.
+#[pyclass(module = "pyckel")]
+#[derive(Clone)]
+pub struct Diagnostic {
+    /// One of `"bug"`, `"error"`, `"warning"`, `"note"` or `"help"`.
+    #[pyo3(get)]
+    severity: &'static str,
+    #[pyo3(get)]
+    message: String,
+    #[pyo3(get)]
+    labels: Vec<Label>,
+    #[pyo3(get)]
+    notes: Vec<String>,
+}
+
+/// A location in the source code which a diagnostic points to.
+#[pyclass(module = "pyckel")]
+#[derive(Clone)]
+pub struct Label {
+    /// Whether this is the main location of the diagnostic, or an additional one.
+    #[pyo3(get)]
+    primary: bool,
+    #[pyo3(get)]
+    message: String,
+    /// The name of the file.
+    #[pyo3(get)]
+    file: String,
+    /// The byte offset of the start of the span.
+    #[pyo3(get)]
+    start: usize,
+    /// The byte offset of the end of the span (excluded).
+    #[pyo3(get)]
+    end: usize,
+    /// The line of the start of the span, starting at 1.
+    #[pyo3(get)]
+    line: usize,
+    /// The column of the start of the span, starting at 1.
+    #[pyo3(get)]
+    column: usize,
+}
+
+#[pymethods]
+impl Diagnostic {
+    fn __repr__(&self) -> String {
+        format!("Diagnostic({}: {:?})", self.severity, self.message)
+    }
+}
+
+#[pymethods]
+impl Label {
+    fn __repr__(&self) -> String {
+        format!("Label({}:{}:{})", self.file, self.line, self.column)
+    }
+}
+
+impl Diagnostic {
+    fn new(files: &Files, diagnostic: &diagnostic::Diagnostic<FileId>) -> Self {
+        let severity = match diagnostic.severity {
+            Severity::Bug => "bug",
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+            Severity::Help => "help",
+        };
+
+        Diagnostic {
+            severity,
+            message: diagnostic.message.clone(),
+            labels: diagnostic
+                .labels
+                .iter()
+                .map(|label| Label::new(files, label))
+                .collect(),
+            notes: diagnostic.notes.clone(),
+        }
+    }
+}
+
+impl Label {
+    fn new(files: &Files, label: &diagnostic::Label<FileId>) -> Self {
+        let (line, column) = files
+            .location(label.file_id, label.range.start as u32)
+            .map(|loc| (loc.line.to_usize() + 1, loc.column.to_usize() + 1))
+            // The span of an error at the end of the input starts after the last character.
+            .unwrap_or_else(|_| {
+                let source = files.source(label.file_id);
+                let line = source.lines().count().max(1);
+                let column = source.lines().last().unwrap_or_default().chars().count() + 1;
+                (line, column)
+            });
+
+        Label {
+            primary: label.style == LabelStyle::Primary,
+            message: label.message.clone(),
+            file: files.name(label.file_id).to_string_lossy().into_owned(),
+            start: label.range.start,
+            end: label.range.end,
+            line,
+            column,
+        }
+    }
+}
+
+/// Converts a Nickel error to the corresponding Python exception.
+pub fn to_exception(program: &Program<CacheImpl>, error: impl Into<Error>) -> PyErr {
+    let error = error.into();
+
+    let new_err: fn(String) -> PyErr = match error {
+        Error::ParseErrors(_) => ParseError::new_err,
+        Error::TypecheckError(_) => TypecheckError::new_err,
+        Error::EvalError(_) => EvalError::new_err,
+        Error::ImportError(_) => ImportError::new_err,
+        Error::ExportError(_) => ExportError::new_err,
+        Error::IOError(_) | Error::ReplError(_) => NickelException::new_err,
+    };
+
+    // Building diagnostics may add generated sources to the files, so we work on a copy.
+    let mut files = program.files().clone();
+    let diagnostics = error.into_diagnostics(&mut files);
+
+    let mut buffer = Vec::new();
+    let config = term::Config::default();
+    for diagnostic in &diagnostics {
+        // unwrap(): writing to a vector can't fail, and the files of the labels are in `files`.
+        term::emit(&mut NoColor::new(&mut buffer), &config, &files, diagnostic).unwrap();
+    }
+
+    let err = new_err(String::from_utf8_lossy(&buffer).into_owned());
+    let diagnostics: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| Diagnostic::new(&files, diagnostic))
+        .collect();
+
+    Python::with_gil(|py| {
+        let diagnostics = diagnostics.into_py(py);
+
+        match err.value(py).setattr("diagnostics", diagnostics) {
+            Ok(()) => err,
+            Err(setattr_err) => setattr_err,
+        }
+    })
+}
+
+/// Registers the exception and diagnostic classes in the `pyckel` module.
+pub fn register(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add("NickelException", py.get_type::<NickelException>())?;
+    m.add("ParseError", py.get_type::<ParseError>())?;
+    m.add("TypecheckError", py.get_type::<TypecheckError>())?;
+    m.add("EvalError", py.get_type::<EvalError>())?;
+    m.add("ImportError", py.get_type::<ImportError>())?;
+    m.add("ExportError", py.get_type::<ExportError>())?;
+    m.add_class::<Diagnostic>()?;
+    m.add_class::<Label>()?;
+    Ok(())
+}
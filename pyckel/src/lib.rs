@@ -1,42 +1,155 @@
-use std::io::Cursor;
+//! Python bindings for Nickel.
+//!
+//! The `pyckel` module exposes a `Program` class to evaluate, export and query Nickel programs
+//! from Python. Errors are raised as `NickelException`s, see [error].
+use std::{io::Cursor, path::PathBuf};
 
 use nickel_lang_core::{
-    error::Error,
-    eval::cache::{Cache, CacheImpl},
-    program::Program,
-    serialize,
+    eval::cache::CacheImpl,
+    program::{FieldPath, Program},
+    serialize::{self, ExportFormat},
+    term::{MergePriority, RichTerm},
 };
 
-use pyo3::{create_exception, exceptions::PyException, prelude::*};
+use pyo3::prelude::*;
 
-create_exception!(pyckel, NickelException, PyException);
+mod error;
+mod value;
 
-/// Turn an internal Nickel error into a PyErr with a fancy diagnostic message
-fn error_to_exception<E: Into<Error>, EC: Cache>(error: E, program: &mut Program<EC>) -> PyErr {
-    NickelException::new_err(program.report_as_str(error.into()))
-}
+use error::{to_exception, NickelException};
 
 /// Evaluate from a Python str of a Nickel expression to a Python str of the resulting JSON.
 #[pyfunction]
 pub fn run(s: String) -> PyResult<String> {
-    let mut program: Program<CacheImpl> =
-        Program::new_from_source(Cursor::new(s), "python", std::io::sink())?;
+    let mut program = PyProgram::from_source(s, None)?;
+    program.export("json", None)
+}
+
+/// A Nickel program, loaded from source code or from files.
+#[pyclass(name = "Program", module = "pyckel", unsendable)]
+pub struct PyProgram {
+    program: Program<CacheImpl>,
+}
+
+impl PyProgram {
+    fn new(program: std::io::Result<Program<CacheImpl>>) -> PyResult<Self> {
+        Ok(PyProgram { program: program? })
+    }
+
+    /// Selects the field to evaluate, given as a dot-separated path. The whole program is
+    /// evaluated if `path` is `None` or empty.
+    fn select_field(&mut self, path: Option<String>) -> PyResult<()> {
+        self.program.field = match path {
+            Some(path) if !path.is_empty() => self
+                .program
+                .parse_field_path(path)
+                .map_err(|err| to_exception(&self.program, err))?,
+            _ => FieldPath::new(),
+        };
+
+        Ok(())
+    }
+
+    fn eval_for_export(&mut self, path: Option<String>) -> PyResult<RichTerm> {
+        self.select_field(path)?;
+        self.program
+            .eval_full_for_export()
+            .map_err(|err| to_exception(&self.program, err))
+    }
+}
+
+#[pymethods]
+impl PyProgram {
+    /// Creates a program from a string of Nickel source code. `name` is used to refer to the
+    /// source in error messages.
+    #[staticmethod]
+    #[args(name = "None")]
+    fn from_source(source: String, name: Option<String>) -> PyResult<Self> {
+        let name = name.unwrap_or_else(|| "python".to_owned());
+        Self::new(Program::new_from_source(
+            Cursor::new(source),
+            name,
+            std::io::sink(),
+        ))
+    }
+
+    /// Creates a program from a file.
+    #[staticmethod]
+    fn from_file(path: PathBuf) -> PyResult<Self> {
+        Self::new(Program::new_from_file(path, std::io::sink()))
+    }
+
+    /// Creates a program from several files, which are merged together.
+    #[staticmethod]
+    fn from_files(paths: Vec<PathBuf>) -> PyResult<Self> {
+        Self::new(Program::new_from_files(paths, std::io::sink()))
+    }
+
+    /// Adds a directory to the list of directories where imports are looked up.
+    fn add_import_path(&mut self, path: PathBuf) {
+        self.program.add_import_paths(std::iter::once(path));
+    }
+
+    /// Overrides a field with an assignment `path.to.field=value`, where `value` is a Nickel
+    /// expression, as with `nickel export --override`. If `force` is true, the value has the
+    /// highest priority and overrides any other definition.
+    #[args(force = "false")]
+    fn add_override(&mut self, assignment: String, force: bool) -> PyResult<()> {
+        let priority = if force {
+            MergePriority::Top
+        } else {
+            MergePriority::default()
+        };
+
+        let field_override = self
+            .program
+            .parse_override(assignment, priority)
+            .map_err(|err| to_exception(&self.program, err))?;
+        self.program.add_overrides([field_override]);
+        Ok(())
+    }
+
+    /// Evaluates the program fully, or the field at the dot-separated `path` if it's given, and
+    /// converts the result to Python dictionaries, lists, strings, numbers, booleans and `None`.
+    #[args(path = "None")]
+    fn eval(&mut self, py: Python<'_>, path: Option<String>) -> PyResult<PyObject> {
+        let rt = self.eval_for_export(path)?;
+        value::to_python(py, &rt).map_err(|err| to_exception(&self.program, err))
+    }
 
-    let term = program
-        .eval_full()
-        .map_err(|error| error_to_exception(error, &mut program))?;
+    /// Evaluates the program fully, or the field at the dot-separated `path` if it's given, and
+    /// serializes the result to `format` (`json`, `yaml`, `toml`, ...).
+    #[args(format = "\"json\"", path = "None")]
+    fn export(&mut self, format: &str, path: Option<String>) -> PyResult<String> {
+        let format = <ExportFormat as clap::ValueEnum>::from_str(format, true).map_err(|_| {
+            NickelException::new_err(format!("unsupported export format `{format}`"))
+        })?;
+        let rt = self.eval_for_export(path)?;
 
-    serialize::validate(serialize::ExportFormat::Json, &term)
-        .map_err(|error| error_to_exception(error, &mut program))?;
+        serialize::validate(format, &rt)
+            .and_then(|()| serialize::to_string(format, &rt))
+            .map_err(|err| to_exception(&self.program, err))
+    }
 
-    let json_string = serialize::to_string(serialize::ExportFormat::Json, &term)
-        .map_err(|error| error_to_exception(error, &mut program))?;
+    /// Returns the metadata of the field at the dot-separated `path`, or of the whole program if
+    /// `path` isn't given, as a dictionary with the keys `doc`, `type`, `contracts`, `optional`,
+    /// `not_exported`, `priority`, `value` and `fields`.
+    #[args(path = "None")]
+    fn query(&mut self, py: Python<'_>, path: Option<String>) -> PyResult<PyObject> {
+        self.select_field(path)?;
+        let field = self
+            .program
+            .query()
+            .map_err(|err| to_exception(&self.program, err))?;
 
-    Ok(json_string)
+        Ok(value::field_to_python(py, &field))
+    }
 }
 
 #[pymodule]
-pub fn pyckel(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+pub fn pyckel(py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(run, m)?)?;
+    m.add_class::<PyProgram>()?;
+    error::register(py, m)?;
     Ok(())
 }
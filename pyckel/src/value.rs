@@ -0,0 +1,117 @@
+//! Conversion of Nickel values to native Python objects.
+use malachite::{num::conversion::traits::RoundingFrom, rounding_modes::RoundingMode, Integer};
+use nickel_lang_core::{
+    error::{Error, ExportError, ExportErrorData},
+    term::{
+        record::{Field, RecordData},
+        Number, RichTerm, Term,
+    },
+};
+use pyo3::{
+    prelude::*,
+    types::{PyDict, PyList, PyLong},
+};
+
+/// Converts a fully evaluated term to a Python object. Records are converted to dictionaries,
+/// arrays to lists and enum tags to strings, as when exporting to JSON. Fails on terms that can't
+/// be exported, such as functions.
+pub fn to_python(py: Python<'_>, rt: &RichTerm) -> Result<PyObject, Error> {
+    let object = match rt.as_ref() {
+        Term::Null => py.None(),
+        Term::Bool(b) => b.into_py(py),
+        Term::Num(n) => number_to_python(py, n),
+        Term::Str(s) => s.as_str().into_py(py),
+        Term::Enum(tag) => tag.label().into_py(py),
+        Term::Array(elts, _) => {
+            let elts = elts
+                .iter()
+                .map(|elt| to_python(py, elt))
+                .collect::<Result<Vec<_>, _>>()?;
+            PyList::new(py, elts).into_py(py)
+        }
+        Term::Record(record) => record_to_python(py, record, rt)?,
+        _ => return Err(ExportError::from(ExportErrorData::NonSerializable(rt.clone())).into()),
+    };
+
+    Ok(object)
+}
+
+/// Integers are converted to Python integers, which have an arbitrary precision, and other numbers
+/// to the closest float.
+fn number_to_python(py: Python<'_>, n: &Number) -> PyObject {
+    if let Ok(i) = i64::try_from(n) {
+        i.into_py(py)
+    } else if let Ok(i) = Integer::try_from(n) {
+        // There's no direct conversion from big integers, so we go through their decimal
+        // representation.
+        py.get_type::<PyLong>()
+            .call1((i.to_string(),))
+            .map(|i| i.into_py(py))
+            .unwrap_or_else(|_| f64::rounding_from(n, RoundingMode::Nearest).0.into_py(py))
+    } else {
+        f64::rounding_from(n, RoundingMode::Nearest).0.into_py(py)
+    }
+}
+
+fn record_to_python(py: Python<'_>, record: &RecordData, rt: &RichTerm) -> Result<PyObject, Error> {
+    let dict = PyDict::new(py);
+    let mut fields: Vec<_> = record
+        .iter_serializable()
+        .collect::<Result<_, _>>()
+        .map_err(|missing| missing.into_eval_err(rt.pos, rt.pos))?;
+    fields.sort_by(|(id1, _), (id2, _)| id1.label().cmp(id2.label()));
+
+    for (id, value) in fields {
+        // unwrap(): setting a string key in a new dictionary can't fail.
+        dict.set_item(id.label(), to_python(py, value)?).unwrap();
+    }
+
+    Ok(dict.into_py(py))
+}
+
+/// Converts the result of a metadata query to a dictionary.
+pub fn field_to_python(py: Python<'_>, field: &Field) -> PyObject {
+    let metadata = &field.metadata;
+    let dict = PyDict::new(py);
+
+    // We use the original user-written types stored in the labels, as in `nickel query`.
+    let typ = metadata
+        .annotation
+        .typ
+        .as_ref()
+        .map(|typ| typ.label.typ.to_string());
+    let contracts: Vec<_> = metadata
+        .annotation
+        .contracts
+        .iter()
+        .map(|ctr| ctr.label.typ.to_string())
+        .collect();
+    // Records are described by the list of their fields rather than by their value.
+    let (value, fields) = match field.value.as_ref().map(|value| (value, value.as_ref())) {
+        Some((_, Term::Record(record) | Term::RecRecord(record, ..))) => {
+            let mut fields: Vec<_> = record.fields.keys().map(|id| id.label()).collect();
+            fields.sort();
+            (None, Some(fields))
+        }
+        Some((value, _)) => (Some(value.to_string()), None),
+        None => (None, None),
+    };
+
+    let items: [(&str, PyObject); 8] = [
+        ("doc", metadata.doc.clone().into_py(py)),
+        ("type", typ.into_py(py)),
+        ("contracts", contracts.into_py(py)),
+        ("optional", metadata.opt.into_py(py)),
+        ("not_exported", metadata.not_exported.into_py(py)),
+        ("priority", metadata.priority.to_string().into_py(py)),
+        ("value", value.into_py(py)),
+        ("fields", fields.into_py(py)),
+    ];
+
+    for (key, value) in items {
+        // unwrap(): setting a string key in a new dictionary can't fail.
+        dict.set_item(key, value).unwrap();
+    }
+
+    dict.into_py(py)
+}
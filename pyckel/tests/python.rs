@@ -0,0 +1,50 @@
+//! Runs the Python test suite `test_pyckel.py` against the extension module built by cargo.
+//!
+//! The module is built with the `extension-module` feature of pyo3, so it can't be loaded in a
+//! Rust test binary: instead, the shared library is copied under the name Python expects for the
+//! `pyckel` module, and the tests are run by the interpreter given by `PYO3_PYTHON` (as when
+//! building pyo3), or `python3`.
+use std::{
+    env::consts::{DLL_PREFIX, DLL_SUFFIX},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use tempfile::tempdir;
+
+/// Finds the shared library of pyckel, which cargo builds next to the test binaries.
+fn library() -> PathBuf {
+    let name = format!("{DLL_PREFIX}pyckel{DLL_SUFFIX}");
+    let exe = std::env::current_exe().expect("the test binary should have a path");
+
+    exe.ancestors()
+        .skip(1)
+        .take(2)
+        .map(|dir| dir.join(&name))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| panic!("couldn't find {name} next to {}", exe.display()))
+}
+
+#[test]
+fn python_tests() {
+    let module_dir = tempdir().expect("should be able to make a temporary directory");
+    let module = if cfg!(windows) {
+        "pyckel.pyd"
+    } else {
+        "pyckel.so"
+    };
+    std::fs::copy(library(), module_dir.path().join(module))
+        .expect("should be able to copy the extension module");
+
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let python = std::env::var("PYO3_PYTHON").unwrap_or_else(|_| "python3".to_owned());
+    let status = Command::new(&python)
+        .args(["-m", "unittest", "-v", "test_pyckel"])
+        .current_dir(&tests_dir)
+        .env("PYTHONPATH", module_dir.path())
+        .env("PYTHONDONTWRITEBYTECODE", "1")
+        .status()
+        .unwrap_or_else(|err| panic!("couldn't run {python}: {err}"));
+
+    assert!(status.success(), "the Python tests failed");
+}
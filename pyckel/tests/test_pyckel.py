@@ -0,0 +1,214 @@
+"""Tests of the Python bindings.
+
+They are run by `cargo test -p pyckel`, which puts the built extension module on
+the `PYTHONPATH`, or with `python -m unittest` after installing pyckel.
+"""
+
+import json
+import os
+import tempfile
+import unittest
+
+import pyckel
+
+
+class TestEval(unittest.TestCase):
+    def test_run(self):
+        self.assertEqual(json.loads(pyckel.run("{ y = 1 + 2 }")), {"y": 3})
+
+    def test_values(self):
+        program = pyckel.Program.from_source(
+            """
+            {
+              nothing = null,
+              bool = true,
+              int = 1,
+              float = 0.5,
+              big = 100000000000000000000000,
+              str = "hello",
+              tag = 'Foo,
+              array = [1, "two"],
+              record = { a.b = 1 },
+              hidden | not_exported = 1,
+            }
+            """
+        )
+
+        self.assertEqual(
+            program.eval(),
+            {
+                "nothing": None,
+                "bool": True,
+                "int": 1,
+                "float": 0.5,
+                "big": 100000000000000000000000,
+                "str": "hello",
+                "tag": "Foo",
+                "array": [1, "two"],
+                "record": {"a": {"b": 1}},
+            },
+        )
+
+    def test_path(self):
+        program = pyckel.Program.from_source("{ a.b = { c = 1 + 1 } }")
+        self.assertEqual(program.eval("a.b.c"), 2)
+        self.assertEqual(program.eval("a"), {"b": {"c": 2}})
+        self.assertEqual(program.eval(""), {"a": {"b": {"c": 2}}})
+
+    def test_files_and_imports(self):
+        with tempfile.TemporaryDirectory() as dir:
+            os.mkdir(os.path.join(dir, "lib"))
+            with open(os.path.join(dir, "lib", "port.ncl"), "w") as f:
+                f.write("8080")
+            with open(os.path.join(dir, "base.ncl"), "w") as f:
+                f.write('{ port = import "port.ncl", host | default = "0.0.0.0" }')
+            with open(os.path.join(dir, "prod.ncl"), "w") as f:
+                f.write('{ host = "example.com" }')
+
+            program = pyckel.Program.from_files(
+                [os.path.join(dir, "base.ncl"), os.path.join(dir, "prod.ncl")]
+            )
+            program.add_import_path(os.path.join(dir, "lib"))
+            self.assertEqual(program.eval(), {"host": "example.com", "port": 8080})
+
+            program = pyckel.Program.from_file(os.path.join(dir, "base.ncl"))
+            with self.assertRaises(pyckel.ImportError):
+                program.eval()
+
+    def test_overrides(self):
+        program = pyckel.Program.from_source("{ a | default = 1, b = 2 }")
+        program.add_override("a=3")
+        self.assertEqual(program.eval(), {"a": 3, "b": 2})
+
+        program = pyckel.Program.from_source("{ a | default = 1, b = 2 }")
+        program.add_override("b=4", force=True)
+        self.assertEqual(program.eval(), {"a": 1, "b": 4})
+
+        with self.assertRaises(pyckel.ParseError):
+            program.add_override("b=")
+
+    def test_non_serializable(self):
+        program = pyckel.Program.from_source("{ f = fun x => x }")
+        with self.assertRaises(pyckel.ExportError):
+            program.eval()
+
+
+class TestExport(unittest.TestCase):
+    def test_formats(self):
+        program = pyckel.Program.from_source('{ a = { b = 1, c = "d" } }')
+
+        self.assertEqual(json.loads(program.export()), {"a": {"b": 1, "c": "d"}})
+        self.assertEqual(program.export("yaml"), "a:\n  b: 1\n  c: d\n")
+        self.assertEqual(program.export("toml", path="a"), 'b = 1\nc = "d"\n')
+        self.assertEqual(program.export("raw", path="a.c"), "d")
+
+    def test_unsupported_format(self):
+        program = pyckel.Program.from_source("{}")
+        with self.assertRaises(pyckel.NickelException) as cm:
+            program.export("csv")
+        self.assertIn("unsupported export format `csv`", str(cm.exception))
+
+    def test_invalid_shape(self):
+        program = pyckel.Program.from_source("[1, 2]")
+        with self.assertRaises(pyckel.ExportError):
+            program.export("toml")
+
+
+class TestQuery(unittest.TestCase):
+    def test_field(self):
+        program = pyckel.Program.from_source(
+            """
+            {
+              server = {
+                port | Number | doc "The port to listen on" | default = 8080,
+                host : String = "localhost",
+                extra | optional,
+              },
+            }
+            """
+        )
+
+        self.assertEqual(
+            program.query("server.port"),
+            {
+                "doc": "The port to listen on",
+                "type": None,
+                "contracts": ["Number"],
+                "optional": False,
+                "not_exported": False,
+                "priority": "default",
+                "value": "8080",
+                "fields": None,
+            },
+        )
+
+        host = program.query("server.host")
+        self.assertEqual(host["type"], "String")
+        self.assertEqual(host["value"], '"localhost"')
+
+        extra = program.query("server.extra")
+        self.assertTrue(extra["optional"])
+        self.assertIsNone(extra["value"])
+
+        self.assertEqual(program.query("server")["fields"], ["extra", "host", "port"])
+        self.assertEqual(program.query()["fields"], ["server"])
+
+    def test_missing_field(self):
+        program = pyckel.Program.from_source("{ a = 1 }")
+        with self.assertRaises(pyckel.EvalError):
+            program.query("b")
+
+
+class TestDiagnostics(unittest.TestCase):
+    def test_eval_error(self):
+        source = '{\n  a = 1 + "x",\n}'
+        program = pyckel.Program.from_source(source, name="bad.ncl")
+
+        with self.assertRaises(pyckel.EvalError) as cm:
+            program.eval()
+        error = cm.exception
+
+        self.assertIsInstance(error, pyckel.NickelException)
+        self.assertIn("error:", str(error))
+        self.assertIn("bad.ncl", str(error))
+
+        self.assertGreaterEqual(len(error.diagnostics), 1)
+        diagnostic = error.diagnostics[0]
+        self.assertIsInstance(diagnostic, pyckel.Diagnostic)
+        self.assertEqual(diagnostic.severity, "error")
+        self.assertIn(diagnostic.message, str(error))
+
+        primary = [label for label in diagnostic.labels if label.primary]
+        self.assertEqual(len(primary), 1)
+        label = primary[0]
+        self.assertIsInstance(label, pyckel.Label)
+        self.assertEqual(label.file, "bad.ncl")
+        self.assertEqual(source[label.start : label.end], '"x"')
+        self.assertEqual((label.line, label.column), (2, 11))
+
+    def test_typecheck_error(self):
+        program = pyckel.Program.from_source('(1 + "x" : Number)')
+        with self.assertRaises(pyckel.TypecheckError) as cm:
+            program.eval()
+        self.assertEqual(cm.exception.diagnostics[0].severity, "error")
+
+    def test_parse_error_at_end(self):
+        source = "{ a = 1,\n  b ="
+        program = pyckel.Program.from_source(source, name="eof.ncl")
+
+        with self.assertRaises(pyckel.ParseError) as cm:
+            program.eval()
+
+        labels = [
+            label
+            for diagnostic in cm.exception.diagnostics
+            for label in diagnostic.labels
+            if label.primary
+        ]
+        self.assertGreaterEqual(len(labels), 1)
+        self.assertEqual(labels[0].file, "eof.ncl")
+        self.assertEqual(labels[0].line, 2)
+
+
+if __name__ == "__main__":
+    unittest.main()